@@ -0,0 +1,47 @@
+//! Decoding a maximum length Write Multiple Registers request
+//!
+//! `cargo bench --bench decode`, compares checking the CRC while decoding the bytes against checking it once into a
+//! `ValidatedFrame` and decoding that.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use modbus_frames::{decoder::CommonRequests, request, ValidatedFrame};
+
+const ITERATIONS: u32 = 200_000;
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    // warm up
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per_iter = start.elapsed() / ITERATIONS;
+    println!("{name:<40} {:>8} ns/iter", per_iter.as_nanos());
+}
+
+fn main() {
+    let mut buf = [0; 256];
+    let registers: Vec<u16> = (0..123).collect();
+    let frame = request::write_multiple_holding_registers(&mut buf, 1, 0, &registers).unwrap();
+    let bytes = frame.raw_bytes();
+
+    bench("typed try_from(bytes)", || {
+        black_box(request::WriteMultipleHoldingRegisters::try_from(black_box(bytes)).unwrap());
+    });
+    bench("ValidatedFrame::try_from, CommonRequests", || {
+        let frame = ValidatedFrame::try_from(black_box(bytes)).unwrap();
+        black_box(frame.decode::<CommonRequests>().unwrap());
+    });
+    let frame = ValidatedFrame::try_from(bytes).unwrap();
+    bench("ValidatedFrame::decode, no CRC", || {
+        black_box(
+            black_box(frame)
+                .decode::<request::WriteMultipleHoldingRegisters>()
+                .unwrap(),
+        );
+    });
+}
@@ -0,0 +1,90 @@
+#ifndef MODBUS_FRAMES_H
+#define MODBUS_FRAMES_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Result of an `mbf_` call, `MBF_STATUS_OK` or the reason it failed
+ */
+typedef enum MbfStatus {
+  MBF_STATUS_OK = 0,
+  /**
+   * a pointer argument was null
+   */
+  MBF_STATUS_NULL_POINTER,
+  /**
+   * frames are 4-256 bytes
+   */
+  MBF_STATUS_INVALID_LENGTH,
+  MBF_STATUS_INVALID_CRC,
+  /**
+   * not one of the common request functions
+   */
+  MBF_STATUS_UNKNOWN_FUNCTION,
+  /**
+   * the length doesn't match the function code
+   */
+  MBF_STATUS_DECODE_INVALID_LENGTH,
+  MBF_STATUS_INVALID_ADDRESS,
+  MBF_STATUS_INVALID_QUANTITY,
+  MBF_STATUS_INVALID_VALUE,
+  MBF_STATUS_BUFFER_TOO_SMALL,
+  /**
+   * any other `Error`
+   */
+  MBF_STATUS_OTHER,
+} MbfStatus;
+
+/**
+ * A decoded common request (functions 1-6, 15 and 16)
+ *
+ * `start_index` is the index for the single writes and `count` is 1. `value` is the register value, or
+ * 1/0 for a coil on/off. Multiple writes point `values` at the coil bytes (least significant bit first) or big
+ * endian registers inside the decoded bytes, `values_len` is their length in bytes. `values` is null otherwise.
+ */
+typedef struct MbfRequest {
+  uint8_t address;
+  uint8_t function;
+  uint16_t start_index;
+  uint16_t count;
+  uint16_t value;
+  const uint8_t *values;
+  size_t values_len;
+} MbfRequest;
+
+/**
+ * Check that `bytes` is a frame of valid length with a valid CRC
+ *
+ * # Safety
+ * `bytes` must be valid for reads of `len` bytes
+ */
+MbfStatus mbf_validate(const uint8_t *bytes, size_t len);
+
+/**
+ * Build a Read Holding Registers (0x03) request for `count` (1-125) registers into `buffer`, writing the frame
+ * length to `frame_len`
+ *
+ * # Safety
+ * `buffer` must be valid for writes of `buffer_len` bytes and `frame_len` valid for a write
+ */
+MbfStatus mbf_build_read_holding(uint8_t *buffer,
+                                 size_t buffer_len,
+                                 uint8_t address,
+                                 uint16_t start_index,
+                                 uint16_t count,
+                                 size_t *frame_len);
+
+/**
+ * Validate and decode a common request frame into `request`
+ *
+ * `request.values` points into `bytes` for multiple writes, it is only valid while `bytes` is
+ *
+ * # Safety
+ * `bytes` must be valid for reads of `len` bytes and `request` valid for a write
+ */
+MbfStatus mbf_decode_common_request(const uint8_t *bytes, size_t len, MbfRequest *request);
+
+#endif  /* MODBUS_FRAMES_H */
@@ -0,0 +1,362 @@
+//! Python bindings for host tooling
+//!
+//! Test benches and commissioning scripts build and decode frames with the same code as the firmware. Build the
+//! extension module with `maturin develop` (or `maturin build`) in this directory, then:
+//!
+//! ```python
+//! import modbus_frames
+//!
+//! request = modbus_frames.read_holding_registers(0x11, 0x6B, 3)
+//! assert request == bytes([0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87])
+//! assert modbus_frames.decode_request(request) == {
+//!     "address": 0x11, "function": 3, "start_index": 0x6B, "count": 3,
+//! }
+//! ```
+//!
+//! Invalid frames and arguments raise `ValueError` with the crate's error message, exception responses raise
+//! `ModbusException` with the exception code as `args[0]`.
+
+use modbus_frames::{
+    builder,
+    decoder::{CommonRequests, CommonResponses},
+    request, Error, Frame, Function,
+};
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyValueError},
+    prelude::*,
+    types::{PyBytes, PyDict},
+};
+
+create_exception!(
+    modbus_frames,
+    ModbusException,
+    PyException,
+    "The device responded with an exception, `args[0]` is the exception code"
+);
+
+fn to_py_err(error: Error) -> PyErr {
+    match error {
+        Error::Exception(exception) => ModbusException::new_err(exception.0),
+        error => PyValueError::new_err(error.to_string()),
+    }
+}
+
+/// `build` a frame into a maximum size buffer and copy it out as `bytes`
+fn frame_bytes<'py>(
+    py: Python<'py>,
+    build: impl FnOnce(&mut [u8]) -> Result<Frame<'_>, Error>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let mut buffer = [0; 256];
+    let frame = build(&mut buffer).map_err(to_py_err)?;
+    Ok(PyBytes::new(py, frame.raw_bytes()))
+}
+
+/// CRC-16/MODBUS of `data`
+#[pyfunction]
+fn crc16(data: &[u8]) -> u16 {
+    modbus_frames::calculate_crc16(data)
+}
+
+/// True if the last two bytes of `data` are the CRC of the rest
+#[pyfunction]
+fn verify_crc16(data: &[u8]) -> bool {
+    modbus_frames::verify_crc16(data)
+}
+
+/// Frame of any function code with `payload` and the CRC appended
+#[pyfunction]
+fn build_frame<'py>(
+    py: Python<'py>,
+    address: u8,
+    function: u8,
+    payload: &[u8],
+) -> PyResult<Bound<'py, PyBytes>> {
+    if payload.len() > 252 {
+        return Err(to_py_err(Error::InvalidLength(payload.len() + 4)));
+    }
+    frame_bytes(py, |buffer| {
+        Ok(builder::build_frame(buffer)
+            .for_address(address)
+            .function(Function(function))
+            .bytes(payload.iter().copied())
+            .finalise()
+            .0)
+    })
+}
+
+/// `(address, function, payload)` of a frame after checking its length and CRC
+#[pyfunction]
+fn decode_frame<'py>(py: Python<'py>, data: &[u8]) -> PyResult<(u8, u8, Bound<'py, PyBytes>)> {
+    let frame = Frame::try_from(data).map_err(to_py_err)?;
+    Ok((
+        frame.address(),
+        frame.function().0,
+        PyBytes::new(py, frame.payload()),
+    ))
+}
+
+#[pyfunction]
+fn read_coils<'py>(
+    py: Python<'py>,
+    address: u8,
+    start_index: u16,
+    count: u16,
+) -> PyResult<Bound<'py, PyBytes>> {
+    frame_bytes(py, |buffer| {
+        request::read_coils(buffer, address, start_index, count)
+    })
+}
+
+#[pyfunction]
+fn read_discrete_inputs<'py>(
+    py: Python<'py>,
+    address: u8,
+    start_index: u16,
+    count: u16,
+) -> PyResult<Bound<'py, PyBytes>> {
+    frame_bytes(py, |buffer| {
+        request::read_discrete_inputs(buffer, address, start_index, count)
+    })
+}
+
+#[pyfunction]
+fn read_holding_registers<'py>(
+    py: Python<'py>,
+    address: u8,
+    start_index: u16,
+    count: u16,
+) -> PyResult<Bound<'py, PyBytes>> {
+    frame_bytes(py, |buffer| {
+        request::read_holding_registers(buffer, address, start_index, count)
+    })
+}
+
+#[pyfunction]
+fn read_input_registers<'py>(
+    py: Python<'py>,
+    address: u8,
+    start_index: u16,
+    count: u16,
+) -> PyResult<Bound<'py, PyBytes>> {
+    frame_bytes(py, |buffer| {
+        request::read_input_registers(buffer, address, start_index, count)
+    })
+}
+
+#[pyfunction]
+fn write_coil<'py>(
+    py: Python<'py>,
+    address: u8,
+    index: u16,
+    on: bool,
+) -> PyResult<Bound<'py, PyBytes>> {
+    frame_bytes(py, |buffer| request::write_coil(buffer, address, index, on))
+}
+
+#[pyfunction]
+fn write_holding_register<'py>(
+    py: Python<'py>,
+    address: u8,
+    index: u16,
+    value: u16,
+) -> PyResult<Bound<'py, PyBytes>> {
+    frame_bytes(py, |buffer| {
+        request::write_holding_register(buffer, address, index, value)
+    })
+}
+
+#[pyfunction]
+fn write_multiple_coils<'py>(
+    py: Python<'py>,
+    address: u8,
+    start_index: u16,
+    coils: Vec<bool>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    frame_bytes(py, |buffer| {
+        request::write_multiple_coils(buffer, address, start_index, &coils)
+    })
+}
+
+#[pyfunction]
+fn write_multiple_holding_registers<'py>(
+    py: Python<'py>,
+    address: u8,
+    start_index: u16,
+    registers: Vec<u16>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    frame_bytes(py, |buffer| {
+        request::write_multiple_holding_registers(buffer, address, start_index, &registers)
+    })
+}
+
+/// Decode a request for functions 1-6, 15 or 16 into a dict
+///
+/// Always has `address` and `function`. Reads add `start_index` and `count`, single writes `index` and `on` or
+/// `value`, multiple writes `start_index` and `coils` or `registers`.
+#[pyfunction]
+fn decode_request<'py>(py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyDict>> {
+    let decoded = CommonRequests::try_from(data).map_err(to_py_err)?;
+    let dict = PyDict::new(py);
+    let frame = decoded.as_frame();
+    dict.set_item("address", frame.address())?;
+    dict.set_item("function", frame.function().0)?;
+    match decoded {
+        CommonRequests::ReadCoils(r) => {
+            dict.set_item("start_index", r.start_index())?;
+            dict.set_item("count", r.coil_count())?;
+        }
+        CommonRequests::ReadDiscreteInputs(r) => {
+            dict.set_item("start_index", r.start_index())?;
+            dict.set_item("count", r.input_count())?;
+        }
+        CommonRequests::ReadHolsingRegisters(r) => {
+            dict.set_item("start_index", r.start_index())?;
+            dict.set_item("count", r.register_count())?;
+        }
+        CommonRequests::ReadInputRegisters(r) => {
+            dict.set_item("start_index", r.start_index())?;
+            dict.set_item("count", r.register_count())?;
+        }
+        CommonRequests::WriteCoil(r) => {
+            dict.set_item("index", r.index())?;
+            dict.set_item(
+                "on",
+                r.coil_state().map_err(|_| to_py_err(Error::InvalidValue))?,
+            )?;
+        }
+        CommonRequests::WriteHoldingRegister(r) => {
+            dict.set_item("index", r.index())?;
+            dict.set_item("value", r.value())?;
+        }
+        CommonRequests::WriteMultipleCoils(r) => {
+            dict.set_item("start_index", r.start_index())?;
+            dict.set_item(
+                "coils",
+                r.iter_coils().map(|(_, on)| on).collect::<Vec<_>>(),
+            )?;
+        }
+        CommonRequests::WriteMultipleHoldingRegisters(r) => {
+            dict.set_item("start_index", r.start_index())?;
+            dict.set_item("registers", r.iter_registers().collect::<Vec<_>>())?;
+        }
+    }
+    Ok(dict)
+}
+
+/// Decode a response for functions 1-6, 15 or 16 into a dict
+///
+/// Always has `address` and `function`. Coil and input reads have `coils`/`inputs` padded to a whole number of
+/// bytes as the response doesn't carry the count, register reads `registers`. Write responses have the same keys
+/// as `decode_request`, with `count` for multiple writes.
+#[pyfunction]
+fn decode_response<'py>(py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyDict>> {
+    let decoded = CommonResponses::try_from(data).map_err(to_py_err)?;
+    let dict = PyDict::new(py);
+    let frame = decoded.as_frame();
+    dict.set_item("address", frame.address())?;
+    dict.set_item("function", frame.function().0)?;
+    match decoded {
+        CommonResponses::ReadCoils(r) => {
+            dict.set_item("coils", r.iter_coils().collect::<Vec<_>>())?;
+        }
+        CommonResponses::ReadDiscreteInputs(r) => {
+            dict.set_item("inputs", r.iter_inputs().collect::<Vec<_>>())?;
+        }
+        CommonResponses::ReadHolsingRegisters(r) => {
+            dict.set_item("registers", r.iter_registers().collect::<Vec<_>>())?;
+        }
+        CommonResponses::ReadInputRegisters(r) => {
+            dict.set_item("registers", r.iter_registers().collect::<Vec<_>>())?;
+        }
+        CommonResponses::WriteCoil(r) => {
+            dict.set_item("index", r.index())?;
+            dict.set_item("on", r.is_on())?;
+        }
+        CommonResponses::WriteHoldingRegister(r) => {
+            dict.set_item("index", r.index())?;
+            dict.set_item("value", r.value())?;
+        }
+        CommonResponses::WriteMultipleCoils(r) => {
+            dict.set_item("start_index", r.start_index())?;
+            dict.set_item("count", r.register_count())?;
+        }
+        CommonResponses::WriteMultipleHoldingRegisters(r) => {
+            dict.set_item("start_index", r.start_index())?;
+            dict.set_item("count", r.register_count())?;
+        }
+    }
+    Ok(dict)
+}
+
+#[pymodule]
+#[pyo3(name = "modbus_frames")]
+fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("ModbusException", m.py().get_type::<ModbusException>())?;
+    m.add_function(wrap_pyfunction!(crc16, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_crc16, m)?)?;
+    m.add_function(wrap_pyfunction!(build_frame, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_frame, m)?)?;
+    m.add_function(wrap_pyfunction!(read_coils, m)?)?;
+    m.add_function(wrap_pyfunction!(read_discrete_inputs, m)?)?;
+    m.add_function(wrap_pyfunction!(read_holding_registers, m)?)?;
+    m.add_function(wrap_pyfunction!(read_input_registers, m)?)?;
+    m.add_function(wrap_pyfunction!(write_coil, m)?)?;
+    m.add_function(wrap_pyfunction!(write_holding_register, m)?)?;
+    m.add_function(wrap_pyfunction!(write_multiple_coils, m)?)?;
+    m.add_function(wrap_pyfunction!(write_multiple_holding_registers, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_request, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_response, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pyo3::{ffi::c_str, prelude::*, types::PyModule};
+
+    #[test]
+    fn python_module() {
+        Python::attach(|py| {
+            let module = PyModule::new(py, "modbus_frames").unwrap();
+            super::python_module(&module).unwrap();
+            let globals = pyo3::types::PyDict::new(py);
+            globals.set_item("mf", module).unwrap();
+            py.run(
+                c_str!(
+                    r#"
+request = mf.read_holding_registers(0x11, 0x6B, 3)
+assert request == bytes([0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87])
+assert mf.verify_crc16(request) and mf.crc16(request[:-2]) == 0x8776
+assert mf.decode_request(request) == {"address": 0x11, "function": 3, "start_index": 0x6B, "count": 3}
+assert mf.decode_frame(request) == (0x11, 3, bytes([0, 0x6B, 0, 3]))
+
+write = mf.write_multiple_coils(1, 2, [True, False, True])
+assert mf.decode_request(write)["coils"] == [True, False, True]
+assert mf.decode_request(mf.write_multiple_holding_registers(1, 2, [7, 8]))["registers"] == [7, 8]
+
+response = mf.build_frame(1, 3, bytes([4, 0, 1, 0, 2]))
+assert mf.decode_response(response) == {"address": 1, "function": 3, "registers": [1, 2]}
+try:
+    mf.decode_response(mf.build_frame(1, 0x83, bytes([2])))
+    assert False
+except mf.ModbusException as e:
+    assert e.args[0] == 2
+try:
+    mf.read_coils(1, 0, 2001)
+    assert False
+except ValueError as e:
+    assert "quantity" in str(e)
+try:
+    mf.decode_frame(request[:-1] + b"\x00")
+    assert False
+except ValueError as e:
+    assert "CRC" in str(e)
+"#
+                ),
+                Some(&globals),
+                None,
+            )
+            .unwrap();
+        });
+    }
+}
@@ -0,0 +1,371 @@
+//! Modbus ASCII framing
+//!
+//! On the wire an ASCII frame is `:` then the address, function, data and LRC each as two hex characters, then
+//! `\r\n`. [`decode`] converts the characters back to bytes giving a [`Frame`], laid out like an RTU frame with a
+//! one byte LRC in place of the CRC. [`encode_in_place`] goes the other way.
+//!
+//! ```
+//! use modbus_frames::{ascii, function};
+//!
+//! let mut buffer = [0; 8];
+//! let frame = ascii::decode(b":1103006B00037E\r\n", &mut buffer).unwrap();
+//! assert_eq!(frame.function(), function::READ_HOLDING_REGISTERS);
+//! assert_eq!(frame.payload(), [0x00, 0x6B, 0x00, 0x03]);
+//! ```
+
+use crate::{builder, calculate_lrc, Device, Error, Exception, Function, ModbusFrame};
+
+/// First character of every frame
+pub const START: u8 = b':';
+/// Default end of frame delimiter, the final character can be changed with `CHANGE_ASCII_DELIMITER`
+pub const END: [u8; 2] = *b"\r\n";
+
+/// A decoded ASCII frame
+/// `|address(1)|function(1)|payload(0..252)|lrc(1)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Frame<'b> {
+    data: &'b [u8],
+}
+
+impl<'b> Frame<'b> {
+    /// Creates a new frame without validation
+    ///
+    /// # UNCHECKED
+    /// if `bytes.len() < 3` The created frame will be invalid and later operations are likely to panic.
+    pub const fn new_unchecked(bytes: &'b [u8]) -> Self {
+        Frame { data: bytes }
+    }
+
+    /// The address byte of the frame
+    pub fn address(&self) -> u8 {
+        self.data[0]
+    }
+
+    /// The device the frame is addressed to (requests) or from (responses)
+    pub fn device(&self) -> Device {
+        Device::new(self.address())
+    }
+
+    /// the function code of the frame
+    pub fn function(&self) -> Function {
+        Function(self.data[1])
+    }
+
+    /// All bytes between the address/function code and LRC
+    pub fn payload(&self) -> &[u8] {
+        &self.data[2..self.data.len() - 1]
+    }
+
+    /// the LRC byte of the frame
+    pub fn lrc(&self) -> u8 {
+        self.data[self.data.len() - 1]
+    }
+
+    /// calculate the expected LRC of the frame
+    pub fn calculate_lrc(&self) -> u8 {
+        calculate_lrc(&self.data[..self.data.len() - 1])
+    }
+
+    /// All of the decoded bytes in the message (address, function, payload, lrc)
+    pub fn raw_bytes(&self) -> &[u8] {
+        self.data
+    }
+
+    /// All of the decoded bytes in the message (address, function, payload, lrc)
+    /// Consumes `Self` to tie the lifetime to the underlying array
+    pub fn into_raw_bytes(self) -> &'b [u8] {
+        self.data
+    }
+
+    /// The address, function and payload without the LRC, the same for a frame in any encoding
+    pub fn canonical_bytes(&self) -> &'b [u8] {
+        &self.data[..self.data.len().saturating_sub(1)]
+    }
+
+    /// true if `other` has the same address, function and payload, whatever its encoding and checksum
+    pub fn semantic_eq<'o>(&self, other: &impl ModbusFrame<'o>) -> bool {
+        ModbusFrame::semantic_eq(self, other)
+    }
+
+    /// Builder for the response, finish it with `finalise_ascii`
+    pub fn response_builder<'buff>(
+        &self,
+        response_buffer: &'buff mut [u8],
+    ) -> builder::Builder<'buff, builder::AddData> {
+        builder::build_frame(response_buffer)
+            .for_address(self.address())
+            .function(self.function())
+    }
+
+    pub fn response_exception<'buff>(
+        &self,
+        response_buffer: &'buff mut [u8],
+        exception: Exception,
+    ) -> (Frame<'buff>, &'buff mut [u8]) {
+        builder::build_frame(response_buffer)
+            .for_address(self.address())
+            .function(Function(self.function().0 | 0x80))
+            .byte(exception.0)
+            .finalise_ascii()
+    }
+
+    /// Copy the frame into `buffer` with a CRC in place of the LRC, so the typed request/response API and
+    /// decoders can be used unchanged
+    pub fn to_rtu<'buff>(&self, buffer: &'buff mut [u8]) -> Result<crate::Frame<'buff>, Error> {
+        let len = self.data.len() - 1;
+        check_len(buffer, len + 2)?;
+        let (frame, _) = builder::build_frame(buffer)
+            .for_address(self.address())
+            .function(self.function())
+            .bytes(self.payload().iter().copied())
+            .finalise();
+        Ok(frame)
+    }
+}
+
+impl<'b> ModbusFrame<'b> for Frame<'b> {
+    fn address(&self) -> u8 {
+        Frame::address(self)
+    }
+
+    fn function(&self) -> Function {
+        Frame::function(self)
+    }
+
+    fn payload(&self) -> &[u8] {
+        Frame::payload(self)
+    }
+
+    fn verify(&self) -> bool {
+        self.lrc() == self.calculate_lrc()
+    }
+
+    fn into_raw_bytes(self) -> &'b [u8] {
+        self.data
+    }
+}
+
+impl<'b> TryFrom<&'b [u8]> for Frame<'b> {
+    type Error = Error;
+
+    /// `bytes` are the decoded bytes, see `decode` for converting from the ASCII characters
+    fn try_from(bytes: &'b [u8]) -> Result<Self, Self::Error> {
+        if bytes.len() < 3 {
+            return Err(Error::InvalidLength(bytes.len()));
+        }
+        let frame = Frame::new_unchecked(bytes);
+        if frame.lrc() != frame.calculate_lrc() {
+            return Err(Error::InvalidLrc);
+        }
+        Ok(frame)
+    }
+}
+
+/// Convert the characters of an ASCII frame, including the start and end delimiters, to bytes in `buffer`
+///
+/// Errors with the index of the first character that isn't a delimiter or upper case hex digit
+/// (`Error::InvalidEncoding`) or if the hex digits don't pair up into bytes (`Error::OddEncodedLength`).
+/// The LRC is verified as for `Frame::try_from`
+pub fn decode<'b>(text: &[u8], buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+    decode_with(text, buffer, false)
+}
+
+/// `decode`, optionally accepting lower case hex digits
+pub(crate) fn decode_with<'b>(
+    text: &[u8],
+    buffer: &'b mut [u8],
+    lowercase: bool,
+) -> Result<Frame<'b>, Error> {
+    if text.first() != Some(&START) {
+        return Err(Error::InvalidEncoding(0));
+    }
+    let end = text.len().max(1 + END.len()) - END.len();
+    if let Some(idx) = (end..end + END.len()).find(|idx| text.get(*idx) != Some(&END[idx - end])) {
+        return Err(Error::InvalidEncoding(idx));
+    }
+    let hex = &text[1..end];
+    if let Some(idx) = hex
+        .iter()
+        .position(|c| hex_to_nibble(*c, lowercase).is_none())
+    {
+        return Err(Error::InvalidEncoding(1 + idx));
+    }
+    if !hex.len().is_multiple_of(2) {
+        return Err(Error::OddEncodedLength(hex.len()));
+    }
+    let len = hex.len() / 2;
+    let out = buffer.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+    for (byte, pair) in out.iter_mut().zip(hex.chunks(2)) {
+        // both digits were checked above
+        *byte = hex_to_nibble(pair[0], lowercase).unwrap_or_default() << 4
+            | hex_to_nibble(pair[1], lowercase).unwrap_or_default();
+    }
+    let buffer: &'b [u8] = buffer;
+    Frame::try_from(&buffer[..len])
+}
+
+/// Number of characters in the ASCII encoding of `len` bytes of address, function and payload
+pub const fn encoded_len(len: usize) -> usize {
+    // start + hex pairs for the bytes and LRC + end
+    1 + 2 * (len + 1) + END.len()
+}
+
+/// Encode the address, function and payload of a frame (e.g. an RTU frame without its CRC) as ASCII characters
+/// in `out`, with the LRC and delimiters
+///
+/// `out` must hold at least `encoded_len(frame_bytes.len())` characters
+///
+/// ```
+/// use modbus_frames::{ascii, request};
+///
+/// let mut rtu = [0; 8];
+/// let (data, _) = request::read_holding_registers(&mut rtu, 0x11, 0x6B, 3).unwrap().parts();
+/// let mut out = [0; 32];
+/// assert_eq!(ascii::encode_in_place(data, &mut out).unwrap(), b":1103006B00037E\r\n");
+/// ```
+pub fn encode_in_place<'o>(frame_bytes: &[u8], out: &'o mut [u8]) -> Result<&'o [u8], Error> {
+    let len = encoded_len(frame_bytes.len());
+    let out = out.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+    out[0] = START;
+    let lrc = calculate_lrc(frame_bytes);
+    let bytes = frame_bytes.iter().chain(core::iter::once(&lrc));
+    for (pair, byte) in out[1..].chunks_exact_mut(2).zip(bytes) {
+        pair[0] = HEX_DIGITS[usize::from(byte >> 4)];
+        pair[1] = HEX_DIGITS[usize::from(byte & 0x0F)];
+    }
+    out[len - END.len()..].copy_from_slice(&END);
+    Ok(out)
+}
+
+const HEX_DIGITS: [u8; 16] = *b"0123456789ABCDEF";
+
+fn check_len(buffer: &[u8], len: usize) -> Result<(), Error> {
+    if buffer.len() < len {
+        Err(Error::BufferTooSmall)
+    } else {
+        Ok(())
+    }
+}
+
+/// upper case hex digits only as required by the specification, unless `lowercase` is set
+fn hex_to_nibble(c: u8, lowercase: bool) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        b'a'..=b'f' if lowercase => Some(c - b'a' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode_in_place, encoded_len, Frame};
+    use crate::{builder, exception, function, request, Error, ModbusFrame};
+
+    #[test]
+    fn decode_frames() {
+        let mut buffer = [0; 8];
+        let frame = decode(b":1103006B00037E\r\n", &mut buffer).unwrap();
+        assert_eq!(frame.address(), 0x11);
+        assert_eq!(frame.lrc(), 0x7E);
+        assert_eq!(
+            frame.raw_bytes(),
+            [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x7E]
+        );
+
+        assert_eq!(
+            decode(b":1103006B00037F\r\n", &mut buffer),
+            Err(Error::InvalidLrc)
+        );
+        assert_eq!(
+            decode(b"1103006B00037E\r\n", &mut buffer),
+            Err(Error::InvalidEncoding(0))
+        );
+        assert_eq!(
+            decode(b":1103006b00037E\r\n", &mut buffer),
+            Err(Error::InvalidEncoding(8))
+        );
+        assert_eq!(
+            decode(b":1103006B00037E\r\r", &mut buffer),
+            Err(Error::InvalidEncoding(16))
+        );
+        assert_eq!(
+            decode(b":1103006B00037E\n", &mut buffer),
+            Err(Error::InvalidEncoding(14))
+        );
+        assert_eq!(decode(b":", &mut buffer), Err(Error::InvalidEncoding(1)));
+        assert_eq!(
+            decode(b":1103006B00037\r\n", &mut buffer),
+            Err(Error::OddEncodedLength(13))
+        );
+        assert_eq!(
+            decode(b":1103006B00037E\r\n", &mut buffer[..4]),
+            Err(Error::BufferTooSmall)
+        );
+        assert_eq!(Frame::try_from(&[1, 2][..]), Err(Error::InvalidLength(2)));
+    }
+
+    #[test]
+    fn responses() {
+        let mut buffer = [0; 8];
+        let request = decode(b":1103006B00037E\r\n", &mut buffer).unwrap();
+        let mut response = [0; 8];
+        let (frame, _) = request
+            .response_builder(&mut response)
+            .byte(2)
+            .register(0x1234)
+            .finalise_ascii();
+        assert_eq!(Frame::try_from(frame.raw_bytes()), Ok(frame));
+        assert_eq!(frame.payload(), [2, 0x12, 0x34]);
+
+        let (frame, _) = request.response_exception(&mut response, exception::ILLEGAL_ADDRESS);
+        assert_eq!(
+            frame.function().0,
+            function::READ_HOLDING_REGISTERS.0 | 0x80
+        );
+        assert_eq!(frame.payload(), [exception::ILLEGAL_ADDRESS.0]);
+        assert!(Frame::try_from(frame.raw_bytes()).is_ok());
+
+        let (frame, _) = builder::build_frame(&mut response)
+            .for_address(1)
+            .function(function::READ_COILS)
+            .finalise_ascii();
+        assert_eq!(frame.raw_bytes(), [1, 1, 0xFE]);
+    }
+
+    #[test]
+    fn typed_api_over_ascii() {
+        let mut buffer = [0; 8];
+        let request = decode(b":1103006B00037E\r\n", &mut buffer).unwrap();
+        let mut rtu = [0; 8];
+        let decoded = request::ReadHoldingRegisters::try_from(request.to_rtu(&mut rtu).unwrap());
+        let decoded = decoded.unwrap();
+        assert_eq!(decoded.register_count(), 3);
+
+        let mut response = [0; 16];
+        let (response, _) = decoded.response_builder(&mut response, [1, 2, 3]);
+        let mut ascii = [0; 16];
+        let response = response.as_frame().to_ascii(&mut ascii).unwrap();
+        assert!(ModbusFrame::verify(&response));
+        assert_eq!(response.payload(), [6, 0, 1, 0, 2, 0, 3]);
+        assert_eq!(request.to_rtu(&mut rtu[..7]), Err(Error::BufferTooSmall));
+    }
+
+    #[test]
+    fn encode() {
+        let mut out = [0; 20];
+        let data = [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03];
+        assert_eq!(encoded_len(data.len()), 17);
+        let text = encode_in_place(&data, &mut out).unwrap();
+        assert_eq!(text, b":1103006B00037E\r\n");
+
+        let mut buffer = [0; 8];
+        let frame = decode(text, &mut buffer).unwrap();
+        assert_eq!(frame.raw_bytes()[..6], data);
+
+        assert_eq!(
+            encode_in_place(&data, &mut out[..16]),
+            Err(Error::BufferTooSmall)
+        );
+    }
+}
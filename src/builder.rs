@@ -1,11 +1,14 @@
 //! construct a modbus frame structure in a provided buffer
 //! internally, this uses the RTU format without the CRC
 
-use core::ops::Rem;
+use core::ops::{Range, Rem};
 
 use byteorder::ByteOrder;
 
-use crate::{calculate_crc16, frame::Frame, Exception, Function};
+use crate::{
+    ascii, calculate_crc16, calculate_lrc, frame::Frame, function, request, source::RegisterSource,
+    Error, Exception, Function, WordOrder,
+};
 
 /// Write modbus messages more conveniently and coherently using named operations.
 #[derive(Debug)]
@@ -13,7 +16,7 @@ use crate::{calculate_crc16, frame::Frame, Exception, Function};
 pub struct Builder<'b, STATE> {
     buffer: &'b mut [u8],
     idx: usize,
-    // typestate (0-sized type to limit available functions)
+    // typestate (tag type to limit available functions, 0-sized except where the state needs tracking)
     _state: STATE,
 }
 
@@ -26,6 +29,22 @@ pub struct AddFunction;
 /// Builder state tag type
 /// add data, then finalise to a frame
 pub struct AddData;
+/// Builder state tag type
+/// payload is complete for the function, finalise to a frame
+pub struct Complete;
+/// Builder state tag type
+/// write multiple holding registers header set, add register values then finalise to a frame
+pub struct AddRegisters;
+/// Builder state tag type
+/// write multiple coils header set, add coil values then finalise to a frame
+pub struct AddCoils {
+    coil_count: u16,
+}
+/// Builder state tag type
+/// payload bytes reserved, fill them through `slot` then finalise to a frame
+pub struct Reserved {
+    start: usize,
+}
 
 /// building frames conveniently
 /// ```
@@ -47,6 +66,101 @@ pub fn build_frame(buff: &'_ mut [u8]) -> Builder<'_, Initial> {
     }
 }
 
+/// Offset of the frame in the buffer given to [`build_frame_aligned`]
+pub const ALIGNED_FRAME_OFFSET: usize = 1;
+
+/// As [`build_frame`], starting the frame at `buffer[ALIGNED_FRAME_OFFSET]` so register data following a byte count
+/// is at an even offset of `buffer`
+///
+/// Register values start at offset 4 of `buffer` for read register responses and offset 8 for write multiple
+/// registers requests. In an [`AlignedBuffer`] they can then be read as aligned (big endian) `u16`s, e.g. by DMA.
+/// Single register writes, with the value at an even offset of the frame, are misaligned by this.
+/// ```
+/// use modbus_frames::builder::{self, AlignedBuffer};
+///
+/// let mut buffer = AlignedBuffer::<256>::new();
+/// let (frame, _) = builder::build_frame_aligned(&mut buffer)
+///     .for_address(1)
+///     .function(modbus_frames::function::READ_HOLDING_REGISTERS)
+///     .byte(4)
+///     .registers([1, 2])
+///     .finalise();
+/// assert_eq!(frame.raw_bytes().len(), 9);
+/// assert_eq!(buffer[4..8], [0, 1, 0, 2]);
+/// ```
+pub fn build_frame_aligned(buffer: &mut [u8]) -> Builder<'_, Initial> {
+    build_frame(buffer.get_mut(ALIGNED_FRAME_OFFSET..).unwrap_or_default())
+}
+
+/// Byte buffer aligned for `u16` access, see [`build_frame_aligned`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C, align(2))]
+pub struct AlignedBuffer<const N: usize>(pub [u8; N]);
+
+impl<const N: usize> AlignedBuffer<N> {
+    pub const fn new() -> Self {
+        AlignedBuffer([0; N])
+    }
+}
+
+impl<const N: usize> Default for AlignedBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> core::ops::Deref for AlignedBuffer<N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<const N: usize> core::ops::DerefMut for AlignedBuffer<N> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+/// Build a frame in the spare capacity of `vec`, which grows by the frame length
+///
+/// Avoids building in a stack buffer and copying, e.g. when `vec` is the element of a transmit queue
+/// ```
+/// use modbus_frames::builder;
+///
+/// let mut vec = heapless::Vec::<u8, 256>::new();
+/// let frame = builder::build_into_heapless(&mut vec, |b| b.for_address(1).read_coils(0, 8).finalise());
+/// assert_eq!(frame.raw_bytes().len(), 8);
+/// assert_eq!(vec.len(), 8);
+/// ```
+#[cfg(feature = "heapless")]
+pub fn build_into_heapless<const N: usize>(
+    vec: &mut heapless::Vec<u8, N>,
+    build: impl for<'x> FnOnce(Builder<'x, Initial>) -> (Frame<'x>, &'x mut [u8]),
+) -> Frame<'_> {
+    let start = vec.len();
+    // filling with zeros can't fail, the vector is only grown to its capacity
+    let _ = vec.resize(N, 0);
+    let len = build(build_frame(&mut vec[start..])).0.raw_bytes().len();
+    vec.truncate(start + len);
+    Frame::new_unchecked(&vec[start..])
+}
+
+/// As [`build_into_heapless`] for an `arrayvec::ArrayVec`
+#[cfg(feature = "arrayvec")]
+pub fn build_into_arrayvec<const N: usize>(
+    vec: &mut arrayvec::ArrayVec<u8, N>,
+    build: impl for<'x> FnOnce(Builder<'x, Initial>) -> (Frame<'x>, &'x mut [u8]),
+) -> Frame<'_> {
+    let start = vec.len();
+    vec.extend(core::iter::repeat_n(0, N - start));
+    let len = build(build_frame(&mut vec[start..])).0.raw_bytes().len();
+    vec.truncate(start + len);
+    Frame::new_unchecked(&vec[start..])
+}
+
 /// following functions can be used in any state to check on the builder progress if neccesary
 impl<'b, STATE> Builder<'b, STATE> {
     pub fn state(&'b self) -> &'b [u8] {
@@ -60,6 +174,33 @@ impl<'b, STATE> Builder<'b, STATE> {
     pub fn bytes_remaining(&self) -> usize {
         self.buffer.len() - self.idx
     }
+
+    fn into_state<NEXT>(self, state: NEXT) -> Builder<'b, NEXT> {
+        Builder {
+            buffer: self.buffer,
+            idx: self.idx,
+            _state: state,
+        }
+    }
+
+    fn finalise_frame(self) -> (Frame<'b>, &'b mut [u8]) {
+        let crc = calculate_crc16(&self.buffer[..self.idx]);
+        byteorder::LittleEndian::write_u16(&mut self.buffer[self.idx..], crc);
+        let (frame, remainder) = self.buffer.split_at_mut(self.idx + 2);
+        (Frame::new_unchecked(frame), remainder)
+    }
+
+    fn finalise_ascii_frame(self) -> (ascii::Frame<'b>, &'b mut [u8]) {
+        self.buffer[self.idx] = calculate_lrc(&self.buffer[..self.idx]);
+        let (frame, remainder) = self.buffer.split_at_mut(self.idx + 1);
+        (ascii::Frame::new_unchecked(frame), remainder)
+    }
+
+    fn finalise_frame_parts(self) -> (&'b [u8], [u8; 2], &'b mut [u8]) {
+        let crc = calculate_crc16(&self.buffer[..self.idx]).to_le_bytes();
+        let (data, remainder) = self.buffer.split_at_mut(self.idx);
+        (data, crc, remainder)
+    }
 }
 
 impl<'b> Builder<'b, Initial> {
@@ -71,6 +212,12 @@ impl<'b> Builder<'b, Initial> {
             _state: AddFunction {},
         }
     }
+
+    /// Exception response to `request`, from the same address with the exception function code
+    pub fn exception_to(self, request: &Frame, exception: Exception) -> (Frame<'b>, &'b mut [u8]) {
+        self.for_address(request.address())
+            .exception(request.function(), exception)
+    }
 }
 
 impl<'b> Builder<'b, AddFunction> {
@@ -90,6 +237,231 @@ impl<'b> Builder<'b, AddFunction> {
     }
 }
 
+/// The following functions set the function code and the payload layout for that function in one step.
+/// Only the remaining payload for the function can then be added, avoiding malformed requests
+///
+/// Quantities outside of the limits in [`request`](crate::request), e.g. more than `MAX_READ_REGISTERS` registers
+/// to read, panic in debug builds. The quantity of a write multiple request is checked when it is finished, `finalise`
+/// panics and `try_finalise` returns `Error::InvalidQuantity`.
+///
+/// ```
+/// use modbus_frames::{builder, request};
+///
+/// let mut buff = [0u8; 20];
+/// let (frame, _) = builder::build_frame(&mut buff)
+///                 .for_address(0x11)
+///                 .write_multiple_registers(1)
+///                 .registers([0xA, 0x102])
+///                 .finalise();
+/// let request = request::WriteMultipleHoldingRegisters::try_from(frame).unwrap();
+/// assert_eq!(request.register_count(), 2);
+/// ```
+impl<'b> Builder<'b, AddFunction> {
+    pub fn read_coils(self, start_index: u16, count: u16) -> Builder<'b, Complete> {
+        debug_assert_quantity(count, request::MAX_READ_BITS);
+        self.fixed_request(function::READ_COILS, start_index, count)
+    }
+
+    pub fn read_discrete_inputs(self, start_index: u16, count: u16) -> Builder<'b, Complete> {
+        debug_assert_quantity(count, request::MAX_READ_BITS);
+        self.fixed_request(function::READ_DISCRETE_INPUTS, start_index, count)
+    }
+
+    pub fn read_holding_registers(self, start_index: u16, count: u16) -> Builder<'b, Complete> {
+        debug_assert_quantity(count, request::MAX_READ_REGISTERS);
+        self.fixed_request(function::READ_HOLDING_REGISTERS, start_index, count)
+    }
+
+    pub fn read_input_registers(self, start_index: u16, count: u16) -> Builder<'b, Complete> {
+        debug_assert_quantity(count, request::MAX_READ_REGISTERS);
+        self.fixed_request(function::READ_INPUT_REGISTERS, start_index, count)
+    }
+
+    pub fn write_coil(self, index: u16, on: bool) -> Builder<'b, Complete> {
+        let value = if on { crate::COIL_ON } else { crate::COIL_OFF };
+        self.fixed_request(function::WRITE_COIL, index, value)
+    }
+
+    pub fn write_holding_register(self, index: u16, value: u16) -> Builder<'b, Complete> {
+        self.fixed_request(function::WRITE_HOLDING_REGISTER, index, value)
+    }
+
+    /// coil count and byte count are filled in by `finalise`
+    pub fn write_multiple_coils(self, start_index: u16) -> Builder<'b, AddCoils> {
+        self.function(function::WRITE_MULTIPLE_COILS)
+            // count placeholders
+            .registers([start_index, 0])
+            .byte(0)
+            .into_state(AddCoils { coil_count: 0 })
+    }
+
+    /// register count and byte count are filled in by `finalise`
+    pub fn write_multiple_registers(self, start_index: u16) -> Builder<'b, AddRegisters> {
+        self.function(function::WRITE_MULTIPLE_HOLDING_REGISTERS)
+            // count placeholders
+            .registers([start_index, 0])
+            .byte(0)
+            .into_state(AddRegisters {})
+    }
+
+    fn fixed_request(self, function: Function, first: u16, second: u16) -> Builder<'b, Complete> {
+        self.function(function)
+            .registers([first, second])
+            .into_state(Complete {})
+    }
+}
+
+impl<'b> Builder<'b, Complete> {
+    pub fn finalise(self) -> (Frame<'b>, &'b mut [u8]) {
+        self.finalise_frame()
+    }
+
+    /// finish without writing the CRC into the buffer, returning `(address/function/payload, crc, remainder)`
+    ///
+    /// for transmitting the CRC from a separate (e.g. scatter-gather DMA) descriptor
+    pub fn finalise_parts(self) -> (&'b [u8], [u8; 2], &'b mut [u8]) {
+        self.finalise_frame_parts()
+    }
+
+    /// finish as an ASCII frame, with an LRC in place of the CRC
+    pub fn finalise_ascii(self) -> (ascii::Frame<'b>, &'b mut [u8]) {
+        self.finalise_ascii_frame()
+    }
+}
+
+/// offset of the first value in a write multiple request
+const WRITE_MULTIPLE_VALUES_IDX: usize = 7;
+
+const QUANTITY_PANIC: &str = "quantity outside of the range allowed by the function";
+
+/// `count` as the quantity of a write multiple request if it is 1-`max`
+fn check_write_quantity(count: usize, max: u16) -> Result<u16, Error> {
+    u16::try_from(count)
+        .ok()
+        .filter(|count| (1..=max).contains(count))
+        .ok_or(Error::InvalidQuantity)
+}
+
+/// catch out of spec read quantities, which would otherwise build a malformed frame
+fn debug_assert_quantity(count: u16, max: u16) {
+    debug_assert!(
+        (1..=max).contains(&count),
+        "quantity {count} outside of 1-{max}"
+    );
+}
+
+impl<'b> Builder<'b, AddRegisters> {
+    /// registers copied into the frame data as big endian bytes
+    pub fn registers<I: IntoIterator<Item = u16>>(mut self, iter: I) -> Self {
+        for register in iter {
+            byteorder::BigEndian::write_u16(&mut self.buffer[self.idx..], register);
+            self.idx += 2;
+        }
+        self
+    }
+
+    /// register copied into the frame data as big endian bytes
+    pub fn register(self, r: u16) -> Self {
+        self.registers([r])
+    }
+
+    /// 32 bit value as two registers in `word_order`
+    pub fn register_pair_u32(self, value: u32, word_order: WordOrder) -> Self {
+        self.registers(word_order.split(value))
+    }
+
+    /// 32 bit float as two registers in `word_order`
+    pub fn register_pair_f32(self, value: f32, word_order: WordOrder) -> Self {
+        self.register_pair_u32(value.to_bits(), word_order)
+    }
+
+    /// 64 bit value as four registers in `word_order`
+    pub fn register_quad_u64(self, value: u64, word_order: WordOrder) -> Self {
+        self.registers(word_order.split_u64(value))
+    }
+
+    /// 64 bit float as four registers in `word_order`
+    pub fn register_quad_f64(self, value: f64, word_order: WordOrder) -> Self {
+        self.register_quad_u64(value.to_bits(), word_order)
+    }
+
+    /// # Panics
+    /// unless 1-123 registers were added, see `try_finalise`
+    pub fn finalise(self) -> (Frame<'b>, &'b mut [u8]) {
+        self.try_finalise().expect(QUANTITY_PANIC)
+    }
+
+    /// `Error::InvalidQuantity` unless 1-123 registers were added
+    pub fn try_finalise(self) -> Result<(Frame<'b>, &'b mut [u8]), Error> {
+        let count = check_write_quantity(
+            (self.idx - WRITE_MULTIPLE_VALUES_IDX) / 2,
+            request::MAX_WRITE_REGISTERS,
+        )?;
+        byteorder::BigEndian::write_u16(&mut self.buffer[4..], count);
+        self.buffer[6] = (count * 2) as u8;
+        Ok(self.finalise_frame())
+    }
+}
+
+impl<'b> Builder<'b, AddCoils> {
+    /// coils are packed into bytes, first coil in LSB
+    pub fn coils(mut self, coils: impl IntoIterator<Item = bool>) -> Self {
+        for coil in coils {
+            let bit_idx = self._state.coil_count.rem(u8::BITS as u16);
+            if bit_idx == 0 {
+                self.buffer[self.idx] = 0;
+                self.idx += 1;
+            }
+            if coil {
+                self.buffer[self.idx - 1] |= 1 << bit_idx;
+            }
+            self._state.coil_count = self._state.coil_count.saturating_add(1);
+        }
+        self
+    }
+
+    pub fn coil(self, coil: bool) -> Self {
+        self.coils([coil])
+    }
+
+    /// # Panics
+    /// unless 1-1968 coils were added, see `try_finalise`
+    pub fn finalise(self) -> (Frame<'b>, &'b mut [u8]) {
+        self.try_finalise().expect(QUANTITY_PANIC)
+    }
+
+    /// `Error::InvalidQuantity` unless 1-1968 coils were added
+    pub fn try_finalise(self) -> Result<(Frame<'b>, &'b mut [u8]), Error> {
+        let count = check_write_quantity(self._state.coil_count.into(), request::MAX_WRITE_COILS)?;
+        byteorder::BigEndian::write_u16(&mut self.buffer[4..], count);
+        self.buffer[6] = (self.idx - WRITE_MULTIPLE_VALUES_IDX) as u8;
+        Ok(self.finalise_frame())
+    }
+}
+
+impl<'b> Builder<'b, Reserved> {
+    /// the reserved bytes, the CRC is calculated over whatever they contain at `finalise`
+    pub fn slot(&mut self) -> &mut [u8] {
+        &mut self.buffer[self._state.start..self.idx]
+    }
+
+    /// continue adding data after the reserved bytes
+    pub fn resume(self) -> Builder<'b, AddData> {
+        self.into_state(AddData {})
+    }
+
+    pub fn finalise(self) -> (Frame<'b>, &'b mut [u8]) {
+        self.finalise_frame()
+    }
+
+    /// finish without writing the CRC into the buffer, returning `(address/function/payload, crc, remainder)`
+    ///
+    /// for transmitting the CRC from a separate (e.g. scatter-gather DMA) descriptor
+    pub fn finalise_parts(self) -> (&'b [u8], [u8; 2], &'b mut [u8]) {
+        self.finalise_frame_parts()
+    }
+}
+
 impl<'b> Builder<'b, AddData> {
     /// bytes copied directly into the frame data as is
     pub fn bytes<I: IntoIterator<Item = u8>>(mut self, iter: I) -> Builder<'b, AddData> {
@@ -132,6 +504,32 @@ impl<'b> Builder<'b, AddData> {
         (self, bit_count)
     }
 
+    /// reserve `len` payload bytes to be written in place through `slot`, e.g. by DMA or an external serialiser
+    ///
+    /// ```
+    /// use modbus_frames::{builder, function};
+    ///
+    /// let mut buff = [0u8; 20];
+    /// let mut builder = builder::build_frame(&mut buff)
+    ///                 .for_address(1)
+    ///                 .function(function::READ_HOLDING_REGISTERS)
+    ///                 .byte(4)
+    ///                 .reserve(4);
+    /// builder.slot().copy_from_slice(&[0, 1, 0, 2]);
+    /// let (frame, _) = builder.finalise();
+    /// assert_eq!(frame.payload(), [4, 0, 1, 0, 2]);
+    /// ```
+    pub fn reserve(self, len: usize) -> Builder<'b, Reserved> {
+        let start = self.idx;
+        assert!(
+            start + len <= self.buffer.len(),
+            "reserved bytes exceed the buffer"
+        );
+        let mut builder = self.into_state(Reserved { start });
+        builder.idx += len;
+        builder
+    }
+
     /// registers copied into the frame data as big endian bytes
     pub fn registers<I: IntoIterator<Item = u16>>(mut self, iter: I) -> Builder<'b, AddData> {
         for register in iter {
@@ -141,11 +539,86 @@ impl<'b> Builder<'b, AddData> {
         self
     }
 
+    /// registers `range` read from `source` into the frame data as big endian bytes
+    ///
+    /// stops at the first exception, which should become the response
+    pub fn registers_from_source(
+        self,
+        mut range: Range<u16>,
+        source: &(impl RegisterSource + ?Sized),
+    ) -> Result<Builder<'b, AddData>, Exception> {
+        range.try_fold(self, |builder, index| {
+            Ok(builder.register(source.read(index)?))
+        })
+    }
+
     /// register copied into the frame data as big endian bytes
     pub fn register(self, r: u16) -> Builder<'b, AddData> {
         self.registers([r].iter().copied())
     }
 
+    /// `write` fills the start of the free space and returns the number of bytes written
+    ///
+    /// For external serializers writing straight into the frame. The space passed excludes the CRC and anything
+    /// past the 256 byte frame limit.
+    /// ```
+    /// use modbus_frames::{builder, Function};
+    ///
+    /// let mut buff = [0u8; 20];
+    /// let (frame, _) = builder::build_frame(&mut buff)
+    ///                 .for_address(1)
+    ///                 .function(Function(0x41))
+    ///                 .payload_from(|free| {
+    ///                     free[..3].copy_from_slice(b"abc");
+    ///                     3
+    ///                 })
+    ///                 .finalise();
+    /// assert_eq!(frame.payload(), b"abc");
+    /// ```
+    ///
+    /// # Panics
+    /// if the returned length is longer than the space given
+    pub fn payload_from(mut self, write: impl FnOnce(&mut [u8]) -> usize) -> Builder<'b, AddData> {
+        let end = self.buffer.len().min(256) - 2;
+        let free = &mut self.buffer[self.idx..end];
+        let len = write(free);
+        assert!(
+            len <= end - self.idx,
+            "written length exceeds the space given"
+        );
+        self.idx += len;
+        self
+    }
+
+    /// 32 bit value as two registers in `word_order`
+    pub fn register_pair_u32(self, value: u32, word_order: WordOrder) -> Builder<'b, AddData> {
+        self.registers(word_order.split(value))
+    }
+
+    /// 32 bit float as two registers in `word_order`
+    pub fn register_pair_f32(self, value: f32, word_order: WordOrder) -> Builder<'b, AddData> {
+        self.register_pair_u32(value.to_bits(), word_order)
+    }
+
+    /// 64 bit value as four registers in `word_order`
+    pub fn register_quad_u64(self, value: u64, word_order: WordOrder) -> Builder<'b, AddData> {
+        self.registers(word_order.split_u64(value))
+    }
+
+    /// 64 bit float as four registers in `word_order`
+    pub fn register_quad_f64(self, value: f64, word_order: WordOrder) -> Builder<'b, AddData> {
+        self.register_quad_u64(value.to_bits(), word_order)
+    }
+
+    /// apply `add` for each item, e.g. writing a header per sub-request
+    pub fn for_each<T>(
+        self,
+        items: impl IntoIterator<Item = T>,
+        add: impl Fn(Self, T) -> Self,
+    ) -> Self {
+        items.into_iter().fold(self, add)
+    }
+
     pub fn count_following_bytes(mut self, to_count: impl FnOnce(Self) -> Self) -> Self {
         let current_idx = self.idx;
         self.idx += 1;
@@ -182,10 +655,197 @@ impl<'b> Builder<'b, AddData> {
     }
 
     pub fn finalise(self) -> (Frame<'b>, &'b mut [u8]) {
-        let crc = calculate_crc16(&self.buffer[..self.idx]);
-        byteorder::LittleEndian::write_u16(&mut self.buffer[self.idx..], crc);
-        let (frame, remainder) = self.buffer.split_at_mut(self.idx + 2);
-        (Frame::new_unchecked(frame), remainder)
+        self.finalise_frame()
+    }
+
+    /// finish without writing the CRC into the buffer, returning `(address/function/payload, crc, remainder)`
+    ///
+    /// for transmitting the CRC from a separate (e.g. scatter-gather DMA) descriptor
+    pub fn finalise_parts(self) -> (&'b [u8], [u8; 2], &'b mut [u8]) {
+        self.finalise_frame_parts()
+    }
+
+    /// finish as an ASCII frame, with an LRC in place of the CRC
+    pub fn finalise_ascii(self) -> (ascii::Frame<'b>, &'b mut [u8]) {
+        self.finalise_ascii_frame()
+    }
+
+    /// continue the frame in a second buffer, the bytes so far are the header
+    ///
+    /// For payloads which are already in (or are written by) e.g. a DMA region, so the header and payload can be
+    /// transmitted from their own buffers without copying either
+    /// ```
+    /// use modbus_frames::{builder, function};
+    ///
+    /// let mut header = [0u8; 3];
+    /// let mut dma = [0, 1, 0, 2, 0, 0];
+    /// let (frame, _) = builder::build_frame(&mut header)
+    ///                 .for_address(1)
+    ///                 .function(function::READ_HOLDING_REGISTERS)
+    ///                 .byte(4)
+    ///                 .split(&mut dma)
+    ///                 .filled(4)
+    ///                 .finalise();
+    /// assert_eq!(frame.iovec(), [&[1, 3, 4][..], &[0, 1, 0, 2, 0x2A, 0x32][..]]);
+    /// ```
+    pub fn split<'p>(self, payload: &'p mut [u8]) -> SplitBuilder<'b, 'p> {
+        let (header, _) = self.buffer.split_at_mut(self.idx);
+        SplitBuilder {
+            header,
+            payload,
+            idx: 0,
+        }
+    }
+}
+
+/// Builds the payload of a frame in a second buffer, see [`Builder::split`]
+///
+/// # Panics
+/// Like [`Builder`], adding more than the payload buffer holds panics, as does finalising without 2 bytes left
+/// for the CRC
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SplitBuilder<'h, 'p> {
+    header: &'h [u8],
+    payload: &'p mut [u8],
+    idx: usize,
+}
+
+impl<'h, 'p> SplitBuilder<'h, 'p> {
+    pub fn bytes_consumed(&self) -> usize {
+        self.header.len() + self.idx
+    }
+
+    /// bytes copied directly into the payload as is
+    pub fn bytes<I: IntoIterator<Item = u8>>(mut self, iter: I) -> Self {
+        for byte in iter {
+            self = self.byte(byte);
+        }
+        self
+    }
+
+    pub fn byte(mut self, b: u8) -> Self {
+        self.payload[self.idx] = b;
+        self.idx += 1;
+        self
+    }
+
+    /// registers copied into the payload as big endian bytes
+    pub fn registers<I: IntoIterator<Item = u16>>(mut self, iter: I) -> Self {
+        for register in iter {
+            byteorder::BigEndian::write_u16(&mut self.payload[self.idx..], register);
+            self.idx += 2;
+        }
+        self
+    }
+
+    pub fn register(self, r: u16) -> Self {
+        self.registers([r])
+    }
+
+    /// the next `len` bytes of the payload buffer were written in place, e.g. by DMA
+    ///
+    /// # Panics
+    /// if they don't leave room for the CRC
+    pub fn filled(mut self, len: usize) -> Self {
+        assert!(
+            self.idx + len + 2 <= self.payload.len(),
+            "filled bytes exceed the payload buffer"
+        );
+        self.idx += len;
+        self
+    }
+
+    /// `write` fills the start of the free payload space and returns the number of bytes written, as
+    /// [`Builder::payload_from`]
+    ///
+    /// # Panics
+    /// if the returned length is longer than the space given
+    pub fn payload_from(mut self, write: impl FnOnce(&mut [u8]) -> usize) -> Self {
+        let end = (self.payload.len() - 2).min(256 - 2 - self.header.len());
+        let free = &mut self.payload[self.idx..end];
+        let len = write(free);
+        assert!(
+            len <= end - self.idx,
+            "written length exceeds the space given"
+        );
+        self.idx += len;
+        self
+    }
+
+    /// write the CRC over both buffers after the payload, returning the frame and the rest of the payload buffer
+    pub fn finalise(self) -> (SplitFrame<'h, 'p>, &'p mut [u8]) {
+        let crc = crate::crc::update(
+            crate::crc::update(crate::crc::INIT, self.header),
+            &self.payload[..self.idx],
+        );
+        byteorder::LittleEndian::write_u16(&mut self.payload[self.idx..], crc);
+        let (payload, remainder) = self.payload.split_at_mut(self.idx + 2);
+        (
+            SplitFrame {
+                header: self.header,
+                payload,
+            },
+            remainder,
+        )
+    }
+}
+
+/// An RTU frame in two parts, the header and the payload followed by the CRC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitFrame<'h, 'p> {
+    header: &'h [u8],
+    payload: &'p [u8],
+}
+
+impl<'h, 'p> SplitFrame<'h, 'p> {
+    /// address, function and any payload built before splitting
+    pub fn header(&self) -> &'h [u8] {
+        self.header
+    }
+
+    /// the rest of the payload and the CRC
+    pub fn payload(&self) -> &'p [u8] {
+        self.payload
+    }
+
+    /// the two parts in transmission order, for a vectored write or chained DMA descriptors
+    pub fn iovec(&self) -> [&[u8]; 2] {
+        [self.header, self.payload]
+    }
+
+    pub fn len(&self) -> usize {
+        self.header.len() + self.payload.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// copy both parts into `buffer` as one frame
+    pub fn copy_into<'b>(&self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        let len = self.len();
+        let available = buffer.len();
+        let frame = buffer.get_mut(..len).ok_or(Error::InsufficientBuffer {
+            needed: len,
+            len: available,
+        })?;
+        let (header, payload) = frame.split_at_mut(self.header.len());
+        header.copy_from_slice(self.header);
+        payload.copy_from_slice(self.payload);
+        Frame::try_from(&*frame)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for SplitFrame<'_, '_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "SplitFrame {{ header: {=[u8]:02x}, payload: {=[u8]:02x} }}",
+            self.header,
+            self.payload
+        );
     }
 }
 
@@ -194,7 +854,7 @@ mod tests {
     use byteorder::ByteOrder;
 
     use super::build_frame;
-    use crate::{calculate_crc16, Function};
+    use crate::{calculate_crc16, request, Error, Function, WordOrder};
 
     #[test]
     fn test_builder() {
@@ -286,4 +946,247 @@ mod tests {
         assert_eq!(frame.payload()[2..4], [0, 10]); // 10 bits
         assert_eq!(frame.payload()[5..7], [0x62, 0x02]);
     }
+
+    #[test]
+    fn function_typestates() {
+        let mut buff = [0; 64];
+        let mut expected = [0; 64];
+
+        let (frame, _) = build_frame(&mut buff)
+            .for_address(0x11)
+            .read_holding_registers(0x6B, 3)
+            .finalise();
+        let (request, _) = request::ReadHoldingRegisters::new(&mut expected, 0x11, 0x6B, 3);
+        assert_eq!(frame.raw_bytes(), request.as_frame().raw_bytes());
+
+        let (frame, _) = build_frame(&mut buff)
+            .for_address(0xB)
+            .write_multiple_coils(27)
+            .coils([true, false, true, true])
+            .coil(false)
+            .coils([false, true, false, true])
+            .finalise();
+        let coils = [true, false, true, true, false, false, true, false, true];
+        let (request, _) = request::WriteMultipleCoils::new(&mut expected, 0xB, 27, coils);
+        assert_eq!(frame.raw_bytes(), request.as_frame().raw_bytes());
+
+        let (frame, _) = build_frame(&mut buff)
+            .for_address(0x11)
+            .write_multiple_registers(1)
+            .register(0xA)
+            .registers([0x102])
+            .finalise();
+        let (request, _) =
+            request::WriteMultipleHoldingRegisters::new(&mut expected, 0x11, 1, [0xA, 0x102]);
+        assert_eq!(frame.raw_bytes(), request.as_frame().raw_bytes());
+    }
+
+    #[test]
+    fn reserve() {
+        let mut buff = [0xAAu8; 12];
+        let mut builder = build_frame(&mut buff)
+            .for_address(1)
+            .function(Function(0x41))
+            .byte(3)
+            .reserve(3);
+        assert_eq!(builder.bytes_consumed(), 6);
+        builder.slot().copy_from_slice(&[7, 8, 9]);
+        let (frame, rem) = builder.resume().byte(10).finalise();
+        assert_eq!(frame.payload(), [3, 7, 8, 9, 10]);
+        assert!(crate::verify_crc16(frame.raw_bytes()));
+        assert_eq!(rem.len(), 3);
+    }
+
+    #[test]
+    fn finalise_parts() {
+        let mut buff = [0u8; 6];
+        let (data, crc, rem) = build_frame(&mut buff)
+            .for_address(0x11)
+            .read_holding_registers(0x6B, 3)
+            .finalise_parts();
+        assert_eq!(data, [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03]);
+        assert_eq!(crc, [0x76, 0x87]);
+        assert!(rem.is_empty());
+    }
+
+    #[test]
+    fn payload_from() {
+        let mut buff = [0u8; 300];
+        let (frame, rem) = build_frame(&mut buff)
+            .for_address(1)
+            .function(Function(0x41))
+            .count_following_bytes(|b| {
+                b.payload_from(|free| {
+                    // address, function, byte count and CRC leave 251 bytes
+                    assert_eq!(free.len(), 251);
+                    free[..2].copy_from_slice(&[7, 8]);
+                    2
+                })
+            })
+            .finalise();
+        assert_eq!(frame.payload(), [2, 7, 8]);
+        assert_eq!(rem.len(), 293);
+    }
+
+    #[test]
+    #[should_panic = "quantity outside of the range allowed by the function"]
+    fn out_of_spec_quantity() {
+        let mut buff = [0u8; 256];
+        let _ = build_frame(&mut buff)
+            .for_address(1)
+            .write_multiple_registers(0)
+            .registers([0; 124])
+            .finalise();
+    }
+
+    #[test]
+    fn try_finalise() {
+        let mut buff = [0u8; 512];
+        let registers = |buff: &mut [u8], count| {
+            build_frame(buff)
+                .for_address(1)
+                .write_multiple_registers(0)
+                .registers((0..count).map(|_| 0))
+                .try_finalise()
+                .map(|(frame, _)| frame.payload().to_vec())
+        };
+        assert_eq!(registers(&mut buff, 0), Err(Error::InvalidQuantity));
+        assert_eq!(registers(&mut buff, 123).unwrap()[2..5], [0, 123, 246]);
+        assert_eq!(registers(&mut buff, 124), Err(Error::InvalidQuantity));
+        assert_eq!(registers(&mut buff, 200), Err(Error::InvalidQuantity));
+
+        let coils = |buff: &mut [u8], count| {
+            build_frame(buff)
+                .for_address(1)
+                .write_multiple_coils(0)
+                .coils((0..count).map(|_| true))
+                .try_finalise()
+                .map(|(frame, _)| frame.payload().to_vec())
+        };
+        assert_eq!(coils(&mut buff, 0), Err(Error::InvalidQuantity));
+        assert_eq!(coils(&mut buff, 1968).unwrap()[2..5], [0x07, 0xB0, 246]);
+        assert_eq!(coils(&mut buff, 1969), Err(Error::InvalidQuantity));
+    }
+
+    #[test]
+    fn wide_values() {
+        let mut buff = [0u8; 64];
+        let (frame, _) = build_frame(&mut buff)
+            .for_address(1)
+            .write_multiple_registers(0)
+            .register_pair_u32(0x0102_0304, WordOrder::HighWordFirst)
+            .register_pair_f32(1.5, WordOrder::LowWordFirst)
+            .register_quad_u64(0x0102_0304_0506_0708, WordOrder::LowWordFirst)
+            .register_quad_f64(-2.0, WordOrder::HighWordFirst)
+            .finalise();
+        let request = request::WriteMultipleHoldingRegisters::try_from(frame).unwrap();
+        let registers = request.iter_registers().collect::<Vec<_>>();
+        assert_eq!(registers.len(), 12);
+        assert_eq!(registers[..2], [0x0102, 0x0304]);
+        assert_eq!(
+            WordOrder::LowWordFirst.join(registers[2], registers[3]),
+            1.5f32.to_bits()
+        );
+        assert_eq!(registers[4..8], [0x0708, 0x0506, 0x0304, 0x0102]);
+        let quad = [registers[8], registers[9], registers[10], registers[11]];
+        assert_eq!(
+            f64::from_bits(WordOrder::HighWordFirst.join_u64(quad)),
+            -2.0
+        );
+        assert_eq!(
+            WordOrder::LowWordFirst.join_u64(WordOrder::LowWordFirst.split_u64(u64::MAX - 5)),
+            u64::MAX - 5
+        );
+    }
+
+    #[cfg(all(feature = "heapless", feature = "arrayvec"))]
+    #[test]
+    fn vec_targets() {
+        let mut buff = [0u8; 8];
+        let expected = request::read_coils(&mut buff, 1, 0, 8).unwrap();
+
+        let mut vec = heapless::Vec::<u8, 16>::new();
+        vec.push(0xAA).unwrap();
+        let frame =
+            super::build_into_heapless(&mut vec, |b| b.for_address(1).read_coils(0, 8).finalise());
+        assert_eq!(frame, expected);
+        assert_eq!(vec.len(), 9);
+
+        let mut vec = arrayvec::ArrayVec::<u8, 8>::new();
+        let frame =
+            super::build_into_arrayvec(&mut vec, |b| b.for_address(1).read_coils(0, 8).finalise());
+        assert_eq!(frame, expected);
+        assert_eq!(vec.as_slice(), expected.raw_bytes());
+    }
+
+    #[test]
+    fn aligned_registers() {
+        let mut buffer = super::AlignedBuffer::<32>::new();
+        let (frame, rem) = super::build_frame_aligned(&mut buffer)
+            .for_address(1)
+            .write_multiple_registers(3)
+            .registers([0x1234, 0x5678])
+            .finalise();
+        assert_eq!(rem.len(), 32 - 1 - 13);
+        let mut expected = [0; 16];
+        let expected =
+            request::write_multiple_holding_registers(&mut expected, 1, 3, &[0x1234, 0x5678])
+                .unwrap();
+        assert_eq!(frame, expected);
+        let data = &buffer[super::ALIGNED_FRAME_OFFSET + 7..];
+        assert_eq!(data.as_ptr() as usize % 2, 0);
+        assert_eq!(data[..4], [0x12, 0x34, 0x56, 0x78]);
+        assert_eq!(core::mem::align_of::<super::AlignedBuffer<3>>(), 2);
+    }
+
+    #[test]
+    fn split_buffers() {
+        let mut header = [0u8; 12];
+        let mut payload = [0u8; 12];
+        let (frame, rem) = build_frame(&mut header)
+            .for_address(1)
+            .function(crate::function::WRITE_MULTIPLE_HOLDING_REGISTERS)
+            .registers([3, 3])
+            .byte(6)
+            .register(0x1234)
+            .split(&mut payload)
+            .register(0x5678)
+            .bytes([0x9A, 0xBC])
+            .finalise();
+        assert_eq!(frame.header().len(), 9);
+        assert_eq!(frame.payload().len(), 6);
+        assert_eq!(rem.len(), 6);
+        let mut expected = [0; 16];
+        let expected = request::write_multiple_holding_registers(
+            &mut expected,
+            1,
+            3,
+            &[0x1234, 0x5678, 0x9ABC],
+        )
+        .unwrap();
+        let mut joined = [0; 16];
+        assert_eq!(frame.copy_into(&mut joined), Ok(expected));
+        assert_eq!(frame.iovec().concat(), expected.raw_bytes());
+        assert_eq!(
+            frame.copy_into(&mut [0; 14]),
+            Err(crate::Error::InsufficientBuffer {
+                needed: 15,
+                len: 14
+            })
+        );
+
+        // payload written in place
+        let mut payload = [0u8; 8];
+        let (frame, _) = build_frame(&mut header)
+            .for_address(1)
+            .function(Function(0x41))
+            .split(&mut payload)
+            .payload_from(|free| {
+                assert_eq!(free.len(), 6);
+                free[..2].copy_from_slice(b"ab");
+                2
+            })
+            .finalise();
+        assert_eq!(frame.copy_into(&mut joined).unwrap().payload(), b"ab");
+    }
 }
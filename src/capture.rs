@@ -0,0 +1,173 @@
+//! Exporting captured frames to pcapng for Wireshark (feature `std`)
+//!
+//! [`PcapngWriter`] writes each frame as an enhanced packet block with its timestamp and, where known, its direction.
+//! There is no registered link type for Modbus RTU, frames are written with `LINKTYPE_USER0` (147). In Wireshark map
+//! it to the `mbrtu` protocol under Preferences > Protocols > DLT_USER.
+//!
+//! ```
+//! use modbus_frames::{capture::{Direction, PcapngWriter}, request};
+//!
+//! let mut buffer = [0; 8];
+//! let request = request::read_coils(&mut buffer, 1, 0, 8).unwrap();
+//! let mut writer = PcapngWriter::new(Vec::new()).unwrap();
+//! writer.write_frame(request, 1_000_000, Some(Direction::Outbound)).unwrap();
+//! let file = writer.into_inner();
+//! assert_eq!(file[..4], [0x0A, 0x0D, 0x0D, 0x0A]);
+//! ```
+
+use std::io::{self, Write};
+
+use crate::{monitor::Event, Frame};
+
+/// `LINKTYPE_USER0`, the first of the link types reserved for private use
+pub const LINKTYPE_USER0: u16 = 147;
+
+/// Frame direction relative to the capturing device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// received, e.g. a response seen by a master
+    Inbound,
+    /// sent, e.g. a request sent by a master
+    Outbound,
+}
+
+/// Writes frames to a pcapng file with microsecond timestamps
+#[derive(Debug)]
+pub struct PcapngWriter<W> {
+    out: W,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    /// Write the section header and the interface description for `LINKTYPE_USER0`
+    pub fn new(mut out: W) -> io::Result<Self> {
+        // section header: type, length, byte order magic, version 1.0, unknown section length, length
+        let mut header = Vec::with_capacity(48);
+        header.extend_from_slice(&0x0A0D_0D0A_u32.to_le_bytes());
+        header.extend_from_slice(&28_u32.to_le_bytes());
+        header.extend_from_slice(&0x1A2B_3C4D_u32.to_le_bytes());
+        header.extend_from_slice(&1_u16.to_le_bytes());
+        header.extend_from_slice(&0_u16.to_le_bytes());
+        header.extend_from_slice(&(-1_i64).to_le_bytes());
+        header.extend_from_slice(&28_u32.to_le_bytes());
+        // interface description: type, length, link type, reserved, snap length, length
+        // the default timestamp resolution is microseconds
+        header.extend_from_slice(&1_u32.to_le_bytes());
+        header.extend_from_slice(&20_u32.to_le_bytes());
+        header.extend_from_slice(&LINKTYPE_USER0.to_le_bytes());
+        header.extend_from_slice(&0_u16.to_le_bytes());
+        header.extend_from_slice(&256_u32.to_le_bytes());
+        header.extend_from_slice(&20_u32.to_le_bytes());
+        out.write_all(&header)?;
+        Ok(PcapngWriter { out })
+    }
+
+    /// Write `frame` captured at `timestamp_us` microseconds since the Unix epoch
+    pub fn write_frame(
+        &mut self,
+        frame: Frame,
+        timestamp_us: u64,
+        direction: Option<Direction>,
+    ) -> io::Result<()> {
+        let data = frame.raw_bytes();
+        let padded = data.len().next_multiple_of(4);
+        // epb_flags option and the end of options marker
+        let options = if direction.is_some() { 12 } else { 0 };
+        let len = (32 + padded + options) as u32;
+
+        let mut block = Vec::with_capacity(len as usize);
+        block.extend_from_slice(&6_u32.to_le_bytes());
+        block.extend_from_slice(&len.to_le_bytes());
+        // interface id
+        block.extend_from_slice(&0_u32.to_le_bytes());
+        block.extend_from_slice(&((timestamp_us >> 32) as u32).to_le_bytes());
+        block.extend_from_slice(&(timestamp_us as u32).to_le_bytes());
+        block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        block.extend_from_slice(data);
+        block.resize(28 + padded, 0);
+        if let Some(direction) = direction {
+            let flags: u32 = match direction {
+                Direction::Inbound => 1,
+                Direction::Outbound => 2,
+            };
+            block.extend_from_slice(&2_u16.to_le_bytes());
+            block.extend_from_slice(&4_u16.to_le_bytes());
+            block.extend_from_slice(&flags.to_le_bytes());
+            block.extend_from_slice(&[0; 4]);
+        }
+        block.extend_from_slice(&len.to_le_bytes());
+        self.out.write_all(&block)
+    }
+
+    /// Write a frame found by the monitor, requests as outbound and responses as inbound (as seen by the master)
+    ///
+    /// Garbage is skipped
+    pub fn write_event(&mut self, event: &Event, timestamp_us: u64) -> io::Result<()> {
+        match *event {
+            Event::Request(frame) => {
+                self.write_frame(frame, timestamp_us, Some(Direction::Outbound))
+            }
+            Event::Response { response, .. } => {
+                self.write_frame(response, timestamp_us, Some(Direction::Inbound))
+            }
+            Event::Garbage(_) => Ok(()),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Direction, PcapngWriter};
+    use crate::{monitor::Monitor, request};
+
+    fn read_u32(bytes: &[u8]) -> u32 {
+        u32::from_le_bytes(bytes[..4].try_into().unwrap())
+    }
+
+    #[test]
+    fn blocks() {
+        let mut buffer = [0; 8];
+        let request = request::read_coils(&mut buffer, 1, 0, 8).unwrap();
+        let mut writer = PcapngWriter::new(Vec::new()).unwrap();
+        writer
+            .write_frame(request, 0x1_0000_0002, Some(Direction::Inbound))
+            .unwrap();
+        for event in Monitor::new(request.raw_bytes()) {
+            writer.write_event(&event, 3).unwrap();
+        }
+        writer.write_frame(request, 4, None).unwrap();
+        let file = writer.into_inner();
+
+        // section header and interface description
+        assert_eq!(read_u32(&file[28..]), 1);
+        let mut blocks = Vec::new();
+        let mut offset = 48;
+        while offset < file.len() {
+            let len = read_u32(&file[offset + 4..]) as usize;
+            // lengths are repeated at the end of every block
+            assert_eq!(read_u32(&file[offset + len - 4..]), len as u32);
+            blocks.push(&file[offset..offset + len]);
+            offset += len;
+        }
+        assert_eq!(offset, file.len());
+        assert_eq!(blocks.len(), 3);
+
+        let block = blocks[0];
+        assert_eq!(read_u32(block), 6);
+        assert_eq!((read_u32(&block[12..]), read_u32(&block[16..])), (1, 2));
+        assert_eq!(read_u32(&block[20..]), 8);
+        assert_eq!(&block[28..36], request.raw_bytes());
+        // epb_flags inbound
+        assert_eq!(block[36..44], [2, 0, 4, 0, 1, 0, 0, 0]);
+        assert_eq!(blocks[1][40], 2);
+        assert_eq!(blocks[2].len(), 40);
+    }
+}
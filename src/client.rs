@@ -0,0 +1,443 @@
+//! Reading and writing values on a device from the client (master) side
+//!
+//! [`Client`] builds requests, sends them through a [`Transport`] and decodes the responses into host types.
+//! Reads pick the function code from the entity type, values wider than a register use the given [`WordOrder`].
+//!
+//! ```
+//! use modbus_frames::{client::{Client, Transport}, entity::Entity, server, Error, Frame, WordOrder};
+//! # struct Loopback<H>(H);
+//! # impl<H: server::Handler> Transport for Loopback<H> {
+//! #     fn transact<'b>(&mut self, request: Frame, response_buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+//! #         Ok(server::dispatch(&mut self.0, request.try_into()?, response_buffer))
+//! #     }
+//! # }
+//! # struct Device;
+//! # impl server::Handler for Device {
+//! #     fn read_input_register(&mut self, index: u16) -> Result<u16, modbus_frames::Exception> {
+//! #         Ok([0x4148, 0xF5C3][index as usize])
+//! #     }
+//! # }
+//! # let transport = Loopback(Device);
+//! let mut client = Client::new(transport, 1);
+//! let temperature = client.read_f32(Entity::input_register(0), WordOrder::HighWordFirst)?;
+//! assert_eq!(temperature, 12.56);
+//! # Ok::<(), Error>(())
+//! ```
+
+use crate::{
+    entity::{Entity, EntityRange},
+    quirks::Quirks,
+    regmap::{Block, Point, Value, ValueType},
+    request::{self, check_response},
+    trace::Trace,
+    Device, Error, Frame, WordOrder,
+};
+
+/// Moves frames between the client and the bus
+pub trait Transport {
+    /// Send `request` and receive the response into `response_buffer`
+    ///
+    /// Return `Error::NoResponse` if the device doesn't respond or the link fails
+    fn transact<'b>(
+        &mut self,
+        request: Frame,
+        response_buffer: &'b mut [u8],
+    ) -> Result<Frame<'b>, Error>;
+
+    /// As `transact`, accepting responses with the deviations in `quirks` (see [`Quirks::decode_frame`])
+    ///
+    /// The default ignores `quirks`, a transport checking the CRC of the response should override it
+    fn transact_with_quirks<'b>(
+        &mut self,
+        request: Frame,
+        response_buffer: &'b mut [u8],
+        _quirks: &Quirks,
+    ) -> Result<Frame<'b>, Error> {
+        self.transact(request, response_buffer)
+    }
+}
+
+/// Client for a single device
+pub struct Client<T, R = ()> {
+    transport: T,
+    device: Device,
+    retries: u8,
+    quirks: Quirks,
+    trace: R,
+    request_buffer: [u8; 256],
+    response_buffer: [u8; 256],
+}
+
+impl<T: Transport> Client<T> {
+    pub fn new(transport: T, address: u8) -> Self {
+        Client {
+            transport,
+            device: Device::new(address),
+            retries: 0,
+            quirks: Quirks::STRICT,
+            trace: (),
+            request_buffer: [0; 256],
+            response_buffer: [0; 256],
+        }
+    }
+}
+
+impl<T: Transport, R: Trace> Client<T, R> {
+    /// Report the frames sent and received to `trace`
+    pub fn with_trace<U: Trace>(self, trace: U) -> Client<T, U> {
+        Client {
+            transport: self.transport,
+            device: self.device,
+            retries: self.retries,
+            quirks: self.quirks,
+            trace,
+            request_buffer: self.request_buffer,
+            response_buffer: self.response_buffer,
+        }
+    }
+
+    /// Send requests up to `retries` more times when there is no valid response
+    ///
+    /// Exception responses are not retried
+    pub fn with_retries(self, retries: u8) -> Self {
+        Client { retries, ..self }
+    }
+
+    /// Talk to a device which doesn't quite follow the specification
+    ///
+    /// With `swapped_crc` requests are sent with a big endian CRC. `quirks` are passed to
+    /// [`Transport::transact_with_quirks`] for the responses, transports using the default accept only what the
+    /// specification allows
+    pub fn with_quirks(self, quirks: Quirks) -> Self {
+        Client { quirks, ..self }
+    }
+
+    pub fn trace(&mut self) -> &mut R {
+        &mut self.trace
+    }
+
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// Direct later requests to a different device
+    pub fn set_device(&mut self, device: Device) {
+        self.device = device;
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+
+    /// Send a request built by `build` in the client's request buffer and return the validated response
+    pub fn transact(
+        &mut self,
+        build: impl FnOnce(&mut [u8], u8) -> Result<Frame<'_>, Error>,
+    ) -> Result<Frame<'_>, Error> {
+        let len = build(&mut self.request_buffer, self.device.address())?
+            .raw_bytes()
+            .len();
+        self.quirks.encode_crc(&mut self.request_buffer[..len]);
+        let request = Frame::new_unchecked(&self.request_buffer[..len]);
+        let mut attempt = 0;
+        let len = loop {
+            self.trace.on_tx(&request);
+            let result = self
+                .transport
+                .transact_with_quirks(request, &mut self.response_buffer, &self.quirks)
+                .and_then(|response| {
+                    self.trace.on_rx(response.raw_bytes());
+                    check_response(request, response).map(|_| response.raw_bytes().len())
+                });
+            match result {
+                Err(error) => {
+                    self.trace.on_error(&error);
+                    let retry = matches!(
+                        error,
+                        Error::NoResponse | Error::InvalidCrc | Error::InvalidLength(_)
+                    );
+                    if !retry || attempt == self.retries {
+                        return Err(error);
+                    }
+                    attempt += 1;
+                    self.trace.on_retry(attempt);
+                }
+                Ok(len) => break len,
+            }
+        };
+        Ok(Frame::new_unchecked(&self.response_buffer[..len]))
+    }
+
+    fn read_value(
+        &mut self,
+        entity: Entity,
+        value_type: ValueType,
+        word_order: WordOrder,
+    ) -> Result<Value, Error> {
+        let point = Point::new("", entity, value_type).with_word_order(word_order);
+        let block = Block {
+            entity_type: entity.entity_type,
+            start_index: entity.index,
+            count: value_type.width(),
+        };
+        let response = self.transact(|buffer, address| block.request(buffer, address))?;
+        point.value(&block, response)
+    }
+
+    /// Read a coil or discrete input
+    pub fn read_bool(&mut self, entity: Entity) -> Result<bool, Error> {
+        let value = self.read_value(entity, ValueType::Bool, WordOrder::HighWordFirst)?;
+        value.as_bool().ok_or(Error::InvalidValue)
+    }
+
+    /// Read a holding or input register
+    pub fn read_u16(&mut self, entity: Entity) -> Result<u16, Error> {
+        match self.read_value(entity, ValueType::U16, WordOrder::HighWordFirst)? {
+            Value::U16(value) => Ok(value),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Read two holding or input registers starting at `entity`
+    pub fn read_u32(&mut self, entity: Entity, word_order: WordOrder) -> Result<u32, Error> {
+        match self.read_value(entity, ValueType::U32, word_order)? {
+            Value::U32(value) => Ok(value),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Read two holding or input registers starting at `entity`
+    pub fn read_f32(&mut self, entity: Entity, word_order: WordOrder) -> Result<f32, Error> {
+        match self.read_value(entity, ValueType::F32, word_order)? {
+            Value::F32(value) => Ok(value),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Read a string of up to `out.len()` bytes, two per register with the first in the high byte
+    ///
+    /// Trailing NUL bytes are removed, `Error::InvalidValue` if the string isn't UTF-8
+    pub fn read_string<'s>(&mut self, entity: Entity, out: &'s mut [u8]) -> Result<&'s str, Error> {
+        if entity.entity_type.is_bit() {
+            return Err(Error::InvalidValue);
+        }
+        let count = out.len().div_ceil(2);
+        let count = u16::try_from(count).map_err(|_| Error::InvalidQuantity)?;
+        let range = EntityRange::new(entity.entity_type, entity.index, count);
+        let response = self.transact(|buffer, address| range.read_request(buffer, address))?;
+        let data = response.payload().get(1..).unwrap_or(&[]);
+        let bytes = data.get(..out.len()).ok_or(Error::InvalidValue)?;
+        out.copy_from_slice(bytes);
+        let len = out.iter().rposition(|b| *b != 0).map_or(0, |last| last + 1);
+        core::str::from_utf8(&out[..len]).map_err(|_| Error::InvalidValue)
+    }
+
+    /// Write a single coil
+    pub fn write_bool(&mut self, index: u16, on: bool) -> Result<(), Error> {
+        self.transact(|buffer, address| request::write_coil(buffer, address, index, on))
+            .map(drop)
+    }
+
+    /// Write a single holding register
+    pub fn write_u16(&mut self, index: u16, value: u16) -> Result<(), Error> {
+        self.transact(|buffer, address| {
+            request::write_holding_register(buffer, address, index, value)
+        })
+        .map(drop)
+    }
+
+    /// Write two holding registers starting at `index`
+    pub fn write_u32(
+        &mut self,
+        index: u16,
+        value: u32,
+        word_order: WordOrder,
+    ) -> Result<(), Error> {
+        let registers = word_order.split(value);
+        self.transact(|buffer, address| {
+            request::write_multiple_holding_registers(buffer, address, index, &registers)
+        })
+        .map(drop)
+    }
+
+    /// Write two holding registers starting at `index`
+    pub fn write_f32(
+        &mut self,
+        index: u16,
+        value: f32,
+        word_order: WordOrder,
+    ) -> Result<(), Error> {
+        self.write_u32(index, value.to_bits(), word_order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Client, Transport};
+    use crate::{
+        entity::Entity, exception, quirks::Quirks, server, trace::Trace, Error, Exception, Frame,
+        ValidatedFrame, WordOrder,
+    };
+
+    #[derive(Default)]
+    struct Registers([u16; 8]);
+
+    impl server::Handler for Registers {
+        fn read_holding_register(&mut self, index: u16) -> Result<u16, Exception> {
+            self.0
+                .get(index as usize)
+                .copied()
+                .ok_or(exception::ILLEGAL_ADDRESS)
+        }
+
+        fn write_holding_register(&mut self, index: u16, value: u16) -> Result<(), Exception> {
+            let reg = self
+                .0
+                .get_mut(index as usize)
+                .ok_or(exception::ILLEGAL_ADDRESS)?;
+            *reg = value;
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Loopback {
+        registers: Registers,
+        /// number of requests to ignore
+        drop: u8,
+        /// send and expect the CRC big endian
+        swapped_crc: bool,
+    }
+
+    impl Loopback {
+        /// the length of the response written to `response_buffer`
+        fn respond(&mut self, request: Frame, response_buffer: &mut [u8]) -> Result<usize, Error> {
+            if self.drop > 0 {
+                self.drop -= 1;
+                return Err(Error::NoResponse);
+            }
+            let quirks = Quirks {
+                swapped_crc: self.swapped_crc,
+                ..Quirks::STRICT
+            };
+            let mut received = [0; 256];
+            let received = &mut received[..request.raw_bytes().len()];
+            received.copy_from_slice(request.raw_bytes());
+            quirks.encode_crc(received);
+            let Ok(request) = ValidatedFrame::try_from(&*received) else {
+                return Err(Error::NoResponse);
+            };
+            let len = server::dispatch(&mut self.registers, request, response_buffer)
+                .raw_bytes()
+                .len();
+            quirks.encode_crc(&mut response_buffer[..len]);
+            Ok(len)
+        }
+    }
+
+    impl Transport for Loopback {
+        fn transact<'b>(
+            &mut self,
+            request: Frame,
+            response_buffer: &'b mut [u8],
+        ) -> Result<Frame<'b>, Error> {
+            let len = self.respond(request, response_buffer)?;
+            Frame::try_from(&response_buffer[..len])
+        }
+
+        fn transact_with_quirks<'b>(
+            &mut self,
+            request: Frame,
+            response_buffer: &'b mut [u8],
+            quirks: &Quirks,
+        ) -> Result<Frame<'b>, Error> {
+            let len = self.respond(request, response_buffer)?;
+            quirks.decode_frame(&response_buffer[..len])
+        }
+    }
+
+    #[derive(Default)]
+    struct Retries(Vec<u8>);
+
+    impl Trace for Retries {
+        fn on_retry(&mut self, attempt: u8) {
+            self.0.push(attempt);
+        }
+    }
+
+    #[test]
+    fn typed_values() {
+        let mut client = Client::new(Loopback::default(), 1);
+
+        client.write_f32(0, 1.5, WordOrder::LowWordFirst).unwrap();
+        assert_eq!(client.transport().registers.0[..2], [0x0000, 0x3FC0]);
+        assert_eq!(
+            client.read_f32(Entity::holding_register(0), WordOrder::LowWordFirst),
+            Ok(1.5)
+        );
+        client
+            .write_u32(2, 0x0001_0002, WordOrder::HighWordFirst)
+            .unwrap();
+        assert_eq!(
+            client.read_u32(Entity::holding_register(2), WordOrder::HighWordFirst),
+            Ok(0x0001_0002)
+        );
+        client.write_u16(4, u16::from_be_bytes(*b"hi")).unwrap();
+        let mut out = [0; 4];
+        assert_eq!(
+            client.read_string(Entity::holding_register(4), &mut out),
+            Ok("hi")
+        );
+
+        assert_eq!(
+            client.read_u16(Entity::holding_register(8)),
+            Err(Error::Exception(exception::ILLEGAL_ADDRESS))
+        );
+        assert_eq!(
+            client.read_u16(Entity::input_register(0)),
+            Err(Error::Exception(exception::ILLEGAL_FUNCTION))
+        );
+    }
+
+    #[test]
+    fn retries() {
+        let transport = Loopback {
+            drop: 2,
+            ..Default::default()
+        };
+        let mut client = Client::new(transport, 1)
+            .with_retries(1)
+            .with_trace(Retries::default());
+        assert_eq!(
+            client.read_u16(Entity::holding_register(0)),
+            Err(Error::NoResponse)
+        );
+        assert_eq!(client.read_u16(Entity::holding_register(0)), Ok(0));
+        assert_eq!(client.trace().0, [1]);
+    }
+
+    #[test]
+    fn swapped_crc() {
+        let transport = Loopback {
+            swapped_crc: true,
+            ..Default::default()
+        };
+        let mut client = Client::new(transport, 1);
+        assert_eq!(client.write_u16(0, 7), Err(Error::NoResponse));
+        let quirks = Quirks {
+            swapped_crc: true,
+            ..Quirks::STRICT
+        };
+        let mut client = client.with_quirks(quirks);
+        client.write_u16(0, 7).unwrap();
+        assert_eq!(client.read_u16(Entity::holding_register(0)), Ok(7));
+        assert_eq!(
+            client.read_u16(Entity::holding_register(8)),
+            Err(Error::Exception(exception::ILLEGAL_ADDRESS))
+        );
+    }
+}
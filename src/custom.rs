@@ -0,0 +1,181 @@
+//! Typed frames for vendor specific function codes
+//!
+//! [`CustomFunction`] provides the `FunctionCode`, `PacketLen` and `TryFrom` implementations the built in
+//! request/response types have, for any function code and payload length. A type alias is all a new function needs.
+//!
+//! ```
+//! use modbus_frames::{custom::CustomFunction, Error};
+//!
+//! // 0x41 with a two byte payload
+//! type ReadSerial<'a> = CustomFunction<'a, 0x41, 2, 2>;
+//!
+//! let mut buffer = [0; 8];
+//! let (request, _) = ReadSerial::new(&mut buffer, 1, &[0, 7]);
+//! let frame = request.as_frame();
+//! assert_eq!(ReadSerial::try_from(frame).unwrap().payload(), [0, 7]);
+//!
+//! let mut buffer = [0; 8];
+//! let frame = modbus_frames::request::read_coils(&mut buffer, 1, 0, 1).unwrap();
+//! assert!(matches!(ReadSerial::try_from(frame), Err(Error::UnexpectedFunction { .. })));
+//! ```
+
+use crate::{
+    builder, response::exception_error, Error, Exception, Frame, Function, FunctionCode, LenHint,
+    PacketLen,
+};
+
+/// A frame with function code `FC` and `MIN_PAYLOAD..=MAX_PAYLOAD` payload bytes
+///
+/// Used for requests and responses alike, exception responses decode as `Error::Exception`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CustomFunction<'a, const FC: u8, const MIN_PAYLOAD: u8 = 0, const MAX_PAYLOAD: u8 = 252>
+{
+    frame: Frame<'a>,
+}
+
+impl<'a, const FC: u8, const MIN_PAYLOAD: u8, const MAX_PAYLOAD: u8>
+    CustomFunction<'a, FC, MIN_PAYLOAD, MAX_PAYLOAD>
+{
+    pub fn new(frame_buffer: &'a mut [u8], address: u8, payload: &[u8]) -> (Self, &'a mut [u8]) {
+        let (frame, rem) = builder::build_frame(frame_buffer)
+            .for_address(address)
+            .function(Self::FUNCTION)
+            .bytes(payload.iter().copied())
+            .finalise();
+        (Self::from_frame_unchecked(frame), rem)
+    }
+
+    pub fn from_bytes_unchecked(bytes: &'a [u8]) -> Self {
+        Self {
+            frame: Frame::new_unchecked(bytes),
+        }
+    }
+
+    pub fn from_frame_unchecked(frame: Frame<'a>) -> Self {
+        Self { frame }
+    }
+
+    pub fn as_frame(&self) -> Frame<'a> {
+        self.frame
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
+    pub fn response_exception<'buff>(
+        &self,
+        response_buffer: &'buff mut [u8],
+        exception: Exception,
+    ) -> (Frame<'buff>, &'buff mut [u8]) {
+        self.frame.response_exception(response_buffer, exception)
+    }
+}
+
+impl<const FC: u8, const MIN_PAYLOAD: u8, const MAX_PAYLOAD: u8> PacketLen
+    for CustomFunction<'_, FC, MIN_PAYLOAD, MAX_PAYLOAD>
+{
+    fn packet_len(&self) -> u8 {
+        self.frame.raw_bytes().len() as u8
+    }
+
+    fn minimum_len() -> u8 {
+        4 + MIN_PAYLOAD
+    }
+
+    fn is_valid_len(len: usize) -> bool {
+        (usize::from(MIN_PAYLOAD) + 4..=usize::from(MAX_PAYLOAD) + 4).contains(&len)
+    }
+
+    fn len_hint() -> LenHint {
+        if MIN_PAYLOAD == MAX_PAYLOAD {
+            LenHint::Exact(Self::minimum_len())
+        } else {
+            LenHint::AtLeast(Self::minimum_len())
+        }
+    }
+}
+
+impl<const FC: u8, const MIN_PAYLOAD: u8, const MAX_PAYLOAD: u8> FunctionCode
+    for CustomFunction<'_, FC, MIN_PAYLOAD, MAX_PAYLOAD>
+{
+    const FUNCTION: Function = Function(FC);
+}
+
+impl<'a, const FC: u8, const MIN_PAYLOAD: u8, const MAX_PAYLOAD: u8> TryFrom<&'a [u8]>
+    for CustomFunction<'a, FC, MIN_PAYLOAD, MAX_PAYLOAD>
+{
+    type Error = crate::Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        let frame = Frame::try_from(bytes)?;
+        Self::try_from(frame)
+    }
+}
+
+impl<'a, const FC: u8, const MIN_PAYLOAD: u8, const MAX_PAYLOAD: u8> TryFrom<Frame<'a>>
+    for CustomFunction<'a, FC, MIN_PAYLOAD, MAX_PAYLOAD>
+{
+    type Error = crate::Error;
+
+    fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
+        if frame.function().0 == FC | 0x80 {
+            Err(exception_error(frame))
+        } else if frame.function() != Self::FUNCTION {
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
+        } else if !Self::is_valid_len(frame.raw_bytes().len()) {
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
+        } else {
+            Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
+        }
+    }
+}
+
+impl<'a, const FC: u8, const MIN_PAYLOAD: u8, const MAX_PAYLOAD: u8>
+    From<CustomFunction<'a, FC, MIN_PAYLOAD, MAX_PAYLOAD>> for Frame<'a>
+{
+    fn from(custom: CustomFunction<'a, FC, MIN_PAYLOAD, MAX_PAYLOAD>) -> Frame<'a> {
+        custom.frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CustomFunction;
+    use crate::{exception, Error, LenHint};
+
+    type Fixed<'a> = CustomFunction<'a, 0x64, 2, 2>;
+    type Variable<'a> = CustomFunction<'a, 0x64>;
+
+    #[test]
+    fn custom_lengths() {
+        let mut buffer = [0; 16];
+        let (frame, _) = Variable::new(&mut buffer, 1, &[1, 2, 3]);
+        let frame = frame.as_frame();
+        assert_eq!(Variable::try_from(frame).unwrap().payload(), [1, 2, 3]);
+        assert_eq!(
+            Fixed::try_from(frame),
+            Err(Error::DecodeInvalidLength {
+                function: crate::Function(0x64),
+                len: 7,
+                expected: LenHint::Exact(6),
+            })
+        );
+
+        let mut response_buffer = [0; 8];
+        let (exception, _) = Fixed::from_frame_unchecked(frame)
+            .response_exception(&mut response_buffer, exception::DEVICE_BUSY);
+        assert_eq!(
+            Variable::try_from(exception),
+            Err(Error::Exception(exception::DEVICE_BUSY))
+        );
+    }
+}
@@ -1,6 +1,9 @@
 //! Take bytes, turn into outputs
 
-use crate::{frame::Frame, function, request, response, Error};
+use crate::{
+    builder, frame::Frame, function, identity::IdentityResponse, quirks::Quirks, request, response,
+    server, Error, Exception, Function, LenHint,
+};
 
 /// The default responses for a decode type
 /// ```
@@ -14,7 +17,7 @@ use crate::{frame::Frame, function, request, response, Error};
 /// let decoded = CommonRequests::try_from(command_frame).unwrap();
 /// assert!(matches!(decoded, CommonRequests::ReadCoils(_)));
 /// ```
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum CommonRequests<'a> {
     ReadCoils(request::ReadCoils<'a>),
@@ -28,9 +31,18 @@ pub enum CommonRequests<'a> {
 }
 
 impl<'a> CommonRequests<'a> {
-    pub fn as_frame(&self) -> Frame {
+    pub fn as_frame(&self) -> Frame<'_> {
         (*self).into()
     }
+
+    /// Exception response to this request
+    pub fn exception_response<'buff>(
+        &self,
+        response_buffer: &'buff mut [u8],
+        exception: Exception,
+    ) -> (Frame<'buff>, &'buff mut [u8]) {
+        builder::build_frame(response_buffer).exception_to(&self.as_frame(), exception)
+    }
 }
 
 impl<'a> From<CommonRequests<'a>> for Frame<'a> {
@@ -54,6 +66,11 @@ impl<'a> TryFrom<&'a [u8]> for CommonRequests<'a> {
     // e.g. which function code
     type Error = crate::Error;
 
+    #[cfg_attr(
+        all(feature = "no-panic", not(debug_assertions)),
+        no_panic::no_panic,
+        inline(never)
+    )]
     fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
         let frame = Frame::try_from(bytes)?;
         Self::try_from(frame)
@@ -63,6 +80,7 @@ impl<'a> TryFrom<&'a [u8]> for CommonRequests<'a> {
 impl<'a> TryFrom<Frame<'a>> for CommonRequests<'a> {
     type Error = crate::Error;
 
+    #[inline(always)]
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
         match frame.function() {
             function::READ_COILS => request::ReadCoils::try_from(frame).map(Self::ReadCoils),
@@ -87,7 +105,7 @@ impl<'a> TryFrom<Frame<'a>> for CommonRequests<'a> {
                     .map(Self::WriteMultipleHoldingRegisters)
             }
             // unknwn function code
-            _ => Err(Error::UnknownFunction),
+            _ => Err(Error::UnknownFunction(frame.function())),
         }
     }
 }
@@ -105,7 +123,7 @@ impl<'a> TryFrom<Frame<'a>> for CommonRequests<'a> {
 /// let decoded = CommonResponses::try_from(response_frame.0).unwrap();
 /// assert!(matches!(decoded, CommonResponses::ReadCoils(_)));
 /// ```
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum CommonResponses<'a> {
     ReadCoils(response::ReadCoils<'a>),
@@ -119,7 +137,7 @@ pub enum CommonResponses<'a> {
 }
 
 impl<'a> CommonResponses<'a> {
-    pub fn as_frame(&self) -> Frame {
+    pub fn as_frame(&self) -> Frame<'_> {
         (*self).into()
     }
 }
@@ -145,6 +163,11 @@ impl<'a> TryFrom<&'a [u8]> for CommonResponses<'a> {
     // e.g. which function code
     type Error = crate::Error;
 
+    #[cfg_attr(
+        all(feature = "no-panic", not(debug_assertions)),
+        no_panic::no_panic,
+        inline(never)
+    )]
     fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
         let frame = Frame::try_from(bytes)?;
         Self::try_from(frame)
@@ -154,7 +177,11 @@ impl<'a> TryFrom<&'a [u8]> for CommonResponses<'a> {
 impl<'a> TryFrom<Frame<'a>> for CommonResponses<'a> {
     type Error = crate::Error;
 
+    #[inline(always)]
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
+        if frame.function().0 & 0x80 != 0 {
+            return Err(response::exception_error(frame));
+        }
         match frame.function() {
             function::READ_COILS => response::ReadCoils::try_from(frame).map(Self::ReadCoils),
             function::READ_DISCRETE_INPUTS => {
@@ -178,16 +205,357 @@ impl<'a> TryFrom<Frame<'a>> for CommonResponses<'a> {
                     .map(Self::WriteMultipleHoldingRegisters)
             }
             // unknwn function code
-            _ => Err(Error::UnknownFunction),
+            _ => Err(Error::UnknownFunction(frame.function())),
+        }
+    }
+}
+
+/// Every standard request, for gateways and monitors which need to decode the complete set
+///
+/// Functions without a typed wrapper decode as `Untyped` once `server::validate_request_frame` accepts them, a frame
+/// it rejects is an `Error::DecodeInvalidLength`.
+/// ```
+/// use modbus_frames::{decoder::{AllRequests, CommonRequests}, request};
+///
+/// let mut buf = [0; 8];
+/// let frame = request::read_coils(&mut buf, 1, 0, 8).unwrap();
+/// assert!(matches!(AllRequests::try_from(frame), Ok(AllRequests::Common(CommonRequests::ReadCoils(_)))));
+/// let frame = request::read_fifo_queue(&mut buf, 1, 4).unwrap();
+/// assert_eq!(AllRequests::try_from(frame), Ok(AllRequests::Untyped(frame)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AllRequests<'a> {
+    Common(CommonRequests<'a>),
+    CanOpenGeneralReference(request::CanOpenGeneralReference<'a>),
+    /// a standard function without a typed wrapper
+    Untyped(Frame<'a>),
+}
+
+impl<'a> AllRequests<'a> {
+    pub fn as_frame(&self) -> Frame<'_> {
+        (*self).into()
+    }
+}
+
+impl<'a> From<AllRequests<'a>> for Frame<'a> {
+    fn from(request: AllRequests<'a>) -> Self {
+        match request {
+            AllRequests::Common(req) => req.into(),
+            AllRequests::CanOpenGeneralReference(req) => req.as_frame(),
+            AllRequests::Untyped(frame) => frame,
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for AllRequests<'a> {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        let frame = Frame::try_from(bytes)?;
+        Self::try_from(frame)
+    }
+}
+
+impl<'a> TryFrom<Frame<'a>> for AllRequests<'a> {
+    type Error = crate::Error;
+
+    fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
+        match CommonRequests::try_from(frame) {
+            Err(Error::UnknownFunction(_)) => {}
+            result => return result.map(Self::Common),
+        }
+        if is_can_open(frame) {
+            request::CanOpenGeneralReference::try_from(frame).map(Self::CanOpenGeneralReference)
+        } else if frame.function().name().is_some() {
+            server::validate_request_frame(frame)
+                .map(|_| Self::Untyped(frame))
+                .map_err(|_| Error::DecodeInvalidLength {
+                    function: frame.function(),
+                    len: frame.raw_bytes().len(),
+                    expected: untyped_request_len(frame.function()),
+                })
+        } else {
+            Err(Error::UnknownFunction(frame.function()))
+        }
+    }
+}
+
+/// Every standard response, the counterpart of [`AllRequests`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AllResponses<'a> {
+    Common(CommonResponses<'a>),
+    CanOpenGeneralReference(response::CanOpenGeneralReference<'a>),
+    Identity(IdentityResponse<'a>),
+    /// a standard function without a typed wrapper
+    Untyped(Frame<'a>),
+}
+
+impl<'a> AllResponses<'a> {
+    pub fn as_frame(&self) -> Frame<'_> {
+        (*self).into()
+    }
+}
+
+impl<'a> From<AllResponses<'a>> for Frame<'a> {
+    fn from(response: AllResponses<'a>) -> Self {
+        match response {
+            AllResponses::Common(res) => res.into(),
+            AllResponses::CanOpenGeneralReference(res) => res.as_frame(),
+            AllResponses::Identity(res) => res.as_frame(),
+            AllResponses::Untyped(frame) => frame,
         }
     }
 }
 
+impl<'a> TryFrom<&'a [u8]> for AllResponses<'a> {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        let frame = Frame::try_from(bytes)?;
+        Self::try_from(frame)
+    }
+}
+
+impl<'a> TryFrom<Frame<'a>> for AllResponses<'a> {
+    type Error = crate::Error;
+
+    fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
+        match CommonResponses::try_from(frame) {
+            Err(Error::UnknownFunction(_)) => {}
+            result => return result.map(Self::Common),
+        }
+        if is_can_open(frame) {
+            response::CanOpenGeneralReference::try_from(frame).map(Self::CanOpenGeneralReference)
+        } else if is_identity(frame) {
+            IdentityResponse::try_from(frame).map(Self::Identity)
+        } else if frame.function().name().is_some() {
+            Ok(Self::Untyped(frame))
+        } else {
+            Err(Error::UnknownFunction(frame.function()))
+        }
+    }
+}
+
+/// encapsulated interface transport with the read device identification MEI type
+fn is_identity(frame: Frame) -> bool {
+    frame.function() == function::ENCAPSULATED_INTERFACE_TRANSPORT
+        && frame.payload().first() == Some(&function::MEI_READ_DEVICE_IDENTIFICATION)
+}
+
+/// frame length reported when `server::validate_request_frame` rejects a request without a typed wrapper
+fn untyped_request_len(function: Function) -> LenHint {
+    match function {
+        function::READ_EXCEPTION_STATUS
+        | function::GET_COMM_EVENT_COUNTER
+        | function::GET_COMM_EVENT_LOG
+        | function::REPORT_SLAVE_ID => LenHint::Exact(4),
+        function::READ_FIFO_QUEUE => LenHint::Exact(6),
+        function::MASK_WRITE_REGISTER => LenHint::Exact(10),
+        function::DIAGNOSTIC => LenHint::AtLeast(6),
+        function::READ_FILE_RECORD => LenHint::AtLeast(12),
+        function::WRITE_FILE_RECORD => LenHint::AtLeast(14),
+        function::READ_WRITE_MULTIPLE_REGISTERS => LenHint::AtLeast(15),
+        _ => LenHint::AtLeast(5),
+    }
+}
+
+/// encapsulated interface transport with the CANopen MEI type
+fn is_can_open(frame: Frame) -> bool {
+    frame.function() == function::ENCAPSULATED_INTERFACE_TRANSPORT
+        && frame.payload().first() == Some(&function::MEI_CANOPEN_GENERAL_REFERENCE)
+}
+
+/// Lenient decoding for gateways, which must forward frames they can't fully parse
+///
+/// Frames with an unknown function code or a payload length the decoder `T` rejects are returned as `Raw` instead
+/// of an error. The CRC must still be valid and exceptions are still errors.
+/// ```
+/// use modbus_frames::{builder, decoder::{CommonRequests, Lenient}, Function};
+///
+/// let mut buf = [0; 8];
+/// let (frame, _) = builder::build_frame(&mut buf).for_address(1).function(Function(0x41)).finalise();
+/// assert!(CommonRequests::try_from(frame).is_err());
+/// assert_eq!(Lenient::<CommonRequests>::try_from(frame), Ok(Lenient::Raw(frame)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Lenient<'a, T> {
+    Decoded(T),
+    Raw(Frame<'a>),
+}
+
+impl<'a, T: Into<Frame<'a>>> Lenient<'a, T> {
+    pub fn into_frame(self) -> Frame<'a> {
+        match self {
+            Lenient::Decoded(decoded) => decoded.into(),
+            Lenient::Raw(frame) => frame,
+        }
+    }
+}
+
+impl<'a, T: TryFrom<Frame<'a>, Error = Error>> TryFrom<&'a [u8]> for Lenient<'a, T> {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        let frame = Frame::try_from(bytes)?;
+        Self::try_from(frame)
+    }
+}
+
+impl<'a, T: TryFrom<Frame<'a>, Error = Error>> TryFrom<Frame<'a>> for Lenient<'a, T> {
+    type Error = crate::Error;
+
+    fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
+        match T::try_from(frame) {
+            Ok(decoded) => Ok(Lenient::Decoded(decoded)),
+            Err(Error::UnknownFunction(_) | Error::DecodeInvalidLength { .. }) => {
+                Ok(Lenient::Raw(frame))
+            }
+            Err(error) => Err(error),
+        }
+    }
+}
+
+/// A request and the response it received, checked with `validate_response`
+///
+/// ```
+/// use modbus_frames::{decoder::Transaction, request, response};
+///
+/// let mut req = [0; 8];
+/// let mut res = [0; 8];
+/// let request = request::read_holding_registers(&mut req, 1, 0, 1).unwrap();
+/// let (response, _) = response::ReadHoldingRegisters::new(&mut res, 1, [7]);
+/// let transaction = Transaction::new(request, response.as_frame()).unwrap();
+/// assert!(matches!(transaction, Transaction::ReadHoldingRegisters { .. }));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Transaction<'a> {
+    ReadCoils {
+        request: request::ReadCoils<'a>,
+        response: response::ReadCoils<'a>,
+    },
+    ReadDiscreteInputs {
+        request: request::ReadDiscreteInputs<'a>,
+        response: response::ReadDiscreteInputs<'a>,
+    },
+    ReadHoldingRegisters {
+        request: request::ReadHoldingRegisters<'a>,
+        response: response::ReadHoldingRegisters<'a>,
+    },
+    ReadInputRegisters {
+        request: request::ReadInputRegisters<'a>,
+        response: response::ReadInputRegisters<'a>,
+    },
+    WriteCoil {
+        request: request::WriteCoil<'a>,
+        response: response::WriteCoil<'a>,
+    },
+    WriteHoldingRegister {
+        request: request::WriteHoldingRegister<'a>,
+        response: response::WriteHoldingRegister<'a>,
+    },
+    WriteMultipleCoils {
+        request: request::WriteMultipleCoils<'a>,
+        response: response::WriteMultipleCoils<'a>,
+    },
+    WriteMultipleHoldingRegisters {
+        request: request::WriteMultipleHoldingRegisters<'a>,
+        response: response::WriteMultipleHoldingRegisters<'a>,
+    },
+    /// the device responded with an exception
+    Exception {
+        request: CommonRequests<'a>,
+        exception: Exception,
+    },
+}
+
+impl<'a> Transaction<'a> {
+    /// Decode `request` and check `response` belongs to it
+    ///
+    /// Errors if either frame can't be decoded or the response doesn't match the request
+    pub fn new(request: Frame<'a>, response: Frame<'a>) -> Result<Self, Error> {
+        Self::with_quirks(request, response, &Quirks::STRICT)
+    }
+
+    /// As `new`, tolerating the deviations `quirks` allows in the response
+    pub fn with_quirks(
+        request: Frame<'a>,
+        response: Frame<'a>,
+        quirks: &Quirks,
+    ) -> Result<Self, Error> {
+        let request = CommonRequests::try_from(request)?;
+        let transaction = match request {
+            CommonRequests::ReadCoils(request) => request
+                .validate_response_with(response, quirks)
+                .map(|response| Transaction::ReadCoils { request, response }),
+            CommonRequests::ReadDiscreteInputs(request) => request
+                .validate_response_with(response, quirks)
+                .map(|response| Transaction::ReadDiscreteInputs { request, response }),
+            CommonRequests::ReadHolsingRegisters(request) => request
+                .validate_response_with(response, quirks)
+                .map(|response| Transaction::ReadHoldingRegisters { request, response }),
+            CommonRequests::ReadInputRegisters(request) => request
+                .validate_response_with(response, quirks)
+                .map(|response| Transaction::ReadInputRegisters { request, response }),
+            CommonRequests::WriteCoil(request) => request
+                .validate_response(response)
+                .map(|response| Transaction::WriteCoil { request, response }),
+            CommonRequests::WriteHoldingRegister(request) => request
+                .validate_response(response)
+                .map(|response| Transaction::WriteHoldingRegister { request, response }),
+            CommonRequests::WriteMultipleCoils(request) => request
+                .validate_response(response)
+                .map(|response| Transaction::WriteMultipleCoils { request, response }),
+            CommonRequests::WriteMultipleHoldingRegisters(request) => request
+                .validate_response(response)
+                .map(|response| Transaction::WriteMultipleHoldingRegisters { request, response }),
+        };
+        match transaction {
+            Err(Error::Exception(exception)) => Ok(Transaction::Exception { request, exception }),
+            transaction => transaction,
+        }
+    }
+
+    pub fn request(&self) -> Frame<'a> {
+        match *self {
+            Transaction::ReadCoils { request, .. } => request.as_frame(),
+            Transaction::ReadDiscreteInputs { request, .. } => request.as_frame(),
+            Transaction::ReadHoldingRegisters { request, .. } => request.as_frame(),
+            Transaction::ReadInputRegisters { request, .. } => request.as_frame(),
+            Transaction::WriteCoil { request, .. } => request.as_frame(),
+            Transaction::WriteHoldingRegister { request, .. } => request.as_frame(),
+            Transaction::WriteMultipleCoils { request, .. } => request.as_frame(),
+            Transaction::WriteMultipleHoldingRegisters { request, .. } => request.as_frame(),
+            Transaction::Exception { request, .. } => request.into(),
+        }
+    }
+
+    /// The response, `None` for exceptions
+    pub fn response(&self) -> Option<Frame<'a>> {
+        Some(match *self {
+            Transaction::ReadCoils { response, .. } => response.as_frame(),
+            Transaction::ReadDiscreteInputs { response, .. } => response.as_frame(),
+            Transaction::ReadHoldingRegisters { response, .. } => response.as_frame(),
+            Transaction::ReadInputRegisters { response, .. } => response.as_frame(),
+            Transaction::WriteCoil { response, .. } => response.as_frame(),
+            Transaction::WriteHoldingRegister { response, .. } => response.as_frame(),
+            Transaction::WriteMultipleCoils { response, .. } => response.as_frame(),
+            Transaction::WriteMultipleHoldingRegisters { response, .. } => response.as_frame(),
+            Transaction::Exception { .. } => return None,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        decoder::{CommonRequests, CommonResponses},
-        function, Frame, COIL_ON,
+        decoder::{
+            AllRequests, AllResponses, CommonRequests, CommonResponses, Lenient, Transaction,
+        },
+        exception, function, request, response, Error, Frame, COIL_ON,
     };
 
     #[test]
@@ -361,4 +729,157 @@ mod tests {
             .collect::<Vec<_>>();
         dbg!(result);
     }
+
+    #[test]
+    fn transactions() {
+        let mut req = [0; 8];
+        let mut res = [0; 8];
+        let request = request::write_coil(&mut req, 3, 9, true).unwrap();
+
+        let (response, _) = response::WriteCoil::new(&mut res, 3, 9, true);
+        let transaction = Transaction::new(request, response.as_frame()).unwrap();
+        assert!(matches!(transaction, Transaction::WriteCoil { .. }));
+        assert_eq!(transaction.request(), request);
+        assert_eq!(transaction.response(), Some(response.as_frame()));
+
+        let (response, _) = request.response_exception(&mut res, exception::DEVICE_BUSY);
+        let transaction = Transaction::new(request, response).unwrap();
+        assert!(matches!(
+            transaction,
+            Transaction::Exception {
+                exception: exception::DEVICE_BUSY,
+                ..
+            }
+        ));
+        assert_eq!(transaction.response(), None);
+
+        let (response, _) = response::WriteCoil::new(&mut res, 3, 9, false);
+        assert_eq!(
+            Transaction::new(request, response.as_frame()),
+            Err(Error::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn exception_response() {
+        let mut req = [0; 8];
+        let request = request::read_coils(&mut req, 0x11, 0, 8).unwrap();
+        let decoded = CommonRequests::try_from(request).unwrap();
+        let mut res = [0; 5];
+        let (response, _) = decoded.exception_response(&mut res, exception::ILLEGAL_ADDRESS);
+        assert_eq!(response.raw_bytes()[..3], [0x11, 0x81, 0x02]);
+        assert_eq!(
+            CommonResponses::try_from(response),
+            Err(Error::Exception(exception::ILLEGAL_ADDRESS))
+        );
+    }
+
+    #[test]
+    fn all_functions() {
+        let mut req = [0; 16];
+        let mut res = [0; 16];
+        let request = request::read_write_multiple_registers(&mut req, 1, 0, 1, 4, &[5]).unwrap();
+        assert_eq!(
+            AllRequests::try_from(request),
+            Ok(AllRequests::Untyped(request))
+        );
+        let (can_open, _) = request::CanOpenGeneralReference::new(&mut res, 1, &[1, 2]);
+        assert!(matches!(
+            AllRequests::try_from(can_open.as_frame()),
+            Ok(AllRequests::CanOpenGeneralReference(_))
+        ));
+
+        let mut custom = [0; 4];
+        let (custom, _) = crate::builder::build_frame(&mut custom)
+            .for_address(1)
+            .function(crate::Function(0x41))
+            .finalise();
+        assert_eq!(
+            AllRequests::try_from(custom),
+            Err(Error::UnknownFunction(crate::Function(0x41)))
+        );
+
+        let (exception, _) = request.response_exception(&mut res, exception::ILLEGAL_ADDRESS);
+        assert_eq!(
+            AllResponses::try_from(exception),
+            Err(Error::Exception(exception::ILLEGAL_ADDRESS))
+        );
+        let (response, _) = crate::builder::build_frame(&mut res)
+            .for_address(1)
+            .function(function::READ_EXCEPTION_STATUS)
+            .byte(0x6D)
+            .finalise();
+        assert_eq!(
+            AllResponses::try_from(response),
+            Ok(AllResponses::Untyped(response))
+        );
+
+        // untyped requests are checked against the specification
+        let (fifo, _) = crate::builder::build_frame(&mut req)
+            .for_address(1)
+            .function(function::READ_FIFO_QUEUE)
+            .registers([4, 0])
+            .finalise();
+        assert_eq!(
+            AllRequests::try_from(fifo),
+            Err(Error::DecodeInvalidLength {
+                function: function::READ_FIFO_QUEUE,
+                len: 8,
+                expected: crate::LenHint::Exact(6),
+            })
+        );
+
+        let request = request::read_device_identification(&mut req, 1, 1, 0).unwrap();
+        assert_eq!(
+            AllRequests::try_from(request),
+            Ok(AllRequests::Untyped(request))
+        );
+        let identity = crate::identity::DeviceIdentity::basic("A", "B", "C");
+        let mut res = [0; 256];
+        let response = identity.respond(request, &mut res).unwrap();
+        assert!(matches!(
+            AllResponses::try_from(response),
+            Ok(AllResponses::Identity(identity)) if identity.objects().count() == 3
+        ));
+    }
+
+    #[test]
+    fn lenient_decode() {
+        let mut buf = [0; 16];
+        let request = request::read_coils(&mut buf, 1, 0, 8).unwrap();
+        let decoded = Lenient::<CommonRequests>::try_from(request).unwrap();
+        assert!(matches!(
+            decoded,
+            Lenient::Decoded(CommonRequests::ReadCoils(_))
+        ));
+        assert_eq!(decoded.into_frame(), request);
+
+        // a read coils request with an extra byte
+        let mut long = [0; 9];
+        let (frame, _) = crate::builder::build_frame(&mut long)
+            .for_address(1)
+            .function(function::READ_COILS)
+            .registers([0, 8])
+            .byte(0)
+            .finalise();
+        assert!(CommonRequests::try_from(frame).is_err());
+        assert_eq!(
+            Lenient::<CommonRequests>::try_from(frame),
+            Ok(Lenient::Raw(frame))
+        );
+
+        let mut bad_crc = [0; 8];
+        bad_crc.copy_from_slice(request.raw_bytes());
+        bad_crc[7] ^= 1;
+        assert_eq!(
+            Lenient::<CommonRequests>::try_from(&bad_crc[..]),
+            Err(Error::InvalidCrc)
+        );
+        let mut res = [0; 8];
+        let (exception, _) = request.response_exception(&mut res, exception::DEVICE_BUSY);
+        assert_eq!(
+            Lenient::<CommonResponses>::try_from(exception),
+            Err(Error::Exception(exception::DEVICE_BUSY))
+        );
+    }
 }
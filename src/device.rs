@@ -0,0 +1,102 @@
+//! Device addresses as documented by the modbus serial line specification
+//!
+//! `0` is the broadcast address, `1..=247` are individual slave addresses and `248..=255` are reserved
+
+use core::ops::RangeInclusive;
+
+use crate::Error;
+
+/// Address of a device on a modbus serial line
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Device(u8);
+
+/// What a device address is used for
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AddressKind {
+    /// Address 0, all slaves process the request and none respond
+    Broadcast,
+    /// Addresses 1-247, a single slave processes the request and responds
+    Slave,
+    /// Addresses 248-255, not to be used
+    Reserved,
+}
+
+/// Broadcast requests are processed by all slaves, no response is sent
+pub const BROADCAST: Device = Device(0);
+/// Addresses which may be assigned to individual slaves
+pub const SLAVE_ADDRESSES: RangeInclusive<u8> = 1..=247;
+/// Addresses reserved by the specification
+pub const RESERVED_ADDRESSES: RangeInclusive<u8> = 248..=255;
+
+impl Device {
+    /// Creates a device from any address, including broadcast and reserved addresses
+    pub const fn new(address: u8) -> Self {
+        Device(address)
+    }
+
+    /// Creates a device for a single slave, only addresses `1..=247` are accepted
+    pub fn try_new(address: u8) -> Result<Self, Error> {
+        if SLAVE_ADDRESSES.contains(&address) {
+            Ok(Device(address))
+        } else {
+            Err(Error::InvalidAddress)
+        }
+    }
+
+    /// The raw address byte
+    pub const fn address(&self) -> u8 {
+        self.0
+    }
+
+    pub fn kind(&self) -> AddressKind {
+        match self.0 {
+            0 => AddressKind::Broadcast,
+            1..=247 => AddressKind::Slave,
+            _ => AddressKind::Reserved,
+        }
+    }
+
+    pub fn is_broadcast(&self) -> bool {
+        self.kind() == AddressKind::Broadcast
+    }
+
+    pub fn is_reserved(&self) -> bool {
+        self.kind() == AddressKind::Reserved
+    }
+}
+
+impl From<u8> for Device {
+    fn from(address: u8) -> Self {
+        Device(address)
+    }
+}
+
+impl From<Device> for u8 {
+    fn from(device: Device) -> Self {
+        device.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AddressKind, Device, BROADCAST};
+    use crate::Error;
+
+    #[test]
+    fn address_kinds() {
+        assert_eq!(BROADCAST.kind(), AddressKind::Broadcast);
+        assert_eq!(Device::new(1).kind(), AddressKind::Slave);
+        assert_eq!(Device::new(247).kind(), AddressKind::Slave);
+        assert_eq!(Device::new(248).kind(), AddressKind::Reserved);
+        assert_eq!(Device::new(255).kind(), AddressKind::Reserved);
+    }
+
+    #[test]
+    fn try_new_only_accepts_slaves() {
+        assert_eq!(Device::try_new(0), Err(Error::InvalidAddress));
+        assert_eq!(Device::try_new(17).map(|d| d.address()), Ok(17));
+        assert_eq!(Device::try_new(248), Err(Error::InvalidAddress));
+    }
+}
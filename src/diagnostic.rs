@@ -0,0 +1,218 @@
+//! Diagnostics (0x08) sub-functions and the communication counters they report
+//!
+//! [`DiagnosticCounters`] answers the counter sub-functions and Get Comm Event Counter (0x0B) directly.
+//! [`MultiServer`](crate::server::MultiServer) updates its counters as frames are processed, `server::dispatch` and
+//! `server::respond` don't count anything, call the `record_` functions alongside them to keep counters.
+
+use byteorder::ByteOrder;
+
+use crate::{exception, function, Exception, Frame, ModbusFrame};
+
+/// Echo the request data
+pub const RETURN_QUERY_DATA: u16 = 0x00;
+/// Restart the serial line port, data 0xFF00 also clears the event log
+pub const RESTART_COMMUNICATIONS: u16 = 0x01;
+pub const RETURN_DIAGNOSTIC_REGISTER: u16 = 0x02;
+/// ASCII mode only, data is the new end of message delimiter in the high byte
+pub const CHANGE_ASCII_DELIMITER: u16 = 0x03;
+/// Stop responding until restarted with `RESTART_COMMUNICATIONS`, no response is sent
+pub const FORCE_LISTEN_ONLY: u16 = 0x04;
+/// Clear every counter and the diagnostic register
+pub const CLEAR_COUNTERS: u16 = 0x0A;
+/// Messages seen on the bus
+pub const BUS_MESSAGE_COUNT: u16 = 0x0B;
+/// CRC errors seen on the bus
+pub const BUS_COMM_ERROR_COUNT: u16 = 0x0C;
+/// Exception responses sent
+pub const BUS_EXCEPTION_ERROR_COUNT: u16 = 0x0D;
+/// Messages addressed to the server, including broadcasts
+pub const SERVER_MESSAGE_COUNT: u16 = 0x0E;
+/// Messages addressed to the server that weren't responded to
+pub const SERVER_NO_RESPONSE_COUNT: u16 = 0x0F;
+/// `NEGATIVE_ACKNOWLEDGE` exception responses sent
+pub const SERVER_NAK_COUNT: u16 = 0x10;
+/// `DEVICE_BUSY` exception responses sent
+pub const SERVER_BUSY_COUNT: u16 = 0x11;
+/// Messages lost to character overruns
+pub const BUS_CHARACTER_OVERRUN_COUNT: u16 = 0x12;
+/// Clear the overrun counter
+pub const CLEAR_OVERRUN_COUNTER: u16 = 0x14;
+
+/// Communication statistics as defined for the Diagnostics (0x08) sub-functions
+///
+/// All counters wrap on overflow
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DiagnosticCounters {
+    pub bus_messages: u16,
+    pub bus_comm_errors: u16,
+    pub bus_exceptions: u16,
+    pub server_messages: u16,
+    pub server_no_responses: u16,
+    pub server_naks: u16,
+    pub server_busy: u16,
+    pub bus_character_overruns: u16,
+    /// Get Comm Event Counter (0x0B) event count, successfully completed requests
+    pub events: u16,
+}
+
+impl DiagnosticCounters {
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// A valid frame was received, addressed to any device
+    pub fn record_bus_message(&mut self) {
+        self.bus_messages = self.bus_messages.wrapping_add(1);
+    }
+
+    /// A frame failed CRC or length validation
+    pub fn record_comm_error(&mut self) {
+        self.bus_comm_errors = self.bus_comm_errors.wrapping_add(1);
+    }
+
+    /// Received bytes were lost to a UART overrun
+    pub fn record_overrun(&mut self) {
+        self.bus_character_overruns = self.bus_character_overruns.wrapping_add(1);
+    }
+
+    /// A broadcast was processed, `completed` if it didn't fail with an exception
+    pub fn record_broadcast(&mut self, request: Frame, completed: bool) {
+        self.server_messages = self.server_messages.wrapping_add(1);
+        self.server_no_responses = self.server_no_responses.wrapping_add(1);
+        if completed && request.function() != function::GET_COMM_EVENT_COUNTER {
+            self.events = self.events.wrapping_add(1);
+        }
+    }
+
+    /// A request addressed to the server was processed, `response` is `None` if nothing was sent
+    ///
+    /// Use `record_broadcast` for broadcasts, which are never responded to
+    pub fn record_request(&mut self, request: Frame, response: Option<&Frame>) {
+        self.server_messages = self.server_messages.wrapping_add(1);
+        let Some(response) = response else {
+            self.server_no_responses = self.server_no_responses.wrapping_add(1);
+            return;
+        };
+        if response.function().0 & 0x80 != 0 {
+            self.bus_exceptions = self.bus_exceptions.wrapping_add(1);
+            match response.payload().first().copied().map(Exception) {
+                Some(exception::NEGATIVE_ACKNOWLEDGE) => {
+                    self.server_naks = self.server_naks.wrapping_add(1)
+                }
+                Some(exception::DEVICE_BUSY) => self.server_busy = self.server_busy.wrapping_add(1),
+                _ => {}
+            }
+        } else if request.function() != function::GET_COMM_EVENT_COUNTER {
+            self.events = self.events.wrapping_add(1);
+        }
+    }
+
+    /// true for the requests answered by `respond`
+    pub fn handles(request: Frame) -> bool {
+        match request.function() {
+            function::GET_COMM_EVENT_COUNTER => true,
+            function::DIAGNOSTIC => matches!(
+                sub_function(request),
+                Some(CLEAR_COUNTERS..=BUS_CHARACTER_OVERRUN_COUNT | CLEAR_OVERRUN_COUNTER)
+            ),
+            _ => false,
+        }
+    }
+
+    /// Answer a request for which `handles` is true
+    pub fn respond<'b>(&mut self, request: Frame, response_buffer: &'b mut [u8]) -> Frame<'b> {
+        let payload = request.payload();
+        if request.function() == function::GET_COMM_EVENT_COUNTER {
+            if !payload.is_empty() {
+                return request
+                    .response_exception(response_buffer, exception::ILLEGAL_DATA)
+                    .0;
+            }
+            return request
+                .response_builder(response_buffer)
+                .registers([0, self.events])
+                .finalise()
+                .0;
+        }
+
+        let sub = sub_function(request).unwrap_or_default();
+        if payload.len() != 4 || byteorder::BigEndian::read_u16(&payload[2..]) != 0 {
+            return request
+                .response_exception(response_buffer, exception::ILLEGAL_DATA)
+                .0;
+        }
+        let value = match sub {
+            CLEAR_COUNTERS => {
+                self.clear();
+                0
+            }
+            BUS_MESSAGE_COUNT => self.bus_messages,
+            BUS_COMM_ERROR_COUNT => self.bus_comm_errors,
+            BUS_EXCEPTION_ERROR_COUNT => self.bus_exceptions,
+            SERVER_MESSAGE_COUNT => self.server_messages,
+            SERVER_NO_RESPONSE_COUNT => self.server_no_responses,
+            SERVER_NAK_COUNT => self.server_naks,
+            SERVER_BUSY_COUNT => self.server_busy,
+            BUS_CHARACTER_OVERRUN_COUNT => self.bus_character_overruns,
+            CLEAR_OVERRUN_COUNTER => {
+                self.bus_character_overruns = 0;
+                0
+            }
+            _ => {
+                return request
+                    .response_exception(response_buffer, exception::ILLEGAL_FUNCTION)
+                    .0
+            }
+        };
+        request
+            .response_builder(response_buffer)
+            .registers([sub, value])
+            .finalise()
+            .0
+    }
+}
+
+/// The sub-function of a Diagnostics (0x08) request
+pub fn sub_function<'f>(request: impl ModbusFrame<'f>) -> Option<u16> {
+    let payload = request.payload();
+    (request.function() == function::DIAGNOSTIC && payload.len() >= 2)
+        .then(|| byteorder::BigEndian::read_u16(payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiagnosticCounters, BUS_MESSAGE_COUNT, CLEAR_COUNTERS, RETURN_QUERY_DATA};
+    use crate::{exception, request};
+
+    #[test]
+    fn counter_requests() {
+        let mut counters = DiagnosticCounters {
+            bus_messages: 7,
+            events: 3,
+            ..Default::default()
+        };
+        let mut req = [0; 8];
+        let mut res = [0; 8];
+
+        let request = request::diagnostic(&mut req, 1, BUS_MESSAGE_COUNT, 0).unwrap();
+        assert!(DiagnosticCounters::handles(request));
+        let response = counters.respond(request, &mut res);
+        assert_eq!(response.payload(), [0, 0x0B, 0, 7]);
+
+        let request = request::diagnostic(&mut req, 1, BUS_MESSAGE_COUNT, 1).unwrap();
+        let response = counters.respond(request, &mut res);
+        assert_eq!(response.payload(), [exception::ILLEGAL_DATA.0]);
+
+        let request = request::get_comm_event_counter(&mut req, 1).unwrap();
+        let response = counters.respond(request, &mut res);
+        assert_eq!(response.payload(), [0, 0, 0, 3]);
+
+        let request = request::diagnostic(&mut req, 1, CLEAR_COUNTERS, 0).unwrap();
+        counters.respond(request, &mut res);
+        assert_eq!(counters, DiagnosticCounters::default());
+
+        let request = request::diagnostic(&mut req, 1, RETURN_QUERY_DATA, 0).unwrap();
+        assert!(!DiagnosticCounters::handles(request));
+    }
+}
@@ -0,0 +1,392 @@
+//! The four tables of the modbus data model and the entities within them
+
+use crate::{decoder::CommonRequests, function, request, Error, Frame, Function};
+
+/// The tables of the modbus data model
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EntityType {
+    /// single bit, read/write
+    Coil,
+    /// single bit, read only
+    DiscreteInput,
+    /// 16 bit, read only
+    InputRegister,
+    /// 16 bit, read/write
+    HoldingRegister,
+}
+
+impl EntityType {
+    pub const ALL: [EntityType; 4] = [
+        EntityType::Coil,
+        EntityType::DiscreteInput,
+        EntityType::InputRegister,
+        EntityType::HoldingRegister,
+    ];
+
+    /// true for coils and discrete inputs
+    pub fn is_bit(&self) -> bool {
+        matches!(self, EntityType::Coil | EntityType::DiscreteInput)
+    }
+
+    /// the function code used to read entities of this type
+    pub fn read_function(&self) -> Function {
+        match self {
+            EntityType::Coil => function::READ_COILS,
+            EntityType::DiscreteInput => function::READ_DISCRETE_INPUTS,
+            EntityType::InputRegister => function::READ_INPUT_REGISTERS,
+            EntityType::HoldingRegister => function::READ_HOLDING_REGISTERS,
+        }
+    }
+
+    /// The type of entity accessed by a function code, `None` for functions not accessing the data model
+    pub fn from_function(function: Function) -> Option<EntityType> {
+        match function {
+            function::READ_COILS | function::WRITE_COIL | function::WRITE_MULTIPLE_COILS => {
+                Some(EntityType::Coil)
+            }
+            function::READ_DISCRETE_INPUTS => Some(EntityType::DiscreteInput),
+            function::READ_INPUT_REGISTERS => Some(EntityType::InputRegister),
+            function::READ_HOLDING_REGISTERS
+            | function::WRITE_HOLDING_REGISTER
+            | function::WRITE_MULTIPLE_HOLDING_REGISTERS
+            | function::MASK_WRITE_REGISTER
+            | function::READ_WRITE_MULTIPLE_REGISTERS
+            | function::READ_FIFO_QUEUE => Some(EntityType::HoldingRegister),
+            _ => None,
+        }
+    }
+
+    /// leading digit of entity numbers, e.g. 4 for holding registers (40001)
+    pub fn number_prefix(&self) -> u32 {
+        match self {
+            EntityType::Coil => 0,
+            EntityType::DiscreteInput => 1,
+            EntityType::InputRegister => 3,
+            EntityType::HoldingRegister => 4,
+        }
+    }
+
+    /// the most entities of this type that can be read by a single request
+    pub fn max_read_count(&self) -> u16 {
+        if self.is_bit() {
+            crate::request::MAX_READ_BITS
+        } else {
+            crate::request::MAX_READ_REGISTERS
+        }
+    }
+}
+
+/// A single coil, discrete input or register
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Entity {
+    pub entity_type: EntityType,
+    /// zero based index used on the wire
+    pub index: u16,
+}
+
+impl Entity {
+    pub const fn new(entity_type: EntityType, index: u16) -> Self {
+        Entity { entity_type, index }
+    }
+
+    pub const fn coil(index: u16) -> Self {
+        Self::new(EntityType::Coil, index)
+    }
+
+    pub const fn discrete_input(index: u16) -> Self {
+        Self::new(EntityType::DiscreteInput, index)
+    }
+
+    pub const fn input_register(index: u16) -> Self {
+        Self::new(EntityType::InputRegister, index)
+    }
+
+    pub const fn holding_register(index: u16) -> Self {
+        Self::new(EntityType::HoldingRegister, index)
+    }
+
+    /// The first entity accessed by a request
+    pub fn from_request(request: &CommonRequests) -> Entity {
+        let range = EntityRange::from_request(request);
+        Entity::new(range.entity_type, range.start_index)
+    }
+
+    /// One based entity number, 5 digits (e.g. 40001) where the index allows and 6 digits (e.g. 410000) otherwise
+    pub fn number(&self) -> u32 {
+        if self.index < 9999 {
+            self.number_5digit().unwrap_or_default()
+        } else {
+            self.number_6digit()
+        }
+    }
+
+    /// `None` if the index is too large for 5 digit numbering
+    pub fn number_5digit(&self) -> Option<u32> {
+        (self.index < 9999)
+            .then(|| self.entity_type.number_prefix() * 10000 + self.index as u32 + 1)
+    }
+
+    pub fn number_6digit(&self) -> u32 {
+        self.entity_type.number_prefix() * 100000 + self.index as u32 + 1
+    }
+
+    /// Parse a 5 or 6 digit entity number
+    ///
+    /// 5 digit numbers are assumed for values below 100000, so coils can only be given in 5 digit form
+    pub fn from_number(number: u32) -> Option<Entity> {
+        let (prefix, offset) = if number < 100000 {
+            (number / 10000, number % 10000)
+        } else {
+            (number / 100000, number % 100000)
+        };
+        let entity_type = match prefix {
+            0 => EntityType::Coil,
+            1 => EntityType::DiscreteInput,
+            3 => EntityType::InputRegister,
+            4 => EntityType::HoldingRegister,
+            _ => return None,
+        };
+        let index = offset.checked_sub(1)?;
+        Some(Entity::new(entity_type, index.try_into().ok()?))
+    }
+}
+
+impl core::fmt::Display for Entity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.number_5digit() {
+            Some(number) => write!(f, "{:05}", number),
+            None => write!(f, "{:06}", self.number_6digit()),
+        }
+    }
+}
+
+/// A contiguous run of entities of the same type
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EntityRange {
+    pub entity_type: EntityType,
+    /// zero based index of the first entity
+    pub start_index: u16,
+    pub count: u16,
+}
+
+impl EntityRange {
+    pub const fn new(entity_type: EntityType, start_index: u16, count: u16) -> Self {
+        EntityRange {
+            entity_type,
+            start_index,
+            count,
+        }
+    }
+
+    /// The entities accessed by a request
+    pub fn from_request(request: &CommonRequests) -> EntityRange {
+        match request {
+            CommonRequests::ReadCoils(req) => {
+                EntityRange::new(EntityType::Coil, req.start_index(), req.coil_count())
+            }
+            CommonRequests::ReadDiscreteInputs(req) => EntityRange::new(
+                EntityType::DiscreteInput,
+                req.start_index(),
+                req.input_count(),
+            ),
+            CommonRequests::ReadHolsingRegisters(req) => EntityRange::new(
+                EntityType::HoldingRegister,
+                req.start_index(),
+                req.register_count(),
+            ),
+            CommonRequests::ReadInputRegisters(req) => EntityRange::new(
+                EntityType::InputRegister,
+                req.start_index(),
+                req.register_count(),
+            ),
+            CommonRequests::WriteCoil(req) => EntityRange::new(EntityType::Coil, req.index(), 1),
+            CommonRequests::WriteHoldingRegister(req) => {
+                EntityRange::new(EntityType::HoldingRegister, req.index(), 1)
+            }
+            CommonRequests::WriteMultipleCoils(req) => {
+                EntityRange::new(EntityType::Coil, req.start_index(), req.coil_count())
+            }
+            CommonRequests::WriteMultipleHoldingRegisters(req) => EntityRange::new(
+                EntityType::HoldingRegister,
+                req.start_index(),
+                req.register_count(),
+            ),
+        }
+    }
+
+    /// index one past the last entity, may be 65536
+    pub fn end(&self) -> u32 {
+        self.start_index as u32 + self.count as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn contains(&self, entity: &Entity) -> bool {
+        entity.entity_type == self.entity_type
+            && entity.index >= self.start_index
+            && (entity.index as u32) < self.end()
+    }
+
+    /// true if every entity of `other` is also in this range
+    pub fn contains_range(&self, other: &EntityRange) -> bool {
+        self.entity_type == other.entity_type
+            && other.start_index >= self.start_index
+            && other.end() <= self.end()
+    }
+
+    /// true if any entity is in both ranges
+    pub fn overlaps(&self, other: &EntityRange) -> bool {
+        self.entity_type == other.entity_type
+            && !self.is_empty()
+            && !other.is_empty()
+            && (self.start_index as u32) < other.end()
+            && (other.start_index as u32) < self.end()
+    }
+
+    /// the entities in the range, in index order
+    pub fn iter(&self) -> impl Iterator<Item = Entity> {
+        let entity_type = self.entity_type;
+        (self.start_index as u32..self.end())
+            .map(move |index| Entity::new(entity_type, index as u16))
+    }
+
+    /// Split into consecutive ranges of at most `max_count` entities
+    ///
+    /// `max_count` must not be 0
+    pub fn split(&self, max_count: u16) -> Split {
+        Split {
+            remaining: *self,
+            max_count,
+        }
+    }
+
+    /// Split into ranges that can each be read by a single request
+    pub fn requests(&self) -> Split {
+        self.split(self.entity_type.max_read_count())
+    }
+
+    /// `(start_address, quantity)` as used in request frames
+    pub fn address_quantity(&self) -> (u16, u16) {
+        (self.start_index, self.count)
+    }
+
+    /// Build the request reading this range from the device at `address`
+    pub fn read_request<'b>(&self, buffer: &'b mut [u8], address: u8) -> Result<Frame<'b>, Error> {
+        let (start, count) = self.address_quantity();
+        match self.entity_type {
+            EntityType::Coil => request::read_coils(buffer, address, start, count),
+            EntityType::DiscreteInput => {
+                request::read_discrete_inputs(buffer, address, start, count)
+            }
+            EntityType::InputRegister => {
+                request::read_input_registers(buffer, address, start, count)
+            }
+            EntityType::HoldingRegister => {
+                request::read_holding_registers(buffer, address, start, count)
+            }
+        }
+    }
+}
+
+/// Iterator returned by [`EntityRange::split`]
+#[derive(Debug, Clone)]
+pub struct Split {
+    remaining: EntityRange,
+    max_count: u16,
+}
+
+impl Iterator for Split {
+    type Item = EntityRange;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining.is_empty() || self.max_count == 0 {
+            return None;
+        }
+        let count = self.remaining.count.min(self.max_count);
+        let range = EntityRange::new(
+            self.remaining.entity_type,
+            self.remaining.start_index,
+            count,
+        );
+        self.remaining.count -= count;
+        // wraps to 0 only when the range is exhausted
+        self.remaining.start_index = self.remaining.start_index.wrapping_add(count);
+        Some(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entity, EntityRange, EntityType};
+    use crate::{decoder::CommonRequests, request};
+
+    #[test]
+    fn ranges() {
+        let range = EntityRange::new(EntityType::HoldingRegister, 10, 4);
+        assert!(range.contains(&Entity::holding_register(10)));
+        assert!(range.contains(&Entity::holding_register(13)));
+        assert!(!range.contains(&Entity::holding_register(14)));
+        assert!(!range.contains(&Entity::input_register(10)));
+
+        assert!(range.overlaps(&EntityRange::new(EntityType::HoldingRegister, 13, 1)));
+        assert!(!range.overlaps(&EntityRange::new(EntityType::HoldingRegister, 14, 1)));
+        assert!(!range.overlaps(&EntityRange::new(EntityType::InputRegister, 10, 4)));
+        assert!(!range.overlaps(&EntityRange::new(EntityType::HoldingRegister, 11, 0)));
+
+        assert!(range.iter().map(|e| e.index).eq(10..14));
+        assert_eq!(range.address_quantity(), (10, 4));
+
+        let top = EntityRange::new(EntityType::Coil, 0xFFFF, 1);
+        assert!(top.iter().eq([Entity::coil(0xFFFF)]));
+    }
+
+    #[test]
+    fn split_at_request_size() {
+        let range = EntityRange::new(EntityType::InputRegister, 65000, 300);
+        let split: Vec<_> = range.requests().map(|r| r.address_quantity()).collect();
+        assert_eq!(split, [(65000, 125), (65125, 125), (65250, 50)]);
+
+        let end = EntityRange::new(EntityType::Coil, 0, u16::MAX).split(u16::MAX);
+        assert_eq!(end.count(), 1);
+
+        let mut buffer = [0; 8];
+        let frame = range
+            .requests()
+            .next()
+            .unwrap()
+            .read_request(&mut buffer, 1)
+            .unwrap();
+        assert_eq!(frame.function(), EntityType::InputRegister.read_function());
+    }
+
+    #[test]
+    fn entity_numbers() {
+        assert_eq!(Entity::holding_register(0).number(), 40001);
+        assert_eq!(Entity::coil(9998).number(), 9999);
+        assert_eq!(Entity::input_register(9999).number(), 310000);
+        assert_eq!(format!("{}", Entity::coil(4)), "00005");
+        assert_eq!(format!("{}", Entity::discrete_input(65535)), "165536");
+
+        assert_eq!(
+            Entity::from_number(40001),
+            Some(Entity::holding_register(0))
+        );
+        assert_eq!(
+            Entity::from_number(465536),
+            Some(Entity::holding_register(65535))
+        );
+        assert_eq!(Entity::from_number(5), Some(Entity::coil(4)));
+        assert_eq!(Entity::from_number(40000), None);
+        assert_eq!(Entity::from_number(20001), None);
+
+        let mut buffer = [0; 8];
+        let frame = request::write_holding_register(&mut buffer, 1, 99, 0).unwrap();
+        let decoded = CommonRequests::try_from(frame).unwrap();
+        assert_eq!(Entity::from_request(&decoded).number(), 40100);
+    }
+}
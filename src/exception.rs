@@ -1,9 +1,46 @@
 //! Exception codes as documented by https://en.wikipedia.org/wiki/Modbus#Exception_responses
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Exception(pub u8);
 
+impl Exception {
+    /// Name of the exception code if it is one of the documented codes
+    pub fn name(&self) -> Option<&'static str> {
+        match *self {
+            ILLEGAL_FUNCTION => Some("IllegalFunction"),
+            ILLEGAL_ADDRESS => Some("IllegalAddress"),
+            ILLEGAL_DATA => Some("IllegalData"),
+            DEVICE_FAILURE => Some("DeviceFailure"),
+            ACKNOWLEDGE => Some("Acknowledge"),
+            DEVICE_BUSY => Some("DeviceBusy"),
+            NEGATIVE_ACKNOWLEDGE => Some("NegativeAcknowledge"),
+            MEMORY_PARITY_ERROR => Some("MemoryParityError"),
+            GATEWAY_PATH_UNAVAILABLE => Some("GatewayPathUnavailable"),
+            GATEWAY_DEVICE_NO_RESPONSE => Some("GatewayDeviceNoResponse"),
+            _ => None,
+        }
+    }
+}
+
+impl core::fmt::Display for Exception {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{} (0x{:02X})", name, self.0),
+            None => write!(f, "Exception (0x{:02X})", self.0),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Exception {
+    fn format(&self, f: defmt::Formatter) {
+        match self.name() {
+            Some(name) => defmt::write!(f, "{=str}({=u8})", name, self.0),
+            None => defmt::write!(f, "Exception({=u8})", self.0),
+        }
+    }
+}
+
 impl From<u8> for Exception {
     fn from(f: u8) -> Self {
         Exception(f)
@@ -0,0 +1,295 @@
+//! C interface (feature `ffi`)
+//!
+//! `extern "C"` functions for validating received frames, building requests and decoding the common requests, so C
+//! firmware can move its framing over one call at a time. The declarations are in `include/modbus_frames.h`,
+//! regenerate it with `cbindgen --config cbindgen.toml --output include/modbus_frames.h` after changing this
+//! module. Build a static library with `cargo rustc --release --features ffi,std --crate-type staticlib`, `no_std`
+//! targets depend on the crate from a `staticlib` crate providing the `#[panic_handler]` instead.
+//!
+//! Every function returns an [`MbfStatus`], outputs are only written on `MBF_STATUS_OK`. Buffers are passed as a
+//! pointer and length and are only accessed for the duration of the call.
+
+use core::slice;
+
+use crate::{decoder::CommonRequests, request, Error, Frame};
+
+/// Result of an `mbf_` call, `MBF_STATUS_OK` or the reason it failed
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MbfStatus {
+    Ok = 0,
+    /// a pointer argument was null
+    NullPointer,
+    /// frames are 4-256 bytes
+    InvalidLength,
+    InvalidCrc,
+    /// not one of the common request functions
+    UnknownFunction,
+    /// the length doesn't match the function code
+    DecodeInvalidLength,
+    InvalidAddress,
+    InvalidQuantity,
+    InvalidValue,
+    BufferTooSmall,
+    /// any other `Error`
+    Other,
+}
+
+impl From<Error> for MbfStatus {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::InvalidLength(_) => MbfStatus::InvalidLength,
+            Error::InvalidCrc => MbfStatus::InvalidCrc,
+            Error::UnknownFunction(_) | Error::UnexpectedFunction { .. } => {
+                MbfStatus::UnknownFunction
+            }
+            Error::DecodeInvalidLength { .. } => MbfStatus::DecodeInvalidLength,
+            Error::InvalidAddress => MbfStatus::InvalidAddress,
+            Error::InvalidQuantity => MbfStatus::InvalidQuantity,
+            Error::InvalidValue => MbfStatus::InvalidValue,
+            Error::BufferTooSmall | Error::InsufficientBuffer { .. } => MbfStatus::BufferTooSmall,
+            _ => MbfStatus::Other,
+        }
+    }
+}
+
+/// A decoded common request (functions 1-6, 15 and 16)
+///
+/// `start_index` is the index for the single writes and `count` is 1. `value` is the register value, or
+/// 1/0 for a coil on/off. Multiple writes point `values` at the coil bytes (least significant bit first) or big
+/// endian registers inside the decoded bytes, `values_len` is their length in bytes. `values` is null otherwise.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MbfRequest {
+    pub address: u8,
+    pub function: u8,
+    pub start_index: u16,
+    pub count: u16,
+    pub value: u16,
+    pub values: *const u8,
+    pub values_len: usize,
+}
+
+impl MbfRequest {
+    fn new(request: CommonRequests) -> Self {
+        let frame = request.as_frame();
+        let mut decoded = MbfRequest {
+            address: frame.address(),
+            function: frame.function().0,
+            start_index: 0,
+            count: 1,
+            value: 0,
+            values: core::ptr::null(),
+            values_len: 0,
+        };
+        match request {
+            CommonRequests::ReadCoils(r) => {
+                (decoded.start_index, decoded.count) = (r.start_index(), r.coil_count())
+            }
+            CommonRequests::ReadDiscreteInputs(r) => {
+                (decoded.start_index, decoded.count) = (r.start_index(), r.input_count())
+            }
+            CommonRequests::ReadHolsingRegisters(r) => {
+                (decoded.start_index, decoded.count) = (r.start_index(), r.register_count())
+            }
+            CommonRequests::ReadInputRegisters(r) => {
+                (decoded.start_index, decoded.count) = (r.start_index(), r.register_count())
+            }
+            CommonRequests::WriteCoil(r) => {
+                (decoded.start_index, decoded.value) = (r.index(), r.is_on().into())
+            }
+            CommonRequests::WriteHoldingRegister(r) => {
+                (decoded.start_index, decoded.value) = (r.index(), r.value())
+            }
+            CommonRequests::WriteMultipleCoils(r) => {
+                // start(2) + count(2) + byte count(1)
+                let values = &r.payload()[5..];
+                (decoded.start_index, decoded.count) = (r.start_index(), r.coil_count());
+                (decoded.values, decoded.values_len) = (values.as_ptr(), values.len());
+            }
+            CommonRequests::WriteMultipleHoldingRegisters(r) => {
+                let values = r.payload_as_registers_unaligned().as_flattened();
+                (decoded.start_index, decoded.count) = (r.start_index(), r.register_count());
+                (decoded.values, decoded.values_len) = (values.as_ptr(), values.len());
+            }
+        }
+        decoded
+    }
+}
+
+/// `len` bytes from `bytes`, `None` if null
+///
+/// # Safety
+/// a non-null `bytes` must be valid for reads of `len` bytes for `'a`
+unsafe fn bytes_from<'a>(bytes: *const u8, len: usize) -> Option<&'a [u8]> {
+    // SAFETY: non-null and valid for `len` bytes per the caller
+    (!bytes.is_null()).then(|| unsafe { slice::from_raw_parts(bytes, len) })
+}
+
+/// Check that `bytes` is a frame of valid length with a valid CRC
+///
+/// # Safety
+/// `bytes` must be valid for reads of `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn mbf_validate(bytes: *const u8, len: usize) -> MbfStatus {
+    // SAFETY: forwarded from the caller
+    let Some(bytes) = (unsafe { bytes_from(bytes, len) }) else {
+        return MbfStatus::NullPointer;
+    };
+    match Frame::try_from(bytes) {
+        Ok(_) => MbfStatus::Ok,
+        Err(error) => error.into(),
+    }
+}
+
+/// Build a Read Holding Registers (0x03) request for `count` (1-125) registers into `buffer`, writing the frame
+/// length to `frame_len`
+///
+/// # Safety
+/// `buffer` must be valid for writes of `buffer_len` bytes and `frame_len` valid for a write
+#[no_mangle]
+pub unsafe extern "C" fn mbf_build_read_holding(
+    buffer: *mut u8,
+    buffer_len: usize,
+    address: u8,
+    start_index: u16,
+    count: u16,
+    frame_len: *mut usize,
+) -> MbfStatus {
+    if buffer.is_null() || frame_len.is_null() {
+        return MbfStatus::NullPointer;
+    }
+    // SAFETY: non-null and valid for `buffer_len` bytes per the caller
+    let buffer = unsafe { slice::from_raw_parts_mut(buffer, buffer_len) };
+    match request::read_holding_registers(buffer, address, start_index, count) {
+        Ok(frame) => {
+            // SAFETY: non-null and valid per the caller
+            unsafe { frame_len.write(frame.raw_bytes().len()) };
+            MbfStatus::Ok
+        }
+        Err(error) => error.into(),
+    }
+}
+
+/// Validate and decode a common request frame into `request`
+///
+/// `request.values` points into `bytes` for multiple writes, it is only valid while `bytes` is
+///
+/// # Safety
+/// `bytes` must be valid for reads of `len` bytes and `request` valid for a write
+#[no_mangle]
+pub unsafe extern "C" fn mbf_decode_common_request(
+    bytes: *const u8,
+    len: usize,
+    request: *mut MbfRequest,
+) -> MbfStatus {
+    if request.is_null() {
+        return MbfStatus::NullPointer;
+    }
+    // SAFETY: forwarded from the caller
+    let Some(bytes) = (unsafe { bytes_from(bytes, len) }) else {
+        return MbfStatus::NullPointer;
+    };
+    match CommonRequests::try_from(bytes) {
+        Ok(decoded) => {
+            // SAFETY: non-null and valid per the caller
+            unsafe { request.write(MbfRequest::new(decoded)) };
+            MbfStatus::Ok
+        }
+        Err(error) => error.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn c_interface() {
+        let mut buffer = [0; 8];
+        let mut len = 0;
+        let status = unsafe {
+            mbf_build_read_holding(buffer.as_mut_ptr(), buffer.len(), 0x11, 0x6B, 3, &mut len)
+        };
+        assert_eq!((status, len), (MbfStatus::Ok, 8));
+        assert_eq!(buffer, [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87]);
+        assert_eq!(
+            unsafe { mbf_build_read_holding(buffer.as_mut_ptr(), 7, 1, 0, 3, &mut len) },
+            MbfStatus::BufferTooSmall
+        );
+        assert_eq!(
+            unsafe { mbf_build_read_holding(buffer.as_mut_ptr(), 8, 1, 0, 126, &mut len) },
+            MbfStatus::InvalidQuantity
+        );
+
+        assert_eq!(unsafe { mbf_validate(buffer.as_ptr(), 8) }, MbfStatus::Ok);
+        assert_eq!(
+            unsafe { mbf_validate(buffer.as_ptr(), 7) },
+            MbfStatus::InvalidCrc
+        );
+        assert_eq!(
+            unsafe { mbf_validate(buffer.as_ptr(), 3) },
+            MbfStatus::InvalidLength
+        );
+        assert_eq!(
+            unsafe { mbf_validate(core::ptr::null(), 8) },
+            MbfStatus::NullPointer
+        );
+
+        let mut decoded = core::mem::MaybeUninit::<MbfRequest>::uninit();
+        let status = unsafe { mbf_decode_common_request(buffer.as_ptr(), 8, decoded.as_mut_ptr()) };
+        assert_eq!(status, MbfStatus::Ok);
+        let decoded = unsafe { decoded.assume_init() };
+        assert_eq!(
+            (
+                decoded.address,
+                decoded.function,
+                decoded.start_index,
+                decoded.count
+            ),
+            (0x11, 0x03, 0x6B, 3)
+        );
+        assert!(decoded.values.is_null());
+
+        let mut buffer = [0; 16];
+        let frame =
+            request::write_multiple_holding_registers(&mut buffer, 1, 2, &[0x1234, 5]).unwrap();
+        let mut decoded = MbfRequest::new(CommonRequests::try_from(frame).unwrap());
+        let status = unsafe {
+            mbf_decode_common_request(
+                frame.raw_bytes().as_ptr(),
+                frame.raw_bytes().len(),
+                &mut decoded,
+            )
+        };
+        assert_eq!(status, MbfStatus::Ok);
+        let values = unsafe { slice::from_raw_parts(decoded.values, decoded.values_len) };
+        assert_eq!((decoded.start_index, decoded.count), (2, 2));
+        assert_eq!(values, [0x12, 0x34, 0, 5]);
+
+        let frame = request::read_exception_status(&mut buffer, 1).unwrap();
+        let status = unsafe {
+            mbf_decode_common_request(
+                frame.raw_bytes().as_ptr(),
+                frame.raw_bytes().len(),
+                &mut decoded,
+            )
+        };
+        assert_eq!(status, MbfStatus::UnknownFunction);
+    }
+
+    #[test]
+    fn header_declarations() {
+        let header = include_str!("../include/modbus_frames.h");
+        for declaration in [
+            "MbfStatus mbf_validate(const uint8_t *bytes, size_t len);",
+            "MbfStatus mbf_decode_common_request(const uint8_t *bytes, size_t len, MbfRequest *request);",
+            "MbfStatus mbf_build_read_holding(uint8_t *buffer,",
+            "typedef struct MbfRequest {",
+            "MBF_STATUS_OTHER,",
+        ] {
+            assert!(header.contains(declaration), "{declaration}");
+        }
+    }
+}
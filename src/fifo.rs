@@ -0,0 +1,127 @@
+//! Read FIFO Queue (0x18) on the server side
+//!
+//! [`FifoQueue`] holds up to 31 queued register values and builds the response, including the byte count and
+//! FIFO count header. Devices with several queues use [`pointer_index`] to find the queue a request is for.
+//!
+//! ```
+//! use modbus_frames::{fifo::{self, FifoQueue}, request};
+//!
+//! let mut events = FifoQueue::new();
+//! events.push(0x01B8).unwrap();
+//! events.push(0x1284).unwrap();
+//!
+//! let mut request_buffer = [0; 8];
+//! let request = request::read_fifo_queue(&mut request_buffer, 1, 0x04DE).unwrap();
+//! assert_eq!(fifo::pointer_index(request), Some(0x04DE));
+//! let mut response_buffer = [0; 256];
+//! let response = events.build_response(request.address(), &mut response_buffer);
+//! assert_eq!(response.payload(), [0, 6, 0, 2, 0x01, 0xB8, 0x12, 0x84]);
+//! ```
+
+use byteorder::ByteOrder;
+
+use crate::{builder, function, Frame, ModbusFrame};
+
+/// The most values a FIFO queue may hold, a longer queue must be answered with the `ILLEGAL_DATA` exception
+pub const MAX_FIFO_COUNT: usize = 31;
+
+/// Register values queued first in first out
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FifoQueue {
+    values: [u16; MAX_FIFO_COUNT],
+    len: u8,
+}
+
+impl FifoQueue {
+    pub const fn new() -> Self {
+        FifoQueue {
+            values: [0; MAX_FIFO_COUNT],
+            len: 0,
+        }
+    }
+
+    /// Queue `value`, returning it if the queue is full
+    pub fn push(&mut self, value: u16) -> Result<(), u16> {
+        let slot = self.values.get_mut(usize::from(self.len)).ok_or(value)?;
+        *slot = value;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Remove the oldest value
+    pub fn pop(&mut self) -> Option<u16> {
+        let first = *self.as_slice().first()?;
+        self.values.copy_within(1..usize::from(self.len), 0);
+        self.len -= 1;
+        Some(first)
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        usize::from(self.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Queued values, oldest first
+    pub fn as_slice(&self) -> &[u16] {
+        &self.values[..usize::from(self.len)]
+    }
+
+    /// Read FIFO Queue response from `address` with the queued values, the queue is left unchanged
+    ///
+    /// `response_buffer` must hold `8 + 2 * len` bytes (70 for a full queue)
+    pub fn build_response<'b>(&self, address: u8, response_buffer: &'b mut [u8]) -> Frame<'b> {
+        let count = self.len();
+        builder::build_frame(response_buffer)
+            .for_address(address)
+            .function(function::READ_FIFO_QUEUE)
+            // byte count includes the FIFO count but not itself
+            .registers([2 + 2 * count as u16, count as u16])
+            .registers(self.as_slice().iter().copied())
+            .finalise()
+            .0
+    }
+}
+
+/// The FIFO pointer address of a Read FIFO Queue (0x18) request
+pub fn pointer_index<'f>(request: impl ModbusFrame<'f>) -> Option<u16> {
+    let payload = request.payload();
+    (request.function() == function::READ_FIFO_QUEUE && payload.len() == 2)
+        .then(|| byteorder::BigEndian::read_u16(payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FifoQueue, MAX_FIFO_COUNT};
+
+    #[test]
+    fn queue() {
+        let mut queue = FifoQueue::new();
+        for value in 0..MAX_FIFO_COUNT as u16 {
+            queue.push(value).unwrap();
+        }
+        assert_eq!(queue.push(100), Err(100));
+        assert_eq!(queue.pop(), Some(0));
+        queue.push(100).unwrap();
+        assert_eq!(queue.as_slice().first(), Some(&1));
+        assert_eq!(queue.as_slice().last(), Some(&100));
+
+        let mut buffer = [0; 70];
+        let response = queue.build_response(2, &mut buffer);
+        assert_eq!(response.payload()[..4], [0, 64, 0, 31]);
+        assert_eq!(response.raw_bytes().len(), 70);
+
+        queue.clear();
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+        let response = queue.build_response(2, &mut buffer);
+        assert_eq!(response.payload(), [0, 2, 0, 0]);
+    }
+}
@@ -0,0 +1,326 @@
+//! Read/Write File Record (0x14/0x15) on the server side
+//!
+//! Devices with files, e.g. calibration tables or logs in EEPROM, implement [`FileStorage`] and return it from
+//! [`Handler::files`]. The server dispatcher then answers both functions: each sub-request is validated, read or
+//! written in order, and [`FileError`]s become exception responses. Records are registers, record `n` of a file
+//! is its `n`th register.
+//!
+//! ```
+//! use modbus_frames::{
+//!     exception, file::FileStorage, request::{self, FileRecord}, server::{self, Handler},
+//! };
+//!
+//! struct Device {
+//!     // files 1 and 2, 16 registers each
+//!     eeprom: [[u16; 16]; 2],
+//! }
+//!
+//! impl Handler for Device {
+//!     fn files(&mut self) -> Option<&mut dyn FileStorage> {
+//!         Some(&mut self.eeprom)
+//!     }
+//! }
+//!
+//! let mut device = Device { eeprom: [[0; 16]; 2] };
+//! device.eeprom[1][4] = 0x1234;
+//! let mut request_buffer = [0; 16];
+//! let record = FileRecord { file_number: 2, record_number: 4, record_length: 1 };
+//! let request = request::read_file_record(&mut request_buffer, 1, &[record]).unwrap();
+//! let mut response_buffer = [0; 256];
+//! let response = server::dispatch(&mut device, request.try_into().unwrap(), &mut response_buffer);
+//! // byte count, then the record length, reference type and data of each sub-request
+//! assert_eq!(response.payload(), [4, 3, 6, 0x12, 0x34]);
+//!
+//! let record = FileRecord { file_number: 3, ..record };
+//! let request = request::read_file_record(&mut request_buffer, 1, &[record]).unwrap();
+//! let response = server::dispatch(&mut device, request.try_into().unwrap(), &mut response_buffer);
+//! assert_eq!(response.payload(), [exception::ILLEGAL_ADDRESS.0]);
+//! ```
+//!
+//! [`Handler::files`]: crate::server::Handler::files
+
+use byteorder::ByteOrder;
+
+use crate::{exception, function, request, server, Exception, Frame};
+
+/// Why a file record couldn't be accessed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FileError {
+    /// no file with the number, `ILLEGAL_ADDRESS`
+    NoSuchFile,
+    /// the records are past the end of the file, `ILLEGAL_ADDRESS`
+    OutOfRange,
+    /// e.g. an EEPROM write in progress, `DEVICE_BUSY`
+    Busy,
+    /// the storage failed, `DEVICE_FAILURE`
+    Failure,
+    /// the data was rejected, e.g. a failed checksum, `ILLEGAL_DATA`
+    Invalid,
+}
+
+impl From<FileError> for Exception {
+    fn from(error: FileError) -> Self {
+        match error {
+            FileError::NoSuchFile | FileError::OutOfRange => exception::ILLEGAL_ADDRESS,
+            FileError::Busy => exception::DEVICE_BUSY,
+            FileError::Failure => exception::DEVICE_FAILURE,
+            FileError::Invalid => exception::ILLEGAL_DATA,
+        }
+    }
+}
+
+/// Files of registers, numbered from 1
+pub trait FileStorage {
+    /// Read `data.len()` records of `file` starting at `record`
+    fn read_record(&mut self, file: u16, record: u16, data: &mut [u16]) -> Result<(), FileError>;
+
+    /// Write `data` to the records of `file` starting at `record`
+    fn write_record(&mut self, file: u16, record: u16, data: &[u16]) -> Result<(), FileError>;
+}
+
+impl<S: FileStorage + ?Sized> FileStorage for &mut S {
+    fn read_record(&mut self, file: u16, record: u16, data: &mut [u16]) -> Result<(), FileError> {
+        (**self).read_record(file, record, data)
+    }
+
+    fn write_record(&mut self, file: u16, record: u16, data: &[u16]) -> Result<(), FileError> {
+        (**self).write_record(file, record, data)
+    }
+}
+
+/// File `n` is element `n - 1`, all files are `N` records long
+impl<const N: usize> FileStorage for [[u16; N]] {
+    fn read_record(&mut self, file: u16, record: u16, data: &mut [u16]) -> Result<(), FileError> {
+        data.copy_from_slice(records(self, file, record, data.len())?);
+        Ok(())
+    }
+
+    fn write_record(&mut self, file: u16, record: u16, data: &[u16]) -> Result<(), FileError> {
+        records(self, file, record, data.len())?.copy_from_slice(data);
+        Ok(())
+    }
+}
+
+impl<const N: usize, const F: usize> FileStorage for [[u16; N]; F] {
+    fn read_record(&mut self, file: u16, record: u16, data: &mut [u16]) -> Result<(), FileError> {
+        self.as_mut_slice().read_record(file, record, data)
+    }
+
+    fn write_record(&mut self, file: u16, record: u16, data: &[u16]) -> Result<(), FileError> {
+        self.as_mut_slice().write_record(file, record, data)
+    }
+}
+
+fn records<const N: usize>(
+    files: &mut [[u16; N]],
+    file: u16,
+    record: u16,
+    len: usize,
+) -> Result<&mut [u16], FileError> {
+    let file = usize::from(file)
+        .checked_sub(1)
+        .and_then(|idx| files.get_mut(idx))
+        .ok_or(FileError::NoSuchFile)?;
+    let start = usize::from(record);
+    file.get_mut(start..start + len)
+        .ok_or(FileError::OutOfRange)
+}
+
+/// `(file, record, length)` of a sub-request, following the reference type
+fn sub_request(sub: &[u8]) -> (u16, u16, usize) {
+    let word = |idx: usize| byteorder::BigEndian::read_u16(&sub[idx..]);
+    (word(1), word(3), usize::from(word(5)))
+}
+
+/// Answer a Read File Record (0x14) or Write File Record (0x15) `request` from `storage`
+///
+/// Sub-requests are handled in order and the first error becomes the response, writes before it are not undone.
+/// Requests for any other function are `ILLEGAL_FUNCTION`.
+pub fn respond<'b, S: FileStorage + ?Sized>(
+    storage: &mut S,
+    request: Frame,
+    response_buffer: &'b mut [u8],
+) -> Result<Frame<'b>, Exception> {
+    match request.function() {
+        function::READ_FILE_RECORD | function::WRITE_FILE_RECORD => {}
+        _ => return Err(exception::ILLEGAL_FUNCTION),
+    }
+    server::validate_request_frame(request)?;
+    // byte count(1), then the sub-requests
+    let subs = &request.payload()[1..];
+
+    if request.function() == function::WRITE_FILE_RECORD {
+        let mut records = subs;
+        while !records.is_empty() {
+            let (file, record, len) = sub_request(records);
+            let mut data = [0; request::MAX_WRITE_REGISTERS as usize];
+            let data = &mut data[..len];
+            for (register, bytes) in data.iter_mut().zip(records[7..].chunks_exact(2)) {
+                *register = byteorder::BigEndian::read_u16(bytes);
+            }
+            storage.write_record(file, record, data)?;
+            records = &records[7 + 2 * len..];
+        }
+        // the response echoes the request
+        return Ok(request
+            .response_builder(response_buffer)
+            .bytes(request.payload().iter().copied())
+            .finalise()
+            .0);
+    }
+
+    // record data length(1) + reference type(1) + data per sub-request
+    let byte_count: usize = subs
+        .chunks_exact(7)
+        .map(|sub| 2 + 2 * sub_request(sub).2)
+        .sum();
+    let mut builder = request
+        .response_builder(response_buffer)
+        .byte(byte_count as u8);
+    for sub in subs.chunks_exact(7) {
+        let (file, record, len) = sub_request(sub);
+        let mut data = [0; request::MAX_READ_REGISTERS as usize];
+        let data = &mut data[..len];
+        storage.read_record(file, record, data)?;
+        builder = builder
+            .byte((1 + 2 * len) as u8)
+            .byte(6)
+            .registers(data.iter().copied());
+    }
+    Ok(builder.finalise().0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileError, FileStorage};
+    use crate::{
+        builder, exception,
+        request::{self, FileRecord, FileRecordData},
+        server::{self, Handler},
+        Exception, Frame,
+    };
+
+    struct Eeprom {
+        files: [[u16; 8]; 2],
+        busy: bool,
+    }
+
+    impl FileStorage for Eeprom {
+        fn read_record(
+            &mut self,
+            file: u16,
+            record: u16,
+            data: &mut [u16],
+        ) -> Result<(), FileError> {
+            self.files.read_record(file, record, data)
+        }
+
+        fn write_record(&mut self, file: u16, record: u16, data: &[u16]) -> Result<(), FileError> {
+            if self.busy {
+                return Err(FileError::Busy);
+            }
+            self.files.write_record(file, record, data)
+        }
+    }
+
+    impl Handler for Eeprom {
+        fn files(&mut self) -> Option<&mut dyn FileStorage> {
+            Some(self)
+        }
+    }
+
+    fn exception(response: Frame) -> Option<Exception> {
+        (response.function().0 & 0x80 != 0).then(|| Exception(response.payload()[0]))
+    }
+
+    #[test]
+    fn file_records() {
+        let mut eeprom = Eeprom {
+            files: [[0; 8]; 2],
+            busy: false,
+        };
+        let mut req = [0; 64];
+        let mut res = [0; 256];
+
+        let writes = [
+            FileRecordData {
+                file_number: 1,
+                record_number: 6,
+                data: &[1, 2],
+            },
+            FileRecordData {
+                file_number: 2,
+                record_number: 0,
+                data: &[0xABCD],
+            },
+        ];
+        let request = request::write_file_record(&mut req, 1, &writes).unwrap();
+        let response = server::dispatch(&mut eeprom, request.try_into().unwrap(), &mut res);
+        assert_eq!(response.payload(), request.payload());
+        assert_eq!(
+            eeprom.files,
+            [[0, 0, 0, 0, 0, 0, 1, 2], [0xABCD, 0, 0, 0, 0, 0, 0, 0]]
+        );
+
+        let records = [
+            FileRecord {
+                file_number: 1,
+                record_number: 5,
+                record_length: 3,
+            },
+            FileRecord {
+                file_number: 2,
+                record_number: 0,
+                record_length: 1,
+            },
+        ];
+        let request = request::read_file_record(&mut req, 1, &records).unwrap();
+        let response = server::dispatch(&mut eeprom, request.try_into().unwrap(), &mut res);
+        assert_eq!(
+            response.payload(),
+            [12, 7, 6, 0, 0, 0, 1, 0, 2, 3, 6, 0xAB, 0xCD]
+        );
+
+        // past the end of the file, a missing file and a busy device
+        let record = FileRecord {
+            file_number: 1,
+            record_number: 6,
+            record_length: 3,
+        };
+        let request = request::read_file_record(&mut req, 1, &[record]).unwrap();
+        let response = server::dispatch(&mut eeprom, request.try_into().unwrap(), &mut res);
+        assert_eq!(exception(response), Some(exception::ILLEGAL_ADDRESS));
+        let record = FileRecord {
+            file_number: 3,
+            record_number: 0,
+            record_length: 1,
+        };
+        let request = request::read_file_record(&mut req, 1, &[record]).unwrap();
+        let response = server::dispatch(&mut eeprom, request.try_into().unwrap(), &mut res);
+        assert_eq!(exception(response), Some(exception::ILLEGAL_ADDRESS));
+        eeprom.busy = true;
+        let request = request::write_file_record(&mut req, 1, &writes).unwrap();
+        let response = server::dispatch(&mut eeprom, request.try_into().unwrap(), &mut res);
+        assert_eq!(exception(response), Some(exception::DEVICE_BUSY));
+
+        // malformed sub-requests are rejected before the storage sees them
+        let (request, _) = builder::build_frame(&mut req)
+            .for_address(1)
+            .function(crate::function::WRITE_FILE_RECORD)
+            .bytes([9, 5])
+            .registers([1, 0, 1, 0xABCD])
+            .finalise();
+        let response = server::dispatch(&mut eeprom, request.try_into().unwrap(), &mut res);
+        assert_eq!(exception(response), Some(exception::ILLEGAL_DATA));
+
+        // without storage
+        let request = request::read_file_record(&mut req, 1, &[record]).unwrap();
+        let response = server::dispatch(
+            &mut crate::source::Coils([false; 1]),
+            request.try_into().unwrap(),
+            &mut res,
+        );
+        assert_eq!(exception(response), Some(exception::ILLEGAL_FUNCTION));
+    }
+}
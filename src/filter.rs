@@ -0,0 +1,340 @@
+//! Request filtering for servers
+//!
+//! A [`Filter`] declares which function codes a server supports and which entities may be read or written.
+//! Requests outside of that policy are answered with the appropriate exception response before any
+//! application code sees them.
+//!
+//! Mask Write Register and Read/Write Multiple Registers are checked against the ranges as well. Other functions
+//! which address data the filter can't check, e.g. file records or FIFO queues, are rejected unless they are
+//! explicitly accepted with [`Filter::allow_unchecked`].
+//!
+//! ```
+//! use modbus_frames::{entity::{EntityRange, EntityType}, filter::Filter, function, request, exception};
+//!
+//! const FILTER: Filter = Filter::new(
+//!     &[function::READ_HOLDING_REGISTERS, function::WRITE_HOLDING_REGISTER],
+//!     &[EntityRange::new(EntityType::HoldingRegister, 0, 100)],
+//!     &[EntityRange::new(EntityType::HoldingRegister, 50, 10)],
+//! );
+//!
+//! let mut request_buffer = [0; 8];
+//! let request = request::write_holding_register(&mut request_buffer, 1, 10, 0).unwrap();
+//! let mut response_buffer = [0; 5];
+//! let (response, _) = FILTER.reject(request, &mut response_buffer).unwrap();
+//! assert_eq!(response.payload(), [exception::ILLEGAL_ADDRESS.0]);
+//! ```
+
+use byteorder::ByteOrder;
+
+use crate::{
+    decoder::CommonRequests,
+    entity::{Entity, EntityRange, EntityType},
+    exception, function, server, Exception, Frame, Function,
+};
+
+/// Allowed functions and entity ranges of a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Filter<'p> {
+    functions: &'p [Function],
+    readable: &'p [EntityRange],
+    writable: &'p [EntityRange],
+    unchecked: &'p [Function],
+}
+
+impl<'p> Filter<'p> {
+    /// A request must be entirely within one of the `readable`/`writable` ranges to be accepted
+    pub const fn new(
+        functions: &'p [Function],
+        readable: &'p [EntityRange],
+        writable: &'p [EntityRange],
+    ) -> Self {
+        Filter {
+            functions,
+            readable,
+            writable,
+            unchecked: &[],
+        }
+    }
+
+    /// Accept requests for `functions` on their function code alone, without checking what they access
+    ///
+    /// For functions whose data the filter can't check against the entity ranges, e.g. Write File Record. The
+    /// functions must also be allowed by `new`.
+    pub const fn allow_unchecked(mut self, functions: &'p [Function]) -> Self {
+        self.unchecked = functions;
+        self
+    }
+
+    pub fn allows_function(&self, function: Function) -> bool {
+        self.functions.contains(&function)
+    }
+
+    /// Check a decoded request against the policy
+    pub fn check(&self, request: &CommonRequests) -> Result<(), Exception> {
+        if !self.allows_function(request.as_frame().function()) {
+            return Err(exception::ILLEGAL_FUNCTION);
+        }
+        let ranges = if is_write(request) {
+            self.writable
+        } else {
+            self.readable
+        };
+        check_range(ranges, EntityRange::from_request(request))
+    }
+
+    /// Check a request frame against the policy
+    ///
+    /// Besides `CommonRequests`, Mask Write Register and Read/Write Multiple Registers are checked against the
+    /// ranges. Functions without entity addresses (exception status, diagnostics, the comm event counter/log, report
+    /// slave ID and read device identification) are checked by function code. Any other function is
+    /// `ILLEGAL_FUNCTION` unless it was accepted with `allow_unchecked`, malformed requests are `ILLEGAL_DATA`.
+    pub fn check_frame(&self, request: Frame) -> Result<(), Exception> {
+        let function = request.function();
+        if !self.allows_function(function) {
+            return Err(exception::ILLEGAL_FUNCTION);
+        }
+        if let Ok(decoded) = CommonRequests::try_from(request) {
+            return self.check(&decoded);
+        }
+        let payload = request.payload();
+        let word = |idx: usize| byteorder::BigEndian::read_u16(&payload[idx..]);
+        let registers = |start, count| EntityRange::new(EntityType::HoldingRegister, start, count);
+        match function {
+            function::MASK_WRITE_REGISTER => {
+                server::validate_request_frame(request)?;
+                check_range(self.writable, registers(word(0), 1))
+            }
+            function::READ_WRITE_MULTIPLE_REGISTERS => {
+                server::validate_request_frame(request)?;
+                check_range(self.readable, registers(word(0), word(2)))?;
+                check_range(self.writable, registers(word(4), word(6)))
+            }
+            function::READ_EXCEPTION_STATUS
+            | function::DIAGNOSTIC
+            | function::GET_COMM_EVENT_COUNTER
+            | function::GET_COMM_EVENT_LOG
+            | function::REPORT_SLAVE_ID => Ok(()),
+            function::ENCAPSULATED_INTERFACE_TRANSPORT
+                if payload.first() == Some(&function::MEI_READ_DEVICE_IDENTIFICATION) =>
+            {
+                Ok(())
+            }
+            // failed to decode as one of `CommonRequests`
+            function::READ_COILS
+            | function::READ_DISCRETE_INPUTS
+            | function::READ_HOLDING_REGISTERS
+            | function::READ_INPUT_REGISTERS
+            | function::WRITE_COIL
+            | function::WRITE_HOLDING_REGISTER
+            | function::WRITE_MULTIPLE_COILS
+            | function::WRITE_MULTIPLE_HOLDING_REGISTERS => Err(exception::ILLEGAL_DATA),
+            _ if self.unchecked.contains(&function) => Ok(()),
+            _ => Err(exception::ILLEGAL_FUNCTION),
+        }
+    }
+
+    /// true if `entity` may be read
+    pub fn can_read(&self, entity: &Entity) -> bool {
+        self.readable.iter().any(|range| range.contains(entity))
+    }
+
+    /// true if `entity` may be written
+    pub fn can_write(&self, entity: &Entity) -> bool {
+        self.writable.iter().any(|range| range.contains(entity))
+    }
+
+    /// Exception response for `request` if it is outside of the policy, `None` if the request should be handled
+    pub fn reject<'b>(
+        &self,
+        request: Frame,
+        response_buffer: &'b mut [u8],
+    ) -> Option<(Frame<'b>, &'b mut [u8])> {
+        let exception = self.check_frame(request).err()?;
+        Some(request.response_exception(response_buffer, exception))
+    }
+}
+
+/// `accessed` must be entirely within one of `ranges`
+fn check_range(ranges: &[EntityRange], accessed: EntityRange) -> Result<(), Exception> {
+    if ranges.iter().any(|range| range.contains_range(&accessed)) {
+        Ok(())
+    } else {
+        Err(exception::ILLEGAL_ADDRESS)
+    }
+}
+
+fn is_write(request: &CommonRequests) -> bool {
+    matches!(
+        request,
+        CommonRequests::WriteCoil(_)
+            | CommonRequests::WriteHoldingRegister(_)
+            | CommonRequests::WriteMultipleCoils(_)
+            | CommonRequests::WriteMultipleHoldingRegisters(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Filter;
+    use crate::{
+        entity::{Entity, EntityRange, EntityType},
+        exception, function, request,
+    };
+
+    const FILTER: Filter = Filter::new(
+        &[
+            function::READ_COILS,
+            function::WRITE_MULTIPLE_COILS,
+            function::READ_HOLDING_REGISTERS,
+            function::REPORT_SLAVE_ID,
+        ],
+        &[
+            EntityRange::new(EntityType::Coil, 0, 16),
+            EntityRange::new(EntityType::HoldingRegister, 100, 10),
+        ],
+        &[EntityRange::new(EntityType::Coil, 8, 8)],
+    );
+
+    #[test]
+    fn policy() {
+        let mut buffer = [0; 32];
+        let check = |frame: Result<crate::Frame, crate::Error>| FILTER.check_frame(frame.unwrap());
+        assert_eq!(check(request::read_coils(&mut buffer, 1, 0, 16)), Ok(()));
+        assert_eq!(
+            check(request::read_coils(&mut buffer, 1, 1, 16)),
+            Err(exception::ILLEGAL_ADDRESS)
+        );
+        assert_eq!(
+            check(request::write_multiple_coils(&mut buffer, 1, 8, &[true; 8])),
+            Ok(())
+        );
+        assert_eq!(
+            check(request::write_multiple_coils(&mut buffer, 1, 7, &[true; 2])),
+            Err(exception::ILLEGAL_ADDRESS)
+        );
+        assert_eq!(
+            check(request::write_coil(&mut buffer, 1, 8, true)),
+            Err(exception::ILLEGAL_FUNCTION)
+        );
+        assert_eq!(
+            check(request::read_holding_registers(&mut buffer, 1, 100, 10)),
+            Ok(())
+        );
+        assert_eq!(check(request::report_slave_id(&mut buffer, 1)), Ok(()));
+        assert_eq!(
+            check(request::read_exception_status(&mut buffer, 1)),
+            Err(exception::ILLEGAL_FUNCTION)
+        );
+
+        assert!(FILTER.can_read(&Entity::holding_register(109)));
+        assert!(!FILTER.can_write(&Entity::coil(7)));
+    }
+
+    #[test]
+    fn unchecked_functions() {
+        const REGISTERS: Filter = Filter::new(
+            &[
+                function::MASK_WRITE_REGISTER,
+                function::READ_WRITE_MULTIPLE_REGISTERS,
+                function::WRITE_FILE_RECORD,
+                function::READ_FIFO_QUEUE,
+            ],
+            &[EntityRange::new(EntityType::HoldingRegister, 0, 100)],
+            &[EntityRange::new(EntityType::HoldingRegister, 50, 10)],
+        );
+        let mut buffer = [0; 64];
+        let check = |filter: Filter, frame: Result<crate::Frame, crate::Error>| {
+            filter.check_frame(frame.unwrap())
+        };
+        assert_eq!(
+            check(
+                REGISTERS,
+                request::mask_write_register(&mut buffer, 1, 59, 0, 1)
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check(
+                REGISTERS,
+                request::mask_write_register(&mut buffer, 1, 60, 0, 1)
+            ),
+            Err(exception::ILLEGAL_ADDRESS)
+        );
+        assert_eq!(
+            check(
+                REGISTERS,
+                request::read_write_multiple_registers(&mut buffer, 1, 0, 100, 50, &[0; 10])
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check(
+                REGISTERS,
+                request::read_write_multiple_registers(&mut buffer, 1, 0, 1, 49, &[0; 2])
+            ),
+            Err(exception::ILLEGAL_ADDRESS)
+        );
+        assert_eq!(
+            check(
+                REGISTERS,
+                request::read_write_multiple_registers(&mut buffer, 1, 99, 2, 50, &[0])
+            ),
+            Err(exception::ILLEGAL_ADDRESS)
+        );
+
+        // file records and FIFO queues can't be checked, they need an explicit opt in
+        let record = request::FileRecordData {
+            file_number: 1,
+            record_number: 0,
+            data: &[1],
+        };
+        assert_eq!(
+            check(
+                REGISTERS,
+                request::write_file_record(&mut buffer, 1, &[record])
+            ),
+            Err(exception::ILLEGAL_FUNCTION)
+        );
+        let opted_in = REGISTERS.allow_unchecked(&[function::WRITE_FILE_RECORD]);
+        assert_eq!(
+            check(
+                opted_in,
+                request::write_file_record(&mut buffer, 1, &[record])
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check(opted_in, request::read_fifo_queue(&mut buffer, 1, 0)),
+            Err(exception::ILLEGAL_FUNCTION)
+        );
+
+        // malformed requests for checked functions
+        let (frame, _) = crate::builder::build_frame(&mut buffer)
+            .for_address(1)
+            .function(function::READ_HOLDING_REGISTERS)
+            .registers([0])
+            .finalise();
+        assert_eq!(FILTER.check_frame(frame), Err(exception::ILLEGAL_DATA));
+        let (frame, _) = crate::builder::build_frame(&mut buffer)
+            .for_address(1)
+            .function(function::MASK_WRITE_REGISTER)
+            .registers([50, 0])
+            .finalise();
+        assert_eq!(REGISTERS.check_frame(frame), Err(exception::ILLEGAL_DATA));
+    }
+
+    #[test]
+    fn reject() {
+        let mut request_buffer = [0; 8];
+        let request = request::read_input_registers(&mut request_buffer, 3, 0, 1).unwrap();
+        let mut response_buffer = [0; 5];
+        let (response, _) = FILTER.reject(request, &mut response_buffer).unwrap();
+        assert_eq!(response.address(), 3);
+        assert_eq!(response.function(), crate::Function(0x84));
+        assert_eq!(response.payload(), [exception::ILLEGAL_FUNCTION.0]);
+
+        let request = request::read_coils(&mut request_buffer, 3, 0, 1).unwrap();
+        assert!(FILTER.reject(request, &mut response_buffer).is_none());
+    }
+}
@@ -1,11 +1,20 @@
+use core::iter::FusedIterator;
+
 use byteorder::ByteOrder;
 
-use crate::{builder, calculate_crc16, verify_crc16, Error, Exception, Function};
+use crate::{
+    ascii, builder, calculate_crc16, function, pdu::Pdu, verify_crc16, Device, Error, Exception,
+    Function, ModbusFrame,
+};
 
 /// Frame provides functions to view a series of bytes in RTU format as a modbus data frame
 /// `|address(1)|function(1)|payload(0..252)|crc16(2)`
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+///
+/// A `Frame` may come from anywhere, including `new_unchecked`. Received bytes are checked once by
+/// [`ValidatedFrame::try_from`], the server entry points take a [`ValidatedFrame`] and the typed decoders taking a
+/// `Frame` (e.g. `request::ReadCoils::try_from(frame)` or `CommonRequests::try_from(frame)`) only check the
+/// function and length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Frame<'b> {
     data: &'b [u8],
 }
@@ -21,45 +30,125 @@ impl<'b> Frame<'b> {
     /// * frame::build_frame will construct a valid frame from various components in a reasonably ergonomic form
     ///
     /// This method is public to allow for potential external extensions
-    pub fn new_unchecked(bytes: &'b [u8]) -> Self {
+    pub const fn new_unchecked(bytes: &'b [u8]) -> Self {
         Frame { data: bytes }
     }
 
-    /// The address byte of the frame
+    /// The address byte of the frame, 0 for an empty frame from `new_unchecked`
     pub fn address(&self) -> u8 {
-        self.data[0]
+        self.data.first().copied().unwrap_or_default()
     }
 
-    /// the function code of the frame
+    /// The device the frame is addressed to (requests) or from (responses)
+    pub fn device(&self) -> Device {
+        Device::new(self.address())
+    }
+
+    /// the function code of the frame, 0 for a frame from `new_unchecked` without one
     pub fn function(&self) -> Function {
-        Function(self.data[1])
+        Function(self.data.get(1).copied().unwrap_or_default())
     }
 
     /// calculate the expected CRC of the frame
     ///
     /// NOTE: if Self::new_unchecked was used to create this instance, there is a possibility this will not be equal to `self.crc()`
+    ///
+    /// # Panics
+    /// if the frame is shorter than 2 bytes, only possible with `new_unchecked`
     pub fn calculate_crc(&self) -> u16 {
+        self.debug_check_len();
         let crc_idx = self.data.len() - 2;
         calculate_crc16(&self.data[..crc_idx])
     }
 
     /// All bytes between the address/function code and CRC
-    pub fn payload(&self) -> &[u8] {
+    ///
+    /// # Panics
+    /// if the frame is shorter than 4 bytes, only possible with `new_unchecked`. See `try_payload`
+    pub fn payload(&self) -> &'b [u8] {
+        self.debug_check_len();
         let crc_idx = self.data.len() - 2;
         &self.data[2..crc_idx]
     }
 
+    /// accessors slice around the CRC, catch frames from `new_unchecked` that are too short to have one
+    fn debug_check_len(&self) {
+        debug_assert!(
+            self.data.len() >= 4,
+            "frame of {} bytes is shorter than the 4 byte minimum",
+            self.data.len()
+        );
+    }
+
+    /// `payload` which returns `None` instead of panicking if the frame is shorter than 4 bytes
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+    pub fn try_payload(&self) -> Option<&'b [u8]> {
+        self.data.get(2..self.data.len().checked_sub(2)?)
+    }
+
+    /// The function code and payload without the address and CRC, as carried by Modbus TCP
+    ///
+    /// # Panics
+    /// if the frame is shorter than 4 bytes, only possible with `new_unchecked`. See `try_pdu`
+    pub fn pdu(&self) -> Pdu<'b> {
+        self.debug_check_len();
+        let crc_idx = self.data.len() - 2;
+        Pdu::new_unchecked(&self.data[1..crc_idx])
+    }
+
+    /// `pdu` which returns `None` instead of panicking if the frame is shorter than 4 bytes
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+    pub fn try_pdu(&self) -> Option<Pdu<'b>> {
+        match self.data.len() {
+            len @ 4.. => self.data.get(1..len - 2).map(Pdu::new_unchecked),
+            _ => None,
+        }
+    }
+
     /// crc bytes as a u16
+    ///
+    /// # Panics
+    /// as `crc_bytes`
     pub fn crc(&self) -> u16 {
         byteorder::LittleEndian::read_u16(self.crc_bytes())
     }
 
     /// The crc bytes
+    ///
+    /// # Panics
+    /// if the frame is shorter than 4 bytes, only possible with `new_unchecked`. See `try_crc_bytes`
     pub fn crc_bytes(&self) -> &[u8] {
+        self.debug_check_len();
         let crc_idx = self.data.len() - 2;
         &self.data[crc_idx..]
     }
 
+    /// `crc_bytes` which returns `None` instead of panicking if the frame is shorter than 4 bytes
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+    pub fn try_crc_bytes(&self) -> Option<&'b [u8]> {
+        match self.data.len() {
+            len @ 4.. => self.data.get(len - 2..),
+            _ => None,
+        }
+    }
+
+    /// The bytes before the CRC and the CRC bytes, e.g. for transmitting from separate DMA descriptors
+    ///
+    /// # Panics
+    /// if the frame is shorter than 4 bytes, only possible with `new_unchecked`. See `try_parts`
+    pub fn parts(&self) -> (&'b [u8], [u8; 2]) {
+        self.try_parts()
+            .expect("frame is shorter than the 4 byte minimum")
+    }
+
+    /// `parts` which returns `None` instead of panicking if the frame is shorter than 4 bytes
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+    pub fn try_parts(&self) -> Option<(&'b [u8], [u8; 2])> {
+        let crc = self.try_crc_bytes()?;
+        let data = self.data.get(..self.data.len() - 2)?;
+        Some((data, [*crc.first()?, *crc.get(1)?]))
+    }
+
     /// All of the bytes in the message (address, function, payload, crc)
     pub fn raw_bytes(&self) -> &[u8] {
         self.data
@@ -71,11 +160,47 @@ impl<'b> Frame<'b> {
         self.data
     }
 
+    /// The address, function and payload without the CRC, the same for a frame in any encoding
+    ///
+    /// Useful as a key for deduplicating frames received over different links
+    pub fn canonical_bytes(&self) -> &'b [u8] {
+        &self.data[..self.data.len().saturating_sub(2)]
+    }
+
+    /// true if `other` has the same address, function and payload, whatever its encoding and checksum
+    pub fn semantic_eq<'o>(&self, other: &impl ModbusFrame<'o>) -> bool {
+        ModbusFrame::semantic_eq(self, other)
+    }
+
+    /// Fixed size key for lookup tables, see [`FrameKey`]
+    pub fn key(&self) -> FrameKey {
+        let mut payload = [0; 4];
+        let available = self.try_payload().unwrap_or_default();
+        let len = available.len().min(4);
+        payload[..len].copy_from_slice(&available[..len]);
+        FrameKey {
+            address: self.address(),
+            function: self.function(),
+            payload,
+        }
+    }
+
     /// Iterator returning the message bytes in RTU format
-    pub fn rtu_bytes(&self) -> impl Iterator<Item = u8> + 'b {
+    pub fn rtu_bytes(
+        &self,
+    ) -> impl ExactSizeIterator<Item = u8> + DoubleEndedIterator + FusedIterator + 'b {
         self.data.iter().copied()
     }
 
+    /// Copy the RTU bytes into `out`, returns the number copied
+    ///
+    /// Only the first `out.len()` bytes are copied if `out` is shorter than the frame
+    pub fn fill(&self, out: &mut [u8]) -> usize {
+        let len = self.data.len().min(out.len());
+        out[..len].copy_from_slice(&self.data[..len]);
+        len
+    }
+
     pub fn response_builder<'buff>(
         &self,
         response_buffer: &'buff mut [u8],
@@ -90,9 +215,238 @@ impl<'b> Frame<'b> {
         response_buffer: &'buff mut [u8],
         exception: Exception,
     ) -> (Frame<'buff>, &'buff mut [u8]) {
-        builder::build_frame(response_buffer)
-            .for_address(self.address())
-            .exception(self.function(), exception)
+        builder::build_frame(response_buffer).exception_to(self, exception)
+    }
+
+    /// Copy the frame into `buffer` with an LRC in place of the CRC, e.g. to send a response built with the
+    /// typed API over an ASCII link
+    ///
+    /// `Error::InvalidLength` for a frame from `new_unchecked` shorter than 4 bytes
+    pub fn to_ascii<'buff>(&self, buffer: &'buff mut [u8]) -> Result<ascii::Frame<'buff>, Error> {
+        if self.data.len() < 4 {
+            return Err(Error::InvalidLength(self.data.len()));
+        }
+        let len = self.data.len() - 1;
+        let out = buffer.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+        out[..len - 1].copy_from_slice(&self.data[..len - 1]);
+        out[len - 1] = crate::calculate_lrc(&self.data[..len - 1]);
+        Ok(ascii::Frame::new_unchecked(out))
+    }
+
+    /// Write a decoded breakdown of the frame for debug output
+    ///
+    /// `addr=0x11 fn=03 ReadHoldingRegisters start=0x006B count=3 crc=OK`
+    ///
+    /// Fields are decoded for the common functions, other payloads are shown as hex
+    pub fn explain(&self, out: &mut impl core::fmt::Write) -> core::fmt::Result {
+        if self.data.len() < 4 {
+            out.write_str("invalid frame")?;
+            return write_hex(out, " data=", self.data);
+        }
+        let function = self.function();
+        let code = Function(function.0 & 0x7F);
+        write!(out, "addr=0x{:02X} fn={:02X}", self.address(), function.0)?;
+        if let Some(name) = code.name() {
+            write!(out, " {}", name)?;
+        }
+        let payload = self.payload();
+        let word = |idx: usize| byteorder::BigEndian::read_u16(&payload[idx..]);
+        match (function, payload.len()) {
+            (_, 1) if function.0 & 0x80 != 0 => {
+                write!(out, " exception={}", Exception(payload[0]))?
+            }
+            // fixed length requests, and the echoed write responses
+            (
+                function::READ_COILS
+                | function::READ_DISCRETE_INPUTS
+                | function::READ_HOLDING_REGISTERS
+                | function::READ_INPUT_REGISTERS
+                | function::WRITE_MULTIPLE_COILS
+                | function::WRITE_MULTIPLE_HOLDING_REGISTERS,
+                4,
+            ) => write!(out, " start=0x{:04X} count={}", word(0), word(2))?,
+            (function::WRITE_COIL | function::WRITE_HOLDING_REGISTER, 4) => {
+                write!(out, " index=0x{:04X} value=0x{:04X}", word(0), word(2))?
+            }
+            (function::WRITE_MULTIPLE_COILS | function::WRITE_MULTIPLE_HOLDING_REGISTERS, 5..) => {
+                write!(out, " start=0x{:04X} count={}", word(0), word(2))?;
+                write_hex(out, " data=", &payload[5..])?
+            }
+            // read responses
+            (
+                function::READ_COILS
+                | function::READ_DISCRETE_INPUTS
+                | function::READ_HOLDING_REGISTERS
+                | function::READ_INPUT_REGISTERS,
+                1..,
+            ) => {
+                write!(out, " bytes={}", payload[0])?;
+                write_hex(out, " data=", &payload[1..])?
+            }
+            (_, 0) => {}
+            _ => write_hex(out, " data=", payload)?,
+        }
+        if self.crc() == self.calculate_crc() {
+            out.write_str(" crc=OK")
+        } else {
+            write!(out, " crc=BAD (expected 0x{:04X})", self.calculate_crc())
+        }
+    }
+
+    /// Write the bytes 16 to a line, each line prefixed with the offset
+    ///
+    /// ```text
+    /// 0000: 11 03 00 6B 00 03 76 87
+    /// ```
+    pub fn hexdump(&self, out: &mut impl core::fmt::Write) -> core::fmt::Result {
+        for (line, chunk) in self.data.chunks(16).enumerate() {
+            write!(out, "{:04X}:", line * 16)?;
+            for byte in chunk {
+                write!(out, " {:02X}", byte)?;
+            }
+            out.write_str("\n")?;
+        }
+        Ok(())
+    }
+
+    /// Parse hex as pasted from a bus analyser into `buffer`, the length and CRC are validated as for `try_from`
+    ///
+    /// Bytes may be separated by whitespace or commas and prefixed with `0x`,
+    /// e.g. `11 03 00 6B 00 03 76 87`, `0x11, 0x03, ..` or `1103006B00037687`
+    pub fn from_hex_str(hex: &str, buffer: &'b mut [u8]) -> Result<Self, Error> {
+        let mut len = 0;
+        for token in hex.split(|c: char| c.is_whitespace() || c == ',') {
+            let token = token
+                .strip_prefix("0x")
+                .or_else(|| token.strip_prefix("0X"))
+                .unwrap_or(token);
+            if token.len() % 2 != 0 {
+                return Err(Error::InvalidValue);
+            }
+            for pair in token.as_bytes().chunks(2) {
+                let pair = core::str::from_utf8(pair).map_err(|_| Error::InvalidValue)?;
+                let byte = u8::from_str_radix(pair, 16).map_err(|_| Error::InvalidValue)?;
+                *buffer.get_mut(len).ok_or(Error::BufferTooSmall)? = byte;
+                len += 1;
+            }
+        }
+        let buffer: &'b [u8] = buffer;
+        Frame::try_from(&buffer[..len])
+    }
+
+    /// The bytes as space separated hex, `11 03 00 6B 00 03 76 87`
+    #[cfg(feature = "std")]
+    pub fn to_hex_string(&self) -> String {
+        let mut out = String::with_capacity(self.data.len() * 3);
+        // writing to a String can't fail
+        let _ = write_hex(&mut out, "", self.data);
+        out
+    }
+}
+
+/// `prefix` followed by space separated hex bytes
+fn write_hex(out: &mut impl core::fmt::Write, prefix: &str, bytes: &[u8]) -> core::fmt::Result {
+    out.write_str(prefix)?;
+    for (idx, byte) in bytes.iter().enumerate() {
+        if idx > 0 {
+            out.write_str(" ")?;
+        }
+        write!(out, "{:02X}", byte)?;
+    }
+    Ok(())
+}
+
+/// `address=0x11 function=ReadHoldingRegisters (0x03) [11 03 00 6B 00 03 76 87]`
+///
+/// exception responses show the exception in place of the function
+impl core::fmt::Display for Frame<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.data.len() >= 4 {
+            let function = self.function();
+            write!(f, "address=0x{:02X} ", self.address())?;
+            match self.payload() {
+                [code] if function.0 & 0x80 != 0 => write!(
+                    f,
+                    "function={} exception={} ",
+                    Function(function.0 & 0x7F),
+                    Exception(*code)
+                )?,
+                _ => write!(f, "function={} ", function)?,
+            }
+        }
+        write_hex(f, "[", self.data)?;
+        f.write_str("]")
+    }
+}
+
+/// Number of payload bytes shown when logging frames with defmt
+#[cfg(feature = "defmt")]
+pub const DEFMT_PREVIEW_LEN: usize = 16;
+
+/// The first `DEFMT_PREVIEW_LEN` bytes and the number of bytes omitted
+#[cfg(feature = "defmt")]
+pub(crate) fn defmt_preview(bytes: &[u8]) -> (&[u8], usize) {
+    let shown = bytes.len().min(DEFMT_PREVIEW_LEN);
+    (&bytes[..shown], bytes.len() - shown)
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Frame<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        if self.data.len() < 4 {
+            defmt::write!(f, "Frame {{ invalid: {=[u8]:02x} }}", self.data);
+            return;
+        }
+        let function = self.function();
+        let (payload, omitted) = defmt_preview(self.payload());
+        if function.0 & 0x80 != 0 && payload.len() == 1 {
+            defmt::write!(
+                f,
+                "Frame {{ address: {=u8}, function: {}, exception: {} }}",
+                self.address(),
+                Function(function.0 & 0x7F),
+                Exception(payload[0])
+            );
+        } else if omitted > 0 {
+            defmt::write!(
+                f,
+                "Frame {{ address: {=u8}, function: {}, payload: {=[u8]:02x}..(+{=usize}) }}",
+                self.address(),
+                function,
+                payload,
+                omitted
+            );
+        } else {
+            defmt::write!(
+                f,
+                "Frame {{ address: {=u8}, function: {}, payload: {=[u8]:02x} }}",
+                self.address(),
+                function,
+                payload
+            );
+        }
+    }
+}
+
+impl<'b> ModbusFrame<'b> for Frame<'b> {
+    fn address(&self) -> u8 {
+        Frame::address(self)
+    }
+
+    fn function(&self) -> Function {
+        Frame::function(self)
+    }
+
+    fn payload(&self) -> &[u8] {
+        Frame::payload(self)
+    }
+
+    fn verify(&self) -> bool {
+        verify_crc16(self.data)
+    }
+
+    fn into_raw_bytes(self) -> &'b [u8] {
+        self.data
     }
 }
 
@@ -101,7 +455,7 @@ impl<'b> TryFrom<&'b [u8]> for Frame<'b> {
 
     fn try_from(bytes: &'b [u8]) -> Result<Self, Self::Error> {
         if bytes.len() < 4 {
-            Err(Self::Error::InvalidLength)
+            Err(Self::Error::InvalidLength(bytes.len()))
         } else if !verify_crc16(bytes) {
             Err(Self::Error::InvalidCrc)
         } else {
@@ -110,10 +464,257 @@ impl<'b> TryFrom<&'b [u8]> for Frame<'b> {
     }
 }
 
+/// A [`Frame`] whose length and CRC have been checked
+///
+/// Made once from received bytes, then decoded without checking the CRC again. The server entry points
+/// (e.g. [`server::dispatch`](crate::server::dispatch)) only take validated frames.
+/// ```
+/// use modbus_frames::{frame::ValidatedFrame, request};
+///
+/// let bytes = [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87];
+/// let frame = ValidatedFrame::try_from(&bytes[..]).unwrap();
+/// let request: request::ReadHoldingRegisters = frame.decode().unwrap();
+/// assert_eq!(request.register_count(), 3);
+/// assert!(ValidatedFrame::try_from(&bytes[..7]).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ValidatedFrame<'b>(Frame<'b>);
+
+impl<'b> ValidatedFrame<'b> {
+    /// Trust `frame` without checking its CRC
+    ///
+    /// For frames from links which can't corrupt bytes, see [`decode_unchecked_crc`]. Frames shorter than 4 bytes
+    /// are still invalid and later operations are likely to panic.
+    pub const fn new_unchecked(frame: Frame<'b>) -> Self {
+        ValidatedFrame(frame)
+    }
+
+    pub fn as_frame(&self) -> Frame<'b> {
+        self.0
+    }
+
+    /// Decode as one of the typed requests/responses or `CommonRequests`/`CommonResponses`, checking only the
+    /// function and length
+    pub fn decode<T: TryFrom<Frame<'b>, Error = Error>>(self) -> Result<T, Error> {
+        T::try_from(self.0)
+    }
+}
+
+impl<'b> core::ops::Deref for ValidatedFrame<'b> {
+    type Target = Frame<'b>;
+
+    fn deref(&self) -> &Frame<'b> {
+        &self.0
+    }
+}
+
+impl<'b> ModbusFrame<'b> for ValidatedFrame<'b> {
+    fn address(&self) -> u8 {
+        self.0.address()
+    }
+
+    fn function(&self) -> Function {
+        self.0.function()
+    }
+
+    fn payload(&self) -> &[u8] {
+        self.0.payload()
+    }
+
+    fn verify(&self) -> bool {
+        ModbusFrame::verify(&self.0)
+    }
+
+    fn into_raw_bytes(self) -> &'b [u8] {
+        self.0.into_raw_bytes()
+    }
+}
+
+impl<'b> From<ValidatedFrame<'b>> for Frame<'b> {
+    fn from(frame: ValidatedFrame<'b>) -> Self {
+        frame.0
+    }
+}
+
+impl<'b> TryFrom<&'b [u8]> for ValidatedFrame<'b> {
+    type Error = Error;
+
+    fn try_from(bytes: &'b [u8]) -> Result<Self, Self::Error> {
+        Frame::try_from(bytes).map(ValidatedFrame)
+    }
+}
+
+/// Check the CRC of a frame from elsewhere, e.g. the builder or `new_unchecked`
+impl<'b> TryFrom<Frame<'b>> for ValidatedFrame<'b> {
+    type Error = Error;
+
+    fn try_from(frame: Frame<'b>) -> Result<Self, Self::Error> {
+        ValidatedFrame::try_from(frame.into_raw_bytes())
+    }
+}
+
+/// The address, function and first four payload bytes of a frame (zero padded)
+///
+/// The first four bytes of a request payload are the start index and count for the common functions, enough to tell
+/// apart the requests a gateway or master has pending without keeping the frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FrameKey {
+    pub address: u8,
+    pub function: Function,
+    pub payload: [u8; 4],
+}
+
+/// Why received bytes failed to decode as a frame, with the context needed to diagnose it later
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DecodeFailure<'a> {
+    /// the bytes that failed to decode
+    pub bytes: &'a [u8],
+    pub error: Error,
+    /// the CRC calculated over the bytes, `None` if there are too few bytes for a CRC
+    pub expected_crc: Option<u16>,
+    /// the CRC received in the last two bytes, `None` if there are too few bytes for a CRC
+    pub found_crc: Option<u16>,
+}
+
+/// `Frame::try_from` returning the bytes and CRCs along with the error on failure
+///
+/// ```
+/// use modbus_frames::{frame, Error};
+///
+/// let failure = frame::decode_verbose(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x88]).unwrap_err();
+/// assert_eq!(failure.error, Error::InvalidCrc);
+/// assert_eq!(failure.expected_crc, Some(0x8776));
+/// assert_eq!(failure.found_crc, Some(0x8876));
+/// ```
+pub fn decode_verbose(bytes: &[u8]) -> Result<Frame<'_>, DecodeFailure<'_>> {
+    Frame::try_from(bytes).map_err(|error| {
+        let crc = (bytes.len() >= 4).then(|| bytes.split_at(bytes.len() - 2));
+        DecodeFailure {
+            bytes,
+            error,
+            expected_crc: crc.map(|(data, _)| calculate_crc16(data)),
+            found_crc: crc.map(|(_, crc)| byteorder::LittleEndian::read_u16(crc)),
+        }
+    })
+}
+
+/// Frame from `bytes` checking only the length, the CRC is not verified
+///
+/// For simulation and tests over transports which can't corrupt bytes (loopback, TCP tunnels), where the CRC
+/// calculation is measurable overhead. Never use it on a serial line.
+/// ```
+/// use modbus_frames::frame;
+///
+/// let frame = frame::decode_unchecked_crc(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0, 0]).unwrap();
+/// assert_eq!(frame.payload(), [0x00, 0x6B, 0x00, 0x03]);
+/// ```
+pub fn decode_unchecked_crc(bytes: &[u8]) -> Result<Frame<'_>, Error> {
+    if bytes.len() < 4 {
+        Err(Error::InvalidLength(bytes.len()))
+    } else {
+        Ok(Frame::new_unchecked(bytes))
+    }
+}
+
+/// `frame CRC verification failed (expected 0x8776 found 0x8876) [11 03 00 6B 00 03 76 88]`
+impl core::fmt::Display for DecodeFailure<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.error)?;
+        if let (Some(expected), Some(found)) = (self.expected_crc, self.found_crc) {
+            if expected != found {
+                write!(f, " (expected 0x{:04X} found 0x{:04X})", expected, found)?;
+            }
+        }
+        write_hex(f, " [", self.bytes)?;
+        f.write_str("]")
+    }
+}
+
+/// A frame which can be edited in place, e.g. by a gateway rewriting the address, then given a new CRC
+///
+/// ```
+/// use modbus_frames::{frame::FrameMut, request};
+///
+/// let mut buffer = [0; 8];
+/// let len = request::read_coils(&mut buffer, 1, 0, 8).unwrap().raw_bytes().len();
+/// let mut frame = FrameMut::try_from(&mut buffer[..len]).unwrap();
+/// frame.set_address(7);
+/// let frame = frame.into_frame();
+/// assert_eq!(frame.address(), 7);
+/// assert!(modbus_frames::verify_crc16(frame.raw_bytes()));
+/// ```
+#[derive(Debug, PartialEq, Eq)]
+pub struct FrameMut<'b> {
+    data: &'b mut [u8],
+}
+
+impl<'b> FrameMut<'b> {
+    /// Creates a new frame without validation
+    ///
+    /// # UNCHECKED
+    /// if `bytes.len() < 4` the created frame will be invalid and later operations are likely to panic
+    pub fn new_unchecked(bytes: &'b mut [u8]) -> Self {
+        FrameMut { data: bytes }
+    }
+
+    pub fn address(&self) -> u8 {
+        self.data[0]
+    }
+
+    pub fn set_address(&mut self, address: u8) {
+        self.data[0] = address;
+    }
+
+    pub fn function(&self) -> Function {
+        Function(self.data[1])
+    }
+
+    pub fn set_function(&mut self, function: Function) {
+        self.data[1] = function.0;
+    }
+
+    /// All bytes between the address/function code and CRC, the length can't be changed
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let crc_idx = self.data.len() - 2;
+        &mut self.data[2..crc_idx]
+    }
+
+    /// View the frame as it is now, the CRC is only correct after `refresh_crc`
+    pub fn as_frame(&self) -> Frame<'_> {
+        Frame::new_unchecked(self.data)
+    }
+
+    /// Recalculate the CRC after editing
+    pub fn refresh_crc(&mut self) {
+        let (data, crc) = self.data.split_at_mut(self.data.len() - 2);
+        crc.copy_from_slice(&calculate_crc16(data).to_le_bytes());
+    }
+
+    /// Finish editing, refreshing the CRC
+    pub fn into_frame(mut self) -> Frame<'b> {
+        self.refresh_crc();
+        Frame::new_unchecked(self.data)
+    }
+}
+
+impl<'b> TryFrom<&'b mut [u8]> for FrameMut<'b> {
+    type Error = Error;
+
+    /// checks the frame is valid before any edits
+    fn try_from(bytes: &'b mut [u8]) -> Result<Self, Self::Error> {
+        Frame::try_from(&*bytes)?;
+        Ok(FrameMut::new_unchecked(bytes))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Frame;
-    use crate::{function, Function};
+    use super::{Frame, FrameMut};
+    use crate::ModbusFrame;
+    use crate::{builder, exception, function, Error, Function};
 
     #[test]
     fn test_frame_views() {
@@ -125,9 +726,27 @@ mod tests {
         assert_eq!(frame.payload(), [2, 3, 4, 5, 6, 7, 8, 9]);
         assert_eq!(frame.raw_bytes(), test_data);
         assert_eq!(frame.raw_bytes(), frame.rtu_bytes().collect::<Vec<_>>());
+        assert_eq!(frame.rtu_bytes().len(), test_data.len());
+        assert_eq!(frame.rtu_bytes().next_back(), Some(69));
+        let mut out = [0; 4];
+        assert_eq!(frame.fill(&mut out), 4);
+        assert_eq!(out, [0, 1, 2, 3]);
         assert_eq!(frame.crc_bytes(), [116, 69]);
         assert_eq!(frame.crc_bytes(), frame.calculate_crc().to_le_bytes());
         assert_eq!(frame.crc(), frame.calculate_crc());
+        assert_eq!(frame.parts(), (&test_data[..10], [116, 69]));
+        assert_eq!(frame.try_payload(), Some(&test_data[2..10]));
+        assert_eq!(frame.try_crc_bytes(), Some(&test_data[10..]));
+        assert_eq!(frame.try_pdu(), Some(frame.pdu()));
+        assert_eq!(frame.try_parts(), Some(frame.parts()));
+
+        for short in [&test_data[..0], &test_data[..2], &test_data[..3]] {
+            let frame = Frame::new_unchecked(short);
+            assert_eq!(frame.try_payload(), None);
+            assert_eq!(frame.try_crc_bytes(), None);
+            assert_eq!(frame.try_pdu(), None);
+            assert_eq!(frame.try_parts(), None);
+        }
     }
 
     #[test]
@@ -145,4 +764,222 @@ mod tests {
         // and since no copies were made, a view of the original bytes is available (excluding CRC)
         assert_eq!(frame.raw_bytes(), bytes);
     }
+
+    #[test]
+    fn display() {
+        let bytes: &[u8] = &[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87];
+        let frame = Frame::try_from(bytes).unwrap();
+        assert_eq!(
+            frame.to_string(),
+            "address=0x11 function=ReadHoldingRegisters (0x03) [11 03 00 6B 00 03 76 87]"
+        );
+
+        let mut buf = [0; 8];
+        let (exception, _) = builder::build_frame(&mut buf)
+            .for_address(0x11)
+            .exception(function::READ_COILS, exception::ILLEGAL_ADDRESS);
+        assert!(exception.to_string().starts_with(
+            "address=0x11 function=ReadCoils (0x01) exception=IllegalAddress (0x02) [11 81 02"
+        ));
+    }
+
+    #[test]
+    fn explain() {
+        let explain = |bytes: &[u8]| {
+            let mut out = String::new();
+            Frame::new_unchecked(bytes).explain(&mut out).unwrap();
+            out
+        };
+        assert_eq!(
+            explain(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87]),
+            "addr=0x11 fn=03 ReadHoldingRegisters start=0x006B count=3 crc=OK"
+        );
+        assert_eq!(
+            explain(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x88]),
+            "addr=0x11 fn=03 ReadHoldingRegisters start=0x006B count=3 crc=BAD (expected 0x8776)"
+        );
+
+        let mut buf = [0; 16];
+        let (response, _) = crate::response::ReadHoldingRegisters::new(&mut buf, 1, [0x1234]);
+        assert_eq!(
+            explain(response.as_frame().raw_bytes()),
+            "addr=0x01 fn=03 ReadHoldingRegisters bytes=2 data=12 34 crc=OK"
+        );
+        let (exception, _) = builder::build_frame(&mut buf)
+            .for_address(2)
+            .exception(function::WRITE_COIL, exception::ILLEGAL_DATA);
+        assert_eq!(
+            explain(exception.raw_bytes()),
+            "addr=0x02 fn=85 WriteCoil exception=IllegalData (0x03) crc=OK"
+        );
+        assert_eq!(explain(&[1, 2, 3]), "invalid frame data=01 02 03");
+
+        let mut out = String::new();
+        Frame::new_unchecked(&[0; 18]).hexdump(&mut out).unwrap();
+        assert_eq!(
+            out,
+            "0000: 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00\n0010: 00 00\n"
+        );
+    }
+
+    #[test]
+    fn hex_strings() {
+        let mut buf = [0; 16];
+        let expected = [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87];
+        for hex in [
+            "11 03 00 6B 00 03 76 87",
+            "  0x11,0x03, 0x00 0x6b\t00 03\n76 87 ",
+            "1103006B00037687",
+        ] {
+            let frame = Frame::from_hex_str(hex, &mut buf).unwrap();
+            assert_eq!(frame.raw_bytes(), expected, "{hex}");
+        }
+        #[cfg(feature = "std")]
+        assert_eq!(
+            Frame::from_hex_str("11 03 00 6B 00 03 76 87", &mut buf)
+                .unwrap()
+                .to_hex_string(),
+            "11 03 00 6B 00 03 76 87"
+        );
+
+        assert_eq!(
+            Frame::from_hex_str("11 03 00 6B 00 03 76 88", &mut buf),
+            Err(Error::InvalidCrc)
+        );
+        assert_eq!(
+            Frame::from_hex_str("11 3 00", &mut buf),
+            Err(Error::InvalidValue)
+        );
+        assert_eq!(
+            Frame::from_hex_str("11 G3", &mut buf),
+            Err(Error::InvalidValue)
+        );
+        assert_eq!(
+            Frame::from_hex_str("11 03 00 6B 00 03 76 87", &mut buf[..4]),
+            Err(Error::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn edit_in_place() {
+        let mut buf = [0; 16];
+        let len = crate::request::write_holding_register(&mut buf, 1, 4, 5)
+            .unwrap()
+            .raw_bytes()
+            .len();
+        let bytes = &mut buf[..len];
+        let mut frame = FrameMut::try_from(&mut *bytes).unwrap();
+        frame.set_address(9);
+        frame.payload_mut()[3] = 6;
+        assert!(!frame.as_frame().verify());
+        frame.refresh_crc();
+        assert!(frame.as_frame().verify());
+        let frame = frame.into_frame();
+        assert_eq!((frame.address(), frame.payload()), (9, &[0, 4, 0, 6][..]));
+
+        bytes[5] = 7;
+        assert_eq!(FrameMut::try_from(bytes), Err(Error::InvalidCrc));
+    }
+
+    #[test]
+    fn verbose_failures() {
+        let failure =
+            super::decode_verbose(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x88]).unwrap_err();
+        assert_eq!(
+            failure.to_string(),
+            "frame CRC verification failed (expected 0x8776 found 0x8876) [11 03 00 6B 00 03 76 88]"
+        );
+
+        let failure = super::decode_verbose(&[0x11, 0x03]).unwrap_err();
+        assert_eq!(failure.error, Error::InvalidLength(2));
+        assert_eq!((failure.expected_crc, failure.found_crc), (None, None));
+        assert!(super::decode_verbose(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87]).is_ok());
+    }
+
+    #[test]
+    fn semantic_eq() {
+        let rtu = Frame::try_from(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87][..]).unwrap();
+        let mut buffer = [0; 8];
+        let ascii = rtu.to_ascii(&mut buffer).unwrap();
+        assert_eq!(rtu.canonical_bytes(), ascii.canonical_bytes());
+        assert_eq!(rtu.canonical_bytes(), [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03]);
+        assert!(rtu.semantic_eq(&ascii));
+        assert!(ascii.semantic_eq(&rtu));
+        // the checksum isn't compared
+        let corrupt = Frame::new_unchecked(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0, 0]);
+        assert!(rtu.semantic_eq(&corrupt));
+        assert_ne!(rtu, corrupt);
+        let other = Frame::new_unchecked(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x04, 0, 0]);
+        assert!(!ascii.semantic_eq(&other));
+        assert_eq!(Frame::new_unchecked(&[1]).canonical_bytes(), []);
+        for short in [&[][..], &[1], &[1, 3, 0]] {
+            assert_eq!(
+                Frame::new_unchecked(short).to_ascii(&mut buffer),
+                Err(Error::InvalidLength(short.len()))
+            );
+        }
+    }
+
+    #[test]
+    fn keys() {
+        use std::collections::HashSet;
+
+        let mut buffer = [0; 32];
+        let (frame, _) = builder::build_frame(&mut buffer)
+            .for_address(0x11)
+            .function(function::READ_HOLDING_REGISTERS)
+            .registers([0x6B, 3])
+            .finalise();
+        let key = frame.key();
+        assert_eq!(
+            key,
+            super::FrameKey {
+                address: 0x11,
+                function: function::READ_HOLDING_REGISTERS,
+                payload: [0, 0x6B, 0, 3],
+            }
+        );
+        let short = Frame::try_from(&[0x11, 0x07, 0x4C, 0x22][..]).unwrap();
+        assert_eq!(short.key().payload, [0; 4]);
+        assert!(short.key() > key);
+
+        let frames: HashSet<Frame> = [frame, frame, short].into_iter().collect();
+        assert_eq!(frames.len(), 2);
+    }
+
+    #[test]
+    fn validated() {
+        let mut buffer = [0; 32];
+        let (frame, _) = builder::build_frame(&mut buffer)
+            .for_address(0x11)
+            .function(function::READ_HOLDING_REGISTERS)
+            .registers([0x6B, 3])
+            .finalise();
+        let validated = super::ValidatedFrame::try_from(frame).unwrap();
+        assert_eq!(validated.as_frame(), frame);
+        assert_eq!(Frame::from(validated), frame);
+
+        let mut corrupt = [0; 8];
+        corrupt.copy_from_slice(frame.raw_bytes());
+        corrupt[7] ^= 1;
+        let frame = Frame::new_unchecked(&corrupt);
+        assert_eq!(
+            super::ValidatedFrame::try_from(frame),
+            Err(Error::InvalidCrc)
+        );
+        assert_eq!(
+            super::ValidatedFrame::try_from(&corrupt[..]),
+            Err(Error::InvalidCrc)
+        );
+        assert_eq!(
+            super::ValidatedFrame::try_from(&corrupt[..3]),
+            Err(Error::InvalidLength(3))
+        );
+    }
+
+    #[test]
+    #[should_panic = "frame is shorter than the 4 byte minimum"]
+    fn short_parts() {
+        Frame::new_unchecked(&[1]).parts();
+    }
 }
@@ -3,9 +3,72 @@
 /// function code specifies how a device processes the frame
 /// top bit is set to indicate an exception response so valid range is 0-127
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Function(pub u8);
 
+impl Function {
+    /// Name of the function code if it is one of the documented codes
+    pub fn name(&self) -> Option<&'static str> {
+        match *self {
+            READ_COILS => Some("ReadCoils"),
+            READ_DISCRETE_INPUTS => Some("ReadDiscreteInputs"),
+            READ_HOLDING_REGISTERS => Some("ReadHoldingRegisters"),
+            READ_INPUT_REGISTERS => Some("ReadInputRegisters"),
+            WRITE_COIL => Some("WriteCoil"),
+            WRITE_HOLDING_REGISTER => Some("WriteHoldingRegister"),
+            WRITE_MULTIPLE_COILS => Some("WriteMultipleCoils"),
+            WRITE_MULTIPLE_HOLDING_REGISTERS => Some("WriteMultipleHoldingRegisters"),
+            READ_EXCEPTION_STATUS => Some("ReadExceptionStatus"),
+            DIAGNOSTIC => Some("Diagnostic"),
+            GET_COMM_EVENT_COUNTER => Some("GetCommEventCounter"),
+            GET_COMM_EVENT_LOG => Some("GetCommEventLog"),
+            REPORT_SLAVE_ID => Some("ReportSlaveId"),
+            READ_FILE_RECORD => Some("ReadFileRecord"),
+            WRITE_FILE_RECORD => Some("WriteFileRecord"),
+            MASK_WRITE_REGISTER => Some("MaskWriteRegister"),
+            READ_WRITE_MULTIPLE_REGISTERS => Some("ReadWriteMultipleRegisters"),
+            READ_FIFO_QUEUE => Some("ReadFifoQueue"),
+            ENCAPSULATED_INTERFACE_TRANSPORT => Some("EncapsulatedInterfaceTransport"),
+            _ => None,
+        }
+    }
+
+    /// true if the code is in one of the ranges reserved for vendor specific functions
+    ///
+    /// ```
+    /// use modbus_frames::{function, Function};
+    /// assert!(Function(65).is_user_defined());
+    /// assert!(Function(110).is_user_defined());
+    /// assert!(!function::READ_COILS.is_user_defined());
+    /// ```
+    pub fn is_user_defined(&self) -> bool {
+        USER_DEFINED_RANGES
+            .iter()
+            .any(|range| range.contains(&self.0))
+    }
+}
+
+/// Function codes reserved for vendor specific functions, these will never be assigned by the specification
+pub const USER_DEFINED_RANGES: [core::ops::RangeInclusive<u8>; 2] = [65..=72, 100..=110];
+
+impl core::fmt::Display for Function {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{} (0x{:02X})", name, self.0),
+            None => write!(f, "Function (0x{:02X})", self.0),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Function {
+    fn format(&self, f: defmt::Formatter) {
+        match self.name() {
+            Some(name) => defmt::write!(f, "{=str}({=u8})", name, self.0),
+            None => defmt::write!(f, "Function({=u8})", self.0),
+        }
+    }
+}
+
 impl From<u8> for Function {
     fn from(f: u8) -> Self {
         Function(f)
@@ -96,18 +159,114 @@ pub const WRITE_MULTIPLE_COILS: Function = Function(15);
 ///    Number of preset/written holding registers (16-bit)
 pub const WRITE_MULTIPLE_HOLDING_REGISTERS: Function = Function(16);
 
-// pub const MASK_WRITE_REGISTER: Function = Function(22);
-// pub const READ_WRITE_MULTIPLE_REGISTERS: Function = Function(23);
-// pub const READ_FIFO_QUEUE: Function = Function(23);
-
-// //diagnostics
-// pub const READ_EXCEPTION_STATUS: Function = Function(7);
-// pub const DIAGNOSTIC: Function = Function(8);
-// pub const GET_COMM_EVENT_COUNTER: Function = Function(11);
-// pub const GET_COM_EVENT_LOG: Function = Function(12);
-// pub const REPORT_SLAVE_ID: Function = Function(17);
-// pub const READ_DEVICE_ID: Function = Function(43);
-
-// // file access
-// pub const READ_FILE: Function = Function(20);
-// pub const WRITE_FILE: Function = Function(21);
+/// Request: no data
+///
+/// Normal response:
+///    Exception status outputs (8-bit), device specific
+pub const READ_EXCEPTION_STATUS: Function = Function(7);
+
+/// Request:
+///    Sub-function code (16-bit)
+///    Data (16 bits per value), depends on the sub-function
+///
+/// Normal response: usually the same as request, depends on the sub-function
+pub const DIAGNOSTIC: Function = Function(8);
+
+/// Request: no data
+///
+/// Normal response:
+///    Status word (16-bit), 0xFFFF if a previous command is still being processed
+///    Event count (16-bit)
+pub const GET_COMM_EVENT_COUNTER: Function = Function(11);
+
+/// Request: no data
+///
+/// Normal response:
+///    Number of bytes to follow (8-bit)
+///    Status word (16-bit)
+///    Event count (16-bit)
+///    Message count (16-bit)
+///    Events (0-64 bytes)
+pub const GET_COMM_EVENT_LOG: Function = Function(12);
+
+/// Request: no data
+///
+/// Normal response:
+///    Number of bytes to follow (8-bit)
+///    Slave ID and run indicator status, device specific
+pub const REPORT_SLAVE_ID: Function = Function(17);
+
+/// Request:
+///    Number of bytes to follow (8-bit)
+///    Sub-requests of 7 bytes each:
+///        Reference type (8-bit), always 6
+///        File number (16-bit)
+///        Record number (16-bit)
+///        Record length in registers (16-bit)
+///
+/// Normal response:
+///    Number of bytes to follow (8-bit)
+///    Sub-responses:
+///        Number of bytes to follow (8-bit)
+///        Reference type (8-bit), always 6
+///        Record data (16 bits per register)
+pub const READ_FILE_RECORD: Function = Function(20);
+
+/// Request:
+///    Number of bytes to follow (8-bit)
+///    Sub-requests:
+///        Reference type (8-bit), always 6
+///        File number (16-bit)
+///        Record number (16-bit)
+///        Record length in registers (16-bit)
+///        Record data (16 bits per register)
+///
+/// Normal response: same as request.
+pub const WRITE_FILE_RECORD: Function = Function(21);
+
+/// Request:
+///    Address of holding register (16-bit)
+///    AND mask (16-bit)
+///    OR mask (16-bit)
+/// The new register value is `(current & and_mask) | (or_mask & !and_mask)`
+///
+/// Normal response: same as request.
+pub const MASK_WRITE_REGISTER: Function = Function(22);
+
+/// Request:
+///    Address of first holding register to read (16-bit)
+///    Number of holding registers to read (16-bit)
+///    Address of first holding register to write (16-bit)
+///    Number of holding registers to write (16-bit)
+///    Number of bytes of register values to follow (8-bit)
+///    New values of holding registers (16 bits per register)
+/// The write is performed before the read
+///
+/// Normal response:
+///    Number of bytes of register values to follow (8-bit)
+///    Register values (16 bits per register)
+pub const READ_WRITE_MULTIPLE_REGISTERS: Function = Function(23);
+
+/// Request:
+///    Address of FIFO pointer register (16-bit)
+///
+/// Normal response:
+///    Number of bytes to follow (16-bit)
+///    Number of registers in the FIFO (16-bit), at most 31
+///    Register values (16 bits per register)
+pub const READ_FIFO_QUEUE: Function = Function(24);
+
+/// Request:
+///    MEI type (8-bit), 13 for CANopen general reference and 14 for read device identification
+///    Data, depends on the MEI type
+///
+/// Normal response:
+///    MEI type (8-bit)
+///    Data, depends on the MEI type
+pub const ENCAPSULATED_INTERFACE_TRANSPORT: Function = Function(43);
+
+/// Encapsulated Interface Transport (0x2B) MEI type carrying a CANopen PDU
+pub const MEI_CANOPEN_GENERAL_REFERENCE: u8 = 0x0D;
+
+/// Encapsulated Interface Transport (0x2B) MEI type reading the device identification objects
+pub const MEI_READ_DEVICE_IDENTIFICATION: u8 = 0x0E;
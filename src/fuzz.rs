@@ -0,0 +1,282 @@
+//! [`arbitrary::Arbitrary`] implementations for fuzzing decoders (feature `arbitrary`)
+//!
+//! Frames borrow their bytes so the generated frames are owned buffers, call `frame()` to decode them.
+//! Every generated frame has a valid CRC. [`AnyFrame`] has an arbitrary function and payload,
+//! [`RequestFrame`] and [`ResponseFrame`] are always structurally valid `CommonRequests` / `CommonResponses`.
+//!
+//! ```ignore
+//! fuzz_target!(|request: RequestFrame| {
+//!     let _ = server::dispatch(&mut handler, request.frame(), &mut [0; 256]);
+//! });
+//! ```
+
+use arbitrary::{Arbitrary, Unstructured};
+
+use crate::{builder, request, response, Device, Exception, Frame, Function};
+
+/// A frame with its own buffer
+#[derive(Clone)]
+pub struct OwnedFrame {
+    bytes: [u8; 256],
+    len: usize,
+}
+
+impl OwnedFrame {
+    fn from_frame(frame: Frame) -> Self {
+        let raw = frame.raw_bytes();
+        let mut bytes = [0; 256];
+        bytes[..raw.len()].copy_from_slice(raw);
+        OwnedFrame {
+            bytes,
+            len: raw.len(),
+        }
+    }
+
+    pub fn frame(&self) -> Frame<'_> {
+        Frame::new_unchecked(&self.bytes[..self.len])
+    }
+}
+
+impl core::fmt::Debug for OwnedFrame {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.bytes[..self.len].fmt(f)
+    }
+}
+
+/// Any address, function and payload of up to 252 bytes
+#[derive(Debug, Clone)]
+pub struct AnyFrame(pub OwnedFrame);
+
+/// One of the requests decoded by `CommonRequests`
+#[derive(Debug, Clone)]
+pub struct RequestFrame(pub OwnedFrame);
+
+/// One of the responses decoded by `CommonResponses`
+#[derive(Debug, Clone)]
+pub struct ResponseFrame(pub OwnedFrame);
+
+impl AnyFrame {
+    pub fn frame(&self) -> Frame<'_> {
+        self.0.frame()
+    }
+}
+
+impl RequestFrame {
+    pub fn frame(&self) -> Frame<'_> {
+        self.0.frame()
+    }
+}
+
+impl ResponseFrame {
+    pub fn frame(&self) -> Frame<'_> {
+        self.0.frame()
+    }
+}
+
+impl<'a> Arbitrary<'a> for AnyFrame {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let address = u.arbitrary()?;
+        let function: Function = u.arbitrary()?;
+        let len = u.int_in_range(0..=252)?;
+        let payload = u.bytes(len.min(u.len()))?;
+        let mut buffer = [0; 256];
+        let (frame, _) = builder::build_frame(&mut buffer)
+            .for_address(address)
+            .function(function)
+            .bytes(payload.iter().copied())
+            .finalise();
+        Ok(AnyFrame(OwnedFrame::from_frame(frame)))
+    }
+}
+
+impl<'a> Arbitrary<'a> for RequestFrame {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let address = u.arbitrary()?;
+        let start: u16 = u.arbitrary()?;
+        let mut buffer = [0; 256];
+        let builder = builder::build_frame(&mut buffer).for_address(address);
+        let frame = match u.int_in_range(0..=7)? {
+            0 => builder
+                .read_coils(start, u.int_in_range(1..=request::MAX_READ_BITS)?)
+                .finalise(),
+            1 => builder
+                .read_discrete_inputs(start, u.int_in_range(1..=request::MAX_READ_BITS)?)
+                .finalise(),
+            2 => builder
+                .read_holding_registers(start, u.int_in_range(1..=request::MAX_READ_REGISTERS)?)
+                .finalise(),
+            3 => builder
+                .read_input_registers(start, u.int_in_range(1..=request::MAX_READ_REGISTERS)?)
+                .finalise(),
+            4 => builder.write_coil(start, u.arbitrary()?).finalise(),
+            5 => builder
+                .write_holding_register(start, u.arbitrary()?)
+                .finalise(),
+            6 => {
+                let mut coils = [false; request::MAX_WRITE_COILS as usize];
+                let count = u.int_in_range(1..=coils.len())?;
+                for coil in &mut coils[..count] {
+                    *coil = u.arbitrary()?;
+                }
+                builder
+                    .write_multiple_coils(start)
+                    .coils(coils[..count].iter().copied())
+                    .finalise()
+            }
+            _ => {
+                let mut registers = [0; request::MAX_WRITE_REGISTERS as usize];
+                let count = u.int_in_range(1..=registers.len())?;
+                for register in &mut registers[..count] {
+                    *register = u.arbitrary()?;
+                }
+                builder
+                    .write_multiple_registers(start)
+                    .registers(registers[..count].iter().copied())
+                    .finalise()
+            }
+        };
+        Ok(RequestFrame(OwnedFrame::from_frame(frame.0)))
+    }
+}
+
+impl<'a> Arbitrary<'a> for ResponseFrame {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let address = u.arbitrary()?;
+        let mut buffer = [0; 256];
+        let frame = match u.int_in_range(0..=7)? {
+            kind @ (0 | 1) => {
+                let mut bits = [false; request::MAX_READ_BITS as usize];
+                let count = u.int_in_range(1..=bits.len())?;
+                for bit in &mut bits[..count] {
+                    *bit = u.arbitrary()?;
+                }
+                let bits = bits[..count].iter().copied();
+                if kind == 0 {
+                    response::ReadCoils::new(&mut buffer, address, bits)
+                        .0
+                        .as_frame()
+                } else {
+                    response::ReadDiscreteInputs::new(&mut buffer, address, bits)
+                        .0
+                        .as_frame()
+                }
+            }
+            kind @ (2 | 3) => {
+                let mut registers = [0; request::MAX_READ_REGISTERS as usize];
+                let count = u.int_in_range(1..=registers.len())?;
+                for register in &mut registers[..count] {
+                    *register = u.arbitrary()?;
+                }
+                let registers = registers[..count].iter().copied();
+                if kind == 2 {
+                    response::ReadHoldingRegisters::new(&mut buffer, address, registers)
+                        .0
+                        .as_frame()
+                } else {
+                    response::ReadInputRegisters::new(&mut buffer, address, registers)
+                        .0
+                        .as_frame()
+                }
+            }
+            4 => response::WriteCoil::new(&mut buffer, address, u.arbitrary()?, u.arbitrary()?)
+                .0
+                .as_frame(),
+            5 => response::WriteHoldingRegister::new(
+                &mut buffer,
+                address,
+                u.arbitrary()?,
+                u.arbitrary()?,
+            )
+            .0
+            .as_frame(),
+            6 => response::WriteMultipleCoils::new(
+                &mut buffer,
+                address,
+                u.arbitrary()?,
+                u.int_in_range(1..=request::MAX_WRITE_COILS)?,
+            )
+            .0
+            .as_frame(),
+            _ => response::WriteMultipleHoldingRegisters::new(
+                &mut buffer,
+                address,
+                u.arbitrary()?,
+                u.int_in_range(1..=request::MAX_WRITE_REGISTERS)?,
+            )
+            .0
+            .as_frame(),
+        };
+        Ok(ResponseFrame(OwnedFrame::from_frame(frame)))
+    }
+}
+
+// written out as the derive needs `std`
+
+impl<'a> Arbitrary<'a> for Function {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Function(u.arbitrary()?))
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        u8::size_hint(depth)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Exception {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Exception(u.arbitrary()?))
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        u8::size_hint(depth)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Device {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Device::new(u.arbitrary()?))
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        u8::size_hint(depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnyFrame, RequestFrame, ResponseFrame};
+    use crate::{
+        decoder::{CommonRequests, CommonResponses},
+        verify_crc16,
+    };
+    use arbitrary::{Arbitrary, Unstructured};
+
+    #[test]
+    fn generated_frames_decode() {
+        // deterministic pseudo-random input
+        let mut state = 0x1234_5678u32;
+        let data: Vec<u8> = (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let mut u = Unstructured::new(&data);
+        while !u.is_empty() {
+            let any = AnyFrame::arbitrary(&mut u).unwrap();
+            assert!(verify_crc16(any.frame().raw_bytes()));
+            let request = RequestFrame::arbitrary(&mut u).unwrap();
+            assert!(
+                CommonRequests::try_from(request.frame()).is_ok(),
+                "{request:?}"
+            );
+            let response = ResponseFrame::arbitrary(&mut u).unwrap();
+            assert!(
+                CommonResponses::try_from(response.frame()).is_ok(),
+                "{response:?}"
+            );
+        }
+    }
+}
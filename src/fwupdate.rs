@@ -0,0 +1,598 @@
+//! Firmware updates over Write File Record (0x15) (feature `fwupdate`)
+//!
+//! The common vendor convention: the image is written to a magic file number in order, continuing into the next file
+//! numbers once a file's 10000 records are used, then a trailer with the length and CRC is written to a control file
+//! to commit it. [`Upload`] turns an image into those requests on the master, [`Receiver`] is the [`FileStorage`]
+//! on the device which checks the order, length and CRC and passes the bytes on to a [`FirmwareSink`].
+//!
+//! Bytes are packed into registers big endian, an odd final byte is padded with `0xFF` (erased flash). The CRC is
+//! the Modbus CRC-16 of the packed bytes, including the padding. The trailer is the three registers `|length high|
+//! length low|CRC|` written to record 0 of the control file, reading them back gives the bytes received so far and
+//! their CRC, e.g. to resume after a reset of the master.
+//!
+//! ```
+//! use modbus_frames::{
+//!     client::Transport,
+//!     file::{FileError, FileStorage},
+//!     fwupdate::{Files, FirmwareSink, Receiver, Upload},
+//!     server::{self, Handler},
+//!     Error, Frame,
+//! };
+//!
+//! #[derive(Default)]
+//! struct Flash {
+//!     staging: Vec<u8>,
+//!     active: Vec<u8>,
+//! }
+//!
+//! impl FirmwareSink for Flash {
+//!     fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), FileError> {
+//!         self.staging.truncate(offset as usize);
+//!         self.staging.extend_from_slice(data);
+//!         Ok(())
+//!     }
+//!
+//!     fn finish(&mut self, len: u32) -> Result<(), FileError> {
+//!         self.staging.truncate(len as usize);
+//!         self.active = core::mem::take(&mut self.staging);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! const FILES: Files = Files::new(0x1000, 0x0FFF);
+//!
+//! struct Bootloader(Receiver<Flash>);
+//!
+//! impl Handler for Bootloader {
+//!     fn files(&mut self) -> Option<&mut dyn FileStorage> {
+//!         Some(&mut self.0)
+//!     }
+//! }
+//!
+//! // the serial link, here straight to the device
+//! struct Link(Bootloader);
+//!
+//! impl Transport for Link {
+//!     fn transact<'b>(&mut self, request: Frame, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+//!         Ok(server::dispatch(&mut self.0, request.try_into()?, buffer))
+//!     }
+//! }
+//!
+//! let image: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+//! let mut link = Link(Bootloader(Receiver::new(Flash::default(), FILES)));
+//! Upload::new(FILES, image.iter().copied()).send_all(&mut link, 1)?;
+//! assert_eq!(link.0 .0.sink().active, image);
+//! # Ok::<(), Error>(())
+//! ```
+
+use crate::{
+    client::Transport,
+    crc,
+    file::{FileError, FileStorage},
+    request::{self, FileRecordData},
+    Error, Frame,
+};
+
+/// Records in each file, record numbers are 0-9999
+pub const RECORDS_PER_FILE: u32 = request::MAX_FILE_RECORD_NUMBER as u32 + 1;
+
+/// Most registers written per request, the largest sub-request that fits a Write File Record
+pub const MAX_CHUNK_REGISTERS: u16 = (request::MAX_WRITE_FILE_RECORD_BYTES - 7) / 2;
+
+/// The file numbers used for an update
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Files {
+    /// the first file of the image, larger images continue into the following file numbers
+    pub image: u16,
+    /// the file the trailer is written to, outside of the image files
+    pub control: u16,
+}
+
+impl Files {
+    pub const fn new(image: u16, control: u16) -> Self {
+        Files { image, control }
+    }
+
+    /// `(file, record)` of the register holding image byte `offset`
+    fn locate(&self, offset: u32) -> Option<(u16, u16)> {
+        let register = offset / 2;
+        let file = u32::from(self.image) + register / RECORDS_PER_FILE;
+        Some((
+            u16::try_from(file).ok()?,
+            (register % RECORDS_PER_FILE) as u16,
+        ))
+    }
+
+    /// byte offset of `record` in image `file`
+    fn offset(&self, file: u16, record: u16) -> Option<u32> {
+        let file = file.checked_sub(self.image)?;
+        Some((u32::from(file) * RECORDS_PER_FILE + u32::from(record)) * 2)
+    }
+}
+
+fn trailer(len: u32, crc: u16) -> [u16; 3] {
+    [(len >> 16) as u16, len as u16, crc]
+}
+
+/// Registers of the image for one request
+struct Chunk {
+    file: u16,
+    record: u16,
+    data: [u16; MAX_CHUNK_REGISTERS as usize],
+    registers: usize,
+}
+
+/// Splits an image into Write File Record requests, master side
+///
+/// Each request writes one chunk of the image, the last request is the trailer.
+#[derive(Debug, Clone)]
+pub struct Upload<I> {
+    image: I,
+    files: Files,
+    chunk: u16,
+    /// image bytes taken
+    len: u32,
+    crc: u16,
+    /// the image is exhausted, only the trailer remains
+    exhausted: bool,
+    done: bool,
+}
+
+impl<I: Iterator<Item = u8>> Upload<I> {
+    pub fn new(files: Files, image: I) -> Self {
+        Upload {
+            image,
+            files,
+            chunk: MAX_CHUNK_REGISTERS,
+            len: 0,
+            crc: crc::INIT,
+            exhausted: false,
+            done: false,
+        }
+    }
+
+    /// Write at most `registers` (1-122) registers per request, e.g. for devices with small receive buffers
+    pub fn with_chunk_registers(mut self, registers: u16) -> Self {
+        self.chunk = registers.clamp(1, MAX_CHUNK_REGISTERS);
+        self
+    }
+
+    /// Image bytes taken so far
+    pub fn sent(&self) -> u32 {
+        self.len
+    }
+
+    /// The trailer has been built, nothing remains
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Build the next request for `address` into `buffer`, `None` once the trailer has been built
+    ///
+    /// Fails with `Error::InvalidQuantity` if the image runs past file number `0xFFFF` or `u32::MAX` bytes.
+    pub fn next_request<'b>(
+        &mut self,
+        address: u8,
+        buffer: &'b mut [u8],
+    ) -> Option<Result<Frame<'b>, Error>> {
+        if self.done {
+            return None;
+        }
+        if !self.exhausted {
+            match self.next_chunk() {
+                Ok(Some(Chunk {
+                    file,
+                    record,
+                    data,
+                    registers,
+                })) => {
+                    let record = FileRecordData {
+                        file_number: file,
+                        record_number: record,
+                        data: &data[..registers],
+                    };
+                    return Some(request::write_file_record(buffer, address, &[record]));
+                }
+                Ok(None) => self.exhausted = true,
+                Err(error) => return Some(Err(error)),
+            }
+        }
+        let data = trailer(self.len, self.crc);
+        let record = FileRecordData {
+            file_number: self.files.control,
+            record_number: 0,
+            data: &data,
+        };
+        let frame = request::write_file_record(buffer, address, &[record]);
+        self.done = frame.is_ok();
+        Some(frame)
+    }
+
+    /// The next chunk, `None` once the image is exhausted
+    fn next_chunk(&mut self) -> Result<Option<Chunk>, Error> {
+        // `len` is always even until the image is exhausted
+        let (file, record) = self.files.locate(self.len).ok_or(Error::InvalidQuantity)?;
+        // chunks stay within a file
+        let registers =
+            usize::from(self.chunk).min((RECORDS_PER_FILE - u32::from(record)) as usize);
+        let mut bytes = [0xFF; 2 * MAX_CHUNK_REGISTERS as usize];
+        let mut taken = 0;
+        for (byte, value) in bytes[..2 * registers].iter_mut().zip(&mut self.image) {
+            *byte = value;
+            taken += 1;
+        }
+        if taken == 0 {
+            return Ok(None);
+        }
+        self.exhausted = taken < 2 * registers;
+        let registers = taken.div_ceil(2);
+        let bytes = &bytes[..2 * registers];
+        let mut data = [0; MAX_CHUNK_REGISTERS as usize];
+        for (register, pair) in data.iter_mut().zip(bytes.chunks_exact(2)) {
+            *register = u16::from_be_bytes([pair[0], pair[1]]);
+        }
+        self.len = self
+            .len
+            .checked_add(taken as u32)
+            .ok_or(Error::InvalidQuantity)?;
+        self.crc = crc::update(self.crc, bytes);
+        Ok(Some(Chunk {
+            file,
+            record,
+            data,
+            registers,
+        }))
+    }
+
+    /// Send every request to `address` over `transport`, stopping at the first failure
+    ///
+    /// Exception responses are `Error::Exception`, a response that doesn't echo the request `Error::InvalidValue`.
+    pub fn send_all<T: Transport + ?Sized>(
+        &mut self,
+        transport: &mut T,
+        address: u8,
+    ) -> Result<(), Error> {
+        let mut request_buffer = [0; 256];
+        let mut response_buffer = [0; 256];
+        while let Some(request) = self.next_request(address, &mut request_buffer) {
+            let request = request?;
+            let response = transport.transact(request, &mut response_buffer)?;
+            request::check_response(request, response)?;
+            if response.payload() != request.payload() {
+                return Err(Error::InvalidValue);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a [`Receiver`] puts the image, e.g. the staging bank of a bootloader
+pub trait FirmwareSink {
+    /// Store `data` at byte `offset` of the image
+    ///
+    /// Offsets are sequential, an offset of 0 restarts the update. The last chunk is written again if the master
+    /// retries it.
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), FileError>;
+
+    /// The trailer matched, the image is the first `len` bytes written
+    fn finish(&mut self, len: u32) -> Result<(), FileError>;
+
+    /// The update was restarted or the trailer didn't match, discard what was written
+    fn abort(&mut self) {}
+}
+
+impl<S: FirmwareSink + ?Sized> FirmwareSink for &mut S {
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), FileError> {
+        (**self).write(offset, data)
+    }
+
+    fn finish(&mut self, len: u32) -> Result<(), FileError> {
+        (**self).finish(len)
+    }
+
+    fn abort(&mut self) {
+        (**self).abort()
+    }
+}
+
+/// Receives an image written by an [`Upload`] into a [`FirmwareSink`], device side
+///
+/// Out of order writes are `OutOfRange`, a trailer which doesn't match the bytes received is `Invalid` and aborts
+/// the update. Other files are `NoSuchFile`, serve them by wrapping the receiver.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Receiver<S> {
+    sink: S,
+    files: Files,
+    /// bytes received, including padding
+    received: u32,
+    crc: u16,
+    /// `(offset, crc)` before the last chunk, to accept a retry of it
+    last: Option<(u32, u16)>,
+}
+
+impl<S: FirmwareSink> Receiver<S> {
+    pub fn new(sink: S, files: Files) -> Self {
+        Receiver {
+            sink,
+            files,
+            received: 0,
+            crc: crc::INIT,
+            last: None,
+        }
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+
+    /// Bytes of the current update received so far, including padding
+    pub fn received(&self) -> u32 {
+        self.received
+    }
+
+    fn reset(&mut self) {
+        self.received = 0;
+        self.crc = crc::INIT;
+        self.last = None;
+    }
+
+    fn commit(&mut self, data: &[u16]) -> Result<(), FileError> {
+        let &[len_high, len_low, crc] = data else {
+            return Err(FileError::Invalid);
+        };
+        let len = u32::from(len_high) << 16 | u32::from(len_low);
+        let padded = len.checked_add(len & 1);
+        if padded != Some(self.received) || crc != self.crc {
+            self.sink.abort();
+            self.reset();
+            return Err(FileError::Invalid);
+        }
+        self.sink.finish(len)?;
+        self.reset();
+        Ok(())
+    }
+}
+
+impl<S: FirmwareSink> FileStorage for Receiver<S> {
+    fn read_record(&mut self, file: u16, record: u16, data: &mut [u16]) -> Result<(), FileError> {
+        if file != self.files.control {
+            return Err(FileError::NoSuchFile);
+        }
+        let status = trailer(self.received, self.crc);
+        let start = usize::from(record);
+        let status = status
+            .get(start..start + data.len())
+            .ok_or(FileError::OutOfRange)?;
+        data.copy_from_slice(status);
+        Ok(())
+    }
+
+    fn write_record(&mut self, file: u16, record: u16, data: &[u16]) -> Result<(), FileError> {
+        if file == self.files.control {
+            return match record {
+                0 => self.commit(data),
+                _ => Err(FileError::OutOfRange),
+            };
+        }
+        let offset = self
+            .files
+            .offset(file, record)
+            .ok_or(FileError::NoSuchFile)?;
+        match self.last {
+            // a restart, or a retry of the first chunk
+            _ if offset == 0 => {
+                if self.received != 0 {
+                    self.sink.abort();
+                }
+                self.reset();
+            }
+            // a retry of the last chunk
+            Some((last, crc)) if offset == last => (self.received, self.crc) = (last, crc),
+            _ if offset == self.received => {}
+            _ => return Err(FileError::OutOfRange),
+        }
+        let mut bytes = [0; 2 * request::MAX_WRITE_REGISTERS as usize];
+        let bytes = &mut bytes[..2 * data.len()];
+        for (pair, register) in bytes.chunks_exact_mut(2).zip(data) {
+            pair.copy_from_slice(&register.to_be_bytes());
+        }
+        self.sink.write(offset, bytes)?;
+        self.last = Some((offset, self.crc));
+        self.received = offset + bytes.len() as u32;
+        self.crc = crc::update(self.crc, bytes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exception,
+        request::FileRecord,
+        server::{self, Handler},
+        Exception,
+    };
+
+    #[derive(Default)]
+    struct Flash {
+        staging: Vec<u8>,
+        image: Option<Vec<u8>>,
+        aborts: usize,
+    }
+
+    impl FirmwareSink for Flash {
+        fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), FileError> {
+            assert!(offset as usize <= self.staging.len());
+            self.staging.truncate(offset as usize);
+            self.staging.extend_from_slice(data);
+            Ok(())
+        }
+
+        fn finish(&mut self, len: u32) -> Result<(), FileError> {
+            self.staging.truncate(len as usize);
+            self.image = Some(core::mem::take(&mut self.staging));
+            Ok(())
+        }
+
+        fn abort(&mut self) {
+            self.staging.clear();
+            self.aborts += 1;
+        }
+    }
+
+    const FILES: Files = Files::new(10, 2);
+
+    struct Device(Receiver<Flash>);
+
+    impl Handler for Device {
+        fn files(&mut self) -> Option<&mut dyn FileStorage> {
+            Some(&mut self.0)
+        }
+    }
+
+    impl Transport for Device {
+        fn transact<'b>(
+            &mut self,
+            request: Frame,
+            response_buffer: &'b mut [u8],
+        ) -> Result<Frame<'b>, Error> {
+            Ok(server::dispatch(
+                self,
+                request.try_into().unwrap(),
+                response_buffer,
+            ))
+        }
+    }
+
+    fn device() -> Device {
+        Device(Receiver::new(Flash::default(), FILES))
+    }
+
+    #[test]
+    fn firmware_update() {
+        // odd length, spanning two files
+        let image: Vec<u8> = (0..3 * RECORDS_PER_FILE + 101)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let mut device = device();
+        let mut upload = Upload::new(FILES, image.iter().copied());
+        upload.send_all(&mut device, 1).unwrap();
+        assert!(upload.is_done());
+        assert_eq!(upload.sent(), image.len() as u32);
+        assert_eq!(device.0.sink().image.as_ref(), Some(&image));
+        assert_eq!(device.0.received(), 0);
+
+        // the default chunk fills the request
+        let mut upload = Upload::new(FILES, image.iter().copied());
+        let mut buffer = [0; 256];
+        let request = upload.next_request(1, &mut buffer).unwrap().unwrap();
+        assert_eq!(request.payload()[0], 0xFB);
+        assert_eq!(request.payload()[6..8], [0, 122]);
+
+        // requests stay within their file and chunk size
+        let mut upload = Upload::new(FILES, image.iter().copied()).with_chunk_registers(64);
+        let mut buffer = [0; 256];
+        let mut requests = Vec::new();
+        while let Some(request) = upload.next_request(1, &mut buffer) {
+            let payload = request.unwrap().payload();
+            let word = |idx: usize| u16::from_be_bytes([payload[idx], payload[idx + 1]]);
+            requests.push((word(2), word(4), word(6)));
+        }
+        assert_eq!(requests[0], (10, 0, 64));
+        assert_eq!(requests[156], (10, 9984, 16));
+        assert_eq!(requests[157], (11, 0, 64));
+        assert_eq!(requests[requests.len() - 2], (11, 4992, 59));
+        assert_eq!(*requests.last().unwrap(), (2, 0, 3));
+
+        // an empty image is only the trailer
+        let mut upload = Upload::new(FILES, core::iter::empty());
+        upload.send_all(&mut device, 1).unwrap();
+        assert_eq!(device.0.sink().image.as_deref(), Some(&[][..]));
+    }
+
+    fn send(device: &mut Device, request: Frame) -> Option<Exception> {
+        let mut buffer = [0; 256];
+        let response = server::dispatch(device, request.try_into().unwrap(), &mut buffer);
+        (response.function().0 & 0x80 != 0).then(|| Exception(response.payload()[0]))
+    }
+
+    #[test]
+    fn rejected_updates() {
+        let mut device = device();
+        let mut req = [[0; 256]; 3];
+        let mut res = [0; 256];
+        let mut upload = Upload::new(FILES, [1, 2, 3, 4, 5].into_iter()).with_chunk_registers(1);
+        let [first, second, third] = req
+            .each_mut()
+            .map(|buffer| upload.next_request(1, buffer).unwrap().unwrap());
+
+        assert_eq!(send(&mut device, first), None);
+        // out of order
+        assert_eq!(send(&mut device, third), Some(exception::ILLEGAL_ADDRESS));
+        assert_eq!(send(&mut device, second), None);
+        // retrying the last chunk is fine
+        assert_eq!(send(&mut device, second), None);
+        assert_eq!(send(&mut device, third), None);
+        assert_eq!(device.0.received(), 6);
+        assert_eq!(device.0.sink().staging, [1, 2, 3, 4, 5, 0xFF]);
+        let mut req = [0; 256];
+
+        // the progress can be read back
+        let status = FileRecord {
+            file_number: 2,
+            record_number: 0,
+            record_length: 3,
+        };
+        let request = request::read_file_record(&mut req, 1, &[status]).unwrap();
+        let response = server::dispatch(&mut device, request.try_into().unwrap(), &mut res);
+        let crc = crc::crc16(&[1, 2, 3, 4, 5, 0xFF]);
+        let [crc_high, crc_low] = crc.to_be_bytes();
+        assert_eq!(response.payload(), [8, 7, 6, 0, 0, 0, 6, crc_high, crc_low]);
+
+        // a trailer with the wrong CRC aborts
+        let data = trailer(5, crc ^ 1);
+        let records = [FileRecordData {
+            file_number: 2,
+            record_number: 0,
+            data: &data,
+        }];
+        let request = request::write_file_record(&mut req, 1, &records).unwrap();
+        assert_eq!(send(&mut device, request), Some(exception::ILLEGAL_DATA));
+        assert_eq!(device.0.sink().aborts, 1);
+        assert_eq!(device.0.sink().image, None);
+        assert_eq!(device.0.received(), 0);
+
+        // restarting discards a partial update
+        let mut upload = Upload::new(FILES, [1, 2, 3, 4, 5].into_iter());
+        let first = upload.next_request(1, &mut req).unwrap().unwrap();
+        assert_eq!(send(&mut device, first), None);
+        let mut upload = Upload::new(FILES, [6, 7].into_iter());
+        let first = upload.next_request(1, &mut req).unwrap().unwrap();
+        assert_eq!(send(&mut device, first), None);
+        assert_eq!(device.0.sink().aborts, 2);
+        let trailer = upload.next_request(1, &mut req).unwrap().unwrap();
+        assert_eq!(send(&mut device, trailer), None);
+        assert!(upload.next_request(1, &mut req).is_none());
+        assert_eq!(device.0.sink().image.as_deref(), Some(&[6, 7][..]));
+
+        // other files
+        let records = [FileRecord {
+            file_number: 3,
+            ..status
+        }];
+        let request = request::read_file_record(&mut req, 1, &records).unwrap();
+        let response = server::dispatch(&mut device, request.try_into().unwrap(), &mut res);
+        assert_eq!(response.payload(), [exception::ILLEGAL_ADDRESS.0]);
+    }
+}
@@ -0,0 +1,409 @@
+//! Forwarding requests from another network to devices on a serial line
+//!
+//! [`Gateway`] tracks the requests forwarded downstream, keyed by the transaction id of the upstream request,
+//! and matches responses (or timeouts) back to them. It performs no I/O, the application reads/writes the
+//! links and supplies the current time as a free running millisecond counter.
+//!
+//! ```
+//! use modbus_frames::{gateway::Gateway, request};
+//!
+//! let mut gateway = Gateway::<4>::new(100);
+//! let mut buffer = [0; 8];
+//! let request = request::read_holding_registers(&mut buffer, 5, 0, 1).unwrap();
+//! gateway.forward(1, request, 0).unwrap();
+//! // no response from device 5
+//! let timeout = gateway.poll(100).unwrap();
+//! let mut response_buffer = [0; 5];
+//! let (response, _) = timeout.exception_response(&mut response_buffer);
+//! assert_eq!((timeout.transaction, response.address()), (1, 5));
+//! ```
+
+use crate::{
+    builder, device::AddressKind, exception, frame::FrameMut, pdu, trace::Trace, Device, Error,
+    Exception, Frame, Function,
+};
+
+/// A request waiting on a response from a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Pending {
+    /// id of the upstream request
+    pub transaction: u16,
+    pub device: Device,
+    pub function: Function,
+    /// time the request was forwarded
+    pub sent: u32,
+    pub timeout: u32,
+}
+
+impl Pending {
+    /// The `GATEWAY_DEVICE_NO_RESPONSE` exception response to send upstream
+    pub fn exception_response<'b>(&self, buffer: &'b mut [u8]) -> (Frame<'b>, &'b mut [u8]) {
+        builder::build_frame(buffer)
+            .for_address(self.device.address())
+            .exception(self.function, exception::GATEWAY_DEVICE_NO_RESPONSE)
+    }
+}
+
+/// Tracks up to `N` outstanding requests, at most one per device
+#[derive(Debug, Clone)]
+pub struct Gateway<'t, const N: usize, R = ()> {
+    pending: [Option<Pending>; N],
+    default_timeout: u32,
+    timeouts: &'t [(Device, u32)],
+    trace: R,
+}
+
+impl<'t, const N: usize> Gateway<'t, N> {
+    /// `timeout` in milliseconds applies to every device
+    pub fn new(timeout: u32) -> Self {
+        Gateway {
+            pending: [None; N],
+            default_timeout: timeout,
+            timeouts: &[],
+            trace: (),
+        }
+    }
+}
+
+impl<'t, const N: usize, R: Trace> Gateway<'t, N, R> {
+    /// Report forwarded requests, matched responses and timeouts to `trace`
+    pub fn with_trace<T: Trace>(self, trace: T) -> Gateway<'t, N, T> {
+        Gateway {
+            pending: self.pending,
+            default_timeout: self.default_timeout,
+            timeouts: self.timeouts,
+            trace,
+        }
+    }
+
+    pub fn trace(&mut self) -> &mut R {
+        &mut self.trace
+    }
+
+    /// Override the timeout for individual devices, e.g. slow devices or those behind a radio link
+    pub fn with_timeouts(self, timeouts: &'t [(Device, u32)]) -> Self {
+        Gateway { timeouts, ..self }
+    }
+
+    /// The timeout in milliseconds used for `device`
+    pub fn timeout(&self, device: Device) -> u32 {
+        self.timeouts
+            .iter()
+            .find(|(d, _)| *d == device)
+            .map_or(self.default_timeout, |(_, timeout)| *timeout)
+    }
+
+    pub fn pending(&self) -> impl Iterator<Item = &Pending> {
+        self.pending.iter().flatten()
+    }
+
+    /// true if a request to `device` is waiting on a response
+    pub fn is_busy(&self, device: Device) -> bool {
+        self.pending().any(|p| p.device == device)
+    }
+
+    /// Record `request` as sent downstream at time `now`
+    ///
+    /// Returns the exception to respond upstream with if the request can't be forwarded:
+    /// `DEVICE_BUSY` if the device already has a request outstanding (or too many requests are outstanding) and
+    /// `GATEWAY_PATH_UNAVAILABLE` for reserved addresses. Broadcasts are accepted but not tracked as no response
+    /// is expected.
+    ///
+    /// Only accepted requests are traced as transmitted, rejections are traced as errors.
+    pub fn forward(&mut self, transaction: u16, request: Frame, now: u32) -> Result<(), Exception> {
+        match self.track(transaction, request, now) {
+            Ok(()) => {
+                self.trace.on_tx(&request);
+                Ok(())
+            }
+            Err(exception) => {
+                self.trace.on_error(&Error::Exception(exception));
+                Err(exception)
+            }
+        }
+    }
+
+    fn track(&mut self, transaction: u16, request: Frame, now: u32) -> Result<(), Exception> {
+        let device = request.device();
+        match device.kind() {
+            AddressKind::Broadcast => return Ok(()),
+            AddressKind::Reserved => return Err(exception::GATEWAY_PATH_UNAVAILABLE),
+            AddressKind::Slave => {}
+        }
+        if self.is_busy(device) {
+            return Err(exception::DEVICE_BUSY);
+        }
+        let timeout = self.timeout(device);
+        let slot = self
+            .pending
+            .iter_mut()
+            .find(|p| p.is_none())
+            .ok_or(exception::DEVICE_BUSY)?;
+        *slot = Some(Pending {
+            transaction,
+            device,
+            function: request.function(),
+            sent: now,
+            timeout,
+        });
+        Ok(())
+    }
+
+    /// Match a response from downstream to its request, `None` if nothing was waiting on it
+    pub fn receive(&mut self, response: Frame) -> Option<Pending> {
+        self.trace.on_rx(response.raw_bytes());
+        let function = Function(response.function().0 & 0x7F);
+        self.pending
+            .iter_mut()
+            .find(
+                |p| matches!(p, Some(p) if p.device == response.device() && p.function == function),
+            )?
+            .take()
+    }
+
+    /// Remove and return a request which has timed out at time `now`
+    ///
+    /// Call repeatedly until `None` to collect every timed out request
+    pub fn poll(&mut self, now: u32) -> Option<Pending> {
+        let timed_out = self
+            .pending
+            .iter_mut()
+            .find(|p| matches!(p, Some(p) if now.wrapping_sub(p.sent) >= p.timeout))?
+            .take();
+        self.trace.on_error(&Error::NoResponse);
+        timed_out
+    }
+}
+
+/// Change the address of `frame` from `from` to `to`, refreshing the CRC
+///
+/// Returns false and leaves the frame unchanged if it isn't addressed to `from`
+pub fn remap_unit_id(frame: &mut FrameMut, from: u8, to: u8) -> bool {
+    if frame.address() != from {
+        return false;
+    }
+    frame.set_address(to);
+    frame.refresh_crc();
+    true
+}
+
+/// Change the unit id of a Modbus TCP ADU from `from` to `to`, the MBAP header has no checksum to fix
+///
+/// Returns false and leaves the ADU unchanged if it isn't addressed to `from` or is too short to have a header
+pub fn remap_mbap_unit_id(adu: &mut [u8], from: u8, to: u8) -> bool {
+    match adu.get_mut(pdu::MBAP_LEN - 1) {
+        Some(unit) if *unit == from => {
+            *unit = to;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Translates addresses between the upstream network and the downstream bus using a table of
+/// `(upstream, downstream)` pairs
+///
+/// Broadcasts pass through unchanged in both directions.
+///
+/// ```
+/// use modbus_frames::{frame::FrameMut, gateway::AddressTranslator, request, Device};
+///
+/// const MAP: [(Device, Device); 1] = [(Device::new(10), Device::new(1))];
+/// let translator = AddressTranslator::new(&MAP);
+///
+/// let mut buffer = [0; 8];
+/// let len = request::read_coils(&mut buffer, 10, 0, 8).unwrap().raw_bytes().len();
+/// let mut frame = FrameMut::try_from(&mut buffer[..len]).unwrap();
+/// assert!(translator.to_downstream(&mut frame));
+/// assert_eq!(frame.into_frame().address(), 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AddressTranslator<'t> {
+    map: &'t [(Device, Device)],
+}
+
+impl<'t> AddressTranslator<'t> {
+    pub fn new(map: &'t [(Device, Device)]) -> Self {
+        AddressTranslator { map }
+    }
+
+    /// The downstream device for an upstream address, `None` if it isn't mapped
+    pub fn downstream(&self, upstream: Device) -> Option<Device> {
+        if upstream.is_broadcast() {
+            return Some(upstream);
+        }
+        self.map
+            .iter()
+            .find(|(up, _)| *up == upstream)
+            .map(|(_, down)| *down)
+    }
+
+    /// The upstream address for a downstream device, `None` if it isn't mapped
+    pub fn upstream(&self, downstream: Device) -> Option<Device> {
+        if downstream.is_broadcast() {
+            return Some(downstream);
+        }
+        self.map
+            .iter()
+            .find(|(_, down)| *down == downstream)
+            .map(|(up, _)| *up)
+    }
+
+    /// Readdress a request for the downstream bus, false if its address isn't mapped
+    ///
+    /// An unmapped request would usually be answered with `GATEWAY_PATH_UNAVAILABLE`
+    pub fn to_downstream(&self, frame: &mut FrameMut) -> bool {
+        match self.downstream(Device::new(frame.address())) {
+            Some(down) => remap_unit_id(frame, frame.address(), down.address()),
+            None => false,
+        }
+    }
+
+    /// Readdress a response from the downstream bus, false if its address isn't mapped
+    pub fn to_upstream(&self, frame: &mut FrameMut) -> bool {
+        match self.upstream(Device::new(frame.address())) {
+            Some(up) => remap_unit_id(frame, frame.address(), up.address()),
+            None => false,
+        }
+    }
+
+    /// `to_downstream` for a Modbus TCP ADU
+    pub fn mbap_to_downstream(&self, adu: &mut [u8]) -> bool {
+        let Some(&unit) = adu.get(pdu::MBAP_LEN - 1) else {
+            return false;
+        };
+        match self.downstream(Device::new(unit)) {
+            Some(down) => remap_mbap_unit_id(adu, unit, down.address()),
+            None => false,
+        }
+    }
+
+    /// `to_upstream` for a Modbus TCP ADU
+    pub fn mbap_to_upstream(&self, adu: &mut [u8]) -> bool {
+        let Some(&unit) = adu.get(pdu::MBAP_LEN - 1) else {
+            return false;
+        };
+        match self.upstream(Device::new(unit)) {
+            Some(up) => remap_mbap_unit_id(adu, unit, up.address()),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AddressTranslator, Gateway};
+    use crate::{
+        exception,
+        frame::FrameMut,
+        pdu::{Mbap, Pdu},
+        request, response, Device,
+    };
+
+    #[test]
+    fn correlate_responses() {
+        const TIMEOUTS: [(Device, u32); 1] = [(Device::new(2), 500)];
+        let mut gateway = Gateway::<2>::new(100).with_timeouts(&TIMEOUTS);
+        let mut buffer = [0; 8];
+
+        let request = request::read_coils(&mut buffer, 1, 0, 8).unwrap();
+        assert_eq!(gateway.forward(10, request, u32::MAX - 10), Ok(()));
+        assert_eq!(
+            gateway.forward(11, request, 0),
+            Err(exception::DEVICE_BUSY),
+            "one request per device"
+        );
+        let request = request::read_coils(&mut buffer, 2, 0, 8).unwrap();
+        assert_eq!(gateway.forward(12, request, 0), Ok(()));
+        let request = request::read_coils(&mut buffer, 3, 0, 8).unwrap();
+        assert_eq!(gateway.forward(13, request, 0), Err(exception::DEVICE_BUSY));
+        let request = request::read_coils(&mut buffer, 0, 0, 8).unwrap();
+        assert_eq!(gateway.forward(14, request, 0), Ok(()));
+
+        let (response, _) = response::ReadCoils::new(&mut buffer, 2, [true]);
+        let pending = gateway.receive(response.as_frame()).unwrap();
+        assert_eq!(pending.transaction, 12);
+        assert!(gateway.receive(response.as_frame()).is_none());
+
+        // timeout across the counter wrapping
+        assert!(gateway.poll(80).is_none());
+        let timed_out = gateway.poll(90).unwrap();
+        assert_eq!(timed_out.transaction, 10);
+        let mut buffer = [0; 5];
+        let (response, _) = timed_out.exception_response(&mut buffer);
+        assert_eq!(response.raw_bytes()[..3], [1, 0x81, 0x0B]);
+        assert_eq!(gateway.pending().count(), 0);
+    }
+
+    #[test]
+    fn translate_addresses() {
+        const MAP: [(Device, Device); 2] = [
+            (Device::new(10), Device::new(1)),
+            (Device::new(11), Device::new(2)),
+        ];
+        let translator = AddressTranslator::new(&MAP);
+
+        let mut buffer = [0; 8];
+        let len = request::read_coils(&mut buffer, 11, 0, 8)
+            .unwrap()
+            .raw_bytes()
+            .len();
+        let mut frame = FrameMut::try_from(&mut buffer[..len]).unwrap();
+        assert!(translator.to_downstream(&mut frame));
+        assert_eq!(frame.as_frame().address(), 2);
+        assert!(!translator.to_downstream(&mut frame));
+        assert!(translator.to_upstream(&mut frame));
+        let frame = frame.into_frame();
+        assert_eq!(frame.address(), 11);
+        assert!(crate::verify_crc16(frame.raw_bytes()));
+
+        let mut adu = [0; 12];
+        let mbap = Mbap {
+            transaction: 3,
+            unit: 10,
+        };
+        let len = frame.pdu().to_mbap(mbap, &mut adu).unwrap().len();
+        assert!(translator.mbap_to_downstream(&mut adu[..len]));
+        assert_eq!(Pdu::from_mbap(&adu[..len]).unwrap().0.unit, 1);
+        assert!(!translator.mbap_to_upstream(&mut adu[..3]));
+
+        // broadcasts are unchanged
+        assert_eq!(translator.downstream(Device::new(0)), Some(Device::new(0)));
+        assert_eq!(translator.upstream(Device::new(5)), None);
+    }
+
+    #[test]
+    fn trace_accepted_requests() {
+        #[derive(Default)]
+        struct Counts {
+            tx: u32,
+            errors: u32,
+        }
+
+        impl crate::trace::Trace for Counts {
+            fn on_tx(&mut self, _frame: &crate::Frame) {
+                self.tx += 1;
+            }
+
+            fn on_error(&mut self, _error: &crate::Error) {
+                self.errors += 1;
+            }
+        }
+
+        let mut gateway = Gateway::<1>::new(100).with_trace(Counts::default());
+        let mut buffer = [0; 8];
+        let request = request::read_coils(&mut buffer, 1, 0, 8).unwrap();
+        assert_eq!(gateway.forward(1, request, 0), Ok(()));
+        assert_eq!(gateway.forward(2, request, 0), Err(exception::DEVICE_BUSY));
+        let request = request::read_coils(&mut buffer, 250, 0, 8).unwrap();
+        assert_eq!(
+            gateway.forward(3, request, 0),
+            Err(exception::GATEWAY_PATH_UNAVAILABLE)
+        );
+        let request = request::read_coils(&mut buffer, 0, 0, 8).unwrap();
+        assert_eq!(gateway.forward(4, request, 0), Ok(()));
+        assert_eq!((gateway.trace().tx, gateway.trace().errors), (2, 2));
+    }
+}
@@ -0,0 +1,223 @@
+//! Write protection for servers
+//!
+//! [`Guard`] wraps a [`Handler`] and rejects the guarded functions with `ILLEGAL_FUNCTION` until the application
+//! unlocks it, e.g. after a vendor specific unlock request. Requests are checked before the wrapped handler sees
+//! them, so a rejected multiple write changes nothing.
+//!
+//! ```
+//! use modbus_frames::{
+//!     entity::{EntityRange, EntityType}, exception, function, guard::Guard, request, server, source::Coils,
+//!     Function, ValidatedFrame,
+//! };
+//!
+//! const GUARDED: &[Function] = &[function::WRITE_COIL, function::WRITE_MULTIPLE_COILS];
+//! const CALIBRATION: &[EntityRange] = &[EntityRange::new(EntityType::Coil, 4, 4)];
+//!
+//! let mut guard = Guard::new(Coils([false; 8]), GUARDED, CALIBRATION);
+//! let mut request_buffer = [0; 8];
+//! let mut response_buffer = [0; 256];
+//! let request = request::write_coil(&mut request_buffer, 1, 4, true).unwrap();
+//! let request = ValidatedFrame::try_from(request).unwrap();
+//! let response = server::dispatch(&mut guard, request, &mut response_buffer);
+//! assert_eq!(response.payload(), [exception::ILLEGAL_FUNCTION.0]);
+//!
+//! guard.unlock();
+//! let response = server::dispatch(&mut guard, request, &mut response_buffer);
+//! assert_eq!(response, request.as_frame());
+//! ```
+
+use crate::{
+    decoder::CommonRequests, entity::EntityRange, exception, file::FileStorage, server::Handler,
+    Exception, Frame, Function,
+};
+
+/// Rejects guarded requests while locked, starts locked
+#[derive(Debug)]
+pub struct Guard<'g, H> {
+    handler: H,
+    functions: &'g [Function],
+    ranges: &'g [EntityRange],
+    unlocked: bool,
+}
+
+impl<'g, H: Handler> Guard<'g, H> {
+    /// Guard requests for `functions` which access any entity in `ranges`
+    ///
+    /// Requests for guarded functions without an entity range (e.g. user defined function codes) are always
+    /// guarded
+    pub fn new(handler: H, functions: &'g [Function], ranges: &'g [EntityRange]) -> Self {
+        Guard {
+            handler,
+            functions,
+            ranges,
+            unlocked: false,
+        }
+    }
+
+    /// Accept guarded requests until `lock`
+    pub fn unlock(&mut self) {
+        self.unlocked = true;
+    }
+
+    pub fn lock(&mut self) {
+        self.unlocked = false;
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.unlocked
+    }
+
+    /// true if `request` is rejected while locked
+    pub fn is_guarded(&self, request: Frame) -> bool {
+        if !self.functions.contains(&request.function()) {
+            return false;
+        }
+        match CommonRequests::try_from(request) {
+            Ok(decoded) => {
+                let accessed = EntityRange::from_request(&decoded);
+                self.ranges.iter().any(|range| range.overlaps(&accessed))
+            }
+            Err(_) => true,
+        }
+    }
+
+    pub fn handler(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    pub fn into_inner(self) -> H {
+        self.handler
+    }
+}
+
+impl<H: Handler> Handler for Guard<'_, H> {
+    fn read_coil(&mut self, index: u16) -> Result<bool, Exception> {
+        self.handler.read_coil(index)
+    }
+
+    fn read_discrete_input(&mut self, index: u16) -> Result<bool, Exception> {
+        self.handler.read_discrete_input(index)
+    }
+
+    fn read_holding_register(&mut self, index: u16) -> Result<u16, Exception> {
+        self.handler.read_holding_register(index)
+    }
+
+    fn read_input_register(&mut self, index: u16) -> Result<u16, Exception> {
+        self.handler.read_input_register(index)
+    }
+
+    fn write_coil(&mut self, index: u16, on: bool) -> Result<(), Exception> {
+        self.handler.write_coil(index, on)
+    }
+
+    fn write_holding_register(&mut self, index: u16, value: u16) -> Result<(), Exception> {
+        self.handler.write_holding_register(index, value)
+    }
+
+    fn restart_communications(&mut self, clear_log: bool) {
+        self.handler.restart_communications(clear_log)
+    }
+
+    fn accept(&mut self, request: Frame) -> Result<(), Exception> {
+        if !self.unlocked && self.is_guarded(request) {
+            return Err(exception::ILLEGAL_FUNCTION);
+        }
+        self.handler.accept(request)
+    }
+
+    fn files(&mut self) -> Option<&mut dyn FileStorage> {
+        self.handler.files()
+    }
+
+    fn other<'b>(
+        &mut self,
+        request: Frame,
+        response_buffer: &'b mut [u8],
+    ) -> Result<Frame<'b>, Exception> {
+        self.handler.other(request, response_buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Guard;
+    use crate::{
+        entity::{EntityRange, EntityType},
+        exception, function, request,
+        server::{self, Handler},
+        Exception, Frame, Function,
+    };
+
+    struct Registers([u16; 6]);
+
+    impl Handler for Registers {
+        fn read_holding_register(&mut self, index: u16) -> Result<u16, Exception> {
+            self.0
+                .get(usize::from(index))
+                .copied()
+                .ok_or(exception::ILLEGAL_ADDRESS)
+        }
+
+        fn write_holding_register(&mut self, index: u16, value: u16) -> Result<(), Exception> {
+            let register = self
+                .0
+                .get_mut(usize::from(index))
+                .ok_or(exception::ILLEGAL_ADDRESS)?;
+            *register = value;
+            Ok(())
+        }
+    }
+
+    const FUNCTIONS: &[Function] = &[
+        function::WRITE_MULTIPLE_HOLDING_REGISTERS,
+        function::WRITE_HOLDING_REGISTER,
+        Function(0x41),
+    ];
+    const RANGES: &[EntityRange] = &[EntityRange::new(EntityType::HoldingRegister, 2, 2)];
+
+    /// the exception code of the response, if any
+    fn exception(guard: &mut Guard<Registers>, request: Frame) -> Option<u8> {
+        let mut response_buffer = [0; 256];
+        let response = server::dispatch(guard, request.try_into().unwrap(), &mut response_buffer);
+        (response.function().0 & 0x80 != 0).then(|| response.payload()[0])
+    }
+
+    #[test]
+    fn guarded_writes() {
+        let mut guard = Guard::new(Registers([0_u16; 6]), FUNCTIONS, RANGES);
+        let mut request_buffer = [0; 32];
+
+        // overlapping the guarded range, nothing is written
+        let request =
+            request::write_multiple_holding_registers(&mut request_buffer, 1, 0, &[1, 2, 3])
+                .unwrap();
+        assert_eq!(
+            exception(&mut guard, request),
+            Some(exception::ILLEGAL_FUNCTION.0)
+        );
+        assert_eq!(guard.handler().0, [0; 6]);
+        // outside of it, or not a guarded function
+        let request = request::write_holding_register(&mut request_buffer, 1, 4, 5).unwrap();
+        assert_eq!(exception(&mut guard, request), None);
+        let request = request::read_holding_registers(&mut request_buffer, 1, 0, 6).unwrap();
+        assert_eq!(exception(&mut guard, request), None);
+        assert!(!guard.is_guarded(request));
+
+        // user defined functions have no range
+        let (request, _) = crate::builder::build_frame(&mut request_buffer)
+            .for_address(1)
+            .function(Function(0x41))
+            .finalise();
+        assert!(guard.is_guarded(request));
+
+        guard.unlock();
+        let request =
+            request::write_multiple_holding_registers(&mut request_buffer, 1, 0, &[1, 2, 3])
+                .unwrap();
+        assert_eq!(exception(&mut guard, request), None);
+        guard.lock();
+        assert!(!guard.is_unlocked());
+        assert_eq!(guard.into_inner().0, [1, 2, 3, 0, 5, 0]);
+    }
+}
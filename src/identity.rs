@@ -0,0 +1,426 @@
+//! Read Device Identification (0x2B / MEI 0x0E)
+//!
+//! [`DeviceIdentity`] holds a server's identification objects and answers requests for them from
+//! [`Handler::other`](crate::server::Handler::other), splitting objects which don't fit one response across several
+//! with "more follows". [`IdentityResponse`] decodes the responses on the client side.
+//!
+//! ```
+//! use modbus_frames::{identity::{DeviceIdentity, IdentityResponse}, request};
+//!
+//! const IDENTITY: DeviceIdentity = DeviceIdentity {
+//!     product_name: Some("Flow Meter"),
+//!     ..DeviceIdentity::basic("ACME", "FM-100", "V1.2")
+//! };
+//!
+//! let mut request_buffer = [0; 8];
+//! let request = request::read_device_identification(&mut request_buffer, 1, 2, 0).unwrap();
+//! let mut response_buffer = [0; 256];
+//! let response = IDENTITY.respond(request, &mut response_buffer).unwrap();
+//! let response = IdentityResponse::try_from(response).unwrap();
+//! assert!(!response.more_follows());
+//! assert_eq!(response.objects().nth(3), Some((4, &b"Flow Meter"[..])));
+//! ```
+
+use crate::{
+    builder, exception, function, pdu, response::exception_error, Error, Exception, Frame,
+};
+
+/// VendorName, ProductCode and MajorMinorRevision, mandatory
+pub const READ_BASIC: u8 = 1;
+/// VendorUrl, ProductName, ModelName and UserApplicationName, optional
+pub const READ_REGULAR: u8 = 2;
+/// Private objects 0x80-0xFF, optional
+pub const READ_EXTENDED: u8 = 3;
+/// One object
+pub const READ_INDIVIDUAL: u8 = 4;
+
+/// MEI type, read device id code, conformity level, more follows, next object id, number of objects
+const HEADER_LEN: usize = 6;
+/// Object bytes available in one response, less the function code and header
+const OBJECTS_LEN: usize = pdu::MAX_PDU_LEN - 1 - HEADER_LEN;
+
+/// The identification objects of a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeviceIdentity {
+    /// object 0
+    pub vendor_name: &'static str,
+    /// object 1
+    pub product_code: &'static str,
+    /// object 2
+    pub major_minor_revision: &'static str,
+    /// object 3
+    pub vendor_url: Option<&'static str>,
+    /// object 4
+    pub product_name: Option<&'static str>,
+    /// object 5
+    pub model_name: Option<&'static str>,
+    /// object 6
+    pub user_application_name: Option<&'static str>,
+    /// private objects, ids 0x80-0xFF in ascending order
+    pub extended: &'static [(u8, &'static str)],
+}
+
+impl DeviceIdentity {
+    /// Only the mandatory objects
+    pub const fn basic(
+        vendor_name: &'static str,
+        product_code: &'static str,
+        major_minor_revision: &'static str,
+    ) -> Self {
+        DeviceIdentity {
+            vendor_name,
+            product_code,
+            major_minor_revision,
+            vendor_url: None,
+            product_name: None,
+            model_name: None,
+            user_application_name: None,
+            extended: &[],
+        }
+    }
+
+    /// The value of object `id`
+    pub fn object(&self, id: u8) -> Option<&'static str> {
+        match id {
+            0 => Some(self.vendor_name),
+            1 => Some(self.product_code),
+            2 => Some(self.major_minor_revision),
+            3 => self.vendor_url,
+            4 => self.product_name,
+            5 => self.model_name,
+            6 => self.user_application_name,
+            0x80.. => self
+                .extended
+                .iter()
+                .find(|(object, _)| *object == id)
+                .map(|(_, value)| *value),
+            _ => None,
+        }
+    }
+
+    /// Highest read device id code supported, individual access is always supported
+    pub fn conformity_level(&self) -> u8 {
+        let regular = [
+            self.vendor_url,
+            self.product_name,
+            self.model_name,
+            self.user_application_name,
+        ];
+        if !self.extended.is_empty() {
+            READ_EXTENDED
+        } else if regular.iter().any(Option::is_some) {
+            READ_REGULAR
+        } else {
+            READ_BASIC
+        }
+    }
+
+    /// The objects read by a read device id code in order, each category includes the ones below it
+    fn objects(&self, code: u8) -> impl Iterator<Item = (u8, &'static str)> + '_ {
+        let ids = match code {
+            READ_BASIC => 0..=2,
+            READ_REGULAR => 0..=6,
+            _ => 0..=0xFF,
+        };
+        ids.filter_map(|id| self.object(id).map(|value| (id, value)))
+    }
+
+    /// Respond to a Read Device Identification request, from `Handler::other`
+    ///
+    /// Stream access (codes 1-3) starts from the requested object, or the first object of the category if the
+    /// device doesn't have it. A category above the conformity level is answered at the conformity level. Objects
+    /// longer than fit in a response are truncated.
+    pub fn respond<'b>(
+        &self,
+        request: Frame,
+        response_buffer: &'b mut [u8],
+    ) -> Result<Frame<'b>, Exception> {
+        let [function::MEI_READ_DEVICE_IDENTIFICATION, code, object_id] = *request.payload() else {
+            return Err(exception::ILLEGAL_FUNCTION);
+        };
+        let level = self.conformity_level();
+        let (objects, more_follows) = match code {
+            READ_INDIVIDUAL => match self.object(object_id) {
+                Some(value) => (Objects::One(object_id, value), None),
+                None => return Err(exception::ILLEGAL_ADDRESS),
+            },
+            READ_BASIC..=READ_EXTENDED => {
+                let code = code.min(level);
+                let first = if self.objects(code).any(|(id, _)| id == object_id) {
+                    object_id
+                } else {
+                    0
+                };
+                let mut len = 0;
+                let mut next = None;
+                let mut count = 0;
+                for (id, value) in self.objects(code).skip_while(|(id, _)| *id < first) {
+                    if len + 2 + truncated(value).len() > OBJECTS_LEN && count > 0 {
+                        next = Some(id);
+                        break;
+                    }
+                    len += 2 + truncated(value).len();
+                    count += 1;
+                }
+                (Objects::Stream { code, first, count }, next)
+            }
+            _ => return Err(exception::ILLEGAL_DATA),
+        };
+
+        let count = objects.iter(self).count();
+        let len = 1
+            + HEADER_LEN
+            + objects
+                .iter(self)
+                .map(|(_, value)| 2 + truncated(value).len())
+                .sum::<usize>();
+        if response_buffer.len() < len + 3 {
+            return Err(exception::DEVICE_FAILURE);
+        }
+        let mut builder = builder::build_frame(response_buffer)
+            .for_address(request.address())
+            .function(function::ENCAPSULATED_INTERFACE_TRANSPORT)
+            .byte(function::MEI_READ_DEVICE_IDENTIFICATION)
+            .byte(objects.code())
+            .byte(level | 0x80)
+            .byte(if more_follows.is_some() { 0xFF } else { 0 })
+            .byte(more_follows.unwrap_or(0))
+            .byte(count as u8);
+        for (id, value) in objects.iter(self) {
+            let value = truncated(value);
+            builder = builder
+                .byte(id)
+                .byte(value.len() as u8)
+                .bytes(value.iter().copied());
+        }
+        Ok(builder.finalise().0)
+    }
+}
+
+/// the longest value which fits in a response on its own
+fn truncated(value: &str) -> &[u8] {
+    let bytes = value.as_bytes();
+    &bytes[..bytes.len().min(OBJECTS_LEN - 2)]
+}
+
+/// the objects in one response
+enum Objects {
+    One(u8, &'static str),
+    Stream { code: u8, first: u8, count: usize },
+}
+
+impl Objects {
+    /// the read device id code answered
+    fn code(&self) -> u8 {
+        match *self {
+            Objects::One(..) => READ_INDIVIDUAL,
+            Objects::Stream { code, .. } => code,
+        }
+    }
+
+    fn iter<'i>(
+        &'i self,
+        identity: &'i DeviceIdentity,
+    ) -> impl Iterator<Item = (u8, &'static str)> + 'i {
+        let (one, stream) = match *self {
+            Objects::One(id, value) => (Some((id, value)), None),
+            Objects::Stream { code, first, count } => (
+                None,
+                Some(
+                    identity
+                        .objects(code)
+                        .skip_while(move |(id, _)| *id < first)
+                        .take(count),
+                ),
+            ),
+        };
+        one.into_iter().chain(stream.into_iter().flatten())
+    }
+}
+
+/// A Read Device Identification response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IdentityResponse<'a> {
+    frame: Frame<'a>,
+}
+
+impl<'a> IdentityResponse<'a> {
+    pub fn as_frame(&self) -> Frame<'a> {
+        self.frame
+    }
+
+    /// The read device id code answered
+    pub fn read_device_id_code(&self) -> u8 {
+        self.frame.payload()[1]
+    }
+
+    /// The highest read device id code the device supports, bit 7 set if individual access is supported
+    pub fn conformity_level(&self) -> u8 {
+        self.frame.payload()[2]
+    }
+
+    /// true if another request starting from `next_object_id` is needed for the rest of the objects
+    pub fn more_follows(&self) -> bool {
+        self.frame.payload()[3] == 0xFF
+    }
+
+    pub fn next_object_id(&self) -> u8 {
+        self.frame.payload()[4]
+    }
+
+    /// The object ids and values
+    pub fn objects(&self) -> impl Iterator<Item = (u8, &'a [u8])> {
+        let payload = self.frame.payload();
+        let mut objects = &payload[HEADER_LEN..];
+        (0..payload[5]).map(move |_| {
+            let (object, rest) = objects.split_at(2 + usize::from(objects[1]));
+            objects = rest;
+            (object[0], &object[2..])
+        })
+    }
+}
+
+impl<'a> TryFrom<Frame<'a>> for IdentityResponse<'a> {
+    type Error = Error;
+
+    fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
+        let expected = function::ENCAPSULATED_INTERFACE_TRANSPORT;
+        if frame.function().0 == expected.0 | 0x80 {
+            return Err(exception_error(frame));
+        } else if frame.function() != expected {
+            return Err(Error::UnexpectedFunction {
+                expected,
+                found: frame.function(),
+            });
+        }
+        let payload = frame.payload();
+        if payload.len() < HEADER_LEN || payload[0] != function::MEI_READ_DEVICE_IDENTIFICATION {
+            return Err(Error::InvalidValue);
+        }
+        // every object fits in the payload, with nothing after the last
+        let mut objects = &payload[HEADER_LEN..];
+        for _ in 0..payload[5] {
+            match objects {
+                [_, len, rest @ ..] if rest.len() >= usize::from(*len) => {
+                    objects = &rest[usize::from(*len)..]
+                }
+                _ => return Err(Error::InvalidLength(frame.raw_bytes().len())),
+            }
+        }
+        if !objects.is_empty() {
+            return Err(Error::InvalidLength(frame.raw_bytes().len()));
+        }
+        Ok(IdentityResponse { frame })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeviceIdentity, IdentityResponse, READ_BASIC, READ_EXTENDED, READ_INDIVIDUAL};
+    use crate::{exception, request, Error};
+
+    const LONG: &str = concat!(
+        "0123456789012345678901234567890123456789012345678901234567890123456789",
+        "0123456789012345678901234567890123456789012345678901234567890123456789",
+    );
+
+    const IDENTITY: DeviceIdentity = DeviceIdentity {
+        extended: &[(0x80, LONG), (0x81, LONG), (0x90, "tail")],
+        ..DeviceIdentity::basic("ACME", "FM-100", "V1.2")
+    };
+
+    fn read(identity: &DeviceIdentity, code: u8, object_id: u8) -> Result<Vec<u8>, Error> {
+        let mut request_buffer = [0; 8];
+        let request =
+            request::read_device_identification(&mut request_buffer, 1, code, object_id).unwrap();
+        let mut response_buffer = [0; 256];
+        let response = identity
+            .respond(request, &mut response_buffer)
+            .map_err(Error::Exception)?;
+        Ok(response.raw_bytes().to_vec())
+    }
+
+    #[test]
+    fn basic() {
+        let bytes = read(&IDENTITY, READ_BASIC, 0).unwrap();
+        assert_eq!(
+            bytes[..17],
+            [
+                0x01, 0x2B, 0x0E, 0x01, 0x83, 0x00, 0x00, 0x03, 0x00, 0x04, b'A', b'C', b'M', b'E',
+                0x01, 0x06, b'F'
+            ]
+        );
+        let response =
+            IdentityResponse::try_from(crate::Frame::try_from(&bytes[..]).unwrap()).unwrap();
+        assert_eq!(response.read_device_id_code(), READ_BASIC);
+        assert_eq!(response.conformity_level(), 0x83);
+        let objects: Vec<_> = response.objects().collect();
+        assert_eq!(objects, [(0, &b"ACME"[..]), (1, b"FM-100"), (2, b"V1.2")]);
+        // starting part way through, or from an object the category doesn't have
+        let bytes = read(&IDENTITY, READ_BASIC, 2).unwrap();
+        let response =
+            IdentityResponse::try_from(crate::Frame::try_from(&bytes[..]).unwrap()).unwrap();
+        assert_eq!(response.objects().count(), 1);
+        assert_eq!(
+            read(&IDENTITY, READ_BASIC, 0x80),
+            read(&IDENTITY, READ_BASIC, 0)
+        );
+        // regular is answered as basic by a basic device
+        let basic = DeviceIdentity::basic("ACME", "FM-100", "V1.2");
+        assert_eq!(basic.conformity_level(), READ_BASIC);
+        let bytes = read(&basic, 2, 0).unwrap();
+        assert_eq!(bytes[3..5], [READ_BASIC, 0x81]);
+    }
+
+    #[test]
+    fn segmented() {
+        let bytes = read(&IDENTITY, READ_EXTENDED, 0).unwrap();
+        let response =
+            IdentityResponse::try_from(crate::Frame::try_from(&bytes[..]).unwrap()).unwrap();
+        assert!(response.more_follows());
+        assert_eq!(response.next_object_id(), 0x81);
+        assert_eq!(response.objects().count(), 4);
+        assert_eq!(response.objects().last(), Some((0x80, LONG.as_bytes())));
+
+        let bytes = read(&IDENTITY, READ_EXTENDED, 0x81).unwrap();
+        let response =
+            IdentityResponse::try_from(crate::Frame::try_from(&bytes[..]).unwrap()).unwrap();
+        assert!(!response.more_follows());
+        assert_eq!(response.next_object_id(), 0);
+        assert_eq!(
+            response.objects().collect::<Vec<_>>(),
+            [(0x81, LONG.as_bytes()), (0x90, &b"tail"[..])]
+        );
+    }
+
+    #[test]
+    fn individual() {
+        let bytes = read(&IDENTITY, READ_INDIVIDUAL, 0x90).unwrap();
+        let response =
+            IdentityResponse::try_from(crate::Frame::try_from(&bytes[..]).unwrap()).unwrap();
+        assert_eq!(
+            response.objects().collect::<Vec<_>>(),
+            [(0x90, &b"tail"[..])]
+        );
+        assert_eq!(
+            read(&IDENTITY, READ_INDIVIDUAL, 3),
+            Err(Error::Exception(exception::ILLEGAL_ADDRESS))
+        );
+
+        let mut request_buffer = [0; 8];
+        let request = request::read_coils(&mut request_buffer, 1, 0, 1).unwrap();
+        assert_eq!(
+            IDENTITY.respond(request, &mut [0; 256]),
+            Err(exception::ILLEGAL_FUNCTION)
+        );
+        assert_eq!(
+            IdentityResponse::try_from(request),
+            Err(Error::UnexpectedFunction {
+                expected: crate::function::ENCAPSULATED_INTERFACE_TRANSPORT,
+                found: crate::function::READ_COILS
+            })
+        );
+    }
+}
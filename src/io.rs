@@ -0,0 +1,363 @@
+//! Framing over blocking `embedded-io` byte pipes (feature `embedded-io`)
+//!
+//! RTU tunnelled over a TCP socket or a USB-CDC port has no inter-frame silence to mark where a frame ends.
+//! [`IoPort`] reads the header and takes the length implied by the function code (see
+//! [`monitor::request_len`](crate::monitor::request_len) and [`monitor::response_len`](crate::monitor::response_len)),
+//! so only the standard functions can be received.
+//! It implements [`Transport`] for the client and [`SerialPort`] for [`HalfDuplex`](crate::rs485::HalfDuplex),
+//! [`IoPort::read_request`] and [`IoPort::write_frame`] are the server's receive and transmit paths.
+//!
+//! ```
+//! use modbus_frames::{io::IoPort, request};
+//!
+//! let mut buffer = [0; 8];
+//! let request = request::read_coils(&mut buffer, 1, 0, 8).unwrap();
+//! let mut port = IoPort::new(request.raw_bytes());
+//! let mut received = [0; 256];
+//! assert_eq!(port.read_request(&mut received), Ok(request));
+//! ```
+
+use embedded_io::{Read, Write};
+
+use crate::{
+    client::Transport,
+    monitor::{self, Size},
+    quirks::Quirks,
+    rs485::SerialPort,
+    Error, Frame, Function,
+};
+
+/// Frames over a blocking byte pipe
+///
+/// I/O errors, including the end of the stream, are reported as `Error::NoResponse`
+#[derive(Debug)]
+pub struct IoPort<T> {
+    io: T,
+}
+
+impl<T> IoPort<T> {
+    pub fn new(io: T) -> Self {
+        IoPort { io }
+    }
+
+    pub fn inner(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+}
+
+impl<T: Write> IoPort<T> {
+    /// Write all of `frame` and flush it
+    pub fn write_frame(&mut self, frame: Frame) -> Result<(), Error> {
+        self.write_all(frame.raw_bytes())
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.io
+            .write_all(bytes)
+            .and_then(|_| self.io.flush())
+            .map_err(|_| Error::NoResponse)
+    }
+}
+
+impl<T: Read> IoPort<T> {
+    /// Read a request into `buffer`
+    pub fn read_request<'b>(&mut self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        self.read_frame(buffer, monitor::request_size, &Quirks::STRICT)
+    }
+
+    /// Read a response into `buffer`
+    pub fn read_response<'b>(&mut self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        self.read_response_with_quirks(buffer, &Quirks::STRICT)
+    }
+
+    /// Read a response into `buffer`, accepting the deviations in `quirks`
+    pub fn read_response_with_quirks<'b>(
+        &mut self,
+        buffer: &'b mut [u8],
+        quirks: &Quirks,
+    ) -> Result<Frame<'b>, Error> {
+        self.read_frame(buffer, monitor::response_size, quirks)
+    }
+
+    /// read a byte at a time until `frame_size` knows the length, then the rest of the frame
+    fn read_frame<'b>(
+        &mut self,
+        buffer: &'b mut [u8],
+        frame_size: fn(&[u8]) -> Size,
+        quirks: &Quirks,
+    ) -> Result<Frame<'b>, Error> {
+        let available = buffer.len();
+        let mut len = 0;
+        let total = loop {
+            match frame_size(&buffer[..len]) {
+                Size::Known(total) => break total,
+                Size::Unknown => return Err(Error::UnknownFunction(Function(buffer[1]))),
+                Size::More => {}
+            }
+            let next = buffer
+                .get_mut(len..len + 1)
+                .ok_or(Error::InsufficientBuffer {
+                    needed: len + 1,
+                    len: available,
+                })?;
+            self.read_exact(next)?;
+            len += 1;
+        };
+        let frame = buffer.get_mut(..total).ok_or(Error::InsufficientBuffer {
+            needed: total,
+            len: available,
+        })?;
+        self.read_exact(&mut frame[len..])?;
+        quirks.decode_frame(frame)
+    }
+
+    fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        self.io.read_exact(buffer).map_err(|_| Error::NoResponse)
+    }
+}
+
+impl<T: Read + Write> SerialPort for IoPort<T> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.write_all(bytes)
+    }
+
+    fn read<'b>(&mut self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        self.read_response(buffer)
+    }
+
+    fn read_with_quirks<'b>(
+        &mut self,
+        buffer: &'b mut [u8],
+        quirks: &Quirks,
+    ) -> Result<Frame<'b>, Error> {
+        self.read_response_with_quirks(buffer, quirks)
+    }
+}
+
+impl<T: Read + Write> Transport for IoPort<T> {
+    fn transact<'b>(
+        &mut self,
+        request: Frame,
+        response_buffer: &'b mut [u8],
+    ) -> Result<Frame<'b>, Error> {
+        self.write_frame(request)?;
+        self.read_response(response_buffer)
+    }
+
+    fn transact_with_quirks<'b>(
+        &mut self,
+        request: Frame,
+        response_buffer: &'b mut [u8],
+        quirks: &Quirks,
+    ) -> Result<Frame<'b>, Error> {
+        self.write_frame(request)?;
+        self.read_response_with_quirks(response_buffer, quirks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IoPort;
+    use crate::{
+        client::Client,
+        entity::Entity,
+        file::FileStorage,
+        quirks::Quirks,
+        request::{self, FileRecord, FileRecordData},
+        response,
+        rs485::{DirectionControl, HalfDuplex, Turnaround},
+        server::{self, Handler},
+        source::Coils,
+        Error, Function,
+    };
+    use core::convert::Infallible;
+
+    /// answers requests written to it from a handler, like a device at the other end of a socket
+    struct Pipe<H = Coils<[bool; 4]>> {
+        device: H,
+        rx: Vec<u8>,
+        /// send and expect the CRC big endian
+        swapped_crc: bool,
+    }
+
+    impl<H> embedded_io::ErrorType for Pipe<H> {
+        type Error = Infallible;
+    }
+
+    impl<H> embedded_io::Read for Pipe<H> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            let len = buf.len().min(self.rx.len());
+            buf[..len].copy_from_slice(&self.rx[..len]);
+            self.rx.drain(..len);
+            Ok(len)
+        }
+    }
+
+    impl<H: Handler> embedded_io::Write for Pipe<H> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            let quirks = Quirks {
+                swapped_crc: self.swapped_crc,
+                ..Quirks::STRICT
+            };
+            let mut request = buf.to_vec();
+            quirks.encode_crc(&mut request);
+            let mut response = [0; 256];
+            if let Ok(request) = crate::ValidatedFrame::try_from(request.as_slice()) {
+                let len = server::dispatch(&mut self.device, request, &mut response)
+                    .raw_bytes()
+                    .len();
+                quirks.encode_crc(&mut response[..len]);
+                self.rx.extend_from_slice(&response[..len]);
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn byte_pipe() {
+        let pipe = Pipe {
+            device: Coils([false, true, false, false]),
+            rx: Vec::new(),
+            swapped_crc: false,
+        };
+        let mut client = Client::new(IoPort::new(pipe), 1);
+        assert_eq!(client.read_bool(Entity::coil(1)), Ok(true));
+        client.write_bool(3, true).unwrap();
+        assert!(client.transport().inner().device.0[3]);
+
+        // the end of the stream mid frame
+        let mut buf = [0; 256];
+        let (request, rem) = request::WriteMultipleCoils::new(&mut buf, 1, 0, [true; 10]);
+        let (response, _) = response::ReadCoils::new(rem, 1, [true; 10]);
+        let (request, response) = (request.as_frame(), response.as_frame());
+        let mut port = IoPort::new(&request.raw_bytes()[..9]);
+        let mut received = [0; 256];
+        assert_eq!(port.read_request(&mut received), Err(Error::NoResponse));
+        let mut port = IoPort::new(request.raw_bytes());
+        assert_eq!(
+            port.read_request(&mut received[..10]),
+            Err(Error::InsufficientBuffer {
+                needed: 11,
+                len: 10
+            })
+        );
+        // back to back responses
+        let stream = [response.raw_bytes(); 2].concat();
+        let mut port = IoPort::new(stream.as_slice());
+        for _ in 0..2 {
+            assert_eq!(port.read_response(&mut received), Ok(response));
+        }
+        assert_eq!(port.read_response(&mut received), Err(Error::NoResponse));
+
+        let identity = [1_u8, 0x2B, 0x0E, 1, 0, 0x70, 0x77];
+        let mut port = IoPort::new(identity.as_slice());
+        assert_eq!(
+            port.read_request(&mut received)
+                .map(|frame| frame.raw_bytes().len()),
+            Ok(7)
+        );
+        // only read device identification has a known length
+        let mut unknown = IoPort::new([1_u8, 0x2B, 0x0D, 0, 0].as_slice());
+        assert_eq!(
+            unknown.read_request(&mut received),
+            Err(Error::UnknownFunction(Function(0x2B)))
+        );
+        let mut unknown = IoPort::new([1_u8, 0x41, 0, 0].as_slice());
+        assert_eq!(
+            unknown.read_response(&mut received),
+            Err(Error::UnknownFunction(Function(0x41)))
+        );
+    }
+
+    struct Eeprom([[u16; 16]; 2]);
+
+    impl Handler for Eeprom {
+        fn files(&mut self) -> Option<&mut dyn FileStorage> {
+            Some(&mut self.0)
+        }
+    }
+
+    #[test]
+    fn file_records() {
+        let pipe = Pipe {
+            device: Eeprom([[0; 16]; 2]),
+            rx: Vec::new(),
+            swapped_crc: false,
+        };
+        let mut client = Client::new(IoPort::new(pipe), 1);
+        let data = [0x1234, 0x5678, 0x9ABC];
+        let record = FileRecordData {
+            file_number: 2,
+            record_number: 4,
+            data: &data,
+        };
+        let response = client
+            .transact(|buf, address| request::write_file_record(buf, address, &[record]))
+            .unwrap();
+        // the response echoes the request
+        assert_eq!(response.raw_bytes().len(), 4 + 1 + 7 + 6);
+        assert_eq!(client.transport().inner().device.0[1][4..7], data);
+
+        let record = FileRecord {
+            file_number: 2,
+            record_number: 5,
+            record_length: 2,
+        };
+        let response = client
+            .transact(|buf, address| request::read_file_record(buf, address, &[record]))
+            .unwrap();
+        assert_eq!(response.payload(), [6, 5, 6, 0x56, 0x78, 0x9A, 0xBC]);
+    }
+
+    struct Driver;
+
+    impl DirectionControl for Driver {
+        fn set_tx(&mut self, _enabled: bool) {}
+
+        fn delay_us(&mut self, _us: u32) {}
+    }
+
+    #[test]
+    fn swapped_crc() {
+        let pipe = || Pipe {
+            device: Coils([false, true, false, false]),
+            rx: Vec::new(),
+            swapped_crc: true,
+        };
+        let quirks = Quirks {
+            swapped_crc: true,
+            ..Quirks::STRICT
+        };
+
+        let mut client = Client::new(IoPort::new(pipe()), 1);
+        assert_eq!(client.read_bool(Entity::coil(1)), Err(Error::NoResponse));
+        let mut client = Client::new(IoPort::new(pipe()), 1).with_quirks(quirks);
+        assert_eq!(client.read_bool(Entity::coil(1)), Ok(true));
+        client.write_bool(3, true).unwrap();
+        assert!(client.transport().inner().device.0[3]);
+
+        let port = HalfDuplex::new(IoPort::new(pipe()), Driver, Turnaround::for_baud(19200));
+        let mut client = Client::new(port, 1).with_quirks(quirks);
+        assert_eq!(client.read_bool(Entity::coil(1)), Ok(true));
+        client.write_bool(3, true).unwrap();
+        assert!(client.transport().port().inner().device.0[3]);
+
+        // a strict read of the same response fails on the CRC
+        let mut buf = [0; 8];
+        let request = request::read_coils(&mut buf, 1, 0, 4).unwrap();
+        let mut request = request.raw_bytes().to_vec();
+        quirks.encode_crc(&mut request);
+        let mut port = IoPort::new(pipe());
+        embedded_io::Write::write(port.inner(), &request).unwrap();
+        let mut received = [0; 256];
+        assert_eq!(port.read_response(&mut received), Err(Error::InvalidCrc));
+    }
+}
@@ -61,44 +61,147 @@
 //! assert_eq!(frame.raw_bytes(), [1, 2, 0, 3, 224, 25]);
 //! assert_eq!(frame.payload(), [0, 3]);
 //! ```
+//!
+//! ## Panics
+//!
+//! Decoding received bytes never panics: `Frame::try_from`, `CommonRequests::try_from` and `CommonResponses::try_from`
+//! on a `&[u8]` report every problem as an `Error`, as do the `request::` constructors and `Frame::to_ascii`. The
+//! accessors of frames made with `new_unchecked` may panic, see their docs. Use the `try_` accessors
+//! (`Frame::try_payload`, `try_pdu`, `try_crc_bytes` and `try_parts`) on a frame that may be too short. Write
+//! multiple frames built by hand can be finished with `try_finalise` to get an error for a bad quantity.
+//!
+//! The builder itself (`byte`, `registers`, ...) and `SplitBuilder` panic if the buffer runs out, check
+//! `Builder::bytes_remaining` or use the `request::` constructors where the buffer size isn't known up front.
+//!
+//! With the `no-panic` feature the decoders, the `request::` constructors for the fixed length, no data and write
+//! multiple requests (including `write_coils_auto` and `write_registers_auto`) and the `try_` accessors are annotated
+//! with `#[no_panic]`, a release build that links one of them fails if the optimiser can't prove it panic free.
+//! `Frame::try_from` is checked as part of the decoders. The annotated functions are inlined into their callers, so
+//! the doctests have to be optimised too:
+//!
+//! ```text
+//! RUSTDOCFLAGS="-C opt-level=3 -C debug-assertions=off" cargo test --release --features no-panic
+//! ```
+//!
+//! The builder, `SplitBuilder`, the remaining variable length request constructors and the panicking frame accessors
+//! are not covered. Debug builds aren't checked as they can't be proven.
 
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 
+pub mod ascii;
 pub mod builder;
+#[cfg(feature = "std")]
+pub mod capture;
+pub mod client;
+mod crc;
+pub mod custom;
 pub mod decoder;
+pub mod device;
+pub mod diagnostic;
+pub mod entity;
 pub mod exception;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fifo;
+pub mod file;
+pub mod filter;
 pub mod frame;
 pub mod function;
+#[cfg(feature = "arbitrary")]
+pub mod fuzz;
+#[cfg(feature = "fwupdate")]
+pub mod fwupdate;
+pub mod gateway;
+pub mod guard;
+pub mod identity;
+#[cfg(feature = "embedded-io")]
+pub mod io;
+pub mod master;
+pub mod monitor;
+pub mod owned;
+pub mod pdu;
+pub mod poll;
+pub mod quirks;
+pub mod regmap;
+#[cfg(feature = "std")]
+pub mod replay;
 pub mod request;
 pub mod response;
+pub mod rs485;
+pub mod rx;
+pub mod scale;
+pub mod server;
+#[cfg(feature = "std")]
+pub mod sim;
+pub mod source;
+#[cfg(feature = "smoltcp")]
+pub mod tcp;
+#[cfg(feature = "std")]
+pub mod testutil;
+pub mod throttle;
+pub mod trace;
+pub mod uart;
 
+pub use device::Device;
 pub use exception::Exception;
-pub use frame::Frame;
+pub use frame::{Frame, ValidatedFrame};
 pub use function::Function;
 
-pub fn calculate_crc16(bytes: &[u8]) -> u16 {
-    crc16::State::<crc16::MODBUS>::calculate(bytes)
+/// CRC-16/MODBUS, transmitted little endian at the end of RTU frames
+pub const fn calculate_crc16(bytes: &[u8]) -> u16 {
+    crc::crc16(bytes)
 }
 
 /// returns true if `bytes.len() > 4` and the last two bytes are the CRC16 of the preceding bytes
-pub fn verify_crc16(bytes: &[u8]) -> bool {
+pub const fn verify_crc16(bytes: &[u8]) -> bool {
     if bytes.len() < 4 {
         false
     } else {
-        let frame = Frame::new_unchecked(bytes);
-        frame.calculate_crc().to_le_bytes() == frame.crc_bytes()
+        let (data, crc) = bytes.split_at(bytes.len() - 2);
+        let [lo, hi] = calculate_crc16(data).to_le_bytes();
+        crc[0] == lo && crc[1] == hi
     }
 }
 
+/// As `verify_crc16`, for devices which transmit the CRC big endian (see `Quirks::swapped_crc`)
+pub const fn verify_crc16_swapped(bytes: &[u8]) -> bool {
+    if bytes.len() < 4 {
+        false
+    } else {
+        let (data, crc) = bytes.split_at(bytes.len() - 2);
+        let [hi, lo] = calculate_crc16(data).to_be_bytes();
+        crc[0] == hi && crc[1] == lo
+    }
+}
+
+/// LRC checksum of the ASCII transmission mode, calculated over the address, function and data bytes
+pub const fn calculate_lrc(bytes: &[u8]) -> u8 {
+    crc::lrc(bytes)
+}
+
 pub trait PacketLen {
     fn packet_len(&self) -> u8;
     fn minimum_len() -> u8;
     /// true if the byte length is valid for this type
     fn is_valid_len(len: usize) -> bool {
-        len >= Self::minimum_len().into() && len <= 252
+        len >= Self::minimum_len().into() && len <= 256
+    }
+    /// the lengths accepted by `is_valid_len`, for error reporting
+    fn len_hint() -> LenHint {
+        LenHint::AtLeast(Self::minimum_len())
     }
 }
 
+/// Describes the frame lengths valid for a type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LenHint {
+    /// only this length is valid
+    Exact(u8),
+    /// this length or longer is valid
+    AtLeast(u8),
+}
+
 pub trait FixedLen: PacketLen {
     const LEN: u8;
 }
@@ -107,6 +210,29 @@ pub trait FunctionCode {
     const FUNCTION: Function;
 }
 
+/// The parts common to RTU and ASCII frames, for code which doesn't depend on the wire encoding
+pub trait ModbusFrame<'b>: Copy {
+    fn address(&self) -> u8;
+    fn function(&self) -> Function;
+    /// All bytes between the address/function code and the checksum
+    fn payload(&self) -> &[u8];
+    /// true if the checksum (CRC or LRC) is correct
+    fn verify(&self) -> bool;
+    /// All of the bytes in the message including the checksum
+    fn into_raw_bytes(self) -> &'b [u8];
+
+    fn device(&self) -> Device {
+        Device::new(self.address())
+    }
+
+    /// true if `other` has the same address, function and payload, the checksums aren't compared
+    fn semantic_eq<'o>(&self, other: &impl ModbusFrame<'o>) -> bool {
+        self.address() == other.address()
+            && self.function() == other.function()
+            && self.payload() == other.payload()
+    }
+}
+
 impl<T: FixedLen> PacketLen for T {
     fn packet_len(&self) -> u8 {
         Self::minimum_len()
@@ -119,22 +245,50 @@ impl<T: FixedLen> PacketLen for T {
     fn is_valid_len(len: usize) -> bool {
         len == Self::minimum_len().into()
     }
+
+    fn len_hint() -> LenHint {
+        LenHint::Exact(Self::LEN)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[non_exhaustive] // new errors may be added later
 pub enum Error {
-    /// Valid message lengths are 4-256 bytes
-    InvalidLength,
+    /// Valid message lengths are 4-256 bytes, contains the length found
+    InvalidLength(usize),
     /// CRC verification failed
     InvalidCrc,
+    /// LRC verification of an ASCII frame failed
+    InvalidLrc,
+    /// An ASCII frame has an invalid character or delimiter, contains the index of the offending character
+    InvalidEncoding(usize),
+    /// An ASCII frame has an odd number of hex characters, contains the number found
+    OddEncodedLength(usize),
     /// Decoding failed because the function code was unknown
-    UnknownFunction,
+    UnknownFunction(Function),
     /// The expected function code was not what was found
-    UnexpectedFunction,
+    UnexpectedFunction { expected: Function, found: Function },
     /// message size is invalid for the function code
-    DecodeInvalidLength,
+    DecodeInvalidLength {
+        function: Function,
+        len: usize,
+        expected: LenHint,
+    },
+    /// Device address is outside of the range valid for the operation
+    InvalidAddress,
+    /// Number of values is outside of the range allowed by the function
+    InvalidQuantity,
+    /// A value is outside of the range allowed by the function
+    InvalidValue,
+    /// The provided buffer is too small to hold the frame
+    BufferTooSmall,
+    /// The provided buffer of `len` bytes is too small, the frame needs `needed` bytes
+    InsufficientBuffer { needed: usize, len: usize },
+    /// The device responded with an exception
+    Exception(Exception),
+    /// No valid response was received from the device
+    NoResponse,
 }
 
 /// When Writing/Reading a single coil, `ON == 0xFF00` and `OFF == 0x0000`
@@ -144,12 +298,122 @@ pub const COIL_ON: u16 = 0xFF00;
 /// All other values are invalid
 pub const COIL_OFF: u16 = 0x0000;
 
+impl core::fmt::Display for LenHint {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LenHint::Exact(len) => write!(f, "{}", len),
+            LenHint::AtLeast(len) => write!(f, "at least {}", len),
+        }
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidLength(len) => {
+                write!(f, "frame length must be 4-256 bytes, found {} bytes", len)
+            }
+            Error::InvalidCrc => f.write_str("frame CRC verification failed"),
+            Error::InvalidLrc => f.write_str("frame LRC verification failed"),
+            Error::InvalidEncoding(idx) => write!(f, "invalid character at index {}", idx),
+            Error::OddEncodedLength(len) => write!(
+                f,
+                "frame has {} hex characters, bytes need two characters each",
+                len
+            ),
+            Error::UnknownFunction(function) => write!(f, "unknown function code {}", function),
+            Error::UnexpectedFunction { expected, found } => write!(
+                f,
+                "unexpected function code {}, expected {}",
+                found, expected
+            ),
+            Error::DecodeInvalidLength {
+                function,
+                len,
+                expected,
+            } => write!(
+                f,
+                "{} byte frame is invalid for function code {}, expected {} bytes",
+                len, function, expected
+            ),
+            Error::InvalidAddress => f.write_str("device address is invalid"),
+            Error::InvalidQuantity => f.write_str("quantity is out of range for the function code"),
+            Error::InvalidValue => f.write_str("value is out of range for the function code"),
+            Error::BufferTooSmall => f.write_str("buffer is too small for the frame"),
+            Error::InsufficientBuffer { needed, len } => write!(
+                f,
+                "{} byte buffer is too small for the frame, {} bytes are needed",
+                len, needed
+            ),
+            Error::Exception(exception) => {
+                write!(f, "device responded with exception {}", exception)
+            }
+            Error::NoResponse => f.write_str("no response from device"),
+        }
+    }
+}
+
+/// Order of the registers making up a value wider than 16 bits
+///
+/// Registers are always transmitted big endian, the word order is device specific
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WordOrder {
+    /// most significant register at the lowest index, as recommended by the specification
+    HighWordFirst,
+    /// least significant register at the lowest index
+    LowWordFirst,
+}
+
+impl WordOrder {
+    /// combine two registers in transmission order into a 32 bit value
+    pub fn join(&self, first: u16, second: u16) -> u32 {
+        match self {
+            WordOrder::HighWordFirst => (first as u32) << 16 | second as u32,
+            WordOrder::LowWordFirst => (second as u32) << 16 | first as u32,
+        }
+    }
+
+    /// split a 32 bit value into two registers in transmission order
+    pub fn split(&self, value: u32) -> [u16; 2] {
+        let (high, low) = ((value >> 16) as u16, value as u16);
+        match self {
+            WordOrder::HighWordFirst => [high, low],
+            WordOrder::LowWordFirst => [low, high],
+        }
+    }
+
+    /// combine four registers in transmission order into a 64 bit value
+    pub fn join_u64(&self, registers: [u16; 4]) -> u64 {
+        let [a, b, c, d] = registers.map(u64::from);
+        match self {
+            WordOrder::HighWordFirst => a << 48 | b << 32 | c << 16 | d,
+            WordOrder::LowWordFirst => d << 48 | c << 32 | b << 16 | a,
+        }
+    }
+
+    /// split a 64 bit value into four registers in transmission order
+    pub fn split_u64(&self, value: u64) -> [u16; 4] {
+        let words = [
+            (value >> 48) as u16,
+            (value >> 32) as u16,
+            (value >> 16) as u16,
+            value as u16,
+        ];
+        match self {
+            WordOrder::HighWordFirst => words,
+            WordOrder::LowWordFirst => [words[3], words[2], words[1], words[0]],
+        }
+    }
+}
+
 // std::error::Error trait obviously isn't available in no_std
-// whould this implement any other error traits?
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
 
 #[cfg(test)]
 mod tests {
-    use crate::{calculate_crc16, verify_crc16};
+    use crate::{builder, calculate_crc16, function, request, verify_crc16, Error, Frame, LenHint};
 
     #[test]
     fn crc_calculation() {
@@ -161,5 +425,101 @@ mod tests {
             u16::from_le_bytes([0x9A, 0x9B]) // CRC is little endian
         );
         assert!(verify_crc16(&message));
+        const {
+            assert!(verify_crc16(&[
+                0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87
+            ]))
+        };
+    }
+
+    #[test]
+    fn error_display() {
+        assert_eq!(
+            Error::InvalidCrc.to_string(),
+            "frame CRC verification failed"
+        );
+        let error = Error::DecodeInvalidLength {
+            function: function::READ_COILS,
+            len: 9,
+            expected: LenHint::Exact(8),
+        };
+        assert_eq!(
+            error.to_string(),
+            "9 byte frame is invalid for function code ReadCoils (0x01), expected 8 bytes"
+        );
+        let error = Error::InsufficientBuffer { needed: 13, len: 8 };
+        assert_eq!(
+            error.to_string(),
+            "8 byte buffer is too small for the frame, 13 bytes are needed"
+        );
+    }
+
+    #[test]
+    fn errors_carry_context() {
+        let mut buf = [0; 16];
+        let (frame, _) = request::ReadCoils::new(&mut buf, 1, 0, 8);
+        assert_eq!(
+            request::ReadHoldingRegisters::try_from(frame.as_frame()),
+            Err(Error::UnexpectedFunction {
+                expected: function::READ_HOLDING_REGISTERS,
+                found: function::READ_COILS
+            })
+        );
+        let (frame, _) = builder::build_frame(&mut buf)
+            .for_address(1)
+            .function(function::WRITE_MULTIPLE_COILS)
+            .register(0)
+            .finalise();
+        assert_eq!(
+            request::WriteMultipleCoils::try_from(frame),
+            Err(Error::DecodeInvalidLength {
+                function: function::WRITE_MULTIPLE_COILS,
+                len: 6,
+                expected: LenHint::AtLeast(9)
+            })
+        );
+        assert_eq!(Frame::try_from(&buf[..3]), Err(Error::InvalidLength(3)));
+    }
+
+    #[cfg(feature = "no-panic")]
+    #[test]
+    fn no_panic_entry_points() {
+        // links every function annotated with `no_panic` so a release build checks them
+        use crate::decoder::{CommonRequests, CommonResponses};
+        let mut buffer = [0; 8];
+        for bytes in [
+            &[][..],
+            &[1, 2, 3],
+            &[0xFF; 300],
+            &[1, 3, 0, 0, 0, 1, 0x84, 0x0A],
+        ] {
+            let _ = Frame::try_from(bytes);
+            let _ = CommonRequests::try_from(bytes);
+            let _ = CommonResponses::try_from(bytes);
+        }
+        assert!(request::read_coils(&mut buffer, 1, 0, 8).is_ok());
+        assert!(request::read_discrete_inputs(&mut buffer, 1, 0, 8).is_ok());
+        assert!(request::read_holding_registers(&mut buffer[..4], 1, 0, 8).is_err());
+        assert!(request::read_input_registers(&mut buffer, 1, 0, 0).is_err());
+        assert!(request::write_coil(&mut buffer, 1, 0, true).is_ok());
+        assert!(request::write_holding_register(&mut buffer, 1, 0, 5).is_ok());
+        assert!(request::read_exception_status(&mut buffer, 1).is_ok());
+        assert!(request::diagnostic(&mut buffer, 1, 0x0B, 0).is_ok());
+        assert!(request::get_comm_event_counter(&mut buffer, 1).is_ok());
+        assert!(request::get_comm_event_log(&mut buffer[..3], 1).is_err());
+        assert!(request::report_slave_id(&mut buffer, 1).is_ok());
+        let mut large = [0; 256];
+        assert!(request::write_multiple_coils(&mut large, 1, 0, &[true; 10]).is_ok());
+        assert!(request::write_multiple_coils(&mut buffer, 1, 0, &[true; 10]).is_err());
+        assert!(request::write_multiple_holding_registers(&mut large, 1, 0, &[1; 123]).is_ok());
+        assert!(request::write_coils_auto(&mut large, 1, 0, &[true]).is_ok());
+        assert!(request::write_registers_auto(&mut large, 1, 0, &[]).is_err());
+        for bytes in [&[][..], &[1, 2, 3], &buffer] {
+            let frame = Frame::new_unchecked(bytes);
+            let _ = frame.try_payload();
+            let _ = frame.try_pdu();
+            let _ = frame.try_crc_bytes();
+            let _ = frame.try_parts();
+        }
     }
 }
@@ -0,0 +1,320 @@
+//! Correlating responses with requests when a master polls several devices interleaved on one bus
+//!
+//! [`Pending`] remembers the last request sent to each device and matches responses back to it, RTU or ASCII.
+//! Like the gateway it performs no I/O, the application supplies the current time as a free running millisecond
+//! counter.
+//!
+//! ```
+//! use modbus_frames::{master::Pending, request, response};
+//!
+//! let mut pending = Pending::<4>::new(100);
+//! let mut buffer = [0; 8];
+//! let request = request::read_holding_registers(&mut buffer, 5, 0, 1).unwrap();
+//! pending.send(request, 0);
+//!
+//! let (response, _) = response::ReadHoldingRegisters::new(&mut buffer, 5, [7]);
+//! let matched = pending.on_response(response.as_frame()).unwrap();
+//! assert_eq!(matched.device.address(), 5);
+//! assert!(pending.poll(1000).is_none());
+//! ```
+
+use crate::{function, Device, Error, Frame, Function, ModbusFrame};
+
+/// A request waiting on a response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Outstanding {
+    pub device: Device,
+    pub function: Function,
+    /// the first four payload bytes of the request, start/count or index/value for the common functions
+    pub header: [u8; 4],
+    /// time the request was sent
+    pub sent: u32,
+}
+
+impl Outstanding {
+    fn from_request<'f>(request: impl ModbusFrame<'f>, now: u32) -> Self {
+        let mut header = [0; 4];
+        let payload = request.payload();
+        let len = payload.len().min(4);
+        header[..len].copy_from_slice(&payload[..len]);
+        Outstanding {
+            device: request.device(),
+            function: request.function(),
+            header,
+            sent: now,
+        }
+    }
+
+    /// true if `response` is from the device and function of the request and is consistent with it
+    ///
+    /// read byte counts and write echoes are checked for the common functions
+    pub fn matches<'f>(&self, response: impl ModbusFrame<'f>) -> bool {
+        if response.device() != self.device || response.function().0 & 0x7F != self.function.0 {
+            return false;
+        }
+        let payload = response.payload();
+        if response.function().0 & 0x80 != 0 {
+            return payload.len() == 1;
+        }
+        let count = u16::from_be_bytes([self.header[2], self.header[3]]);
+        match self.function {
+            function::READ_COILS | function::READ_DISCRETE_INPUTS => {
+                payload.first().map(|b| u16::from(*b)) == Some(count.div_ceil(8))
+            }
+            function::READ_HOLDING_REGISTERS | function::READ_INPUT_REGISTERS => {
+                payload.first().map(|b| u16::from(*b)) == Some(2 * count)
+            }
+            function::WRITE_COIL
+            | function::WRITE_HOLDING_REGISTER
+            | function::WRITE_MULTIPLE_COILS
+            | function::WRITE_MULTIPLE_HOLDING_REGISTERS => payload == self.header,
+            _ => true,
+        }
+    }
+}
+
+/// A finished request of up to `N` bytes kept to send repeatedly without rebuilding it or recalculating the CRC
+///
+/// ```
+/// use modbus_frames::{master::CachedRequest, request, response};
+///
+/// let mut buffer = [0; 16];
+/// let cached = CachedRequest::<8>::new(request::read_holding_registers(&mut buffer, 5, 0, 2).unwrap()).unwrap();
+/// assert_eq!(cached.response_len(), Some(9));
+///
+/// let (response, _) = response::ReadHoldingRegisters::new(&mut buffer, 5, [1, 2]);
+/// assert!(cached.matches(response.as_frame()));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CachedRequest<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+    outstanding: Outstanding,
+    response_len: Option<usize>,
+}
+
+impl<const N: usize> CachedRequest<N> {
+    /// Copy `request`, `Error::InsufficientBuffer` if it is longer than `N`
+    pub fn new(request: Frame) -> Result<Self, Error> {
+        let raw = request.raw_bytes();
+        let mut bytes = [0; N];
+        bytes
+            .get_mut(..raw.len())
+            .ok_or(Error::InsufficientBuffer {
+                needed: raw.len(),
+                len: N,
+            })?
+            .copy_from_slice(raw);
+        let outstanding = Outstanding::from_request(request, 0);
+        let count = u16::from_be_bytes([outstanding.header[2], outstanding.header[3]]);
+        let response_len = match outstanding.function {
+            // address, function, byte count, data, CRC
+            function::READ_COILS | function::READ_DISCRETE_INPUTS => {
+                Some(5 + usize::from(count.div_ceil(8)))
+            }
+            function::READ_HOLDING_REGISTERS | function::READ_INPUT_REGISTERS => {
+                Some(5 + 2 * usize::from(count))
+            }
+            function::WRITE_COIL
+            | function::WRITE_HOLDING_REGISTER
+            | function::WRITE_MULTIPLE_COILS
+            | function::WRITE_MULTIPLE_HOLDING_REGISTERS => Some(8),
+            _ => None,
+        };
+        Ok(CachedRequest {
+            bytes,
+            len: raw.len(),
+            outstanding,
+            response_len,
+        })
+    }
+
+    /// The request to send
+    pub fn frame(&self) -> Frame<'_> {
+        Frame::new_unchecked(&self.bytes[..self.len])
+    }
+
+    pub fn device(&self) -> Device {
+        self.outstanding.device
+    }
+
+    pub fn function(&self) -> Function {
+        self.outstanding.function
+    }
+
+    /// Length of a normal (not exception) response, `None` if the function doesn't imply it
+    ///
+    /// Lets a receiver stop reading as soon as the response is complete
+    pub fn response_len(&self) -> Option<usize> {
+        self.response_len
+    }
+
+    /// The request as sent at time `now`, e.g. to track it without a [`Pending`]
+    pub fn outstanding(&self, now: u32) -> Outstanding {
+        Outstanding {
+            sent: now,
+            ..self.outstanding
+        }
+    }
+
+    /// true if `response` answers the request, as [`Outstanding::matches`] with the response length also checked
+    pub fn matches(&self, response: Frame) -> bool {
+        let len = response.raw_bytes().len();
+        let expected = match self.response_len {
+            _ if response.function().0 & 0x80 != 0 => Some(5),
+            expected => expected,
+        };
+        expected.is_none_or(|expected| expected == len) && self.outstanding.matches(response)
+    }
+}
+
+/// The last request sent to each of up to `N` devices
+#[derive(Debug, Clone)]
+pub struct Pending<const N: usize> {
+    outstanding: [Option<Outstanding>; N],
+    timeout: u32,
+}
+
+impl<const N: usize> Pending<N> {
+    /// `timeout` in milliseconds applies to every device
+    pub fn new(timeout: u32) -> Self {
+        Pending {
+            outstanding: [None; N],
+            timeout,
+        }
+    }
+
+    pub fn outstanding(&self) -> impl Iterator<Item = &Outstanding> {
+        self.outstanding.iter().flatten()
+    }
+
+    /// true if a request to `device` is waiting on a response
+    pub fn is_waiting(&self, device: Device) -> bool {
+        self.outstanding().any(|o| o.device == device)
+    }
+
+    /// Record `request` as sent at time `now`, broadcasts aren't recorded as they have no response
+    ///
+    /// Returns the request it replaces: the previous request to the same device, or the oldest request if
+    /// `N` devices are already waiting. The replaced request will no longer match a response.
+    pub fn send<'f>(&mut self, request: impl ModbusFrame<'f>, now: u32) -> Option<Outstanding> {
+        if request.device().is_broadcast() {
+            return None;
+        }
+        let outstanding = Outstanding::from_request(request, now);
+        let slot = match self
+            .outstanding
+            .iter()
+            .position(|o| matches!(o, Some(o) if o.device == outstanding.device))
+            .or_else(|| self.outstanding.iter().position(Option::is_none))
+        {
+            Some(idx) => &mut self.outstanding[idx],
+            None => self
+                .outstanding
+                .iter_mut()
+                .max_by_key(|o| o.map(|o| now.wrapping_sub(o.sent)))?,
+        };
+        slot.replace(outstanding)
+    }
+
+    /// Match a response to its request, `None` if no request is waiting on it
+    pub fn on_response<'f>(&mut self, response: impl ModbusFrame<'f>) -> Option<Outstanding> {
+        self.outstanding
+            .iter_mut()
+            .find(|o| matches!(o, Some(o) if o.matches(response)))?
+            .take()
+    }
+
+    /// Remove and return a request which has timed out at time `now`
+    ///
+    /// Call repeatedly until `None` to collect every timed out request
+    pub fn poll(&mut self, now: u32) -> Option<Outstanding> {
+        let timeout = self.timeout;
+        self.outstanding
+            .iter_mut()
+            .find(|o| matches!(o, Some(o) if now.wrapping_sub(o.sent) >= timeout))?
+            .take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CachedRequest, Pending};
+    use crate::{builder, exception, function, request, response, Device, Error};
+
+    #[test]
+    fn correlate_responses() {
+        let mut pending = Pending::<2>::new(100);
+        let mut req = [0; 8];
+        let mut res = [0; 8];
+
+        let request = request::read_coils(&mut req, 1, 0, 9).unwrap();
+        assert_eq!(pending.send(request, u32::MAX - 10), None);
+        let request = request::write_holding_register(&mut req, 2, 4, 5).unwrap();
+        assert_eq!(pending.send(request, 0), None);
+        // replaces the oldest
+        let request = request::read_coils(&mut req, 3, 0, 9).unwrap();
+        let replaced = pending.send(request, 10).unwrap();
+        assert_eq!(replaced.device, Device::new(1));
+        // broadcasts aren't tracked
+        let request = request::read_coils(&mut req, 0, 0, 9).unwrap();
+        assert_eq!(pending.send(request, 10), None);
+        assert!(!pending.is_waiting(Device::new(0)));
+
+        // echo doesn't match the request
+        let (response, _) = response::WriteHoldingRegister::new(&mut res, 2, 4, 6);
+        assert!(pending.on_response(response.as_frame()).is_none());
+        let (response, _) = response::WriteHoldingRegister::new(&mut res, 2, 4, 5);
+        assert!(pending.on_response(response.as_frame()).is_some());
+        assert!(!pending.is_waiting(Device::new(2)));
+
+        // byte count doesn't match the coil count
+        let (response, _) = response::ReadCoils::new(&mut res, 3, [true]);
+        assert!(pending.on_response(response.as_frame()).is_none());
+        let request = request::read_coils(&mut req, 3, 0, 9).unwrap();
+        let (response, _) = request.response_exception(&mut res, exception::DEVICE_BUSY);
+        assert!(pending.on_response(response).is_some());
+
+        let request = request::read_coils(&mut req, 4, 0, 9).unwrap();
+        pending.send(request, 20);
+        assert!(pending.poll(119).is_none());
+        assert_eq!(pending.poll(120).unwrap().device, Device::new(4));
+    }
+
+    #[test]
+    fn cached_request() {
+        let mut req = [0; 16];
+        let mut res = [0; 16];
+        let request = request::read_coils(&mut req, 3, 0, 9).unwrap();
+        let cached = CachedRequest::<8>::new(request).unwrap();
+        assert_eq!(cached.frame(), request);
+        assert_eq!(cached.device(), Device::new(3));
+        assert_eq!(cached.function(), function::READ_COILS);
+        assert_eq!(cached.response_len(), Some(7));
+        assert_eq!(cached.outstanding(20).sent, 20);
+
+        let (response, _) = response::ReadCoils::new(&mut res, 3, [true; 9]);
+        assert!(cached.matches(response.as_frame()));
+        // the byte count is right but there's an extra data byte
+        let (response, _) = builder::build_frame(&mut res)
+            .for_address(3)
+            .function(function::READ_COILS)
+            .bytes([2, 0, 0, 0])
+            .finalise();
+        assert!(!cached.matches(response));
+        let (response, _) = request.response_exception(&mut res, exception::DEVICE_BUSY);
+        assert!(cached.matches(response));
+
+        let request =
+            request::write_multiple_holding_registers(&mut req, 3, 0, &[1, 2, 3]).unwrap();
+        assert_eq!(
+            CachedRequest::<8>::new(request),
+            Err(Error::InsufficientBuffer { needed: 15, len: 8 })
+        );
+        let cached = CachedRequest::<16>::new(request).unwrap();
+        assert_eq!(cached.frame(), request);
+        assert_eq!(cached.response_len(), Some(8));
+    }
+}
@@ -0,0 +1,582 @@
+//! Split bytes captured from a tapped bus into frames
+//!
+//! A bus monitor sees both sides of every exchange with no framing other than timing. [`Monitor`] recovers the
+//! frames from a capture buffer using the length implied by the function code where it is known and scanning for a
+//! valid CRC where it isn't. Bytes that don't belong to any frame are skipped and reported so the monitor can
+//! resynchronise after noise or a partial capture.
+//!
+//! ```
+//! use modbus_frames::{builder, function, monitor::{Event, Monitor}};
+//!
+//! let mut buf = [0; 64];
+//! let (request, rem) = builder::build_frame(&mut buf)
+//!     .for_address(0x11)
+//!     .function(function::READ_HOLDING_REGISTERS)
+//!     .registers([0x6B, 1])
+//!     .finalise();
+//! let (response, _) = builder::build_frame(rem)
+//!     .for_address(0x11)
+//!     .function(function::READ_HOLDING_REGISTERS)
+//!     .byte(2)
+//!     .register(0x1234)
+//!     .finalise();
+//!
+//! let capture = [request.raw_bytes(), response.raw_bytes()].concat();
+//! let mut monitor = Monitor::new(&capture);
+//! assert_eq!(monitor.next(), Some(Event::Request(request)));
+//! assert_eq!(monitor.next(), Some(Event::Response { request: Some(request), response }));
+//! assert_eq!(monitor.next(), None);
+//! ```
+
+use core::ops::Range;
+
+use crate::{crc, function, verify_crc16, Frame, Function};
+
+/// largest frame allowed by the RTU format
+const MAX_FRAME_LEN: usize = 256;
+
+/// A single item recovered from the captured bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event<'b> {
+    /// A frame sent by the master
+    Request(Frame<'b>),
+    /// A frame sent by a slave, paired with the request it answers if that was seen
+    Response {
+        request: Option<Frame<'b>>,
+        response: Frame<'b>,
+    },
+    /// Bytes which could not be decoded as part of any frame
+    Garbage(&'b [u8]),
+}
+
+/// Iterates over the frames in a buffer of bytes captured from the bus
+#[derive(Debug, Clone)]
+pub struct Monitor<'b> {
+    bytes: &'b [u8],
+    /// last request seen that is still waiting for a response
+    pending: Option<Frame<'b>>,
+}
+
+impl<'b> Monitor<'b> {
+    pub fn new(bytes: &'b [u8]) -> Self {
+        Monitor {
+            bytes,
+            pending: None,
+        }
+    }
+
+    /// bytes not yet consumed by the iterator
+    pub fn remaining(&self) -> &'b [u8] {
+        self.bytes
+    }
+
+    /// true if `frame` is an answer (normal or exception) to the pending request
+    fn answers_pending(&self, bytes: &[u8]) -> bool {
+        match self.pending {
+            Some(request) => {
+                request.address() == bytes[0] && request.function().0 == bytes[1] & 0x7F
+            }
+            None => false,
+        }
+    }
+
+    /// attempt to find a valid frame at the start of `bytes`, returning it's length and direction
+    fn frame_at(&self, bytes: &[u8]) -> Option<(usize, Direction)> {
+        if bytes.len() < 4 {
+            return None;
+        }
+        let expect_response = self.answers_pending(bytes);
+        let (first, second): (LenFn, LenFn) = if expect_response {
+            (response_len, request_len)
+        } else {
+            (request_len, response_len)
+        };
+        let fits = |len: &usize| *len <= bytes.len() && verify_crc16(&bytes[..*len]);
+        let len = first(bytes)
+            .filter(fits)
+            .or_else(|| second(bytes).filter(fits))
+            // unknown function code, look for any length with a valid CRC
+            .or_else(|| scan_crc(bytes))?;
+
+        let direction = match classify(Frame::new_unchecked(&bytes[..len])) {
+            Some(Classification::Request) => Direction::Request,
+            Some(Classification::Response) => Direction::Response,
+            // can't tell from the frame alone, assume it belongs with the previous request if possible
+            _ if expect_response => Direction::Response,
+            _ => Direction::Request,
+        };
+        Some((len, direction))
+    }
+}
+
+type LenFn = fn(&[u8]) -> Option<usize>;
+
+/// Direction a frame was sent in, as far as can be told from the frame alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Classification {
+    /// Only valid as a request
+    Request,
+    /// Only valid as a response
+    Response,
+    /// Valid as either, e.g. the echoed response to a single write or an unknown function code
+    Either,
+}
+
+/// Determine whether a frame is a request or response using the function code and payload structure
+///
+/// Returns `None` if the frame is a known function code but is not structurally valid in either direction.
+/// Frames with an unknown function code are classified as [`Classification::Either`]
+///
+/// ```
+/// use modbus_frames::{request, monitor::{classify, Classification}};
+///
+/// let mut buf = [0; 16];
+/// let (write, _) = request::WriteHoldingRegister::new(&mut buf, 0x11, 1, 3);
+/// // the response to a single register write is an echo of the request
+/// assert_eq!(classify(write.as_frame()), Some(Classification::Either));
+/// ```
+pub fn classify(frame: Frame) -> Option<Classification> {
+    let bytes = frame.raw_bytes();
+    let request = request_len(bytes);
+    let response = response_len(bytes);
+    if request.is_none() && response.is_none() {
+        return Some(Classification::Either);
+    }
+    match (request == Some(bytes.len()), response == Some(bytes.len())) {
+        (true, true) => Some(Classification::Either),
+        (true, false) => Some(Classification::Request),
+        (false, true) => Some(Classification::Response),
+        (false, false) => None,
+    }
+}
+
+/// Which side of the exchange a frame was sent by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Request,
+    Response,
+}
+
+impl<'b> Iterator for Monitor<'b> {
+    type Item = Event<'b>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+
+        let found = (0..self.bytes.len())
+            .find_map(|skip| self.frame_at(&self.bytes[skip..]).map(|f| (skip, f)));
+        let (skip, (len, direction)) = match found {
+            Some(found) => found,
+            None => {
+                let garbage = self.bytes;
+                self.bytes = &[];
+                return Some(Event::Garbage(garbage));
+            }
+        };
+        if skip > 0 {
+            let (garbage, rest) = self.bytes.split_at(skip);
+            self.bytes = rest;
+            return Some(Event::Garbage(garbage));
+        }
+
+        let (frame, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        let frame = Frame::new_unchecked(frame);
+        match direction {
+            Direction::Request => {
+                // broadcasts are never answered
+                self.pending = Some(frame).filter(|f| f.address() != 0);
+                Some(Event::Request(frame))
+            }
+            Direction::Response => Some(Event::Response {
+                request: self.pending.take(),
+                response: frame,
+            }),
+        }
+    }
+}
+
+/// The first valid frame in `bytes` and the bytes following it, for receive paths handing over a frame plus noise
+///
+/// Each start position is tried in turn, checking the CRC at the lengths implied by the function code and falling
+/// back to scanning every length for unknown function codes. Bytes before the frame are discarded.
+/// ```
+/// use modbus_frames::{monitor, request};
+///
+/// let mut buffer = [0; 8];
+/// let request = request::read_coils(&mut buffer, 1, 0, 8).unwrap();
+/// let received = [&[0xFF, 0x00][..], request.raw_bytes(), &[0x42]].concat();
+/// assert_eq!(monitor::decode_search(&received), Some((request, &[0x42][..])));
+/// ```
+pub fn decode_search(bytes: &[u8]) -> Option<(Frame<'_>, &[u8])> {
+    (0..bytes.len().saturating_sub(3)).find_map(|start| {
+        let bytes = &bytes[start..];
+        let fits = |len: &usize| *len <= bytes.len() && verify_crc16(&bytes[..*len]);
+        let len = request_len(bytes)
+            .filter(fits)
+            .or_else(|| response_len(bytes).filter(fits))
+            .or_else(|| scan_crc(bytes))?;
+        let (frame, rest) = bytes.split_at(len);
+        Some((Frame::new_unchecked(frame), rest))
+    })
+}
+
+/// Every valid frame in `bytes` with its byte range, for buffers holding several back to back frames
+///
+/// Repeats [`decode_search`] from the end of each frame, noise between frames is skipped.
+/// ```
+/// use modbus_frames::{monitor, request};
+///
+/// let mut a = [0; 8];
+/// let a = request::read_coils(&mut a, 1, 0, 8).unwrap();
+/// let mut b = [0; 8];
+/// let b = request::write_coil(&mut b, 2, 3, true).unwrap();
+/// let received = [a.raw_bytes(), &[0xFF], b.raw_bytes()].concat();
+/// let frames = monitor::frames(&received).collect::<Vec<_>>();
+/// assert_eq!(frames, [(0..8, a), (9..17, b)]);
+/// ```
+pub fn frames(bytes: &[u8]) -> Frames<'_> {
+    Frames { bytes, offset: 0 }
+}
+
+/// Iterator returned by [`frames`]
+#[derive(Debug, Clone)]
+pub struct Frames<'b> {
+    bytes: &'b [u8],
+    offset: usize,
+}
+
+impl<'b> Iterator for Frames<'b> {
+    type Item = (Range<usize>, Frame<'b>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let remaining = &self.bytes[self.offset..];
+        let Some((frame, rest)) = decode_search(remaining) else {
+            self.offset = self.bytes.len();
+            return None;
+        };
+        let end = self.bytes.len() - rest.len();
+        let range = end - frame.raw_bytes().len()..end;
+        self.offset = end;
+        Some((range, frame))
+    }
+}
+
+/// find the shortest prefix of `bytes` ending with a valid CRC
+fn scan_crc(bytes: &[u8]) -> Option<usize> {
+    // running CRC to avoid recalculating the whole prefix at every length
+    let mut crc = crc::update(crc::INIT, &bytes[..2]);
+    for len in 4..=bytes.len().min(MAX_FRAME_LEN) {
+        if crc.to_le_bytes() == bytes[len - 2..len] {
+            return Some(len);
+        }
+        crc = crc::update(crc, &bytes[len - 2..len - 1]);
+    }
+    None
+}
+
+/// How much of a frame the function code and the bytes so far tell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Size {
+    /// the length of the whole frame
+    Known(usize),
+    /// more bytes are needed to tell
+    More,
+    /// the length isn't implied by the function code
+    Unknown,
+}
+
+impl Size {
+    /// the length from a byte count at `idx`, `header` being the bytes around it that it doesn't count
+    fn count_at(bytes: &[u8], idx: usize, header: usize) -> Size {
+        bytes
+            .get(idx)
+            .map_or(Size::More, |&n| Size::Known(header + usize::from(n)))
+    }
+
+    fn len(self) -> Option<usize> {
+        match self {
+            Size::Known(len) => Some(len),
+            Size::More | Size::Unknown => None,
+        }
+    }
+}
+
+/// Length of the request frame at the start of `bytes` as implied by the function code
+///
+/// `None` if the function code is not known or there are not enough bytes to tell. Diagnostics (0x08) requests are
+/// taken to have the 2 data bytes of every sub-function other than Return Query Data.
+pub fn request_len(bytes: &[u8]) -> Option<usize> {
+    request_size(bytes).len()
+}
+
+pub(crate) fn request_size(bytes: &[u8]) -> Size {
+    let Some(&function) = bytes.get(1) else {
+        return Size::More;
+    };
+    match Function(function) {
+        function::READ_EXCEPTION_STATUS
+        | function::GET_COMM_EVENT_COUNTER
+        | function::GET_COMM_EVENT_LOG
+        | function::REPORT_SLAVE_ID => Size::Known(4),
+        function::READ_FIFO_QUEUE => Size::Known(6),
+        function::READ_COILS
+        | function::READ_DISCRETE_INPUTS
+        | function::READ_HOLDING_REGISTERS
+        | function::READ_INPUT_REGISTERS
+        | function::WRITE_COIL
+        | function::WRITE_HOLDING_REGISTER
+        | function::DIAGNOSTIC => Size::Known(8),
+        function::MASK_WRITE_REGISTER => Size::Known(10),
+        // header(6) + byte count(1) + values(n) + crc(2)
+        function::WRITE_MULTIPLE_COILS | function::WRITE_MULTIPLE_HOLDING_REGISTERS => {
+            Size::count_at(bytes, 6, 9)
+        }
+        // header(2) + byte count(1) + sub-requests(n) + crc(2)
+        function::READ_FILE_RECORD | function::WRITE_FILE_RECORD => Size::count_at(bytes, 2, 5),
+        // header(10) + byte count(1) + values(n) + crc(2)
+        function::READ_WRITE_MULTIPLE_REGISTERS => Size::count_at(bytes, 10, 13),
+        function::ENCAPSULATED_INTERFACE_TRANSPORT => match bytes.get(2) {
+            None => Size::More,
+            // header(2) + MEI type, read device id code, object id(3) + crc(2)
+            Some(&function::MEI_READ_DEVICE_IDENTIFICATION) => Size::Known(7),
+            Some(_) => Size::Unknown,
+        },
+        _ => Size::Unknown,
+    }
+}
+
+/// Length of the response frame at the start of `bytes` as implied by the function code
+///
+/// `None` if the function code is not known or there are not enough bytes to tell. Diagnostics (0x08) responses are
+/// taken to have the 2 data bytes of every sub-function other than Return Query Data.
+pub fn response_len(bytes: &[u8]) -> Option<usize> {
+    response_size(bytes).len()
+}
+
+pub(crate) fn response_size(bytes: &[u8]) -> Size {
+    let Some(&function) = bytes.get(1) else {
+        return Size::More;
+    };
+    if function & 0x80 != 0 {
+        // address(1) + function(1) + exception(1) + crc(2)
+        return Size::Known(5);
+    }
+    match Function(function) {
+        function::READ_EXCEPTION_STATUS => Size::Known(5),
+        function::WRITE_COIL
+        | function::WRITE_HOLDING_REGISTER
+        | function::WRITE_MULTIPLE_COILS
+        | function::WRITE_MULTIPLE_HOLDING_REGISTERS
+        | function::DIAGNOSTIC
+        | function::GET_COMM_EVENT_COUNTER => Size::Known(8),
+        function::MASK_WRITE_REGISTER => Size::Known(10),
+        // header(2) + byte count(1) + values(n) + crc(2)
+        function::READ_COILS
+        | function::READ_DISCRETE_INPUTS
+        | function::READ_HOLDING_REGISTERS
+        | function::READ_INPUT_REGISTERS
+        | function::GET_COMM_EVENT_LOG
+        | function::REPORT_SLAVE_ID
+        | function::READ_FILE_RECORD
+        | function::WRITE_FILE_RECORD
+        | function::READ_WRITE_MULTIPLE_REGISTERS => Size::count_at(bytes, 2, 5),
+        // header(2) + byte count(2) + FIFO count and values(n) + crc(2)
+        function::READ_FIFO_QUEUE => match bytes.get(2..4) {
+            Some(&[hi, lo]) => Size::Known(6 + usize::from(u16::from_be_bytes([hi, lo]))),
+            _ => Size::More,
+        },
+        function::ENCAPSULATED_INTERFACE_TRANSPORT => match bytes.get(2) {
+            None => Size::More,
+            Some(&function::MEI_READ_DEVICE_IDENTIFICATION) => identity_response_size(bytes),
+            Some(_) => Size::Unknown,
+        },
+        _ => Size::Unknown,
+    }
+}
+
+/// header(2) + MEI type, read device id code, conformity, more follows, next object, object count(6) + objects +
+/// crc(2), each object being its id, length and value
+fn identity_response_size(bytes: &[u8]) -> Size {
+    let Some(&count) = bytes.get(7) else {
+        return Size::More;
+    };
+    let mut len = 8;
+    for _ in 0..count {
+        match bytes.get(len + 1) {
+            Some(&object_len) => len += 2 + usize::from(object_len),
+            None => return Size::More,
+        }
+    }
+    Size::Known(len + 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, Classification, Event, Monitor};
+    use crate::{builder, exception, function, request, response, Function};
+
+    #[test]
+    fn resynchronises_after_garbage() {
+        let mut buf = [0; 256];
+        let (request, rem) =
+            request::WriteMultipleHoldingRegisters::new(&mut buf, 0x11, 1, [0xA, 0x102]);
+        let (response, rem) = response::WriteMultipleHoldingRegisters::new(rem, 0x11, 1, 2);
+        let (broadcast, rem) = request::WriteHoldingRegister::new(rem, 0, 1, 3);
+        let (exception, _) = builder::build_frame(rem)
+            .for_address(0x11)
+            .exception(function::READ_COILS, exception::ILLEGAL_ADDRESS);
+
+        let mut capture = vec![0xFF, 0x00];
+        capture.extend_from_slice(request.as_frame().raw_bytes());
+        capture.extend_from_slice(response.as_frame().raw_bytes());
+        capture.extend_from_slice(&[0x12, 0x34, 0x56]);
+        capture.extend_from_slice(broadcast.as_frame().raw_bytes());
+        capture.extend_from_slice(exception.raw_bytes());
+
+        let events = Monitor::new(&capture).collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                Event::Garbage(&[0xFF, 0x00]),
+                Event::Request(request.as_frame()),
+                Event::Response {
+                    request: Some(request.as_frame()),
+                    response: response.as_frame()
+                },
+                Event::Garbage(&[0x12, 0x34, 0x56]),
+                Event::Request(broadcast.as_frame()),
+                Event::Response {
+                    request: None,
+                    response: exception
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn echoed_writes_pair_up() {
+        let mut buf = [0; 64];
+        let (request, rem) = request::WriteCoil::new(&mut buf, 3, 0xAC, crate::COIL_ON);
+        let (response, rem) = request.response_builder(rem);
+        let (unknown, _) = builder::build_frame(rem)
+            .for_address(3)
+            .function(Function(0x41))
+            .bytes([1, 2, 3])
+            .finalise();
+
+        let capture = [
+            request.as_frame().raw_bytes(),
+            response.as_frame().raw_bytes(),
+            unknown.raw_bytes(),
+        ]
+        .concat();
+        let events = Monitor::new(&capture).collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                Event::Request(request.as_frame()),
+                Event::Response {
+                    request: Some(request.as_frame()),
+                    response: response.as_frame()
+                },
+                Event::Request(unknown),
+            ]
+        );
+    }
+
+    #[test]
+    fn classification() {
+        let mut buf = [0; 64];
+        let (read, rem) = request::ReadHoldingRegisters::new(&mut buf, 0x11, 0x6B, 3);
+        let (registers, rem) = read.response_builder(rem, [1, 2, 3]);
+        let (coils, rem) = response::ReadCoils::new(rem, 0x11, [true; 24]);
+        let (exception, rem) = read.response_exception(rem, exception::DEVICE_BUSY);
+        let (truncated, _) = builder::build_frame(rem)
+            .for_address(0x11)
+            .function(function::READ_HOLDING_REGISTERS)
+            .byte(6)
+            .register(1)
+            .finalise();
+
+        assert_eq!(classify(read.as_frame()), Some(Classification::Request));
+        assert_eq!(
+            classify(registers.as_frame()),
+            Some(Classification::Response)
+        );
+        // 3 bytes of coils is the same length as a request
+        assert_eq!(classify(coils.as_frame()), Some(Classification::Either));
+        assert_eq!(classify(exception), Some(Classification::Response));
+        assert_eq!(classify(truncated), None);
+    }
+
+    #[test]
+    fn search() {
+        let mut buf = [0; 16];
+        let (response, _) = response::ReadHoldingRegisters::new(&mut buf, 0x11, [1, 2]);
+        let response = response.as_frame();
+        let received = [&[0x03, 0x11][..], response.raw_bytes()].concat();
+        assert_eq!(super::decode_search(&received), Some((response, &[][..])));
+        assert_eq!(super::decode_search(&received[..8]), None);
+        assert_eq!(super::decode_search(&[]), None);
+
+        let received = [response.raw_bytes(), response.raw_bytes(), &[0, 1]].concat();
+        let mut frames = super::frames(&received);
+        assert_eq!(frames.next(), Some((0..9, response)));
+        assert_eq!(frames.next(), Some((9..18, response)));
+        assert_eq!(frames.next(), None);
+    }
+
+    #[test]
+    fn standard_lengths() {
+        use super::{request_len, response_len};
+
+        type Build = fn(&mut [u8]) -> Result<crate::Frame<'_>, crate::Error>;
+        let mut buf = [0; 32];
+        let requests: [Build; 9] = [
+            |buf| request::read_exception_status(buf, 1),
+            |buf| request::diagnostic(buf, 1, 0x0B, 0),
+            |buf| request::get_comm_event_counter(buf, 1),
+            |buf| request::get_comm_event_log(buf, 1),
+            |buf| request::report_slave_id(buf, 1),
+            |buf| request::mask_write_register(buf, 1, 4, 0xF2, 0x25),
+            |buf| request::read_write_multiple_registers(buf, 1, 0, 2, 4, &[5, 6]),
+            |buf| request::read_fifo_queue(buf, 1, 4),
+            |buf| request::read_device_identification(buf, 1, 1, 0),
+        ];
+        for build in requests {
+            let frame = build(&mut buf).unwrap();
+            let bytes = frame.raw_bytes();
+            assert_eq!(request_len(bytes), Some(bytes.len()), "{frame}");
+            // no more of the frame is needed than the header
+            assert_eq!(
+                request_len(&bytes[..bytes.len() - 2]),
+                Some(bytes.len()),
+                "{frame}"
+            );
+        }
+
+        // FIFO count 2, two values
+        let (fifo, rem) = builder::build_frame(&mut buf)
+            .for_address(1)
+            .function(function::READ_FIFO_QUEUE)
+            .registers([6, 2, 0x1B8, 0x1284])
+            .finalise();
+        assert_eq!(response_len(fifo.raw_bytes()), Some(12));
+        assert_eq!(response_len(&fifo.raw_bytes()[..3]), None);
+        // two objects of 1 and 2 bytes
+        let (identity, _) = builder::build_frame(rem)
+            .for_address(1)
+            .function(function::ENCAPSULATED_INTERFACE_TRANSPORT)
+            .bytes([0x0E, 1, 0x01, 0, 0, 2, 0, 1, b'A', 1, 2, b'F', b'M'])
+            .finalise();
+        assert_eq!(response_len(identity.raw_bytes()), Some(17));
+        assert_eq!(response_len(&identity.raw_bytes()[..10]), None);
+        assert_eq!(response_len(&[1, 0x2B, 0x0D]), None);
+        assert_eq!(request_len(&[1, 0x41]), None);
+    }
+}
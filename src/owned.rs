@@ -0,0 +1,436 @@
+//! Requests as plain values
+//!
+//! The `request::` wrappers borrow the frame bytes they were decoded from. The types here copy the fields out
+//! instead, so they can be stored, passed between tasks and compared without a buffer or lifetime. `encode_into`
+//! writes the request into a buffer, `decode` reads one back from a frame, and each converts from the matching
+//! zero-copy wrapper.
+//!
+//! The multiple writes carry exactly `N` values, the count is checked against `N` when decoding.
+//!
+//! ```
+//! use modbus_frames::{owned::ReadHoldingRegistersReq, request};
+//!
+//! let request = ReadHoldingRegistersReq {
+//!     address: 0x11,
+//!     start_index: 0x6B,
+//!     count: 3,
+//! };
+//! let mut buf = [0; 8];
+//! let frame = request.encode_into(&mut buf).unwrap();
+//! assert_eq!(frame.raw_bytes(), [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87]);
+//! assert_eq!(ReadHoldingRegistersReq::decode(frame), Ok(request));
+//!
+//! let wrapper = request::ReadHoldingRegisters::try_from(frame).unwrap();
+//! assert_eq!(ReadHoldingRegistersReq::from(wrapper), request);
+//! ```
+
+use crate::{request, Error, Frame};
+
+/// Read Coils (0x01)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReadCoilsReq {
+    pub address: u8,
+    pub start_index: u16,
+    pub count: u16,
+}
+
+impl ReadCoilsReq {
+    pub fn encode_into<'b>(&self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        request::read_coils(buffer, self.address, self.start_index, self.count)
+    }
+
+    /// As `encode_into`, returning the zero-copy wrapper
+    pub fn encode_request<'b>(
+        &self,
+        buffer: &'b mut [u8],
+    ) -> Result<request::ReadCoils<'b>, Error> {
+        self.encode_into(buffer)
+            .map(request::ReadCoils::from_frame_unchecked)
+    }
+
+    pub fn decode(frame: Frame) -> Result<Self, Error> {
+        request::ReadCoils::try_from(frame).map(Self::from)
+    }
+}
+
+impl From<request::ReadCoils<'_>> for ReadCoilsReq {
+    fn from(request: request::ReadCoils<'_>) -> Self {
+        ReadCoilsReq {
+            address: request.as_frame().address(),
+            start_index: request.start_index(),
+            count: request.coil_count(),
+        }
+    }
+}
+
+/// Read Discrete Inputs (0x02)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReadDiscreteInputsReq {
+    pub address: u8,
+    pub start_index: u16,
+    pub count: u16,
+}
+
+impl ReadDiscreteInputsReq {
+    pub fn encode_into<'b>(&self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        request::read_discrete_inputs(buffer, self.address, self.start_index, self.count)
+    }
+
+    /// As `encode_into`, returning the zero-copy wrapper
+    pub fn encode_request<'b>(
+        &self,
+        buffer: &'b mut [u8],
+    ) -> Result<request::ReadDiscreteInputs<'b>, Error> {
+        self.encode_into(buffer)
+            .map(request::ReadDiscreteInputs::from_frame_unchecked)
+    }
+
+    pub fn decode(frame: Frame) -> Result<Self, Error> {
+        request::ReadDiscreteInputs::try_from(frame).map(Self::from)
+    }
+}
+
+impl From<request::ReadDiscreteInputs<'_>> for ReadDiscreteInputsReq {
+    fn from(request: request::ReadDiscreteInputs<'_>) -> Self {
+        ReadDiscreteInputsReq {
+            address: request.as_frame().address(),
+            start_index: request.start_index(),
+            count: request.input_count(),
+        }
+    }
+}
+
+/// Read Holding Registers (0x03)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReadHoldingRegistersReq {
+    pub address: u8,
+    pub start_index: u16,
+    pub count: u16,
+}
+
+impl ReadHoldingRegistersReq {
+    pub fn encode_into<'b>(&self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        request::read_holding_registers(buffer, self.address, self.start_index, self.count)
+    }
+
+    /// As `encode_into`, returning the zero-copy wrapper
+    pub fn encode_request<'b>(
+        &self,
+        buffer: &'b mut [u8],
+    ) -> Result<request::ReadHoldingRegisters<'b>, Error> {
+        self.encode_into(buffer)
+            .map(request::ReadHoldingRegisters::from_frame_unchecked)
+    }
+
+    pub fn decode(frame: Frame) -> Result<Self, Error> {
+        request::ReadHoldingRegisters::try_from(frame).map(Self::from)
+    }
+}
+
+impl From<request::ReadHoldingRegisters<'_>> for ReadHoldingRegistersReq {
+    fn from(request: request::ReadHoldingRegisters<'_>) -> Self {
+        ReadHoldingRegistersReq {
+            address: request.as_frame().address(),
+            start_index: request.start_index(),
+            count: request.register_count(),
+        }
+    }
+}
+
+/// Read Input Registers (0x04)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReadInputRegistersReq {
+    pub address: u8,
+    pub start_index: u16,
+    pub count: u16,
+}
+
+impl ReadInputRegistersReq {
+    pub fn encode_into<'b>(&self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        request::read_input_registers(buffer, self.address, self.start_index, self.count)
+    }
+
+    /// As `encode_into`, returning the zero-copy wrapper
+    pub fn encode_request<'b>(
+        &self,
+        buffer: &'b mut [u8],
+    ) -> Result<request::ReadInputRegisters<'b>, Error> {
+        self.encode_into(buffer)
+            .map(request::ReadInputRegisters::from_frame_unchecked)
+    }
+
+    pub fn decode(frame: Frame) -> Result<Self, Error> {
+        request::ReadInputRegisters::try_from(frame).map(Self::from)
+    }
+}
+
+impl From<request::ReadInputRegisters<'_>> for ReadInputRegistersReq {
+    fn from(request: request::ReadInputRegisters<'_>) -> Self {
+        ReadInputRegistersReq {
+            address: request.as_frame().address(),
+            start_index: request.start_index(),
+            count: request.register_count(),
+        }
+    }
+}
+
+/// Write Single Coil (0x05)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WriteCoilReq {
+    pub address: u8,
+    pub index: u16,
+    pub on: bool,
+}
+
+impl WriteCoilReq {
+    pub fn encode_into<'b>(&self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        request::write_coil(buffer, self.address, self.index, self.on)
+    }
+
+    /// As `encode_into`, returning the zero-copy wrapper
+    pub fn encode_request<'b>(
+        &self,
+        buffer: &'b mut [u8],
+    ) -> Result<request::WriteCoil<'b>, Error> {
+        self.encode_into(buffer)
+            .map(request::WriteCoil::from_frame_unchecked)
+    }
+
+    /// A coil value other than `COIL_ON` or `COIL_OFF` is `Error::InvalidValue`
+    pub fn decode(frame: Frame) -> Result<Self, Error> {
+        request::WriteCoil::try_from(frame)?.try_into()
+    }
+}
+
+impl TryFrom<request::WriteCoil<'_>> for WriteCoilReq {
+    type Error = Error;
+
+    fn try_from(request: request::WriteCoil<'_>) -> Result<Self, Error> {
+        Ok(WriteCoilReq {
+            address: request.as_frame().address(),
+            index: request.index(),
+            on: request.coil_state().map_err(|_| Error::InvalidValue)?,
+        })
+    }
+}
+
+/// Write Single Holding Register (0x06)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WriteHoldingRegisterReq {
+    pub address: u8,
+    pub index: u16,
+    pub value: u16,
+}
+
+impl WriteHoldingRegisterReq {
+    pub fn encode_into<'b>(&self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        request::write_holding_register(buffer, self.address, self.index, self.value)
+    }
+
+    /// As `encode_into`, returning the zero-copy wrapper
+    pub fn encode_request<'b>(
+        &self,
+        buffer: &'b mut [u8],
+    ) -> Result<request::WriteHoldingRegister<'b>, Error> {
+        self.encode_into(buffer)
+            .map(request::WriteHoldingRegister::from_frame_unchecked)
+    }
+
+    pub fn decode(frame: Frame) -> Result<Self, Error> {
+        request::WriteHoldingRegister::try_from(frame).map(Self::from)
+    }
+}
+
+impl From<request::WriteHoldingRegister<'_>> for WriteHoldingRegisterReq {
+    fn from(request: request::WriteHoldingRegister<'_>) -> Self {
+        WriteHoldingRegisterReq {
+            address: request.as_frame().address(),
+            index: request.index(),
+            value: request.value(),
+        }
+    }
+}
+
+/// Write Multiple Coils (0x0F) of exactly `N` coils
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WriteMultipleCoilsReq<const N: usize> {
+    pub address: u8,
+    pub start_index: u16,
+    pub coils: [bool; N],
+}
+
+impl<const N: usize> WriteMultipleCoilsReq<N> {
+    pub fn encode_into<'b>(&self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        request::write_multiple_coils(buffer, self.address, self.start_index, &self.coils)
+    }
+
+    /// As `encode_into`, returning the zero-copy wrapper
+    pub fn encode_request<'b>(
+        &self,
+        buffer: &'b mut [u8],
+    ) -> Result<request::WriteMultipleCoils<'b>, Error> {
+        self.encode_into(buffer)
+            .map(request::WriteMultipleCoils::from_frame_unchecked)
+    }
+
+    /// A request for other than `N` coils is `Error::InvalidQuantity`
+    pub fn decode(frame: Frame) -> Result<Self, Error> {
+        request::WriteMultipleCoils::try_from(frame)?.try_into()
+    }
+}
+
+impl<const N: usize> TryFrom<request::WriteMultipleCoils<'_>> for WriteMultipleCoilsReq<N> {
+    type Error = Error;
+
+    fn try_from(request: request::WriteMultipleCoils<'_>) -> Result<Self, Error> {
+        if usize::from(request.coil_count()) != N
+            || usize::from(request.payload_len()) != N.div_ceil(8)
+        {
+            return Err(Error::InvalidQuantity);
+        }
+        let mut coils = [false; N];
+        for (coil, (_, on)) in coils.iter_mut().zip(request.iter_coils()) {
+            *coil = on;
+        }
+        Ok(WriteMultipleCoilsReq {
+            address: request.as_frame().address(),
+            start_index: request.start_index(),
+            coils,
+        })
+    }
+}
+
+/// Write Multiple Holding Registers (0x10) of exactly `N` registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WriteMultipleHoldingRegistersReq<const N: usize> {
+    pub address: u8,
+    pub start_index: u16,
+    pub registers: [u16; N],
+}
+
+impl<const N: usize> WriteMultipleHoldingRegistersReq<N> {
+    pub fn encode_into<'b>(&self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        request::write_multiple_holding_registers(
+            buffer,
+            self.address,
+            self.start_index,
+            &self.registers,
+        )
+    }
+
+    /// As `encode_into`, returning the zero-copy wrapper
+    pub fn encode_request<'b>(
+        &self,
+        buffer: &'b mut [u8],
+    ) -> Result<request::WriteMultipleHoldingRegisters<'b>, Error> {
+        self.encode_into(buffer)
+            .map(request::WriteMultipleHoldingRegisters::from_frame_unchecked)
+    }
+
+    /// A request for other than `N` registers is `Error::InvalidQuantity`
+    pub fn decode(frame: Frame) -> Result<Self, Error> {
+        request::WriteMultipleHoldingRegisters::try_from(frame)?.try_into()
+    }
+}
+
+impl<const N: usize> TryFrom<request::WriteMultipleHoldingRegisters<'_>>
+    for WriteMultipleHoldingRegistersReq<N>
+{
+    type Error = Error;
+
+    fn try_from(request: request::WriteMultipleHoldingRegisters<'_>) -> Result<Self, Error> {
+        if usize::from(request.register_count()) != N
+            || request.payload_as_registers_unaligned().len() != N
+        {
+            return Err(Error::InvalidQuantity);
+        }
+        let mut registers = [0; N];
+        for (register, value) in registers.iter_mut().zip(request.iter_registers()) {
+            *register = value;
+        }
+        Ok(WriteMultipleHoldingRegistersReq {
+            address: request.as_frame().address(),
+            start_index: request.start_index(),
+            registers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn round_trip() {
+        let mut buf = [0; 32];
+
+        let read = ReadCoilsReq {
+            address: 1,
+            start_index: 2,
+            count: 3,
+        };
+        let frame = read.encode_into(&mut buf).unwrap();
+        assert_eq!(ReadCoilsReq::decode(frame), Ok(read));
+        assert!(ReadDiscreteInputsReq::decode(frame).is_err());
+        let read = ReadInputRegistersReq {
+            address: 1,
+            start_index: 2,
+            count: 126,
+        };
+        assert_eq!(read.encode_into(&mut buf), Err(Error::InvalidQuantity));
+
+        let write = WriteCoilReq {
+            address: 1,
+            index: 4,
+            on: true,
+        };
+        let wrapper = write.encode_request(&mut buf).unwrap();
+        assert_eq!(WriteCoilReq::try_from(wrapper), Ok(write));
+        // not a valid coil value
+        let (frame, _) = crate::builder::build_frame(&mut buf)
+            .for_address(1)
+            .function(crate::function::WRITE_COIL)
+            .registers([4, 0x1234])
+            .finalise();
+        assert_eq!(WriteCoilReq::decode(frame), Err(Error::InvalidValue));
+
+        let write = WriteMultipleCoilsReq {
+            address: 1,
+            start_index: 8,
+            coils: [true, false, true, true, false, false, false, false, true],
+        };
+        let frame = write.encode_into(&mut buf).unwrap();
+        assert_eq!(WriteMultipleCoilsReq::decode(frame), Ok(write));
+        assert_eq!(
+            WriteMultipleCoilsReq::<8>::decode(frame),
+            Err(Error::InvalidQuantity)
+        );
+
+        let write = WriteMultipleHoldingRegistersReq {
+            address: 1,
+            start_index: 8,
+            registers: [1, 0x1234, 0xFFFF],
+        };
+        let frame = write.encode_into(&mut buf).unwrap();
+        assert_eq!(WriteMultipleHoldingRegistersReq::decode(frame), Ok(write));
+        assert_eq!(
+            WriteMultipleHoldingRegistersReq::<2>::decode(frame),
+            Err(Error::InvalidQuantity)
+        );
+        let empty = WriteMultipleHoldingRegistersReq::<0> {
+            address: 1,
+            start_index: 0,
+            registers: [],
+        };
+        assert_eq!(empty.encode_into(&mut buf), Err(Error::InvalidQuantity));
+    }
+}
@@ -0,0 +1,166 @@
+//! The protocol data unit, function code and data without the address or checksum of a serial frame
+//!
+//! Modbus TCP carries the [`Pdu`] behind an MBAP header instead of an address and CRC. Converting to and from an
+//! RTU [`Frame`] lets the typed request/response wrappers and decoders serve every transport.
+//!
+//! ```
+//! use modbus_frames::{pdu::Pdu, request};
+//!
+//! // read holding registers over TCP, unit 5
+//! let adu = [0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x05, 0x03, 0x00, 0x6B, 0x00, 0x03];
+//! let (mbap, pdu) = Pdu::from_mbap(&adu).unwrap();
+//! assert_eq!((mbap.transaction, mbap.unit), (1, 5));
+//!
+//! let mut rtu = [0; 256];
+//! let request = request::ReadHoldingRegisters::try_from(pdu.to_rtu(mbap.unit, &mut rtu).unwrap()).unwrap();
+//! assert_eq!(request.register_count(), 3);
+//! ```
+
+use byteorder::ByteOrder;
+
+use crate::{builder, Error, Frame, Function};
+
+/// Function code and data, 1-253 bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Pdu<'a> {
+    data: &'a [u8],
+}
+
+/// The longest PDU, limited by the 256 byte RTU frame
+pub const MAX_PDU_LEN: usize = 253;
+
+/// Length of the MBAP header preceding the PDU in Modbus TCP
+pub const MBAP_LEN: usize = 7;
+
+/// Modbus TCP header fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Mbap {
+    /// echoed by the server to match responses to requests
+    pub transaction: u16,
+    /// the device address behind a gateway, 0xFF when addressing the TCP device itself
+    pub unit: u8,
+}
+
+impl<'a> Pdu<'a> {
+    /// Creates a new PDU without validation
+    ///
+    /// # UNCHECKED
+    /// if `bytes` is empty the created PDU will be invalid and later operations are likely to panic
+    pub const fn new_unchecked(bytes: &'a [u8]) -> Self {
+        Pdu { data: bytes }
+    }
+
+    pub fn function(&self) -> Function {
+        Function(self.data[0])
+    }
+
+    /// All bytes after the function code
+    pub fn data(&self) -> &'a [u8] {
+        &self.data[1..]
+    }
+
+    /// The function code and data
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Split a Modbus TCP ADU into its header and PDU
+    pub fn from_mbap(bytes: &'a [u8]) -> Result<(Mbap, Self), Error> {
+        if bytes.len() < MBAP_LEN {
+            return Err(Error::InvalidLength(bytes.len()));
+        }
+        let (header, pdu) = bytes.split_at(MBAP_LEN);
+        if byteorder::BigEndian::read_u16(&header[2..]) != 0 {
+            // only protocol 0 is Modbus
+            return Err(Error::InvalidValue);
+        }
+        // the length field counts the unit id
+        if usize::from(byteorder::BigEndian::read_u16(&header[4..])) != pdu.len() + 1 {
+            return Err(Error::InvalidLength(bytes.len()));
+        }
+        let mbap = Mbap {
+            transaction: byteorder::BigEndian::read_u16(header),
+            unit: header[6],
+        };
+        Ok((mbap, Self::try_from(pdu)?))
+    }
+
+    /// Serial frame for device `address`, adding the address and CRC
+    pub fn to_rtu<'b>(&self, address: u8, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        if buffer.len() < self.data.len() + 3 {
+            return Err(Error::BufferTooSmall);
+        }
+        Ok(builder::build_frame(buffer)
+            .for_address(address)
+            .function(self.function())
+            .bytes(self.data().iter().copied())
+            .finalise()
+            .0)
+    }
+
+    /// Modbus TCP ADU, the MBAP header followed by the PDU
+    pub fn to_mbap<'b>(&self, mbap: Mbap, buffer: &'b mut [u8]) -> Result<&'b [u8], Error> {
+        let len = MBAP_LEN + self.data.len();
+        let out = buffer.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+        byteorder::BigEndian::write_u16(out, mbap.transaction);
+        byteorder::BigEndian::write_u16(&mut out[2..], 0);
+        byteorder::BigEndian::write_u16(&mut out[4..], self.data.len() as u16 + 1);
+        out[6] = mbap.unit;
+        out[MBAP_LEN..].copy_from_slice(self.data);
+        Ok(out)
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Pdu<'a> {
+    type Error = Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        if bytes.is_empty() || bytes.len() > MAX_PDU_LEN {
+            Err(Error::InvalidLength(bytes.len()))
+        } else {
+            Ok(Pdu::new_unchecked(bytes))
+        }
+    }
+}
+
+impl<'b> From<Frame<'b>> for Pdu<'b> {
+    fn from(frame: Frame<'b>) -> Self {
+        frame.pdu()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mbap, Pdu, MBAP_LEN};
+    use crate::{request, Error};
+
+    #[test]
+    fn transports() {
+        let mut rtu = [0; 8];
+        let request = request::read_coils(&mut rtu, 7, 0x13, 0x25).unwrap();
+        let pdu = request.pdu();
+        assert_eq!(pdu.as_bytes(), [0x01, 0x00, 0x13, 0x00, 0x25]);
+
+        let mbap = Mbap {
+            transaction: 0x1234,
+            unit: 7,
+        };
+        let mut tcp = [0; 12];
+        let adu = pdu.to_mbap(mbap, &mut tcp).unwrap();
+        assert_eq!(adu[..MBAP_LEN], [0x12, 0x34, 0, 0, 0, 6, 7]);
+        assert_eq!(Pdu::from_mbap(adu), Ok((mbap, pdu)));
+        assert_eq!(pdu.to_mbap(mbap, &mut [0; 11]), Err(Error::BufferTooSmall));
+
+        let mut rtu = [0; 8];
+        assert_eq!(pdu.to_rtu(7, &mut rtu), Ok(request));
+
+        let mut bad = tcp;
+        bad[5] = 7;
+        assert_eq!(Pdu::from_mbap(&bad), Err(Error::InvalidLength(12)));
+        bad[5] = 6;
+        bad[3] = 1;
+        assert_eq!(Pdu::from_mbap(&bad), Err(Error::InvalidValue));
+    }
+}
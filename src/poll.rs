@@ -0,0 +1,164 @@
+//! Scheduling periodic polls, the core loop of a data concentrator
+//!
+//! [`Scheduler`] holds a static table of [`Job`]s and, given the current time, yields the next request to send.
+//! It performs no I/O and the time is a free running millisecond (or any other tick) counter which may wrap.
+//!
+//! ```
+//! use modbus_frames::{poll::{Job, Scheduler}, request, Frame};
+//!
+//! const FAST: [u8; 8] = request::read_holding_registers_const(1, 0, 4);
+//! const SLOW: [u8; 8] = request::read_input_registers_const(2, 0, 10);
+//! static JOBS: [Job; 2] = [
+//!     Job::new(Frame::new_unchecked(&FAST), 100, 1),
+//!     Job::new(Frame::new_unchecked(&SLOW), 1000, 0),
+//! ];
+//!
+//! let mut scheduler = Scheduler::new(&JOBS, 0);
+//! // both are due, the higher priority job goes first
+//! assert_eq!(scheduler.next(0).unwrap().request.address(), 1);
+//! assert_eq!(scheduler.next(0).unwrap().request.address(), 2);
+//! assert!(scheduler.next(50).is_none());
+//! assert_eq!(scheduler.next_due(), Some(100));
+//! ```
+
+use crate::Frame;
+
+/// A request sent every `period` ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Job<'t> {
+    pub request: Frame<'t>,
+    pub period: u32,
+    /// when several jobs are due the highest priority is sent first
+    pub priority: u8,
+}
+
+impl<'t> Job<'t> {
+    pub const fn new(request: Frame<'t>, period: u32, priority: u8) -> Self {
+        Job {
+            request,
+            period,
+            priority,
+        }
+    }
+}
+
+/// A job to send now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Poll<'t> {
+    /// index of the job in the table
+    pub job: usize,
+    pub request: Frame<'t>,
+    /// time the job is next due, the response should be handled before then
+    pub deadline: u32,
+}
+
+/// Decides which of `N` jobs to send next
+#[derive(Debug, Clone)]
+pub struct Scheduler<'t, const N: usize> {
+    jobs: &'t [Job<'t>; N],
+    due: [u32; N],
+}
+
+impl<'t, const N: usize> Scheduler<'t, N> {
+    /// Every job is due at time `now`
+    pub fn new(jobs: &'t [Job<'t>; N], now: u32) -> Self {
+        Scheduler {
+            jobs,
+            due: [now; N],
+        }
+    }
+
+    pub fn jobs(&self) -> &'t [Job<'t>; N] {
+        self.jobs
+    }
+
+    /// The highest priority job due at `now`, ties go to the longest overdue then the first in the table
+    ///
+    /// The job is then due again a period later. A job that has fallen more than a period behind is
+    /// rescheduled from `now` rather than sent repeatedly to catch up.
+    pub fn next(&mut self, now: u32) -> Option<Poll<'t>> {
+        let (idx, job) = self
+            .jobs
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| is_due(self.due[*idx], now))
+            .max_by_key(|(idx, job)| {
+                (
+                    job.priority,
+                    now.wrapping_sub(self.due[*idx]),
+                    core::cmp::Reverse(*idx),
+                )
+            })?;
+        let mut due = self.due[idx].wrapping_add(job.period);
+        if is_due(due, now) {
+            due = now.wrapping_add(job.period);
+        }
+        self.due[idx] = due;
+        Some(Poll {
+            job: idx,
+            request: job.request,
+            deadline: due,
+        })
+    }
+
+    /// The time the next job falls due, e.g. to sleep until then
+    pub fn next_due(&self) -> Option<u32> {
+        // relative to an arbitrary due time so the comparison survives the counter wrapping
+        let base = *self.due.first()?;
+        self.due
+            .iter()
+            .copied()
+            .min_by_key(|due| due.wrapping_sub(base) as i32)
+    }
+
+    /// Send job `job` at the next call to `next`, e.g. after a write changed the values it reads
+    pub fn trigger(&mut self, job: usize, now: u32) {
+        if let Some(due) = self.due.get_mut(job) {
+            *due = now;
+        }
+    }
+}
+
+/// `due` is at or before `now`, allowing for the counter wrapping
+fn is_due(due: u32, now: u32) -> bool {
+    now.wrapping_sub(due) as i32 >= 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Job, Scheduler};
+    use crate::{request, Frame};
+
+    const A: [u8; 8] = request::read_coils_const(1, 0, 1);
+    const B: [u8; 8] = request::read_coils_const(2, 0, 1);
+    static JOBS: [Job; 2] = [
+        Job::new(Frame::new_unchecked(&A), 10, 0),
+        Job::new(Frame::new_unchecked(&B), 25, 0),
+    ];
+
+    fn sent(scheduler: &mut Scheduler<2>, now: u32) -> Vec<usize> {
+        core::iter::from_fn(|| scheduler.next(now))
+            .map(|poll| poll.job)
+            .collect()
+    }
+
+    #[test]
+    fn periods() {
+        let start = u32::MAX - 15;
+        let mut scheduler = Scheduler::new(&JOBS, start);
+        assert_eq!(sent(&mut scheduler, start), [0, 1]);
+        assert_eq!(scheduler.next_due(), Some(start.wrapping_add(10)));
+        assert_eq!(sent(&mut scheduler, start.wrapping_add(9)), []);
+        assert_eq!(sent(&mut scheduler, start.wrapping_add(10)), [0]);
+        // more than a period late, only sent once
+        assert_eq!(sent(&mut scheduler, start.wrapping_add(45)), [0, 1]);
+        assert_eq!(scheduler.next_due(), Some(start.wrapping_add(50)));
+
+        scheduler.trigger(1, start.wrapping_add(46));
+        let poll = scheduler.next(start.wrapping_add(46)).unwrap();
+        assert_eq!((poll.job, poll.request.address()), (1, 2));
+        assert_eq!(poll.deadline, start.wrapping_add(71));
+    }
+}
@@ -0,0 +1,158 @@
+//! Tolerating devices which don't quite follow the specification
+//!
+//! The decoders are strict by default. [`Quirks`] relaxes the checks field devices commonly get wrong, so talking
+//! to them doesn't need a fork of the validation logic. Each quirk is off in [`Quirks::STRICT`] (the `Default`).
+//!
+//! ```
+//! use modbus_frames::{quirks::Quirks, request};
+//!
+//! let quirks = Quirks {
+//!     trailing_garbage: true,
+//!     ..Quirks::STRICT
+//! };
+//! let mut buffer = [0; 8];
+//! let request = request::read_coils(&mut buffer, 1, 0, 8).unwrap();
+//! let mut received = [0xFF; 10];
+//! received[..8].copy_from_slice(request.raw_bytes());
+//! assert_eq!(quirks.decode_frame(&received), Ok(request));
+//! ```
+
+use crate::{ascii, verify_crc16, verify_crc16_swapped, Error, Frame};
+
+/// Deviations from the specification to accept when decoding
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Quirks {
+    /// bytes following a valid frame are ignored, the frame is the longest prefix ending in a valid CRC
+    pub trailing_garbage: bool,
+    /// read responses may return up to this many more data bytes than requested, the byte count must still match
+    /// the data
+    pub extra_byte_count: u8,
+    /// ASCII frames may use lower case hex digits
+    pub lowercase_hex: bool,
+    /// the device sends and expects the CRC big endian, frames with either byte order are accepted
+    pub swapped_crc: bool,
+}
+
+impl Quirks {
+    /// no deviations accepted
+    pub const STRICT: Quirks = Quirks {
+        trailing_garbage: false,
+        extra_byte_count: 0,
+        lowercase_hex: false,
+        swapped_crc: false,
+    };
+
+    /// As `verify_crc16`, also accepting a big endian CRC with `swapped_crc`
+    pub fn verify_crc16(&self, bytes: &[u8]) -> bool {
+        verify_crc16(bytes) || (self.swapped_crc && verify_crc16_swapped(bytes))
+    }
+
+    /// As `Frame::try_from`, with `trailing_garbage` and `swapped_crc`
+    ///
+    /// A frame accepted with a swapped CRC keeps the bytes as received, so `Frame::verify` is false for it
+    pub fn decode_frame<'b>(&self, bytes: &'b [u8]) -> Result<Frame<'b>, Error> {
+        match Frame::try_from(bytes) {
+            Err(Error::InvalidCrc) if self.trailing_garbage => (4..=bytes.len())
+                .rev()
+                .find(|len| self.verify_crc16(&bytes[..*len]))
+                .map(|len| Frame::new_unchecked(&bytes[..len]))
+                .ok_or(Error::InvalidCrc),
+            Err(Error::InvalidCrc) if self.verify_crc16(bytes) => Ok(Frame::new_unchecked(bytes)),
+            result => result,
+        }
+    }
+
+    /// Swap the CRC of `frame`, built by the builder or a `request::` constructor, to the byte order the device
+    /// expects with `swapped_crc`
+    pub fn encode_crc(&self, frame: &mut [u8]) {
+        if let (true, Some([.., lo, hi])) = (self.swapped_crc, frame.get_mut(2..)) {
+            core::mem::swap(lo, hi);
+        }
+    }
+
+    /// As `ascii::decode`, with `lowercase_hex`
+    pub fn decode_ascii<'b>(
+        &self,
+        text: &[u8],
+        buffer: &'b mut [u8],
+    ) -> Result<ascii::Frame<'b>, Error> {
+        ascii::decode_with(text, buffer, self.lowercase_hex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Quirks;
+    use crate::{builder, decoder::Transaction, function, request, verify_crc16_swapped, Error};
+
+    #[test]
+    fn quirks() {
+        let lenient = Quirks {
+            trailing_garbage: true,
+            extra_byte_count: 2,
+            lowercase_hex: true,
+            swapped_crc: true,
+        };
+        let mut req = [0; 8];
+        let request = request::read_holding_registers(&mut req, 0x11, 0x6B, 1).unwrap();
+
+        let mut received = [0xFF; 11];
+        received[..8].copy_from_slice(request.raw_bytes());
+        assert_eq!(
+            Quirks::STRICT.decode_frame(&received),
+            Err(Error::InvalidCrc)
+        );
+        assert_eq!(lenient.decode_frame(&received), Ok(request));
+        assert_eq!(lenient.decode_frame(&received[1..]), Err(Error::InvalidCrc));
+
+        // two registers returned for one requested
+        let mut res = [0; 16];
+        let (response, _) = builder::build_frame(&mut res)
+            .for_address(0x11)
+            .function(function::READ_HOLDING_REGISTERS)
+            .byte(4)
+            .registers([1, 2])
+            .finalise();
+        assert_eq!(
+            Transaction::new(request, response),
+            Err(Error::InvalidQuantity)
+        );
+        assert!(Transaction::with_quirks(request, response, &lenient).is_ok());
+
+        // the CRC big endian
+        let mut swapped = [0; 8];
+        swapped.copy_from_slice(request.raw_bytes());
+        lenient.encode_crc(&mut swapped);
+        assert_eq!(
+            swapped[6..],
+            [request.raw_bytes()[7], request.raw_bytes()[6]]
+        );
+        assert!(verify_crc16_swapped(&swapped));
+        assert_eq!(
+            Quirks::STRICT.decode_frame(&swapped),
+            Err(Error::InvalidCrc)
+        );
+        assert!(!Quirks::STRICT.verify_crc16(&swapped));
+        let decoded = lenient.decode_frame(&swapped).unwrap();
+        assert_eq!(decoded.raw_bytes(), swapped);
+        assert_eq!(decoded.payload(), request.payload());
+        received[..8].copy_from_slice(&swapped);
+        assert_eq!(lenient.decode_frame(&received), Ok(decoded));
+        let mut unchanged = swapped;
+        Quirks::STRICT.encode_crc(&mut unchanged);
+        assert_eq!(unchanged, swapped);
+
+        let mut buffer = [0; 8];
+        let text = b":1103006b00037e\r\n";
+        assert_eq!(
+            Quirks::STRICT.decode_ascii(text, &mut buffer),
+            Err(Error::InvalidEncoding(8))
+        );
+        let frame = lenient.decode_ascii(text, &mut buffer).unwrap();
+        assert_eq!(
+            frame.raw_bytes(),
+            [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x7E]
+        );
+    }
+}
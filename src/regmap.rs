@@ -0,0 +1,473 @@
+//! Table driven reading of named values from a device
+//!
+//! The values of interest on a device are declared once as a table of [`Point`]s. [`RegisterMap::blocks`] then
+//! works out the fewest read requests covering every point, and [`Point::value`] pulls a point's value out of
+//! the response to the block containing it. Points may also carry a [`Scale`] to convert to engineering units.
+//!
+//! ```
+//! use modbus_frames::{entity::Entity, regmap::{Point, RegisterMap, Value, ValueType}, response, WordOrder};
+//!
+//! const POINTS: [Point; 3] = [
+//!     Point::new("voltage", Entity::input_register(0), ValueType::U16),
+//!     Point::new("energy", Entity::input_register(1), ValueType::U32)
+//!         .with_word_order(WordOrder::LowWordFirst),
+//!     Point::new("running", Entity::coil(7), ValueType::Bool),
+//! ];
+//! const MAP: RegisterMap = RegisterMap::new(&POINTS);
+//!
+//! // one request for the coil and one for the three input registers
+//! let mut blocks = MAP.blocks();
+//! let coils = blocks.next().unwrap();
+//! let registers = blocks.next().unwrap();
+//! assert_eq!((registers.start_index, registers.count), (0, 3));
+//! assert!(blocks.next().is_none());
+//!
+//! let mut buf = [0; 32];
+//! let (response, _) = response::ReadInputRegisters::new(&mut buf, 1, [230, 0x5678, 0x1234]);
+//! let energy = MAP.find("energy").unwrap();
+//! assert_eq!(energy.value(&registers, response.as_frame()), Ok(Value::U32(0x1234_5678)));
+//! ```
+
+use crate::{
+    entity::{Entity, EntityRange, EntityType},
+    scale::Scale,
+    Error, Frame, LenHint, WordOrder,
+};
+
+/// How the value of a point is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ValueType {
+    /// a coil/discrete input, or a register which is on when non-zero
+    Bool,
+    U16,
+    I16,
+    /// two registers
+    U32,
+    /// two registers
+    I32,
+    /// two registers, IEEE 754 single precision
+    F32,
+}
+
+impl ValueType {
+    /// number of entities the value occupies
+    pub const fn width(&self) -> u16 {
+        match self {
+            ValueType::Bool | ValueType::U16 | ValueType::I16 => 1,
+            ValueType::U32 | ValueType::I32 | ValueType::F32 => 2,
+        }
+    }
+}
+
+/// A decoded point value
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Value {
+    Bool(bool),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+}
+
+impl Value {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// integer value of any type except `F32`, `true` is 1
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Bool(b) => Some(b as i64),
+            Value::U16(v) => Some(v.into()),
+            Value::I16(v) => Some(v.into()),
+            Value::U32(v) => Some(v.into()),
+            Value::I32(v) => Some(v.into()),
+            Value::F32(_) => None,
+        }
+    }
+
+    /// numeric value of any type, `true` is 1.0
+    pub fn as_f32(&self) -> f32 {
+        match *self {
+            Value::Bool(b) => b as u8 as f32,
+            Value::U16(v) => v as f32,
+            Value::I16(v) => v as f32,
+            Value::U32(v) => v as f32,
+            Value::I32(v) => v as f32,
+            Value::F32(v) => v,
+        }
+    }
+}
+
+/// A named value on a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Point {
+    pub name: &'static str,
+    /// the first entity holding the value
+    pub entity: Entity,
+    pub value_type: ValueType,
+    /// only used by 32 bit values
+    pub word_order: WordOrder,
+    /// conversion from the decoded value to engineering units
+    pub scale: Scale,
+}
+
+impl Point {
+    /// A point with the high word first, as recommended by the modbus specification
+    pub const fn new(name: &'static str, entity: Entity, value_type: ValueType) -> Self {
+        Point {
+            name,
+            entity,
+            value_type,
+            word_order: WordOrder::HighWordFirst,
+            scale: Scale::IDENTITY,
+        }
+    }
+
+    pub const fn with_word_order(self, word_order: WordOrder) -> Self {
+        Point { word_order, ..self }
+    }
+
+    pub const fn with_scale(self, scale: Scale) -> Self {
+        Point { scale, ..self }
+    }
+
+    /// The point value in engineering units (see `scale`)
+    pub fn engineering_value(&self, block: &Block, response: Frame) -> Result<f32, Error> {
+        Ok(match self.value(block, response)? {
+            Value::F32(v) => {
+                (v * self.scale.mul as f32 + self.scale.add as f32) / self.scale.div as f32
+            }
+            value => self.scale.to_f32(value.as_i64().unwrap_or_default()),
+        })
+    }
+
+    /// The point value in engineering units multiplied by `multiplier`, without using floating point
+    ///
+    /// `F32` points can't be converted and return `InvalidValue`, as do conversions with a zero divisor or which overflow
+    pub fn fixed_value(
+        &self,
+        block: &Block,
+        response: Frame,
+        multiplier: i64,
+    ) -> Result<i64, Error> {
+        let raw = self
+            .value(block, response)?
+            .as_i64()
+            .ok_or(Error::InvalidValue)?;
+        self.scale
+            .to_fixed(raw, multiplier)
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// index after the last entity used by the point
+    fn end(&self) -> u32 {
+        self.entity.index as u32 + self.value_type.width() as u32
+    }
+
+    /// Decode the point from the response to the read request for `block`
+    pub fn value(&self, block: &Block, response: Frame) -> Result<Value, Error> {
+        if !block.contains(self) {
+            return Err(Error::InvalidValue);
+        }
+        let function = block.entity_type.read_function();
+        if response.function() != function {
+            return Err(Error::UnexpectedFunction {
+                expected: function,
+                found: response.function(),
+            });
+        }
+
+        let offset = (self.entity.index - block.start_index) as usize;
+        let data = response.payload().get(1..).unwrap_or(&[]);
+        let len_error = |needed: usize| Error::DecodeInvalidLength {
+            function,
+            len: response.raw_bytes().len(),
+            // header(2) + byte count(1) + data + crc(2)
+            expected: LenHint::AtLeast((5 + needed).min(u8::MAX.into()) as u8),
+        };
+
+        if block.entity_type.is_bit() {
+            if self.value_type != ValueType::Bool {
+                return Err(Error::InvalidValue);
+            }
+            let byte = data.get(offset / 8).ok_or(len_error(offset / 8 + 1))?;
+            return Ok(Value::Bool(byte & (1 << (offset % 8)) != 0));
+        }
+
+        let width = self.value_type.width() as usize;
+        let bytes = data
+            .get(offset * 2..(offset + width) * 2)
+            .ok_or(len_error((offset + width) * 2))?;
+        let first = u16::from_be_bytes([bytes[0], bytes[1]]);
+        let wide = || {
+            let second = u16::from_be_bytes([bytes[2], bytes[3]]);
+            self.word_order.join(first, second)
+        };
+        Ok(match self.value_type {
+            ValueType::Bool => Value::Bool(first != 0),
+            ValueType::U16 => Value::U16(first),
+            ValueType::I16 => Value::I16(first as i16),
+            ValueType::U32 => Value::U32(wide()),
+            ValueType::I32 => Value::I32(wide() as i32),
+            ValueType::F32 => Value::F32(f32::from_bits(wide())),
+        })
+    }
+}
+
+/// A range of entities read by a single request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Block {
+    pub entity_type: EntityType,
+    pub start_index: u16,
+    pub count: u16,
+}
+
+impl Block {
+    /// true if the whole point is read by this block
+    pub fn contains(&self, point: &Point) -> bool {
+        point.entity.entity_type == self.entity_type
+            && point.entity.index >= self.start_index
+            && point.end() <= self.start_index as u32 + self.count as u32
+    }
+
+    pub fn range(&self) -> EntityRange {
+        EntityRange::new(self.entity_type, self.start_index, self.count)
+    }
+
+    /// Build the request reading this block from the device at `address`
+    pub fn request<'b>(&self, buffer: &'b mut [u8], address: u8) -> Result<Frame<'b>, Error> {
+        self.range().read_request(buffer, address)
+    }
+}
+
+/// A table of points on a device
+///
+/// Points must not overlap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RegisterMap<'t> {
+    points: &'t [Point],
+    max_gap: u16,
+}
+
+impl<'t> RegisterMap<'t> {
+    /// only contiguous points are read by the same request
+    pub const fn new(points: &'t [Point]) -> Self {
+        RegisterMap { points, max_gap: 0 }
+    }
+
+    /// Allow up to `max_gap` unused entities between points read by the same request
+    ///
+    /// fewer requests are needed, but some devices respond with an exception when unused entities are read
+    pub const fn with_max_gap(self, max_gap: u16) -> Self {
+        RegisterMap { max_gap, ..self }
+    }
+
+    pub fn points(&self) -> &'t [Point] {
+        self.points
+    }
+
+    pub fn find(&self, name: &str) -> Option<&'t Point> {
+        self.points.iter().find(|point| point.name == name)
+    }
+
+    /// The read requests needed to read every point, grouped by entity type and ordered by index
+    pub fn blocks(&self) -> Blocks<'t> {
+        Blocks {
+            points: self.points,
+            max_gap: self.max_gap,
+            type_idx: 0,
+            cursor: 0,
+        }
+    }
+}
+
+/// Iterator over the read requests needed to read a register map
+#[derive(Debug, Clone)]
+pub struct Blocks<'t> {
+    points: &'t [Point],
+    max_gap: u16,
+    /// index into `EntityType::ALL`
+    type_idx: usize,
+    /// all points of the current type before this index are covered
+    cursor: u32,
+}
+
+impl Iterator for Blocks<'_> {
+    type Item = Block;
+
+    fn next(&mut self) -> Option<Block> {
+        while let Some(&entity_type) = EntityType::ALL.get(self.type_idx) {
+            let cursor = self.cursor;
+            let candidates = self
+                .points
+                .iter()
+                .filter(|p| p.entity.entity_type == entity_type && p.entity.index as u32 >= cursor);
+            let first = match candidates.clone().min_by_key(|p| p.entity.index) {
+                Some(first) => first,
+                None => {
+                    self.type_idx += 1;
+                    self.cursor = 0;
+                    continue;
+                }
+            };
+
+            let start = first.entity.index as u32;
+            let limit = start + entity_type.max_read_count() as u32;
+            let mut end = first.end();
+            // the table isn't sorted, keep extending the block until no more points fit
+            while let Some(next_end) = candidates
+                .clone()
+                .filter(|p| {
+                    p.end() > end
+                        && p.entity.index as u32 <= end + self.max_gap as u32
+                        && p.end() <= limit
+                })
+                .map(|p| p.end())
+                .max()
+            {
+                end = next_end;
+            }
+            // points that didn't fit are left for the next block
+            self.cursor = end;
+            return Some(Block {
+                entity_type,
+                start_index: start as u16,
+                count: (end - start) as u16,
+            });
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Block, Point, RegisterMap, Value, ValueType};
+    use crate::{
+        entity::{Entity, EntityType},
+        response,
+        scale::Scale,
+        Error, WordOrder,
+    };
+
+    const POINTS: [Point; 7] = [
+        Point::new("setpoint", Entity::holding_register(10), ValueType::F32),
+        Point::new("mode", Entity::holding_register(12), ValueType::I16)
+            .with_scale(Scale::new(1, 10, 5)),
+        Point::new("count", Entity::holding_register(3), ValueType::U32)
+            .with_word_order(WordOrder::LowWordFirst),
+        Point::new("far", Entity::holding_register(200), ValueType::U16),
+        Point::new("alarm", Entity::discrete_input(9), ValueType::Bool),
+        Point::new("door", Entity::discrete_input(2), ValueType::Bool),
+        Point::new("enable", Entity::holding_register(5), ValueType::Bool),
+    ];
+
+    fn block(entity_type: EntityType, start_index: u16, count: u16) -> Block {
+        Block {
+            entity_type,
+            start_index,
+            count,
+        }
+    }
+
+    #[test]
+    fn minimal_blocks() {
+        let map = RegisterMap::new(&POINTS);
+        assert_eq!(
+            map.blocks().collect::<Vec<_>>(),
+            [
+                block(EntityType::DiscreteInput, 2, 1),
+                block(EntityType::DiscreteInput, 9, 1),
+                block(EntityType::HoldingRegister, 3, 3),
+                block(EntityType::HoldingRegister, 10, 3),
+                block(EntityType::HoldingRegister, 200, 1),
+            ]
+        );
+
+        let map = RegisterMap::new(&POINTS).with_max_gap(8);
+        assert_eq!(
+            map.blocks().collect::<Vec<_>>(),
+            [
+                block(EntityType::DiscreteInput, 2, 8),
+                block(EntityType::HoldingRegister, 3, 10),
+                block(EntityType::HoldingRegister, 200, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn point_values() {
+        let map = RegisterMap::new(&POINTS).with_max_gap(8);
+        let blocks = map.blocks().collect::<Vec<_>>();
+
+        let mut buf = [0; 64];
+        let (inputs, rem) = response::ReadDiscreteInputs::new(
+            &mut buf,
+            1,
+            [true, false, false, false, false, false, false, true],
+        );
+        let door = map.find("door").unwrap();
+        let alarm = map.find("alarm").unwrap();
+        assert_eq!(
+            door.value(&blocks[0], inputs.as_frame()),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            alarm.value(&blocks[0], inputs.as_frame()),
+            Ok(Value::Bool(true))
+        );
+
+        let setpoint = 21.5_f32.to_bits();
+        let registers = [
+            0x5678,
+            0x1234,
+            1,
+            0,
+            0,
+            0,
+            0,
+            (setpoint >> 16) as u16,
+            setpoint as u16,
+            0xFFFF,
+        ];
+        let (holding, _) = response::ReadHoldingRegisters::new(rem, 1, registers);
+        let value = |name| {
+            map.find(name)
+                .unwrap()
+                .value(&blocks[1], holding.as_frame())
+        };
+        assert_eq!(value("count"), Ok(Value::U32(0x1234_5678)));
+        assert_eq!(value("enable"), Ok(Value::Bool(true)));
+        assert_eq!(value("setpoint"), Ok(Value::F32(21.5)));
+        assert_eq!(value("mode"), Ok(Value::I16(-1)));
+
+        let mode = map.find("mode").unwrap();
+        assert_eq!(
+            mode.engineering_value(&blocks[1], holding.as_frame()),
+            Ok(0.4)
+        );
+        assert_eq!(
+            mode.fixed_value(&blocks[1], holding.as_frame(), 100),
+            Ok(40)
+        );
+        let setpoint = map.find("setpoint").unwrap();
+        assert_eq!(
+            setpoint.fixed_value(&blocks[1], holding.as_frame(), 1),
+            Err(Error::InvalidValue)
+        );
+        assert_eq!(value("far"), Err(Error::InvalidValue));
+        assert_eq!(
+            door.value(&blocks[1], holding.as_frame()),
+            Err(Error::InvalidValue)
+        );
+    }
+}
@@ -0,0 +1,311 @@
+//! Replaying captured traffic against a server (feature `std`)
+//!
+//! Field captures, either pcapng files written by [`capture`](crate::capture) or raw bytes from a tapped bus, are
+//! turned into [`Record`]s. [`replay_server`] feeds the requests to a [`MultiServer`] with the original timing and
+//! compares each response with the recorded one, for regression testing device firmware against real traffic.
+//!
+//! ```
+//! use modbus_frames::{replay, request, response, server::MultiServer, source::Coils, Device};
+//!
+//! let mut buf = [0; 16];
+//! let (request, rem) = request::ReadCoils::new(&mut buf, 1, 0, 2);
+//! let (response, _) = response::ReadCoils::new(rem, 1, [true, false]);
+//! let capture = [request.as_frame().raw_bytes(), response.as_frame().raw_bytes()].concat();
+//!
+//! let mut server = MultiServer::new([(Device::new(1), Coils([true, false]))]);
+//! let report = replay::replay_server(&mut server, replay::stream_records(&capture), |_| {}, |_| {});
+//! assert_eq!((report.requests, report.matched), (1, 1));
+//! ```
+
+use crate::{
+    capture::Direction,
+    monitor::{self, Classification, Event, Monitor},
+    server::{Handler, MultiServer},
+    trace::Trace,
+    Error, Frame,
+};
+
+/// A captured frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record<'r> {
+    /// microseconds, only the differences between records are used
+    pub timestamp_us: u64,
+    /// `Outbound` for requests and `Inbound` for responses, as seen by the master
+    pub direction: Direction,
+    pub bytes: &'r [u8],
+}
+
+/// Records for the frames in bytes captured from a bus without timing, see [`Monitor`]
+///
+/// All timestamps are zero and undecodable bytes are skipped
+pub fn stream_records(bytes: &[u8]) -> impl Iterator<Item = Record<'_>> {
+    Monitor::new(bytes).filter_map(|event| {
+        let (direction, frame) = match event {
+            Event::Request(frame) => (Direction::Outbound, frame),
+            Event::Response { response, .. } => (Direction::Inbound, response),
+            Event::Garbage(_) => return None,
+        };
+        Some(Record {
+            timestamp_us: 0,
+            direction,
+            bytes: frame.into_raw_bytes(),
+        })
+    })
+}
+
+/// Records for the packets of a little endian pcapng file with microsecond timestamps
+///
+/// Packets without a direction flag are classified by their content, see [`monitor::classify`]. Blocks other than
+/// enhanced packet blocks are skipped.
+pub fn pcapng_records(file: &[u8]) -> PcapngRecords<'_> {
+    PcapngRecords { file, offset: 0 }
+}
+
+/// Iterator returned by [`pcapng_records`]
+#[derive(Debug, Clone)]
+pub struct PcapngRecords<'r> {
+    file: &'r [u8],
+    offset: usize,
+}
+
+impl<'r> PcapngRecords<'r> {
+    fn read_u32(&self, idx: usize) -> Result<u32, Error> {
+        self.file
+            .get(idx..idx + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or(Error::InvalidLength(self.file.len()))
+    }
+
+    /// the next block as `(type, body)`
+    fn next_block(&mut self) -> Result<(u32, &'r [u8]), Error> {
+        let block_type = self.read_u32(self.offset)?;
+        let len = self.read_u32(self.offset + 4)? as usize;
+        if len < 12 || !len.is_multiple_of(4) {
+            return Err(Error::InvalidLength(len));
+        }
+        let body = self
+            .file
+            .get(self.offset + 8..self.offset + len - 4)
+            .ok_or(Error::InvalidLength(self.file.len()))?;
+        if block_type == 0x0A0D_0D0A && body.get(..4) != Some(&0x1A2B_3C4D_u32.to_le_bytes()) {
+            // big endian sections aren't supported
+            return Err(Error::InvalidValue);
+        }
+        self.offset += len;
+        Ok((block_type, body))
+    }
+}
+
+impl<'r> Iterator for PcapngRecords<'r> {
+    type Item = Result<Record<'r>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.offset < self.file.len() {
+            let (block_type, body) = match self.next_block() {
+                Ok(block) => block,
+                Err(error) => {
+                    self.offset = self.file.len();
+                    return Some(Err(error));
+                }
+            };
+            if block_type == 6 {
+                return Some(packet_record(body));
+            }
+        }
+        None
+    }
+}
+
+/// decode the body of an enhanced packet block
+fn packet_record(body: &[u8]) -> Result<Record<'_>, Error> {
+    let word = |idx: usize| {
+        body.get(idx..idx + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or(Error::InvalidLength(body.len()))
+    };
+    let timestamp_us = u64::from(word(4)?) << 32 | u64::from(word(8)?);
+    let len = word(12)? as usize;
+    let bytes = body
+        .get(20..20 + len)
+        .ok_or(Error::InvalidLength(body.len()))?;
+    let mut options = body.get(20 + len.next_multiple_of(4)..).unwrap_or_default();
+    let mut flags = None;
+    while let [code_lo, code_hi, len_lo, len_hi, rest @ ..] = options {
+        let (code, len) = (
+            u16::from_le_bytes([*code_lo, *code_hi]),
+            u16::from_le_bytes([*len_lo, *len_hi]),
+        );
+        let value = rest.get(..len.into()).unwrap_or_default();
+        if code == 0 {
+            break;
+        }
+        if let (2, [a, b, c, d]) = (code, value) {
+            flags = Some(u32::from_le_bytes([*a, *b, *c, *d]));
+        }
+        options = rest
+            .get(usize::from(len).next_multiple_of(4)..)
+            .unwrap_or_default();
+    }
+    let direction = match flags.map(|f| f & 0b11) {
+        Some(1) => Direction::Inbound,
+        Some(2) => Direction::Outbound,
+        _ => match Frame::try_from(bytes).ok().and_then(monitor::classify) {
+            Some(Classification::Response) => Direction::Inbound,
+            _ => Direction::Outbound,
+        },
+    };
+    Ok(Record {
+        timestamp_us,
+        direction,
+        bytes,
+    })
+}
+
+/// Outcome of a replay
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Report {
+    /// requests given to the server
+    pub requests: u32,
+    /// requests answered exactly as recorded, including those correctly not answered
+    pub matched: u32,
+    pub mismatched: u32,
+    /// recorded responses without a preceding request
+    pub unpaired: u32,
+}
+
+/// A request the server answered differently than recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch<'r, 'a> {
+    pub request: &'r [u8],
+    /// the recorded response, `None` if there was none
+    pub expected: Option<&'r [u8]>,
+    /// the server's response, `None` if it didn't respond
+    pub actual: Option<Frame<'a>>,
+}
+
+/// Feed the requests in `records` to `server`, comparing each response with the recorded response that follows
+///
+/// `wait_us` is called with the time between records to reproduce the original timing, pass `|_| {}` to run as
+/// fast as possible.
+pub fn replay_server<'r, H: Handler, const N: usize, R: Trace>(
+    server: &mut MultiServer<H, N, R>,
+    records: impl IntoIterator<Item = Record<'r>>,
+    mut wait_us: impl FnMut(u64),
+    mut on_mismatch: impl FnMut(Mismatch<'r, '_>),
+) -> Report {
+    let mut report = Report::default();
+    let mut records = records.into_iter().peekable();
+    let mut response_buffer = [0; 256];
+    let mut last = None;
+    while let Some(record) = records.next() {
+        if let Some(last) = last {
+            wait_us(record.timestamp_us.saturating_sub(last));
+        }
+        last = Some(record.timestamp_us);
+        if record.direction == Direction::Inbound {
+            report.unpaired += 1;
+            continue;
+        }
+        report.requests += 1;
+        let expected = records.next_if(|next| next.direction == Direction::Inbound);
+        if let Some(expected) = expected {
+            last = Some(expected.timestamp_us);
+        }
+        let expected = expected.map(|expected| expected.bytes);
+        let actual = server.process_bytes(record.bytes, &mut response_buffer);
+        if actual.map(Frame::into_raw_bytes) == expected {
+            report.matched += 1;
+        } else {
+            report.mismatched += 1;
+            on_mismatch(Mismatch {
+                request: record.bytes,
+                expected,
+                actual,
+            });
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pcapng_records, replay_server, Record, Report};
+    use crate::{
+        capture::{Direction, PcapngWriter},
+        exception, request, response,
+        server::MultiServer,
+        source::Coils,
+        Device, COIL_ON,
+    };
+
+    #[test]
+    fn replay_capture() {
+        let mut buf = [0; 64];
+        let (read, rem) = request::ReadCoils::new(&mut buf, 1, 0, 2);
+        let (answer, rem) = response::ReadCoils::new(rem, 1, [true, true]);
+        let (missing, rem) = request::ReadCoils::new(rem, 1, 8, 1);
+        let (broadcast, rem) = request::WriteCoil::new(rem, 0, 1, COIL_ON);
+        let (unknown, _) = request::ReadCoils::new(rem, 1, 9, 1);
+
+        let mut writer = PcapngWriter::new(Vec::new()).unwrap();
+        writer
+            .write_frame(read.as_frame(), 100, Some(Direction::Outbound))
+            .unwrap();
+        writer.write_frame(answer.as_frame(), 150, None).unwrap();
+        writer.write_frame(missing.as_frame(), 400, None).unwrap();
+        writer
+            .write_frame(broadcast.as_frame(), 500, Some(Direction::Outbound))
+            .unwrap();
+        writer
+            .write_frame(unknown.as_frame(), 700, Some(Direction::Outbound))
+            .unwrap();
+        writer
+            .write_frame(answer.as_frame(), 710, Some(Direction::Inbound))
+            .unwrap();
+        let file = writer.into_inner();
+
+        let records = pcapng_records(&file)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            records[1],
+            Record {
+                timestamp_us: 150,
+                direction: Direction::Inbound,
+                bytes: answer.as_frame().raw_bytes(),
+            }
+        );
+
+        let mut server = MultiServer::new([(Device::new(1), Coils([false; 2]))]);
+        let mut waits = Vec::new();
+        let mut mismatches = Vec::new();
+        let report = replay_server(
+            &mut server,
+            records,
+            |us| waits.push(us),
+            |mismatch| {
+                mismatches.push((
+                    mismatch.request.to_vec(),
+                    mismatch.actual.map(|f| f.raw_bytes().to_vec()),
+                ))
+            },
+        );
+        assert_eq!(
+            report,
+            Report {
+                requests: 4,
+                matched: 1,
+                mismatched: 3,
+                unpaired: 0
+            }
+        );
+        assert_eq!(waits, [250, 100, 200]);
+        // the recorded device had both coils on
+        assert_eq!(mismatches[0].0, read.as_frame().raw_bytes());
+        // coil 8 doesn't exist
+        let mut res = [0; 8];
+        let (illegal, _) = missing.response_exception(&mut res, exception::ILLEGAL_ADDRESS);
+        assert_eq!(mismatches[1].1.as_deref(), Some(illegal.raw_bytes()));
+        assert_eq!(mismatches.len(), 3);
+    }
+}
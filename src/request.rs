@@ -1,20 +1,22 @@
-use core::ops::Rem;
-
 use crate::{
-    builder, function, response, Error, Exception, FixedLen, Frame, Function, FunctionCode,
-    PacketLen,
+    builder, exception, function, quirks::Quirks, response, Error, Exception, FixedLen, Frame,
+    Function, FunctionCode, PacketLen,
 };
 
 use bitvec::prelude::*;
 use byteorder::ByteOrder;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ReadCoils<'a> {
     frame: Frame<'a>,
 }
 
 impl<'a> ReadCoils<'a> {
+    /// Buffer length needed for the response to a request for `count` coils
+    pub const fn response_buffer_len(count: u16) -> usize {
+        response::ReadCoils::required_buffer_len(count)
+    }
+
     pub fn new(
         frame_buffer: &'a mut [u8],
         address: u8,
@@ -43,6 +45,11 @@ impl<'a> ReadCoils<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn start_index(&self) -> u16 {
         byteorder::BigEndian::read_u16(&self.frame.payload()[0..])
     }
@@ -59,7 +66,7 @@ impl<'a> ReadCoils<'a> {
         let (frame, rem) = self
             .frame
             .response_builder(response_buffer)
-            .count_bits(coils)
+            .count_following_bytes(|builder| builder.bits(coils).0)
             .finalise();
         (response::ReadCoils::from_frame_unchecked(frame), rem)
     }
@@ -71,6 +78,31 @@ impl<'a> ReadCoils<'a> {
     ) -> (Frame<'buff>, &'buff mut [u8]) {
         self.frame.response_exception(response_buffer, exception)
     }
+
+    /// The response to this request, checking the address, function and coil count
+    ///
+    /// Exception responses are returned as `Error::Exception`
+    pub fn validate_response<'r>(
+        &self,
+        response: Frame<'r>,
+    ) -> Result<response::ReadCoils<'r>, Error> {
+        self.validate_response_with(response, &Quirks::STRICT)
+    }
+
+    /// As `validate_response`, tolerating the byte count `quirks` allows
+    pub fn validate_response_with<'r>(
+        &self,
+        response: Frame<'r>,
+        quirks: &Quirks,
+    ) -> Result<response::ReadCoils<'r>, Error> {
+        check_response(self.frame, response)?;
+        check_byte_count(
+            response,
+            (self.coil_count().div_ceil(8)).into(),
+            quirks.extra_byte_count,
+        )?;
+        response::ReadCoils::try_from(response)
+    }
 }
 
 impl FixedLen for ReadCoils<'_> {
@@ -95,9 +127,16 @@ impl<'a> TryFrom<Frame<'a>> for ReadCoils<'a> {
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
         if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -110,13 +149,30 @@ impl<'a> From<ReadCoils<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for ReadCoils<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "ReadCoils {{ address: {=u8}, start: {=u16}, count: {=u16} }}",
+            self.frame.address(),
+            self.start_index(),
+            self.coil_count(),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ReadDiscreteInputs<'a> {
     frame: Frame<'a>,
 }
 
 impl<'a> ReadDiscreteInputs<'a> {
+    /// Buffer length needed for the response to a request for `count` inputs
+    pub const fn response_buffer_len(count: u16) -> usize {
+        response::ReadDiscreteInputs::required_buffer_len(count)
+    }
+
     pub fn new(
         frame_buffer: &'a mut [u8],
         address: u8,
@@ -145,6 +201,11 @@ impl<'a> ReadDiscreteInputs<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn start_index(&self) -> u16 {
         byteorder::BigEndian::read_u16(&self.frame.payload()[0..])
     }
@@ -161,29 +222,7 @@ impl<'a> ReadDiscreteInputs<'a> {
         let (frame, rem) = self
             .frame
             .response_builder(response_buffer)
-            .count_following_bytes(|mut builder| {
-                // LSB is the addressed input with following addresses in order
-                let coil_iter = inputs.into_iter().enumerate();
-
-                let mut write_last = false;
-                let mut b = 0;
-                for (idx, val) in coil_iter {
-                    write_last = true;
-                    if val {
-                        b |= 1 << idx;
-                    }
-                    if 0 == idx.rem(u8::BITS as usize) {
-                        builder = builder.byte(b);
-                        b = 0;
-                        write_last = false;
-                    }
-                }
-                if write_last {
-                    builder = builder.byte(b);
-                }
-
-                builder
-            })
+            .count_following_bytes(|builder| builder.bits(inputs).0)
             .finalise();
         (
             response::ReadDiscreteInputs::from_frame_unchecked(frame),
@@ -198,6 +237,31 @@ impl<'a> ReadDiscreteInputs<'a> {
     ) -> (Frame<'buff>, &'buff mut [u8]) {
         self.frame.response_exception(response_buffer, exception)
     }
+
+    /// The response to this request, checking the address, function and input count
+    ///
+    /// Exception responses are returned as `Error::Exception`
+    pub fn validate_response<'r>(
+        &self,
+        response: Frame<'r>,
+    ) -> Result<response::ReadDiscreteInputs<'r>, Error> {
+        self.validate_response_with(response, &Quirks::STRICT)
+    }
+
+    /// As `validate_response`, tolerating the byte count `quirks` allows
+    pub fn validate_response_with<'r>(
+        &self,
+        response: Frame<'r>,
+        quirks: &Quirks,
+    ) -> Result<response::ReadDiscreteInputs<'r>, Error> {
+        check_response(self.frame, response)?;
+        check_byte_count(
+            response,
+            (self.input_count().div_ceil(8)).into(),
+            quirks.extra_byte_count,
+        )?;
+        response::ReadDiscreteInputs::try_from(response)
+    }
 }
 
 impl FixedLen for ReadDiscreteInputs<'_> {
@@ -222,9 +286,16 @@ impl<'a> TryFrom<Frame<'a>> for ReadDiscreteInputs<'a> {
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
         if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -237,13 +308,30 @@ impl<'a> From<ReadDiscreteInputs<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for ReadDiscreteInputs<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "ReadDiscreteInputs {{ address: {=u8}, start: {=u16}, count: {=u16} }}",
+            self.frame.address(),
+            self.start_index(),
+            self.input_count(),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ReadHoldingRegisters<'a> {
     frame: Frame<'a>,
 }
 
 impl<'a> ReadHoldingRegisters<'a> {
+    /// Buffer length needed for the response to a request for `count` registers
+    pub const fn response_buffer_len(count: u16) -> usize {
+        response::ReadHoldingRegisters::required_buffer_len(count)
+    }
+
     pub fn new(
         frame_buffer: &'a mut [u8],
         address: u8,
@@ -272,6 +360,11 @@ impl<'a> ReadHoldingRegisters<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn start_index(&self) -> u16 {
         byteorder::BigEndian::read_u16(&self.frame.payload()[0..])
     }
@@ -303,6 +396,31 @@ impl<'a> ReadHoldingRegisters<'a> {
     ) -> (Frame<'buff>, &'buff mut [u8]) {
         self.frame.response_exception(response_buffer, exception)
     }
+
+    /// The response to this request, checking the address, function and register count
+    ///
+    /// Exception responses are returned as `Error::Exception`
+    pub fn validate_response<'r>(
+        &self,
+        response: Frame<'r>,
+    ) -> Result<response::ReadHoldingRegisters<'r>, Error> {
+        self.validate_response_with(response, &Quirks::STRICT)
+    }
+
+    /// As `validate_response`, tolerating the byte count `quirks` allows
+    pub fn validate_response_with<'r>(
+        &self,
+        response: Frame<'r>,
+        quirks: &Quirks,
+    ) -> Result<response::ReadHoldingRegisters<'r>, Error> {
+        check_response(self.frame, response)?;
+        check_byte_count(
+            response,
+            (2 * self.register_count()).into(),
+            quirks.extra_byte_count,
+        )?;
+        response::ReadHoldingRegisters::try_from(response)
+    }
 }
 
 impl FixedLen for ReadHoldingRegisters<'_> {
@@ -327,9 +445,16 @@ impl<'a> TryFrom<Frame<'a>> for ReadHoldingRegisters<'a> {
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
         if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -342,13 +467,30 @@ impl<'a> From<ReadHoldingRegisters<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for ReadHoldingRegisters<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "ReadHoldingRegisters {{ address: {=u8}, start: {=u16}, count: {=u16} }}",
+            self.frame.address(),
+            self.start_index(),
+            self.register_count(),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ReadInputRegisters<'a> {
     frame: Frame<'a>,
 }
 
 impl<'a> ReadInputRegisters<'a> {
+    /// Buffer length needed for the response to a request for `count` registers
+    pub const fn response_buffer_len(count: u16) -> usize {
+        response::ReadInputRegisters::required_buffer_len(count)
+    }
+
     pub fn new(
         frame_buffer: &'a mut [u8],
         address: u8,
@@ -377,6 +519,11 @@ impl<'a> ReadInputRegisters<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn start_index(&self) -> u16 {
         byteorder::BigEndian::read_u16(&self.frame.payload()[0..])
     }
@@ -408,6 +555,31 @@ impl<'a> ReadInputRegisters<'a> {
     ) -> (Frame<'buff>, &'buff mut [u8]) {
         self.frame.response_exception(response_buffer, exception)
     }
+
+    /// The response to this request, checking the address, function and register count
+    ///
+    /// Exception responses are returned as `Error::Exception`
+    pub fn validate_response<'r>(
+        &self,
+        response: Frame<'r>,
+    ) -> Result<response::ReadInputRegisters<'r>, Error> {
+        self.validate_response_with(response, &Quirks::STRICT)
+    }
+
+    /// As `validate_response`, tolerating the byte count `quirks` allows
+    pub fn validate_response_with<'r>(
+        &self,
+        response: Frame<'r>,
+        quirks: &Quirks,
+    ) -> Result<response::ReadInputRegisters<'r>, Error> {
+        check_response(self.frame, response)?;
+        check_byte_count(
+            response,
+            (2 * self.register_count()).into(),
+            quirks.extra_byte_count,
+        )?;
+        response::ReadInputRegisters::try_from(response)
+    }
 }
 
 impl FixedLen for ReadInputRegisters<'_> {
@@ -432,9 +604,16 @@ impl<'a> TryFrom<Frame<'a>> for ReadInputRegisters<'a> {
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
         if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -447,8 +626,20 @@ impl<'a> From<ReadInputRegisters<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for ReadInputRegisters<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "ReadInputRegisters {{ address: {=u8}, start: {=u16}, count: {=u16} }}",
+            self.frame.address(),
+            self.start_index(),
+            self.register_count(),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct WriteCoil<'a> {
     frame: Frame<'a>,
 }
@@ -482,6 +673,11 @@ impl<'a> WriteCoil<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn index(&self) -> u16 {
         byteorder::BigEndian::read_u16(&self.frame.payload()[0..])
     }
@@ -490,10 +686,21 @@ impl<'a> WriteCoil<'a> {
         byteorder::BigEndian::read_u16(&self.frame.payload()[2..])
     }
 
+    /// NOTE: any value other than `COIL_ON` is treated as off, use `coil_state` to reject invalid values
     pub fn is_on(&self) -> bool {
         self.value() == super::COIL_ON
     }
 
+    /// `Ok(true)` for `COIL_ON`, `Ok(false)` for `COIL_OFF`
+    /// and the `ILLEGAL_DATA` exception a server should respond with for any other value
+    pub fn coil_state(&self) -> Result<bool, Exception> {
+        match self.value() {
+            super::COIL_ON => Ok(true),
+            super::COIL_OFF => Ok(false),
+            _ => Err(exception::ILLEGAL_DATA),
+        }
+    }
+
     pub fn response_builder<'buff>(
         &self,
         response_buffer: &'buff mut [u8],
@@ -513,6 +720,19 @@ impl<'a> WriteCoil<'a> {
     ) -> (Frame<'buff>, &'buff mut [u8]) {
         self.frame.response_exception(response_buffer, exception)
     }
+
+    /// The response to this request, checking the address, function and that the request was echoed
+    ///
+    /// Exception responses are returned as `Error::Exception`
+    pub fn validate_response<'r>(
+        &self,
+        response: Frame<'r>,
+    ) -> Result<response::WriteCoil<'r>, Error> {
+        check_response(self.frame, response)?;
+        let response = response::WriteCoil::try_from(response)?;
+        check_echo(self.frame, response.as_frame())?;
+        Ok(response)
+    }
 }
 
 impl FixedLen for WriteCoil<'_> {
@@ -538,9 +758,16 @@ impl<'a> TryFrom<Frame<'a>> for WriteCoil<'a> {
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
         if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -553,8 +780,20 @@ impl<'a> From<WriteCoil<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for WriteCoil<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "WriteCoil {{ address: {=u8}, index: {=u16}, value: {=u16} }}",
+            self.frame.address(),
+            self.index(),
+            self.value(),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct WriteHoldingRegister<'a> {
     frame: Frame<'a>,
 }
@@ -588,6 +827,11 @@ impl<'a> WriteHoldingRegister<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn index(&self) -> u16 {
         byteorder::BigEndian::read_u16(&self.frame.payload()[0..])
     }
@@ -618,6 +862,19 @@ impl<'a> WriteHoldingRegister<'a> {
     ) -> (Frame<'buff>, &'buff mut [u8]) {
         self.frame.response_exception(response_buffer, exception)
     }
+
+    /// The response to this request, checking the address, function and that the request was echoed
+    ///
+    /// Exception responses are returned as `Error::Exception`
+    pub fn validate_response<'r>(
+        &self,
+        response: Frame<'r>,
+    ) -> Result<response::WriteHoldingRegister<'r>, Error> {
+        check_response(self.frame, response)?;
+        let response = response::WriteHoldingRegister::try_from(response)?;
+        check_echo(self.frame, response.as_frame())?;
+        Ok(response)
+    }
 }
 
 impl FixedLen for WriteHoldingRegister<'_> {
@@ -643,9 +900,16 @@ impl<'a> TryFrom<Frame<'a>> for WriteHoldingRegister<'a> {
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
         if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -658,13 +922,31 @@ impl<'a> From<WriteHoldingRegister<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for WriteHoldingRegister<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "WriteHoldingRegister {{ address: {=u8}, index: {=u16}, value: {=u16} }}",
+            self.frame.address(),
+            self.index(),
+            self.value(),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct WriteMultipleCoils<'a> {
     frame: Frame<'a>,
 }
 
 impl<'a> WriteMultipleCoils<'a> {
+    /// Frame length for writing `count` coils, the response is always `response::WriteMultipleCoils::LEN` bytes
+    pub const fn required_buffer_len(count: u16) -> usize {
+        // address(1) + function(1) + start(2) + count(2) + byte count(1) + packed bits + crc(2)
+        9 + (count as usize).div_ceil(8)
+    }
+
     pub fn new(
         frame_buffer: &'a mut [u8],
         address: u8,
@@ -680,6 +962,36 @@ impl<'a> WriteMultipleCoils<'a> {
         (Self::from_frame_unchecked(frame), rem)
     }
 
+    /// Build from coil states already packed least significant bit first, `bitmap` must be `count.div_ceil(8)` bytes
+    ///
+    /// Unused bits of the last byte are cleared
+    pub fn from_bitmap(
+        frame_buffer: &'a mut [u8],
+        address: u8,
+        start_index: u16,
+        count: u16,
+        bitmap: &[u8],
+    ) -> Result<(Self, &'a mut [u8]), Error> {
+        check_quantity(count.into(), MAX_WRITE_COILS)?;
+        let len = usize::from(count).div_ceil(8);
+        if bitmap.len() != len {
+            return Err(Error::InvalidLength(bitmap.len()));
+        }
+        // start(2) + count(2) + byte count(1) + coil bytes
+        check_buffer(frame_buffer, 5 + len)?;
+        let mut builder = builder::build_frame(frame_buffer)
+            .for_address(address)
+            .function(Self::FUNCTION)
+            .registers([start_index, count])
+            .byte(len as u8)
+            .reserve(len);
+        let slot = builder.slot();
+        slot.copy_from_slice(bitmap);
+        slot[len - 1] &= 0xFF >> ((8 - count % 8) % 8);
+        let (frame, rem) = builder.finalise();
+        Ok((Self::from_frame_unchecked(frame), rem))
+    }
+
     pub fn from_bytes_unchecked(bytes: &'a [u8]) -> Self {
         Self {
             frame: Frame::new_unchecked(bytes),
@@ -694,6 +1006,11 @@ impl<'a> WriteMultipleCoils<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn start_index(&self) -> u16 {
         byteorder::BigEndian::read_u16(&self.frame.payload()[0..])
     }
@@ -742,6 +1059,19 @@ impl<'a> WriteMultipleCoils<'a> {
     ) -> (Frame<'buff>, &'buff mut [u8]) {
         self.frame.response_exception(response_buffer, exception)
     }
+
+    /// The response to this request, checking the address, function, start index and count
+    ///
+    /// Exception responses are returned as `Error::Exception`
+    pub fn validate_response<'r>(
+        &self,
+        response: Frame<'r>,
+    ) -> Result<response::WriteMultipleCoils<'r>, Error> {
+        check_response(self.frame, response)?;
+        let response = response::WriteMultipleCoils::try_from(response)?;
+        check_echo(self.frame, response.as_frame())?;
+        Ok(response)
+    }
 }
 
 impl PacketLen for WriteMultipleCoils<'_> {
@@ -773,9 +1103,16 @@ impl<'a> TryFrom<Frame<'a>> for WriteMultipleCoils<'a> {
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
         if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -788,13 +1125,35 @@ impl<'a> From<WriteMultipleCoils<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for WriteMultipleCoils<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        let (values, omitted) = crate::frame::defmt_preview(&self.frame.payload()[5..]);
+        defmt::write!(
+            f,
+            "WriteMultipleCoils {{ address: {=u8}, start: {=u16}, count: {=u16}, values: {=[u8]:02x}..(+{=usize}) }}",
+            self.frame.address(),
+            self.start_index(),
+            self.coil_count(),
+            values,
+            omitted,
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct WriteMultipleHoldingRegisters<'a> {
     frame: Frame<'a>,
 }
 
 impl<'a> WriteMultipleHoldingRegisters<'a> {
+    /// Frame length for writing `count` registers, the response is always
+    /// `response::WriteMultipleHoldingRegisters::LEN` bytes
+    pub const fn required_buffer_len(count: u16) -> usize {
+        // address(1) + function(1) + start(2) + count(2) + byte count(1) + registers + crc(2)
+        9 + 2 * count as usize
+    }
+
     pub fn new(
         frame_buffer: &'a mut [u8],
         address: u8,
@@ -810,6 +1169,29 @@ impl<'a> WriteMultipleHoldingRegisters<'a> {
         (Self::from_frame_unchecked(frame), rem)
     }
 
+    /// As `new` for 1-123 registers, checking the buffer can hold the `9 + 2 * registers.len()` byte frame
+    pub fn from_registers(
+        frame_buffer: &'a mut [u8],
+        address: u8,
+        start_index: u16,
+        registers: &[u16],
+    ) -> Result<(Self, &'a mut [u8]), Error> {
+        check_quantity(registers.len(), MAX_WRITE_REGISTERS)?;
+        let needed = Self::required_buffer_len(registers.len() as u16);
+        if frame_buffer.len() < needed {
+            return Err(Error::InsufficientBuffer {
+                needed,
+                len: frame_buffer.len(),
+            });
+        }
+        Ok(Self::new(
+            frame_buffer,
+            address,
+            start_index,
+            registers.iter().copied(),
+        ))
+    }
+
     pub fn from_bytes_unchecked(bytes: &'a [u8]) -> Self {
         Self {
             frame: Frame::new_unchecked(bytes),
@@ -824,6 +1206,11 @@ impl<'a> WriteMultipleHoldingRegisters<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn payload_len(&self) -> u8 {
         self.frame.payload()[4]
     }
@@ -836,10 +1223,24 @@ impl<'a> WriteMultipleHoldingRegisters<'a> {
         byteorder::BigEndian::read_u16(&self.frame.payload()[2..])
     }
 
+    /// register values as big endian byte pairs borrowed from the frame, convert each with `u16::from_be_bytes`
+    ///
+    /// a fast path for large reads, the slice needs no copy or alignment and iterating it has no per-register bounds checks
+    pub fn payload_as_registers_unaligned(&self) -> &'a [[u8; 2]] {
+        self.frame.payload()[5..].as_chunks().0
+    }
+
     pub fn iter_registers(&'_ self) -> impl Iterator<Item = u16> + '_ {
-        self.frame.payload()[5..]
-            .chunks(2)
-            .map(byteorder::BigEndian::read_u16)
+        self.payload_as_registers_unaligned()
+            .iter()
+            .map(|r| u16::from_be_bytes(*r))
+    }
+
+    /// `(index, value)` for each register written
+    pub fn iter_indexed_registers(&'_ self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.iter_registers()
+            .enumerate()
+            .map(|(idx, value)| (self.start_index() + idx as u16, value))
     }
 
     pub fn response_builder<'buff>(
@@ -867,6 +1268,19 @@ impl<'a> WriteMultipleHoldingRegisters<'a> {
     ) -> (Frame<'buff>, &'buff mut [u8]) {
         self.frame.response_exception(response_buffer, exception)
     }
+
+    /// The response to this request, checking the address, function, start index and count
+    ///
+    /// Exception responses are returned as `Error::Exception`
+    pub fn validate_response<'r>(
+        &self,
+        response: Frame<'r>,
+    ) -> Result<response::WriteMultipleHoldingRegisters<'r>, Error> {
+        check_response(self.frame, response)?;
+        let response = response::WriteMultipleHoldingRegisters::try_from(response)?;
+        check_echo(self.frame, response.as_frame())?;
+        Ok(response)
+    }
 }
 
 impl PacketLen for WriteMultipleHoldingRegisters<'_> {
@@ -898,9 +1312,16 @@ impl<'a> TryFrom<Frame<'a>> for WriteMultipleHoldingRegisters<'a> {
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
         if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -913,9 +1334,780 @@ impl<'a> From<WriteMultipleHoldingRegisters<'a>> for Frame<'a> {
     }
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for WriteMultipleHoldingRegisters<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        let (values, omitted) = crate::frame::defmt_preview(&self.frame.payload()[5..]);
+        defmt::write!(
+            f,
+            "WriteMultipleHoldingRegisters {{ address: {=u8}, start: {=u16}, count: {=u16}, values: {=[u8]:02x}..(+{=usize}) }}",
+            self.frame.address(),
+            self.start_index(),
+            self.register_count(),
+            values,
+            omitted,
+        );
+    }
+}
+
+/// CANopen General Reference (0x2B / MEI 0x0D) request, the CANopen PDU is carried unchanged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CanOpenGeneralReference<'a> {
+    frame: Frame<'a>,
+}
+
+impl<'a> CanOpenGeneralReference<'a> {
+    pub fn new(frame_buffer: &'a mut [u8], address: u8, pdu: &[u8]) -> (Self, &'a mut [u8]) {
+        let (frame, rem) = builder::build_frame(frame_buffer)
+            .for_address(address)
+            .function(Self::FUNCTION)
+            .byte(function::MEI_CANOPEN_GENERAL_REFERENCE)
+            .bytes(pdu.iter().copied())
+            .finalise();
+        (Self::from_frame_unchecked(frame), rem)
+    }
+
+    pub fn from_bytes_unchecked(bytes: &'a [u8]) -> Self {
+        Self {
+            frame: Frame::new_unchecked(bytes),
+        }
+    }
+
+    pub fn from_frame_unchecked(frame: Frame<'a>) -> Self {
+        Self { frame }
+    }
+
+    pub fn as_frame(&self) -> Frame<'a> {
+        self.frame
+    }
+
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
+    /// the CANopen PDU following the MEI type
+    pub fn pdu(&self) -> &'a [u8] {
+        &self.frame.payload()[1..]
+    }
+
+    pub fn response_builder<'buff>(
+        &self,
+        response_buffer: &'buff mut [u8],
+        pdu: &[u8],
+    ) -> (response::CanOpenGeneralReference<'buff>, &'buff mut [u8]) {
+        let (frame, rem) = self
+            .frame
+            .response_builder(response_buffer)
+            .byte(function::MEI_CANOPEN_GENERAL_REFERENCE)
+            .bytes(pdu.iter().copied())
+            .finalise();
+        (
+            response::CanOpenGeneralReference::from_frame_unchecked(frame),
+            rem,
+        )
+    }
+
+    pub fn response_exception<'buff>(
+        &self,
+        response_buffer: &'buff mut [u8],
+        exception: Exception,
+    ) -> (Frame<'buff>, &'buff mut [u8]) {
+        self.frame.response_exception(response_buffer, exception)
+    }
+}
+
+impl PacketLen for CanOpenGeneralReference<'_> {
+    fn packet_len(&self) -> u8 {
+        self.frame.raw_bytes().len() as u8
+    }
+
+    fn minimum_len() -> u8 {
+        5
+    }
+}
+
+impl FunctionCode for CanOpenGeneralReference<'_> {
+    const FUNCTION: Function = function::ENCAPSULATED_INTERFACE_TRANSPORT;
+}
+
+impl<'a> TryFrom<&'a [u8]> for CanOpenGeneralReference<'a> {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        let frame = Frame::try_from(bytes)?;
+        Self::try_from(frame)
+    }
+}
+
+impl<'a> TryFrom<Frame<'a>> for CanOpenGeneralReference<'a> {
+    type Error = crate::Error;
+
+    fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
+        if frame.function() != Self::FUNCTION {
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
+        } else if !Self::is_valid_len(frame.raw_bytes().len()) {
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
+        } else if frame.payload()[0] != function::MEI_CANOPEN_GENERAL_REFERENCE {
+            Err(Error::InvalidValue)
+        } else {
+            Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
+        }
+    }
+}
+
+impl<'a> From<CanOpenGeneralReference<'a>> for Frame<'a> {
+    fn from(command: CanOpenGeneralReference<'_>) -> Frame<'_> {
+        command.frame
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for CanOpenGeneralReference<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        let (pdu, omitted) = crate::frame::defmt_preview(self.pdu());
+        defmt::write!(
+            f,
+            "CanOpenGeneralReference {{ address: {=u8}, pdu: {=[u8]:02x}..(+{=usize}) }}",
+            self.frame.address(),
+            pdu,
+            omitted,
+        );
+    }
+}
+
+/// Most coils/discrete inputs that can be read by a single request
+pub const MAX_READ_BITS: u16 = 2000;
+/// Most registers that can be read by a single request
+pub const MAX_READ_REGISTERS: u16 = 125;
+/// Most coils that can be written by a single request
+pub const MAX_WRITE_COILS: u16 = 1968;
+/// Most registers that can be written by a single request
+pub const MAX_WRITE_REGISTERS: u16 = 123;
+/// Most registers that can be written by a single read/write multiple registers request
+pub const MAX_READ_WRITE_REGISTERS: u16 = 121;
+/// Highest record number allowed in a file record sub-request
+pub const MAX_FILE_RECORD_NUMBER: u16 = 0x270F;
+/// Most bytes of sub-requests in a read file record request
+pub const MAX_READ_FILE_RECORD_BYTES: u16 = 0xF5;
+/// Most bytes of sub-requests, including their data, in a write file record request
+pub const MAX_WRITE_FILE_RECORD_BYTES: u16 = 0xFB;
+
+/// reference type of every file record sub-request
+const FILE_RECORD_REFERENCE: u8 = 6;
+
+/// A file record sub-request for `read_file_record`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FileRecord {
+    /// files are numbered from 1
+    pub file_number: u16,
+    pub record_number: u16,
+    /// number of registers to read
+    pub record_length: u16,
+}
+
+/// A file record sub-request for `write_file_record`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FileRecordData<'d> {
+    /// files are numbered from 1
+    pub file_number: u16,
+    pub record_number: u16,
+    pub data: &'d [u16],
+}
+
+/// Check the buffer can hold a frame with a payload of `payload_len` bytes
+fn check_buffer(buffer: &[u8], payload_len: usize) -> Result<(), Error> {
+    // address(1) + function(1) + payload + crc(2)
+    if buffer.len() < payload_len + 4 {
+        Err(Error::BufferTooSmall)
+    } else {
+        Ok(())
+    }
+}
+
+#[inline]
+fn check_quantity(count: usize, max: u16) -> Result<(), Error> {
+    if count == 0 || count > max.into() {
+        Err(Error::InvalidQuantity)
+    } else {
+        Ok(())
+    }
+}
+
+/// Errors for exception responses and responses which don't belong to `request`
+pub(crate) fn check_response(request: Frame, response: Frame) -> Result<(), Error> {
+    if response.address() != request.address() {
+        return Err(Error::InvalidAddress);
+    }
+    if response.function().0 == request.function().0 | 0x80 {
+        let code = response.payload().first().copied().unwrap_or_default();
+        return Err(Error::Exception(Exception(code)));
+    }
+    if response.function() != request.function() {
+        return Err(Error::UnexpectedFunction {
+            expected: request.function(),
+            found: response.function(),
+        });
+    }
+    Ok(())
+}
+
+/// read responses are `|byte count|data|`, the count may exceed `expected` by up to `extra`
+fn check_byte_count(response: Frame, expected: usize, extra: u8) -> Result<(), Error> {
+    let counts = expected..=expected + usize::from(extra);
+    match response.payload() {
+        [count, data @ ..]
+            if counts.contains(&usize::from(*count)) && data.len() == (*count).into() =>
+        {
+            Ok(())
+        }
+        _ => Err(Error::InvalidQuantity),
+    }
+}
+
+/// write responses repeat the first four bytes of the request payload
+fn check_echo(request: Frame, response: Frame) -> Result<(), Error> {
+    if response.payload().get(..4) == request.payload().get(..4) {
+        Ok(())
+    } else {
+        Err(Error::InvalidValue)
+    }
+}
+
+fn check_file_record(file_number: u16, record_number: u16) -> Result<(), Error> {
+    if file_number == 0 || record_number > MAX_FILE_RECORD_NUMBER {
+        Err(Error::InvalidValue)
+    } else {
+        Ok(())
+    }
+}
+
+/// written without the builder or indexing so the constructors can be checked by `no_panic`
+#[inline]
+fn fixed_request(
+    buffer: &mut [u8],
+    address: u8,
+    function: Function,
+    registers: [u16; 2],
+) -> Result<Frame<'_>, Error> {
+    let frame: &mut [u8; 8] = buffer
+        .get_mut(..8)
+        .and_then(|frame| frame.try_into().ok())
+        .ok_or(Error::BufferTooSmall)?;
+    *frame = fixed_request_const(address, function, registers);
+    Ok(Frame::new_unchecked(frame))
+}
+
+/// Any 8 byte request with two register values, for use in const contexts
+///
+/// ```
+/// use modbus_frames::{function, request};
+///
+/// const REQUEST: [u8; 8] = request::fixed_request_const(0x11, function::READ_HOLDING_REGISTERS, [0x6B, 3]);
+/// assert_eq!(REQUEST, [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87]);
+/// ```
+#[inline]
+pub const fn fixed_request_const(address: u8, function: Function, registers: [u16; 2]) -> [u8; 8] {
+    let [a0, a1] = registers[0].to_be_bytes();
+    let [b0, b1] = registers[1].to_be_bytes();
+    let mut bytes = [address, function.0, a0, a1, b0, b1, 0, 0];
+    let [crc0, crc1] = crate::crc::crc16(bytes.split_at(6).0).to_le_bytes();
+    bytes[6] = crc0;
+    bytes[7] = crc1;
+    bytes
+}
+
+/// Read Coils (0x01) request built at compile time, panics if `count` isn't 1-2000
+pub const fn read_coils_const(address: u8, start_index: u16, count: u16) -> [u8; 8] {
+    assert!(count >= 1 && count <= MAX_READ_BITS, "count must be 1-2000");
+    fixed_request_const(address, function::READ_COILS, [start_index, count])
+}
+
+/// Read Discrete Inputs (0x02) request built at compile time, panics if `count` isn't 1-2000
+pub const fn read_discrete_inputs_const(address: u8, start_index: u16, count: u16) -> [u8; 8] {
+    assert!(count >= 1 && count <= MAX_READ_BITS, "count must be 1-2000");
+    fixed_request_const(
+        address,
+        function::READ_DISCRETE_INPUTS,
+        [start_index, count],
+    )
+}
+
+/// Read Holding Registers (0x03) request built at compile time, panics if `count` isn't 1-125
+pub const fn read_holding_registers_const(address: u8, start_index: u16, count: u16) -> [u8; 8] {
+    assert!(
+        count >= 1 && count <= MAX_READ_REGISTERS,
+        "count must be 1-125"
+    );
+    fixed_request_const(
+        address,
+        function::READ_HOLDING_REGISTERS,
+        [start_index, count],
+    )
+}
+
+/// Read Input Registers (0x04) request built at compile time, panics if `count` isn't 1-125
+pub const fn read_input_registers_const(address: u8, start_index: u16, count: u16) -> [u8; 8] {
+    assert!(
+        count >= 1 && count <= MAX_READ_REGISTERS,
+        "count must be 1-125"
+    );
+    fixed_request_const(
+        address,
+        function::READ_INPUT_REGISTERS,
+        [start_index, count],
+    )
+}
+
+/// Write Single Coil (0x05) request built at compile time
+pub const fn write_coil_const(address: u8, index: u16, on: bool) -> [u8; 8] {
+    let value = if on { crate::COIL_ON } else { crate::COIL_OFF };
+    fixed_request_const(address, function::WRITE_COIL, [index, value])
+}
+
+/// Write Single Register (0x06) request built at compile time
+pub const fn write_holding_register_const(address: u8, index: u16, value: u16) -> [u8; 8] {
+    fixed_request_const(address, function::WRITE_HOLDING_REGISTER, [index, value])
+}
+
+#[inline]
+fn no_data_request(buffer: &mut [u8], address: u8, function: Function) -> Result<Frame<'_>, Error> {
+    let frame: &mut [u8; 4] = buffer
+        .get_mut(..4)
+        .and_then(|frame| frame.try_into().ok())
+        .ok_or(Error::BufferTooSmall)?;
+    let [crc0, crc1] = crate::crc::crc16(&[address, function.0]).to_le_bytes();
+    *frame = [address, function.0, crc0, crc1];
+    Ok(Frame::new_unchecked(frame))
+}
+
+/// `|address|function|start|count|byte count|values|crc|` with the values filled in by `write_values`
+#[inline]
+fn write_multiple_request<'b>(
+    buffer: &'b mut [u8],
+    address: u8,
+    function: Function,
+    [start_index, count]: [u16; 2],
+    byte_count: u8,
+    write_values: impl FnOnce(&mut [u8]),
+) -> Result<Frame<'b>, Error> {
+    let len = 7 + usize::from(byte_count) + 2;
+    let frame = buffer.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+    let (header, rest) = frame.split_at_mut_checked(7).ok_or(Error::BufferTooSmall)?;
+    let [start_hi, start_lo] = start_index.to_be_bytes();
+    let [count_hi, count_lo] = count.to_be_bytes();
+    let fields = [
+        address, function.0, start_hi, start_lo, count_hi, count_lo, byte_count,
+    ];
+    header
+        .iter_mut()
+        .zip(fields)
+        .for_each(|(dst, src)| *dst = src);
+    let (values, crc) = rest
+        .split_at_mut_checked(byte_count.into())
+        .ok_or(Error::BufferTooSmall)?;
+    write_values(values);
+    let value = crate::crc::update(crate::crc::update(crate::crc::INIT, header), values);
+    crc.iter_mut()
+        .zip(value.to_le_bytes())
+        .for_each(|(dst, src)| *dst = src);
+    Ok(Frame::new_unchecked(frame))
+}
+
+/// Read Coils (0x01) request for `count` coils, `count` must be 1-2000
+///
+/// ```
+/// use modbus_frames::{request, Error};
+///
+/// let mut buf = [0; 8];
+/// let frame = request::read_coils(&mut buf, 0x11, 0x13, 0x25).unwrap();
+/// assert_eq!(frame.raw_bytes(), [0x11, 0x01, 0x00, 0x13, 0x00, 0x25, 0x0E, 0x84]);
+/// assert_eq!(request::read_coils(&mut buf, 0x11, 0, 2001), Err(Error::InvalidQuantity));
+/// ```
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn read_coils(
+    buffer: &mut [u8],
+    address: u8,
+    start_index: u16,
+    count: u16,
+) -> Result<Frame<'_>, Error> {
+    check_quantity(count.into(), MAX_READ_BITS)?;
+    fixed_request(buffer, address, function::READ_COILS, [start_index, count])
+}
+
+/// Read Discrete Inputs (0x02) request for `count` inputs, `count` must be 1-2000
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn read_discrete_inputs(
+    buffer: &mut [u8],
+    address: u8,
+    start_index: u16,
+    count: u16,
+) -> Result<Frame<'_>, Error> {
+    check_quantity(count.into(), MAX_READ_BITS)?;
+    fixed_request(
+        buffer,
+        address,
+        function::READ_DISCRETE_INPUTS,
+        [start_index, count],
+    )
+}
+
+/// Read Holding Registers (0x03) request for `count` registers, `count` must be 1-125
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn read_holding_registers(
+    buffer: &mut [u8],
+    address: u8,
+    start_index: u16,
+    count: u16,
+) -> Result<Frame<'_>, Error> {
+    check_quantity(count.into(), MAX_READ_REGISTERS)?;
+    fixed_request(
+        buffer,
+        address,
+        function::READ_HOLDING_REGISTERS,
+        [start_index, count],
+    )
+}
+
+/// Read Input Registers (0x04) request for `count` registers, `count` must be 1-125
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn read_input_registers(
+    buffer: &mut [u8],
+    address: u8,
+    start_index: u16,
+    count: u16,
+) -> Result<Frame<'_>, Error> {
+    check_quantity(count.into(), MAX_READ_REGISTERS)?;
+    fixed_request(
+        buffer,
+        address,
+        function::READ_INPUT_REGISTERS,
+        [start_index, count],
+    )
+}
+
+/// Write Single Coil (0x05) request
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn write_coil(
+    buffer: &mut [u8],
+    address: u8,
+    index: u16,
+    on: bool,
+) -> Result<Frame<'_>, Error> {
+    let value = if on { crate::COIL_ON } else { crate::COIL_OFF };
+    fixed_request(buffer, address, function::WRITE_COIL, [index, value])
+}
+
+/// Write Single Holding Register (0x06) request
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn write_holding_register(
+    buffer: &mut [u8],
+    address: u8,
+    index: u16,
+    value: u16,
+) -> Result<Frame<'_>, Error> {
+    fixed_request(
+        buffer,
+        address,
+        function::WRITE_HOLDING_REGISTER,
+        [index, value],
+    )
+}
+
+/// Read Exception Status (0x07) request
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn read_exception_status(buffer: &mut [u8], address: u8) -> Result<Frame<'_>, Error> {
+    no_data_request(buffer, address, function::READ_EXCEPTION_STATUS)
+}
+
+/// Diagnostics (0x08) request for a sub-function taking a single data value
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn diagnostic(
+    buffer: &mut [u8],
+    address: u8,
+    sub_function: u16,
+    data: u16,
+) -> Result<Frame<'_>, Error> {
+    fixed_request(buffer, address, function::DIAGNOSTIC, [sub_function, data])
+}
+
+/// Get Comm Event Counter (0x0B) request
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn get_comm_event_counter(buffer: &mut [u8], address: u8) -> Result<Frame<'_>, Error> {
+    no_data_request(buffer, address, function::GET_COMM_EVENT_COUNTER)
+}
+
+/// Get Comm Event Log (0x0C) request
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn get_comm_event_log(buffer: &mut [u8], address: u8) -> Result<Frame<'_>, Error> {
+    no_data_request(buffer, address, function::GET_COMM_EVENT_LOG)
+}
+
+/// Write Multiple Coils (0x0F) request, 1-1968 coils may be written
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn write_multiple_coils<'b>(
+    buffer: &'b mut [u8],
+    address: u8,
+    start_index: u16,
+    coils: &[bool],
+) -> Result<Frame<'b>, Error> {
+    check_quantity(coils.len(), MAX_WRITE_COILS)?;
+    let count = [start_index, coils.len() as u16];
+    let byte_count = coils.len().div_ceil(8) as u8;
+    write_multiple_request(
+        buffer,
+        address,
+        function::WRITE_MULTIPLE_COILS,
+        count,
+        byte_count,
+        |values| {
+            // first coil in the LSB
+            for (byte, coils) in values.iter_mut().zip(coils.chunks(8)) {
+                *byte = coils
+                    .iter()
+                    .rev()
+                    .fold(0, |bits, on| bits << 1 | u8::from(*on));
+            }
+        },
+    )
+}
+
+/// Write Multiple Holding Registers (0x10) request, 1-123 registers may be written
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn write_multiple_holding_registers<'b>(
+    buffer: &'b mut [u8],
+    address: u8,
+    start_index: u16,
+    registers: &[u16],
+) -> Result<Frame<'b>, Error> {
+    check_quantity(registers.len(), MAX_WRITE_REGISTERS)?;
+    let count = [start_index, registers.len() as u16];
+    let byte_count = (2 * registers.len()) as u8;
+    let function = function::WRITE_MULTIPLE_HOLDING_REGISTERS;
+    write_multiple_request(buffer, address, function, count, byte_count, |values| {
+        for (bytes, register) in values.chunks_exact_mut(2).zip(registers) {
+            bytes
+                .iter_mut()
+                .zip(register.to_be_bytes())
+                .for_each(|(dst, src)| *dst = src);
+        }
+    })
+}
+
+/// Write Single Coil (0x05) for one coil, otherwise Write Multiple Coils (0x0F)
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn write_coils_auto<'b>(
+    buffer: &'b mut [u8],
+    address: u8,
+    start_index: u16,
+    coils: &[bool],
+) -> Result<Frame<'b>, Error> {
+    match coils {
+        [on] => write_coil(buffer, address, start_index, *on),
+        _ => write_multiple_coils(buffer, address, start_index, coils),
+    }
+}
+
+/// Write Single Holding Register (0x06) for one register, otherwise Write Multiple Holding Registers (0x10)
+///
+/// ```
+/// use modbus_frames::{function, request};
+///
+/// let mut buf = [0; 16];
+/// let frame = request::write_registers_auto(&mut buf, 1, 0, &[5]).unwrap();
+/// assert_eq!(frame.function(), function::WRITE_HOLDING_REGISTER);
+/// let frame = request::write_registers_auto(&mut buf, 1, 0, &[5, 6]).unwrap();
+/// assert_eq!(frame.function(), function::WRITE_MULTIPLE_HOLDING_REGISTERS);
+/// ```
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn write_registers_auto<'b>(
+    buffer: &'b mut [u8],
+    address: u8,
+    start_index: u16,
+    registers: &[u16],
+) -> Result<Frame<'b>, Error> {
+    match registers {
+        [value] => write_holding_register(buffer, address, start_index, *value),
+        _ => write_multiple_holding_registers(buffer, address, start_index, registers),
+    }
+}
+
+/// Report Slave ID (0x11) request
+#[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+pub fn report_slave_id(buffer: &mut [u8], address: u8) -> Result<Frame<'_>, Error> {
+    no_data_request(buffer, address, function::REPORT_SLAVE_ID)
+}
+
+/// Read File Record (0x14) request, at most 35 sub-requests fit in a frame
+pub fn read_file_record<'b>(
+    buffer: &'b mut [u8],
+    address: u8,
+    records: &[FileRecord],
+) -> Result<Frame<'b>, Error> {
+    let byte_count = 7 * records.len();
+    if records.is_empty() || byte_count > MAX_READ_FILE_RECORD_BYTES.into() {
+        return Err(Error::InvalidQuantity);
+    }
+    for record in records {
+        check_file_record(record.file_number, record.record_number)?;
+    }
+    check_buffer(buffer, 1 + byte_count)?;
+    Ok(builder::build_frame(buffer)
+        .for_address(address)
+        .function(function::READ_FILE_RECORD)
+        .byte(byte_count as u8)
+        .for_each(records, |builder, record| {
+            builder.byte(FILE_RECORD_REFERENCE).registers([
+                record.file_number,
+                record.record_number,
+                record.record_length,
+            ])
+        })
+        .finalise()
+        .0)
+}
+
+/// Write File Record (0x15) request
+pub fn write_file_record<'b>(
+    buffer: &'b mut [u8],
+    address: u8,
+    records: &[FileRecordData],
+) -> Result<Frame<'b>, Error> {
+    let byte_count: usize = records.iter().map(|r| 7 + 2 * r.data.len()).sum();
+    if records.is_empty() || byte_count > MAX_WRITE_FILE_RECORD_BYTES.into() {
+        return Err(Error::InvalidQuantity);
+    }
+    for record in records {
+        check_file_record(record.file_number, record.record_number)?;
+    }
+    check_buffer(buffer, 1 + byte_count)?;
+    Ok(builder::build_frame(buffer)
+        .for_address(address)
+        .function(function::WRITE_FILE_RECORD)
+        .byte(byte_count as u8)
+        .for_each(records, |builder, record| {
+            builder
+                .byte(FILE_RECORD_REFERENCE)
+                .registers([
+                    record.file_number,
+                    record.record_number,
+                    record.data.len() as u16,
+                ])
+                .registers(record.data.iter().copied())
+        })
+        .finalise()
+        .0)
+}
+
+/// Mask Write Register (0x16) request
+pub fn mask_write_register(
+    buffer: &mut [u8],
+    address: u8,
+    index: u16,
+    and_mask: u16,
+    or_mask: u16,
+) -> Result<Frame<'_>, Error> {
+    check_buffer(buffer, 6)?;
+    Ok(builder::build_frame(buffer)
+        .for_address(address)
+        .function(function::MASK_WRITE_REGISTER)
+        .registers([index, and_mask, or_mask])
+        .finalise()
+        .0)
+}
+
+/// Read/Write Multiple Registers (0x17) request
+///
+/// 1-125 registers may be read and 1-121 registers written
+pub fn read_write_multiple_registers<'b>(
+    buffer: &'b mut [u8],
+    address: u8,
+    read_start_index: u16,
+    read_count: u16,
+    write_start_index: u16,
+    registers: &[u16],
+) -> Result<Frame<'b>, Error> {
+    check_quantity(read_count.into(), MAX_READ_REGISTERS)?;
+    check_quantity(registers.len(), MAX_READ_WRITE_REGISTERS)?;
+    // read start(2) + read count(2) + write start(2) + write count(2) + byte count(1) + registers
+    check_buffer(buffer, 9 + 2 * registers.len())?;
+    Ok(builder::build_frame(buffer)
+        .for_address(address)
+        .function(function::READ_WRITE_MULTIPLE_REGISTERS)
+        .registers([read_start_index, read_count, write_start_index])
+        .count_registers(registers.iter().copied())
+        .finalise()
+        .0)
+}
+
+/// Read FIFO Queue (0x18) request
+pub fn read_fifo_queue(
+    buffer: &mut [u8],
+    address: u8,
+    fifo_pointer_index: u16,
+) -> Result<Frame<'_>, Error> {
+    check_buffer(buffer, 2)?;
+    Ok(builder::build_frame(buffer)
+        .for_address(address)
+        .function(function::READ_FIFO_QUEUE)
+        .register(fifo_pointer_index)
+        .finalise()
+        .0)
+}
+
+/// Encapsulated Interface Transport (0x2B) request with arbitrary MEI type and data
+pub fn encapsulated_interface_transport<'b>(
+    buffer: &'b mut [u8],
+    address: u8,
+    mei_type: u8,
+    data: &[u8],
+) -> Result<Frame<'b>, Error> {
+    // MEI type(1) + data, at most 252 bytes of payload
+    if data.len() > 251 {
+        return Err(Error::InvalidQuantity);
+    }
+    check_buffer(buffer, 1 + data.len())?;
+    Ok(builder::build_frame(buffer)
+        .for_address(address)
+        .function(function::ENCAPSULATED_INTERFACE_TRANSPORT)
+        .byte(mei_type)
+        .bytes(data.iter().copied())
+        .finalise()
+        .0)
+}
+
+/// Read Device Identification (0x2B / MEI 0x0E) request
+///
+/// `read_device_id_code` is 1 (basic), 2 (regular), 3 (extended) or 4 (single object)
+pub fn read_device_identification(
+    buffer: &mut [u8],
+    address: u8,
+    read_device_id_code: u8,
+    object_id: u8,
+) -> Result<Frame<'_>, Error> {
+    if !(1..=4).contains(&read_device_id_code) {
+        return Err(Error::InvalidValue);
+    }
+    encapsulated_interface_transport(
+        buffer,
+        address,
+        function::MEI_READ_DEVICE_IDENTIFICATION,
+        &[read_device_id_code, object_id],
+    )
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{function, request, COIL_ON};
+    use crate::{exception, function, request, response, Error, COIL_ON};
 
     #[test]
     fn command_read_coils() {
@@ -1019,7 +2211,12 @@ mod tests {
         for command in commands {
             assert_eq!(command.index(), 0xAC);
             assert!(command.is_on());
+            assert_eq!(command.coil_state(), Ok(true));
         }
+
+        let (command, _) = request::WriteCoil::new(&mut buf, 0x11, 0xAC, 0x1234);
+        assert!(!command.is_on());
+        assert_eq!(command.coil_state(), Err(exception::ILLEGAL_DATA));
     }
 
     #[test]
@@ -1075,6 +2272,21 @@ mod tests {
             .collect::<Vec<_>>();
             assert_eq!(coils, desired);
         }
+
+        // padding bits are cleared
+        let mut bitmap_buf = [0; 16];
+        let (command, _) =
+            request::WriteMultipleCoils::from_bitmap(&mut bitmap_buf, 0xB, 27, 9, &[0x4D, 0xFF])
+                .unwrap();
+        assert_eq!(command.as_frame(), frame);
+        assert_eq!(
+            request::WriteMultipleCoils::from_bitmap(&mut bitmap_buf, 0xB, 27, 9, &[0x4D]),
+            Err(Error::InvalidLength(1))
+        );
+        assert_eq!(
+            request::WriteMultipleCoils::from_bitmap(&mut bitmap_buf, 0xB, 27, 0, &[]),
+            Err(Error::InvalidQuantity)
+        );
     }
 
     #[test]
@@ -1101,6 +2313,302 @@ mod tests {
             let registers = command.iter_registers().collect::<Vec<_>>();
             let desired = [0xA, 0x0102];
             assert_eq!(registers, desired);
+            assert_eq!(
+                command.iter_indexed_registers().collect::<Vec<_>>(),
+                [(1, 0xA), (2, 0x0102)]
+            );
         }
+
+        let mut slice_buf = [0; 13];
+        assert_eq!(
+            request::WriteMultipleHoldingRegisters::from_registers(
+                &mut slice_buf[..12],
+                0x11,
+                1,
+                &[0xA, 0x0102]
+            ),
+            Err(Error::InsufficientBuffer {
+                needed: 13,
+                len: 12
+            })
+        );
+        let (command, _) = request::WriteMultipleHoldingRegisters::from_registers(
+            &mut slice_buf,
+            0x11,
+            1,
+            &[0xA, 0x0102],
+        )
+        .unwrap();
+        assert_eq!(command.as_frame(), frame);
+        assert_eq!(
+            request::WriteMultipleHoldingRegisters::from_registers(&mut buf, 0x11, 1, &[0; 124]),
+            Err(Error::InvalidQuantity)
+        );
+    }
+
+    #[test]
+    fn validate_response() {
+        let mut req = [0; 8];
+        let mut res = [0; 16];
+        let request = request::ReadHoldingRegisters::new(&mut req, 1, 0, 2).0;
+
+        let (response, _) = crate::response::ReadHoldingRegisters::new(&mut res, 1, [1, 2]);
+        let response = request.validate_response(response.as_frame()).unwrap();
+        assert_eq!(response.iter_registers().collect::<Vec<_>>(), [1, 2]);
+
+        let (response, _) = crate::response::ReadHoldingRegisters::new(&mut res, 1, [1]);
+        assert_eq!(
+            request.validate_response(response.as_frame()),
+            Err(Error::InvalidQuantity)
+        );
+        let (response, _) = crate::response::ReadHoldingRegisters::new(&mut res, 2, [1, 2]);
+        assert_eq!(
+            request.validate_response(response.as_frame()),
+            Err(Error::InvalidAddress)
+        );
+        let (response, _) = request.response_exception(&mut res, exception::ILLEGAL_ADDRESS);
+        assert_eq!(
+            request.validate_response(response),
+            Err(Error::Exception(exception::ILLEGAL_ADDRESS))
+        );
+        let (response, _) = crate::response::ReadInputRegisters::new(&mut res, 1, [1, 2]);
+        assert!(matches!(
+            request.validate_response(response.as_frame()),
+            Err(Error::UnexpectedFunction { .. })
+        ));
+
+        let request = request::WriteHoldingRegister::new(&mut req, 1, 4, 5).0;
+        let (response, _) = crate::response::WriteHoldingRegister::new(&mut res, 1, 4, 5);
+        assert!(request.validate_response(response.as_frame()).is_ok());
+        let (response, _) = crate::response::WriteHoldingRegister::new(&mut res, 1, 4, 6);
+        assert_eq!(
+            request.validate_response(response.as_frame()),
+            Err(Error::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn auto_write_function() {
+        let mut buf = [0; 16];
+        let frame = request::write_coils_auto(&mut buf, 1, 2, &[true]).unwrap();
+        assert_eq!(frame.raw_bytes()[..6], [1, 0x05, 0, 2, 0xFF, 0]);
+        let frame = request::write_coils_auto(&mut buf, 1, 2, &[true, false]).unwrap();
+        assert_eq!(frame.function(), function::WRITE_MULTIPLE_COILS);
+        assert_eq!(
+            request::write_coils_auto(&mut buf, 1, 2, &[]),
+            Err(Error::InvalidQuantity)
+        );
+
+        let frame = request::write_registers_auto(&mut buf, 1, 2, &[7]).unwrap();
+        assert_eq!(frame.raw_bytes()[..6], [1, 0x06, 0, 2, 0, 7]);
+        let frame = request::write_registers_auto(&mut buf, 1, 2, &[7, 8]).unwrap();
+        assert_eq!(frame.function(), function::WRITE_MULTIPLE_HOLDING_REGISTERS);
+        assert_eq!(
+            request::write_registers_auto(&mut buf, 1, 2, &[]),
+            Err(Error::InvalidQuantity)
+        );
+    }
+
+    #[test]
+    fn const_requests() {
+        const READ: [u8; 8] = super::read_input_registers_const(0x11, 0x08, 1);
+        const WRITE: [u8; 8] = super::write_coil_const(0x11, 0xAC, true);
+        let mut buf = [0; 8];
+        assert_eq!(
+            READ,
+            super::read_input_registers(&mut buf, 0x11, 0x08, 1)
+                .unwrap()
+                .raw_bytes()
+        );
+        assert_eq!(
+            WRITE,
+            super::write_coil(&mut buf, 0x11, 0xAC, true)
+                .unwrap()
+                .raw_bytes()
+        );
+    }
+
+    #[test]
+    fn one_shot_requests() {
+        let mut buf = [0; 256];
+        let frame = request::write_multiple_holding_registers(&mut buf, 0x11, 1, &[0xA, 0x102]);
+        assert_eq!(
+            frame.unwrap().raw_bytes(),
+            [0x11, 0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02, 0xC6, 0xF0]
+        );
+        let frame = request::write_multiple_coils(&mut buf, 0xB, 27, &[true; 9]).unwrap();
+        assert_eq!(frame.payload(), [0, 27, 0, 9, 2, 0xFF, 0x01]);
+
+        // example from the modbus application protocol specification
+        let records = [
+            request::FileRecord {
+                file_number: 4,
+                record_number: 1,
+                record_length: 2,
+            },
+            request::FileRecord {
+                file_number: 3,
+                record_number: 9,
+                record_length: 2,
+            },
+        ];
+        let frame = request::read_file_record(&mut buf, 1, &records).unwrap();
+        assert_eq!(
+            frame.payload(),
+            [
+                0x0E, 0x06, 0x00, 0x04, 0x00, 0x01, 0x00, 0x02, 0x06, 0x00, 0x03, 0x00, 0x09, 0x00,
+                0x02
+            ]
+        );
+        let frame = request::read_write_multiple_registers(&mut buf, 1, 3, 6, 0xE, &[0xFF; 3]);
+        assert_eq!(
+            frame.unwrap().payload(),
+            [0x00, 0x03, 0x00, 0x06, 0x00, 0x0E, 0x00, 0x03, 0x06, 0, 0xFF, 0, 0xFF, 0, 0xFF]
+        );
+
+        assert_eq!(
+            request::read_holding_registers(&mut buf, 1, 0, 126),
+            Err(Error::InvalidQuantity)
+        );
+        assert_eq!(
+            request::write_multiple_holding_registers(&mut buf, 1, 0, &[]),
+            Err(Error::InvalidQuantity)
+        );
+        assert_eq!(
+            request::read_device_identification(&mut buf, 1, 5, 0),
+            Err(Error::InvalidValue)
+        );
+        assert_eq!(
+            request::read_coils(&mut buf[..7], 1, 0, 1),
+            Err(Error::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn file_record_limits() {
+        let mut buf = [0; 256];
+        // a single sub-request of 122 registers is the largest write, 7 + 2 * 122 = 0xFB bytes
+        let data = [0x1234; 122];
+        let record = request::FileRecordData {
+            file_number: 1,
+            record_number: 0,
+            data: &data,
+        };
+        let frame = request::write_file_record(&mut buf, 1, &[record]).unwrap();
+        assert_eq!(frame.payload()[0], 0xFB);
+        assert_eq!(crate::server::validate_request_frame(frame), Ok(()));
+        let data = [0x1234; 123];
+        let record = request::FileRecordData {
+            data: &data,
+            ..record
+        };
+        assert_eq!(
+            request::write_file_record(&mut buf, 1, &[record]),
+            Err(Error::InvalidQuantity)
+        );
+
+        // 35 sub-requests of 7 bytes are the largest read
+        let record = request::FileRecord {
+            file_number: 1,
+            record_number: 0,
+            record_length: 1,
+        };
+        let frame = request::read_file_record(&mut buf, 1, &[record; 35]).unwrap();
+        assert_eq!(frame.payload()[0], 0xF5);
+        assert_eq!(crate::server::validate_request_frame(frame), Ok(()));
+        assert_eq!(
+            request::read_file_record(&mut buf, 1, &[record; 36]),
+            Err(Error::InvalidQuantity)
+        );
+    }
+
+    #[test]
+    fn canopen_general_reference() {
+        let mut req = [0; 16];
+        let mut res = [0; 16];
+        let pdu = [0x40, 0x00, 0x10, 0x00];
+        let (request, _) = request::CanOpenGeneralReference::new(&mut req, 3, &pdu);
+        let request = request::CanOpenGeneralReference::try_from(request.as_frame()).unwrap();
+        assert_eq!(request.pdu(), pdu);
+
+        let (response, _) = request.response_builder(&mut res, &[0x43, 0x00, 0x10, 0x00, 1]);
+        let response = response::CanOpenGeneralReference::try_from(response.as_frame()).unwrap();
+        assert_eq!(response.pdu(), [0x43, 0x00, 0x10, 0x00, 1]);
+
+        let (exception, _) = request.response_exception(&mut res, exception::ILLEGAL_FUNCTION);
+        assert_eq!(
+            response::CanOpenGeneralReference::try_from(exception),
+            Err(Error::Exception(exception::ILLEGAL_FUNCTION))
+        );
+
+        // other MEI types aren't CANopen
+        let frame = request::read_device_identification(&mut req, 3, 1, 0).unwrap();
+        assert_eq!(
+            request::CanOpenGeneralReference::try_from(frame),
+            Err(Error::InvalidValue)
+        );
+        let frame = request::encapsulated_interface_transport(&mut req, 3, 0x0D, &[]).unwrap();
+        assert!(request::CanOpenGeneralReference::try_from(frame).is_ok());
+    }
+
+    #[test]
+    fn buffer_lens() {
+        let mut buf = [0; 256];
+        let coils = [true; 19];
+        let (response, _) = response::ReadCoils::new(&mut buf, 1, coils);
+        assert_eq!(
+            response.as_frame().raw_bytes().len(),
+            request::ReadCoils::response_buffer_len(19)
+        );
+        let (response, _) = response::ReadInputRegisters::new(&mut buf, 1, [1, 2, 3]);
+        assert_eq!(
+            response.as_frame().raw_bytes().len(),
+            request::ReadInputRegisters::response_buffer_len(3)
+        );
+        let (command, _) = request::WriteMultipleCoils::new(&mut buf, 1, 0, coils);
+        assert_eq!(
+            command.as_frame().raw_bytes().len(),
+            request::WriteMultipleCoils::required_buffer_len(19)
+        );
+        assert_eq!(
+            request::ReadHoldingRegisters::response_buffer_len(request::MAX_READ_REGISTERS),
+            255
+        );
+    }
+
+    #[test]
+    fn raw_payload() {
+        // a vendor appending a byte to read holding registers
+        let mut buf = [0; 9];
+        let (frame, _) = crate::builder::build_frame(&mut buf)
+            .for_address(1)
+            .function(function::READ_HOLDING_REGISTERS)
+            .registers([0x6B, 3])
+            .byte(0xA5)
+            .finalise();
+        assert!(request::ReadHoldingRegisters::try_from(frame).is_err());
+        let command = request::ReadHoldingRegisters::from_frame_unchecked(frame);
+        assert_eq!(command.register_count(), 3);
+        assert_eq!(command.payload().last(), Some(&0xA5));
+    }
+
+    #[test]
+    fn crc_checked_once() {
+        let mut buf = [0; 256];
+        let registers: Vec<u16> = (0..123).collect();
+        let frame =
+            request::write_multiple_holding_registers(&mut buf, 0x11, 0, &registers).unwrap();
+        let mut corrupt = frame.raw_bytes().to_vec();
+        *corrupt.last_mut().unwrap() ^= 0xFF;
+
+        assert_eq!(
+            request::WriteMultipleHoldingRegisters::try_from(corrupt.as_slice()),
+            Err(Error::InvalidCrc)
+        );
+        // converting a frame doesn't check the CRC again
+        let unchecked = crate::Frame::new_unchecked(&corrupt);
+        let command = request::WriteMultipleHoldingRegisters::try_from(unchecked).unwrap();
+        assert_eq!(command.register_count(), 123);
+        assert!(crate::decoder::CommonRequests::try_from(unchecked).is_ok());
     }
 }
@@ -1,15 +1,36 @@
-use crate::{builder, function, Error, FixedLen, Frame, Function, FunctionCode, PacketLen};
+use crate::{
+    builder, function, request, Error, FixedLen, Frame, Function, FunctionCode, PacketLen,
+};
 
 use bitvec::prelude::*;
 use byteorder::ByteOrder;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// The error for an exception response
+///
+/// `Error::Exception` if the frame is a well formed exception response, otherwise `Error::DecodeInvalidLength`
+/// so a device reported exception can always be told apart from a corrupt frame
+pub fn exception_error(frame: Frame) -> Error {
+    match frame.try_payload().unwrap_or_default() {
+        [code] if frame.function().0 & 0x80 != 0 => Error::Exception(crate::Exception(*code)),
+        _ => Error::DecodeInvalidLength {
+            function: frame.function(),
+            len: frame.raw_bytes().len(),
+            expected: crate::LenHint::Exact(5),
+        },
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ReadCoils<'a> {
     frame: Frame<'a>,
 }
 
 impl<'a> ReadCoils<'a> {
+    /// Frame length for `count` coils, address(1) + function(1) + byte count(1) + packed bits + crc(2)
+    pub const fn required_buffer_len(count: u16) -> usize {
+        5 + (count as usize).div_ceil(8)
+    }
+
     pub fn new(
         frame_buffer: &'a mut [u8],
         address: u8,
@@ -37,6 +58,11 @@ impl<'a> ReadCoils<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn payload_len(&self) -> u8 {
         self.frame.payload()[0]
     }
@@ -52,6 +78,34 @@ impl<'a> ReadCoils<'a> {
             .iter()
             .map(|bit| *bit)
     }
+
+    /// Copy coils into `coils`, returning the number copied
+    ///
+    /// As with `iter_coils` the padding bits of the last byte are included if `coils` is long enough
+    pub fn copy_coils_into(&self, coils: &mut [bool]) -> usize {
+        let mut count = 0;
+        for (dst, src) in coils.iter_mut().zip(self.iter_coils()) {
+            *dst = src;
+            count += 1;
+        }
+        count
+    }
+
+    /// Copy the packed bytes, least significant bit first, into `bytes`, returning the number copied
+    pub fn copy_packed_into(&self, bytes: &mut [u8]) -> usize {
+        let packed = &self.frame.payload()[1..];
+        let len = bytes.len().min(packed.len());
+        bytes[..len].copy_from_slice(&packed[..len]);
+        len
+    }
+
+    /// As `iter_coils` but stops after the number of coils requested instead of including the padding bits
+    pub fn iter_coils_counted(
+        &'_ self,
+        request: &request::ReadCoils,
+    ) -> impl Iterator<Item = bool> + '_ {
+        self.iter_coils().take(request.coil_count().into())
+    }
 }
 
 impl PacketLen for ReadCoils<'_> {
@@ -81,10 +135,19 @@ impl<'a> TryFrom<Frame<'a>> for ReadCoils<'a> {
     type Error = crate::Error;
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
-        if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+        if frame.function().0 == Self::FUNCTION.0 | 0x80 {
+            Err(exception_error(frame))
+        } else if frame.function() != Self::FUNCTION {
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -97,13 +160,31 @@ impl<'a> From<ReadCoils<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for ReadCoils<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        let (values, omitted) = crate::frame::defmt_preview(&self.frame.payload()[1..]);
+        defmt::write!(
+            f,
+            "ReadCoils {{ address: {=u8}, values: {=[u8]:02x}..(+{=usize}) }}",
+            self.frame.address(),
+            values,
+            omitted,
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ReadDiscreteInputs<'a> {
     frame: Frame<'a>,
 }
 
 impl<'a> ReadDiscreteInputs<'a> {
+    /// Frame length for `count` inputs, address(1) + function(1) + byte count(1) + packed bits + crc(2)
+    pub const fn required_buffer_len(count: u16) -> usize {
+        5 + (count as usize).div_ceil(8)
+    }
+
     pub fn new(
         frame_buffer: &'a mut [u8],
         address: u8,
@@ -131,6 +212,11 @@ impl<'a> ReadDiscreteInputs<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn payload_len(&self) -> u8 {
         self.frame.payload()[0]
     }
@@ -146,6 +232,34 @@ impl<'a> ReadDiscreteInputs<'a> {
             .iter()
             .map(|bit| *bit)
     }
+
+    /// Copy inputs into `inputs`, returning the number copied
+    ///
+    /// As with `iter_inputs` the padding bits of the last byte are included if `inputs` is long enough
+    pub fn copy_inputs_into(&self, inputs: &mut [bool]) -> usize {
+        let mut count = 0;
+        for (dst, src) in inputs.iter_mut().zip(self.iter_inputs()) {
+            *dst = src;
+            count += 1;
+        }
+        count
+    }
+
+    /// Copy the packed bytes, least significant bit first, into `bytes`, returning the number copied
+    pub fn copy_packed_into(&self, bytes: &mut [u8]) -> usize {
+        let packed = &self.frame.payload()[1..];
+        let len = bytes.len().min(packed.len());
+        bytes[..len].copy_from_slice(&packed[..len]);
+        len
+    }
+
+    /// As `iter_inputs` but stops after the number of inputs requested instead of including the padding bits
+    pub fn iter_inputs_counted(
+        &'_ self,
+        request: &request::ReadDiscreteInputs,
+    ) -> impl Iterator<Item = bool> + '_ {
+        self.iter_inputs().take(request.input_count().into())
+    }
 }
 
 impl PacketLen for ReadDiscreteInputs<'_> {
@@ -175,10 +289,19 @@ impl<'a> TryFrom<Frame<'a>> for ReadDiscreteInputs<'a> {
     type Error = crate::Error;
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
-        if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+        if frame.function().0 == Self::FUNCTION.0 | 0x80 {
+            Err(exception_error(frame))
+        } else if frame.function() != Self::FUNCTION {
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -191,13 +314,31 @@ impl<'a> From<ReadDiscreteInputs<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for ReadDiscreteInputs<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        let (values, omitted) = crate::frame::defmt_preview(&self.frame.payload()[1..]);
+        defmt::write!(
+            f,
+            "ReadDiscreteInputs {{ address: {=u8}, values: {=[u8]:02x}..(+{=usize}) }}",
+            self.frame.address(),
+            values,
+            omitted,
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ReadHoldingRegisters<'a> {
     frame: Frame<'a>,
 }
 
 impl<'a> ReadHoldingRegisters<'a> {
+    /// Frame length for `count` registers, address(1) + function(1) + byte count(1) + registers + crc(2)
+    pub const fn required_buffer_len(count: u16) -> usize {
+        5 + 2 * count as usize
+    }
+
     pub fn new(
         frame_buffer: &'a mut [u8],
         address: u8,
@@ -225,14 +366,48 @@ impl<'a> ReadHoldingRegisters<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn payload_len(&self) -> u8 {
         self.frame.payload()[0]
     }
 
+    /// register values as big endian byte pairs borrowed from the frame, convert each with `u16::from_be_bytes`
+    ///
+    /// a fast path for large reads, the slice needs no copy or alignment and iterating it has no per-register bounds checks
+    pub fn payload_as_registers_unaligned(&self) -> &'a [[u8; 2]] {
+        self.frame.payload()[1..].as_chunks().0
+    }
+
     pub fn iter_registers(&'_ self) -> impl Iterator<Item = u16> + '_ {
-        self.frame.payload()[1..]
-            .chunks(2)
-            .map(byteorder::BigEndian::read_u16)
+        self.payload_as_registers_unaligned()
+            .iter()
+            .map(|r| u16::from_be_bytes(*r))
+    }
+
+    /// `(index, value)` for each register, the indices are those of `request`
+    /// Copy register values into `registers`, returning the number copied
+    ///
+    /// Stops at the end of `registers` or the payload, whichever is shorter
+    pub fn copy_registers_into(&self, registers: &mut [u16]) -> usize {
+        let values = self.payload_as_registers_unaligned();
+        for (dst, src) in registers.iter_mut().zip(values) {
+            *dst = u16::from_be_bytes(*src);
+        }
+        registers.len().min(values.len())
+    }
+
+    pub fn iter_indexed_registers(
+        &'_ self,
+        request: &request::ReadHoldingRegisters,
+    ) -> impl Iterator<Item = (u16, u16)> + '_ {
+        let start = request.start_index();
+        self.iter_registers()
+            .enumerate()
+            .map(move |(idx, value)| (start + idx as u16, value))
     }
 }
 
@@ -263,10 +438,19 @@ impl<'a> TryFrom<Frame<'a>> for ReadHoldingRegisters<'a> {
     type Error = crate::Error;
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
-        if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+        if frame.function().0 == Self::FUNCTION.0 | 0x80 {
+            Err(exception_error(frame))
+        } else if frame.function() != Self::FUNCTION {
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -279,13 +463,31 @@ impl<'a> From<ReadHoldingRegisters<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for ReadHoldingRegisters<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        let (values, omitted) = crate::frame::defmt_preview(&self.frame.payload()[1..]);
+        defmt::write!(
+            f,
+            "ReadHoldingRegisters {{ address: {=u8}, values: {=[u8]:02x}..(+{=usize}) }}",
+            self.frame.address(),
+            values,
+            omitted,
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ReadInputRegisters<'a> {
     frame: Frame<'a>,
 }
 
 impl<'a> ReadInputRegisters<'a> {
+    /// Frame length for `count` registers, address(1) + function(1) + byte count(1) + registers + crc(2)
+    pub const fn required_buffer_len(count: u16) -> usize {
+        5 + 2 * count as usize
+    }
+
     pub fn new(
         frame_buffer: &'a mut [u8],
         address: u8,
@@ -313,14 +515,48 @@ impl<'a> ReadInputRegisters<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn payload_len(&self) -> u8 {
         self.frame.payload()[0]
     }
 
+    /// register values as big endian byte pairs borrowed from the frame, convert each with `u16::from_be_bytes`
+    ///
+    /// a fast path for large reads, the slice needs no copy or alignment and iterating it has no per-register bounds checks
+    pub fn payload_as_registers_unaligned(&self) -> &'a [[u8; 2]] {
+        self.frame.payload()[1..].as_chunks().0
+    }
+
     pub fn iter_registers(&'_ self) -> impl Iterator<Item = u16> + '_ {
-        self.frame.payload()[1..]
-            .chunks(2)
-            .map(byteorder::BigEndian::read_u16)
+        self.payload_as_registers_unaligned()
+            .iter()
+            .map(|r| u16::from_be_bytes(*r))
+    }
+
+    /// `(index, value)` for each register, the indices are those of `request`
+    /// Copy register values into `registers`, returning the number copied
+    ///
+    /// Stops at the end of `registers` or the payload, whichever is shorter
+    pub fn copy_registers_into(&self, registers: &mut [u16]) -> usize {
+        let values = self.payload_as_registers_unaligned();
+        for (dst, src) in registers.iter_mut().zip(values) {
+            *dst = u16::from_be_bytes(*src);
+        }
+        registers.len().min(values.len())
+    }
+
+    pub fn iter_indexed_registers(
+        &'_ self,
+        request: &request::ReadInputRegisters,
+    ) -> impl Iterator<Item = (u16, u16)> + '_ {
+        let start = request.start_index();
+        self.iter_registers()
+            .enumerate()
+            .map(move |(idx, value)| (start + idx as u16, value))
     }
 }
 
@@ -351,10 +587,19 @@ impl<'a> TryFrom<Frame<'a>> for ReadInputRegisters<'a> {
     type Error = crate::Error;
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
-        if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+        if frame.function().0 == Self::FUNCTION.0 | 0x80 {
+            Err(exception_error(frame))
+        } else if frame.function() != Self::FUNCTION {
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -367,8 +612,21 @@ impl<'a> From<ReadInputRegisters<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for ReadInputRegisters<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        let (values, omitted) = crate::frame::defmt_preview(&self.frame.payload()[1..]);
+        defmt::write!(
+            f,
+            "ReadInputRegisters {{ address: {=u8}, values: {=[u8]:02x}..(+{=usize}) }}",
+            self.frame.address(),
+            values,
+            omitted,
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct WriteCoil<'a> {
     frame: Frame<'a>,
 }
@@ -409,6 +667,11 @@ impl<'a> WriteCoil<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn index(&self) -> u16 {
         byteorder::BigEndian::read_u16(&self.frame.payload()[0..])
     }
@@ -440,10 +703,19 @@ impl<'a> TryFrom<Frame<'a>> for WriteCoil<'a> {
     type Error = crate::Error;
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
-        if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+        if frame.function().0 == Self::FUNCTION.0 | 0x80 {
+            Err(exception_error(frame))
+        } else if frame.function() != Self::FUNCTION {
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -456,8 +728,20 @@ impl<'a> From<WriteCoil<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for WriteCoil<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "WriteCoil {{ address: {=u8}, index: {=u16}, on: {=bool} }}",
+            self.frame.address(),
+            self.index(),
+            self.is_on(),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct WriteHoldingRegister<'a> {
     frame: Frame<'a>,
 }
@@ -491,6 +775,11 @@ impl<'a> WriteHoldingRegister<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn index(&self) -> u16 {
         byteorder::BigEndian::read_u16(&self.frame.payload()[0..])
     }
@@ -522,10 +811,19 @@ impl<'a> TryFrom<Frame<'a>> for WriteHoldingRegister<'a> {
     type Error = crate::Error;
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
-        if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+        if frame.function().0 == Self::FUNCTION.0 | 0x80 {
+            Err(exception_error(frame))
+        } else if frame.function() != Self::FUNCTION {
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -538,8 +836,20 @@ impl<'a> From<WriteHoldingRegister<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for WriteHoldingRegister<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "WriteHoldingRegister {{ address: {=u8}, index: {=u16}, value: {=u16} }}",
+            self.frame.address(),
+            self.index(),
+            self.value(),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct WriteMultipleCoils<'a> {
     frame: Frame<'a>,
 }
@@ -573,6 +883,11 @@ impl<'a> WriteMultipleCoils<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn start_index(&self) -> u16 {
         byteorder::BigEndian::read_u16(&self.frame.payload()[0..])
     }
@@ -603,10 +918,19 @@ impl<'a> TryFrom<Frame<'a>> for WriteMultipleCoils<'a> {
     type Error = crate::Error;
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
-        if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+        if frame.function().0 == Self::FUNCTION.0 | 0x80 {
+            Err(exception_error(frame))
+        } else if frame.function() != Self::FUNCTION {
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -619,8 +943,20 @@ impl<'a> From<WriteMultipleCoils<'a>> for Frame<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "defmt")]
+impl defmt::Format for WriteMultipleCoils<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "WriteMultipleCoils {{ address: {=u8}, start: {=u16}, count: {=u16} }}",
+            self.frame.address(),
+            self.start_index(),
+            self.register_count(),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct WriteMultipleHoldingRegisters<'a> {
     frame: Frame<'a>,
 }
@@ -654,6 +990,11 @@ impl<'a> WriteMultipleHoldingRegisters<'a> {
         self.frame
     }
 
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
     pub fn start_index(&self) -> u16 {
         byteorder::BigEndian::read_u16(&self.frame.payload()[0..])
     }
@@ -684,10 +1025,19 @@ impl<'a> TryFrom<Frame<'a>> for WriteMultipleHoldingRegisters<'a> {
     type Error = crate::Error;
 
     fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
-        if frame.function() != Self::FUNCTION {
-            Err(Error::UnexpectedFunction)
+        if frame.function().0 == Self::FUNCTION.0 | 0x80 {
+            Err(exception_error(frame))
+        } else if frame.function() != Self::FUNCTION {
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
         } else if !Self::is_valid_len(frame.raw_bytes().len()) {
-            Err(Error::DecodeInvalidLength)
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
         } else {
             Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
         }
@@ -700,13 +1050,137 @@ impl<'a> From<WriteMultipleHoldingRegisters<'a>> for Frame<'a> {
     }
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for WriteMultipleHoldingRegisters<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "WriteMultipleHoldingRegisters {{ address: {=u8}, start: {=u16}, count: {=u16} }}",
+            self.frame.address(),
+            self.start_index(),
+            self.register_count(),
+        );
+    }
+}
+
+/// CANopen General Reference (0x2B / MEI 0x0D) response, the CANopen PDU is carried unchanged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CanOpenGeneralReference<'a> {
+    frame: Frame<'a>,
+}
+
+impl<'a> CanOpenGeneralReference<'a> {
+    pub fn new(frame_buffer: &'a mut [u8], address: u8, pdu: &[u8]) -> (Self, &'a mut [u8]) {
+        let (frame, rem) = builder::build_frame(frame_buffer)
+            .for_address(address)
+            .function(Self::FUNCTION)
+            .byte(function::MEI_CANOPEN_GENERAL_REFERENCE)
+            .bytes(pdu.iter().copied())
+            .finalise();
+        (Self::from_frame_unchecked(frame), rem)
+    }
+
+    pub fn from_bytes_unchecked(bytes: &'a [u8]) -> Self {
+        Self {
+            frame: Frame::new_unchecked(bytes),
+        }
+    }
+
+    pub fn from_frame_unchecked(frame: Frame<'a>) -> Self {
+        Self { frame }
+    }
+
+    pub fn as_frame(&self) -> Frame<'a> {
+        self.frame
+    }
+
+    /// all bytes between the function code and the CRC
+    pub fn payload(&self) -> &'a [u8] {
+        self.frame.payload()
+    }
+
+    /// the CANopen PDU following the MEI type
+    pub fn pdu(&self) -> &'a [u8] {
+        &self.frame.payload()[1..]
+    }
+}
+
+impl PacketLen for CanOpenGeneralReference<'_> {
+    fn packet_len(&self) -> u8 {
+        self.frame.raw_bytes().len() as u8
+    }
+
+    fn minimum_len() -> u8 {
+        5
+    }
+}
+
+impl FunctionCode for CanOpenGeneralReference<'_> {
+    const FUNCTION: Function = function::ENCAPSULATED_INTERFACE_TRANSPORT;
+}
+
+impl<'a> TryFrom<&'a [u8]> for CanOpenGeneralReference<'a> {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        let frame = Frame::try_from(bytes)?;
+        Self::try_from(frame)
+    }
+}
+
+impl<'a> TryFrom<Frame<'a>> for CanOpenGeneralReference<'a> {
+    type Error = crate::Error;
+
+    fn try_from(frame: Frame<'a>) -> Result<Self, Self::Error> {
+        if frame.function().0 == Self::FUNCTION.0 | 0x80 {
+            Err(exception_error(frame))
+        } else if frame.function() != Self::FUNCTION {
+            Err(Error::UnexpectedFunction {
+                expected: Self::FUNCTION,
+                found: frame.function(),
+            })
+        } else if !Self::is_valid_len(frame.raw_bytes().len()) {
+            Err(Error::DecodeInvalidLength {
+                function: Self::FUNCTION,
+                len: frame.raw_bytes().len(),
+                expected: Self::len_hint(),
+            })
+        } else if frame.payload()[0] != function::MEI_CANOPEN_GENERAL_REFERENCE {
+            Err(Error::InvalidValue)
+        } else {
+            Ok(Self::from_bytes_unchecked(frame.into_raw_bytes()))
+        }
+    }
+}
+
+impl<'a> From<CanOpenGeneralReference<'a>> for Frame<'a> {
+    fn from(command: CanOpenGeneralReference<'_>) -> Frame<'_> {
+        command.frame
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for CanOpenGeneralReference<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        let (pdu, omitted) = crate::frame::defmt_preview(self.pdu());
+        defmt::write!(
+            f,
+            "CanOpenGeneralReference {{ address: {=u8}, pdu: {=[u8]:02x}..(+{=usize}) }}",
+            self.frame.address(),
+            pdu,
+            omitted,
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{function, response, COIL_ON};
+    use crate::{function, request, response, COIL_ON};
 
     #[test]
     fn response_read_coils() {
         let mut buf = [0; 256];
+        let mut request_buf = [0; 8];
         // 0xB, 0x1, 0x4, 0xCD, 0x6B, 0xB2, 0x7F, 0x2B, 0xE1
         let (frame, _remainder) = crate::builder::build_frame(&mut buf)
             .for_address(0xB)
@@ -730,12 +1204,25 @@ mod tests {
             ];
             // Note that the last false bit may be padding (always zeroes) or part of the message. Need to know what the request was to tell
             assert_eq!(coils, desired);
+
+            let (request, _) = request::ReadCoils::new(&mut request_buf, 0xB, 0, 30);
+            let coils = response.iter_coils_counted(&request).collect::<Vec<_>>();
+            assert_eq!(coils, desired[..30]);
+
+            let mut coils = [false; 40];
+            assert_eq!(response.copy_coils_into(&mut coils), 32);
+            assert_eq!(coils[..32], desired);
+            assert_eq!(response.copy_coils_into(&mut coils[..10]), 10);
+            let mut packed = [0; 3];
+            assert_eq!(response.copy_packed_into(&mut packed), 3);
+            assert_eq!(packed, [0xCD, 0x6B, 0xB2]);
         }
     }
 
     #[test]
     fn response_read_discrete_inputs() {
         let mut buf = [0; 256];
+        let mut request_buf = [0; 8];
 
         let (frame, _remainder) = crate::builder::build_frame(&mut buf)
             .for_address(0xB)
@@ -759,6 +1246,10 @@ mod tests {
             ];
             // Note that the last false bit may be padding (always zeroes) or part of the message. Need to know what the request was to tell
             assert_eq!(coils, desired);
+
+            let (request, _) = request::ReadDiscreteInputs::new(&mut request_buf, 0xB, 0, 30);
+            let coils = response.iter_inputs_counted(&request).collect::<Vec<_>>();
+            assert_eq!(coils, desired[..30]);
         }
     }
 
@@ -777,6 +1268,9 @@ mod tests {
             response::ReadHoldingRegisters::try_from(frame.raw_bytes()).unwrap(),
             response::ReadHoldingRegisters::try_from(frame).unwrap(),
         ];
+        let mut request_buf = [0; 8];
+        let request = request::read_holding_registers(&mut request_buf, 0x11, 0x6B, 3).unwrap();
+        let request = request::ReadHoldingRegisters::from_frame_unchecked(request);
 
         for response in responses {
             assert_eq!(response.payload_len(), 6);
@@ -784,6 +1278,16 @@ mod tests {
                 response.iter_registers().collect::<Vec<_>>(),
                 [0xAE41, 0x5652, 0x4340]
             );
+            assert_eq!(
+                response.payload_as_registers_unaligned(),
+                [[0xAE, 0x41], [0x56, 0x52], [0x43, 0x40]]
+            );
+            assert_eq!(
+                response
+                    .iter_indexed_registers(&request)
+                    .collect::<Vec<_>>(),
+                [(0x6B, 0xAE41), (0x6C, 0x5652), (0x6D, 0x4340)]
+            );
         }
     }
 
@@ -895,4 +1399,28 @@ mod tests {
             assert_eq!(response.register_count(), 2);
         }
     }
+
+    #[test]
+    fn exception_responses() {
+        let mut buf = [0; 8];
+        let (frame, _) = crate::builder::build_frame(&mut buf)
+            .for_address(1)
+            .function(crate::Function(function::READ_HOLDING_REGISTERS.0 | 0x80))
+            .byte(crate::exception::ILLEGAL_DATA.0)
+            .finalise();
+        assert_eq!(
+            response::ReadHoldingRegisters::try_from(frame),
+            Err(crate::Error::Exception(crate::exception::ILLEGAL_DATA))
+        );
+
+        let (frame, _) = crate::builder::build_frame(&mut buf)
+            .for_address(1)
+            .function(crate::Function(function::READ_HOLDING_REGISTERS.0 | 0x80))
+            .bytes([1, 2])
+            .finalise();
+        assert!(matches!(
+            response::ReadHoldingRegisters::try_from(frame),
+            Err(crate::Error::DecodeInvalidLength { .. })
+        ));
+    }
 }
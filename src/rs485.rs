@@ -0,0 +1,233 @@
+//! Driver enable handling for half duplex (RS-485) transceivers
+//!
+//! [`HalfDuplex`] wraps a [`SerialPort`] and a [`DirectionControl`], enabling the driver around every frame it sends.
+//! It implements [`Transport`] for the client and [`HalfDuplex::send`] is the server's transmit path.
+//!
+//! ```
+//! use modbus_frames::{rs485::{DirectionControl, HalfDuplex, SerialPort, Turnaround}, request, Error, Frame};
+//!
+//! struct Pin(bool);
+//!
+//! impl DirectionControl for Pin {
+//!     fn set_tx(&mut self, enabled: bool) {
+//!         self.0 = enabled;
+//!     }
+//!
+//!     fn delay_us(&mut self, _us: u32) {}
+//! }
+//!
+//! struct Loopback;
+//!
+//! impl SerialPort for Loopback {
+//!     fn write(&mut self, _bytes: &[u8]) -> Result<(), Error> {
+//!         Ok(())
+//!     }
+//!
+//!     fn read<'b>(&mut self, _buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+//!         Err(Error::NoResponse)
+//!     }
+//! }
+//!
+//! let mut port = HalfDuplex::new(Loopback, Pin(false), Turnaround::for_baud(9600));
+//! let mut buffer = [0; 8];
+//! port.send(request::read_coils(&mut buffer, 1, 0, 8).unwrap()).unwrap();
+//! // the driver is released once the frame has been sent
+//! assert!(!port.direction().0);
+//! ```
+
+use crate::{client::Transport, quirks::Quirks, rx, Error, Frame};
+
+/// Drives the driver enable (DE, and usually the inverted receiver enable RE) of a transceiver
+pub trait DirectionControl {
+    /// `true` to transmit, `false` to release the bus and receive
+    fn set_tx(&mut self, enabled: bool);
+
+    /// Wait `us` microseconds, used for the turnaround delays
+    fn delay_us(&mut self, us: u32);
+}
+
+/// Byte level access to the serial port behind [`HalfDuplex`]
+pub trait SerialPort {
+    /// Write all of `bytes`, returning once they have been handed to the UART
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Error>;
+
+    /// Receive a frame into `buffer`, `Error::NoResponse` on timeout
+    fn read<'b>(&mut self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error>;
+
+    /// As `read`, accepting frames with the deviations in `quirks`. The default ignores `quirks`
+    fn read_with_quirks<'b>(
+        &mut self,
+        buffer: &'b mut [u8],
+        _quirks: &Quirks,
+    ) -> Result<Frame<'b>, Error> {
+        self.read(buffer)
+    }
+}
+
+/// Delays around enabling the driver
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Turnaround {
+    /// after enabling the driver, before the first byte
+    pub pre_us: u32,
+    /// after the last byte is written, before releasing the driver
+    pub post_us: u32,
+}
+
+impl Turnaround {
+    /// One bit time for the driver to settle before sending and one character time after the last byte is written
+    /// for it to leave the shift register
+    pub const fn for_baud(baud: u32) -> Self {
+        Turnaround {
+            pre_us: 1_000_000_u32.div_ceil(baud),
+            post_us: rx::char_ticks(baud, 1_000_000),
+        }
+    }
+}
+
+/// A serial port on a half duplex bus, transmitting only while the driver is enabled
+#[derive(Debug)]
+pub struct HalfDuplex<P, D> {
+    port: P,
+    direction: D,
+    turnaround: Turnaround,
+}
+
+impl<P: SerialPort, D: DirectionControl> HalfDuplex<P, D> {
+    /// The driver is released (receiving) to start with
+    pub fn new(port: P, mut direction: D, turnaround: Turnaround) -> Self {
+        direction.set_tx(false);
+        HalfDuplex {
+            port,
+            direction,
+            turnaround,
+        }
+    }
+
+    pub fn port(&mut self) -> &mut P {
+        &mut self.port
+    }
+
+    pub fn direction(&mut self) -> &mut D {
+        &mut self.direction
+    }
+
+    pub fn into_inner(self) -> (P, D) {
+        (self.port, self.direction)
+    }
+
+    /// Send `frame` with the driver enabled, releasing it afterwards even if the write fails
+    pub fn send(&mut self, frame: Frame) -> Result<(), Error> {
+        self.direction.set_tx(true);
+        self.direction.delay_us(self.turnaround.pre_us);
+        let result = self.port.write(frame.raw_bytes());
+        if result.is_ok() {
+            self.direction.delay_us(self.turnaround.post_us);
+        }
+        self.direction.set_tx(false);
+        result
+    }
+
+    /// Receive a frame with the driver released
+    pub fn receive<'b>(&mut self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        self.port.read(buffer)
+    }
+}
+
+impl<P: SerialPort, D: DirectionControl> Transport for HalfDuplex<P, D> {
+    fn transact<'b>(
+        &mut self,
+        request: Frame,
+        response_buffer: &'b mut [u8],
+    ) -> Result<Frame<'b>, Error> {
+        self.send(request)?;
+        self.receive(response_buffer)
+    }
+
+    fn transact_with_quirks<'b>(
+        &mut self,
+        request: Frame,
+        response_buffer: &'b mut [u8],
+        quirks: &Quirks,
+    ) -> Result<Frame<'b>, Error> {
+        self.send(request)?;
+        self.port.read_with_quirks(response_buffer, quirks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DirectionControl, HalfDuplex, SerialPort, Turnaround};
+    use crate::{client::Transport, request, Error, Frame};
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Tx(bool),
+        Delay(u32),
+        Write(usize),
+        Read,
+    }
+
+    #[derive(Default)]
+    struct Log(Vec<Event>);
+
+    impl DirectionControl for &mut Log {
+        fn set_tx(&mut self, enabled: bool) {
+            self.0.push(Event::Tx(enabled));
+        }
+
+        fn delay_us(&mut self, us: u32) {
+            self.0.push(Event::Delay(us));
+        }
+    }
+
+    struct Port(Vec<Event>, [u8; 8]);
+
+    impl SerialPort for Port {
+        fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+            self.0.push(Event::Write(bytes.len()));
+            self.1.copy_from_slice(bytes);
+            Ok(())
+        }
+
+        fn read<'b>(&mut self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+            self.0.push(Event::Read);
+            buffer[..8].copy_from_slice(&self.1);
+            Frame::try_from(&buffer[..8])
+        }
+    }
+
+    #[test]
+    fn direction_switching() {
+        assert_eq!(
+            Turnaround::for_baud(19200),
+            Turnaround {
+                pre_us: 53,
+                post_us: 573
+            }
+        );
+
+        let mut log = Log::default();
+        let mut port = HalfDuplex::new(
+            Port(Vec::new(), [0; 8]),
+            &mut log,
+            Turnaround::for_baud(19200),
+        );
+        let mut req = [0; 8];
+        let request = request::read_coils(&mut req, 1, 0, 8).unwrap();
+        let mut res = [0; 8];
+        assert_eq!(port.transact(request, &mut res), Ok(request));
+        let (port, _) = port.into_inner();
+        assert_eq!(port.0, [Event::Write(8), Event::Read]);
+        assert_eq!(
+            log.0,
+            [
+                Event::Tx(false),
+                Event::Tx(true),
+                Event::Delay(53),
+                Event::Delay(573),
+                Event::Tx(false)
+            ]
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Assembling received bytes into frames using the RTU inter-frame silence
+//!
+//! An ISR or DMA completion pushes bytes into [`RxRing`] as they arrive, the application polls it for frames. A frame
+//! ends when the line has been silent for 3.5 characters, see [`silence_ticks`]. Bursts that fail the CRC are resynced
+//! by skipping leading noise, anything else is dropped. Like the gateway it performs no I/O and time is a free
+//! running tick counter which may wrap.
+//!
+//! ```
+//! use modbus_frames::{request, rx::{self, RxRing}};
+//!
+//! // 9600 baud with a millisecond tick
+//! let mut ring = RxRing::<256>::new(rx::silence_ticks(9600, 1000));
+//! let mut buffer = [0; 8];
+//! let request = request::read_coils(&mut buffer, 1, 0, 8).unwrap();
+//! ring.push(&request.raw_bytes()[..3], 0);
+//! ring.push(&request.raw_bytes()[3..], 1);
+//! assert!(ring.poll(2).is_none());
+//! assert_eq!(ring.poll(6), Some(request));
+//! ```
+
+use crate::{verify_crc16, Frame};
+
+/// Serial character length in bits: start, 8 data, parity (or a second stop bit) and stop
+const CHAR_BITS: u64 = 11;
+
+/// Ticks taken to transmit one character at `baud`, rounded up
+pub const fn char_ticks(baud: u32, ticks_per_second: u32) -> u32 {
+    (CHAR_BITS * ticks_per_second as u64).div_ceil(baud as u64) as u32
+}
+
+/// Ticks of silence marking the end of a frame, rounded up
+///
+/// 3.5 characters, or the fixed 1.75ms the spec recommends above 19200 baud
+pub const fn silence_ticks(baud: u32, ticks_per_second: u32) -> u32 {
+    if baud > 19200 {
+        (1750 * ticks_per_second as u64).div_ceil(1_000_000) as u32
+    } else {
+        (7 * CHAR_BITS * ticks_per_second as u64).div_ceil(2 * baud as u64) as u32
+    }
+}
+
+/// Receive buffer of `N` bytes which splits the byte stream into frames
+///
+/// The storage is compacted as frames are taken so every frame is contiguous. `N` should be at least 256 to hold the
+/// longest frame, a burst that doesn't fit is dropped.
+#[derive(Debug, Clone)]
+pub struct RxRing<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+    /// end of a burst followed by silence which hasn't been polled yet
+    burst_end: Option<usize>,
+    /// length of the burst returned by the last poll, removed at the next push or poll
+    consumed: usize,
+    last_rx: u32,
+    silence: u32,
+    /// the current burst didn't fit, ignore bytes until the next silence
+    overflow: bool,
+    dropped: u32,
+}
+
+impl<const N: usize> RxRing<N> {
+    /// `silence` is the number of ticks ending a frame, see [`silence_ticks`]
+    pub const fn new(silence: u32) -> Self {
+        RxRing {
+            buffer: [0; N],
+            len: 0,
+            burst_end: None,
+            consumed: 0,
+            last_rx: 0,
+            silence,
+            overflow: false,
+            dropped: 0,
+        }
+    }
+
+    /// Add bytes received at time `now`
+    pub fn push(&mut self, bytes: &[u8], now: u32) {
+        self.release();
+        if now.wrapping_sub(self.last_rx) >= self.silence {
+            // the silence before these bytes ends the previous burst
+            self.overflow = false;
+            let start = self.burst_end.unwrap_or(0);
+            if self.len > start {
+                if start > 0 {
+                    // an earlier burst was never polled, keep the newer one
+                    self.buffer.copy_within(start..self.len, 0);
+                    self.len -= start;
+                    self.dropped = self.dropped.wrapping_add(1);
+                }
+                self.burst_end = Some(self.len);
+            }
+        }
+        self.last_rx = now;
+        if self.overflow {
+            return;
+        }
+        if self.len + bytes.len() > N {
+            self.len = self.burst_end.unwrap_or(0);
+            self.overflow = true;
+            self.dropped = self.dropped.wrapping_add(1);
+            return;
+        }
+        self.buffer[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    /// The next complete frame at time `now`
+    ///
+    /// The frame is valid until the next call to `push` or `poll`
+    pub fn poll(&mut self, now: u32) -> Option<Frame<'_>> {
+        self.release();
+        let silent = now.wrapping_sub(self.last_rx) >= self.silence;
+        let end = match self.burst_end.take() {
+            Some(end) => end,
+            None if silent && !self.overflow && self.len > 0 => self.len,
+            None => {
+                self.overflow &= !silent;
+                return None;
+            }
+        };
+        self.consumed = end;
+        // resync past noise at the start of the burst, the frame must end with it
+        match (0..end).find(|skip| verify_crc16(&self.buffer[*skip..end])) {
+            Some(skip) => Some(Frame::new_unchecked(&self.buffer[skip..end])),
+            None => {
+                self.dropped = self.dropped.wrapping_add(1);
+                None
+            }
+        }
+    }
+
+    /// Number of bursts dropped for a bad CRC, overflowing the buffer or not being polled in time
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Discard all received bytes, e.g. after a timeout or when switching direction
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.burst_end = None;
+        self.consumed = 0;
+        self.overflow = false;
+    }
+
+    /// remove the bytes of the last polled burst
+    fn release(&mut self) {
+        if self.consumed > 0 {
+            self.buffer.copy_within(self.consumed..self.len, 0);
+            self.len -= self.consumed;
+            self.consumed = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{char_ticks, silence_ticks, RxRing};
+    use crate::request;
+
+    #[test]
+    fn timing() {
+        assert_eq!(char_ticks(9600, 1_000_000), 1146);
+        assert_eq!(silence_ticks(9600, 1_000_000), 4011);
+        assert_eq!(silence_ticks(9600, 1000), 5);
+        assert_eq!(silence_ticks(115200, 1_000_000), 1750);
+    }
+
+    #[test]
+    fn bursts() {
+        let mut a = [0; 8];
+        let a = request::read_coils(&mut a, 1, 0, 8).unwrap();
+        let mut b = [0; 8];
+        let b = request::write_coil(&mut b, 2, 3, true).unwrap();
+        let mut ring = RxRing::<16>::new(5);
+
+        // leading noise is skipped
+        ring.push(&[0xFF], 100);
+        ring.push(a.raw_bytes(), 101);
+        assert_eq!(ring.poll(105), None);
+        assert_eq!(ring.poll(106), Some(a));
+        assert_eq!(ring.poll(200), None);
+
+        // a burst not polled before the next is complete is dropped
+        ring.push(a.raw_bytes(), 300);
+        ring.push(b.raw_bytes(), 310);
+        ring.push(a.raw_bytes(), 320);
+        assert_eq!(ring.dropped(), 1);
+        assert_eq!(ring.poll(321), Some(b));
+        assert_eq!(ring.poll(330), Some(a));
+
+        // bad CRC and overflow
+        ring.push(&a.raw_bytes()[..7], 400);
+        assert_eq!(ring.poll(410), None);
+        assert_eq!(ring.dropped(), 2);
+        ring.push(a.raw_bytes(), 500);
+        ring.push(a.raw_bytes(), 501);
+        ring.push(a.raw_bytes(), 502);
+        assert_eq!(ring.dropped(), 3);
+        assert_eq!(ring.poll(510), None);
+        ring.push(b.raw_bytes(), 520);
+        assert_eq!(ring.poll(530), Some(b));
+    }
+}
@@ -0,0 +1,173 @@
+//! Conversion between raw register values and engineering units
+//!
+//! Field devices commonly encode a physical value as a linear transform of the raw register value.
+//! [`Scale`] holds that transform as integers so it can be applied exactly using fixed point arithmetic,
+//! floating point conversions are provided for convenience.
+//!
+//! ```
+//! use modbus_frames::scale::{Scale, Scaled};
+//!
+//! // temperature in tenths of a degree with a -40.0 offset
+//! const TEMPERATURE: Scale = Scale::new(1, 10, -400);
+//! let reading = Scaled::new(652_u16, TEMPERATURE);
+//! assert_eq!(reading.to_fixed(10), Some(252)); // 25.2 degrees, in tenths
+//! assert_eq!(reading.to_f32(), 25.2);
+//! assert_eq!(TEMPERATURE.raw_from_fixed(252, 10), Some(652));
+//! ```
+
+/// `engineering = (raw * mul + add) / div`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Scale {
+    pub mul: i32,
+    /// the fixed point conversions return `None` for 0
+    pub div: i32,
+    pub add: i32,
+}
+
+impl Scale {
+    /// engineering value is the raw value
+    pub const IDENTITY: Scale = Scale::new(1, 1, 0);
+
+    pub const fn new(mul: i32, div: i32, add: i32) -> Self {
+        Scale { mul, div, add }
+    }
+
+    /// engineering value multiplied by `multiplier`, rounded to the nearest integer
+    /// e.g. `multiplier = 100` gives the value in hundredths
+    ///
+    /// `None` if `div` is 0 or the result overflows
+    pub fn to_fixed(&self, raw: i64, multiplier: i64) -> Option<i64> {
+        let num = raw
+            .checked_mul(self.mul.into())?
+            .checked_add(self.add.into())?
+            .checked_mul(multiplier)?;
+        div_round(num, self.div.into())
+    }
+
+    pub fn to_f32(&self, raw: i64) -> f32 {
+        (raw * self.mul as i64 + self.add as i64) as f32 / self.div as f32
+    }
+
+    /// inverse of `to_fixed`, rounded to the nearest raw value
+    ///
+    /// `None` if `mul` or `multiplier` is 0 or the result overflows
+    pub fn raw_from_fixed(&self, value: i64, multiplier: i64) -> Option<i64> {
+        let num = value
+            .checked_mul(self.div.into())?
+            .checked_sub(i64::from(self.add).checked_mul(multiplier)?)?;
+        div_round(num, i64::from(self.mul).checked_mul(multiplier)?)
+    }
+
+    /// inverse of `to_f32`, rounded to the nearest raw value
+    pub fn raw_from_f32(&self, value: f32) -> i64 {
+        let raw = (value * self.div as f32 - self.add as f32) / self.mul as f32;
+        // f32::round isn't available in core
+        if raw < 0.0 {
+            (raw - 0.5) as i64
+        } else {
+            (raw + 0.5) as i64
+        }
+    }
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// integer division rounding half away from zero, `None` for a zero `den` or a result outside of `i64`
+fn div_round(num: i64, den: i64) -> Option<i64> {
+    let (num_abs, den_abs) = (num.unsigned_abs(), den.unsigned_abs());
+    // in u128 so `num_abs + den_abs / 2` can't overflow
+    let quotient = (u128::from(num_abs) + u128::from(den_abs / 2)).checked_div(den_abs.into())?;
+    let quotient = i128::try_from(quotient).ok()?;
+    let quotient = if (num < 0) != (den < 0) {
+        -quotient
+    } else {
+        quotient
+    };
+    i64::try_from(quotient).ok()
+}
+
+/// A raw value paired with the scale converting it to engineering units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Scaled<T> {
+    pub raw: T,
+    pub scale: Scale,
+}
+
+impl<T: Into<i64> + Copy> Scaled<T> {
+    pub fn new(raw: T, scale: Scale) -> Self {
+        Scaled { raw, scale }
+    }
+
+    /// see `Scale::to_fixed`
+    pub fn to_fixed(&self, multiplier: i64) -> Option<i64> {
+        self.scale.to_fixed(self.raw.into(), multiplier)
+    }
+
+    pub fn to_f32(&self) -> f32 {
+        self.scale.to_f32(self.raw.into())
+    }
+}
+
+impl<T: TryFrom<i64>> Scaled<T> {
+    /// Raw value for an engineering value given in fixed point, `None` if the conversion fails (see
+    /// `Scale::raw_from_fixed`) or the raw value doesn't fit in `T`
+    pub fn from_fixed(value: i64, multiplier: i64, scale: Scale) -> Option<Self> {
+        let raw = T::try_from(scale.raw_from_fixed(value, multiplier)?).ok()?;
+        Some(Scaled { raw, scale })
+    }
+
+    /// Raw value for an engineering value, `None` if the raw value doesn't fit in `T`
+    pub fn from_f32(value: f32, scale: Scale) -> Option<Self> {
+        let raw = T::try_from(scale.raw_from_f32(value)).ok()?;
+        Some(Scaled { raw, scale })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scale, Scaled};
+
+    #[test]
+    fn fixed_point_round_trip() {
+        // 0.01 units per count
+        let scale = Scale::new(1, 100, 0);
+        assert_eq!(Scaled::new(12345_u16, scale).to_fixed(10), Some(1235));
+        assert_eq!(Scaled::new(-12345_i16, scale).to_fixed(10), Some(-1235));
+        assert_eq!(scale.raw_from_fixed(1235, 10), Some(12350));
+        assert_eq!(
+            Scaled::<u16>::from_fixed(-1, 1, scale),
+            None,
+            "negative values don't fit in u16"
+        );
+
+        // 0-10000 counts is 0-100%
+        let percent = Scaled::<u16>::from_f32(42.5, scale).unwrap();
+        assert_eq!(percent.raw, 4250);
+        assert_eq!(percent.to_f32(), 42.5);
+    }
+
+    #[test]
+    fn invalid_scales() {
+        // zero divisors
+        assert_eq!(Scale::new(1, 0, 0).to_fixed(5, 1), None);
+        assert_eq!(Scale::new(0, 1, 0).raw_from_fixed(5, 1), None);
+        assert_eq!(Scale::IDENTITY.raw_from_fixed(5, 0), None);
+        assert_eq!(Scaled::<u16>::from_fixed(5, 1, Scale::new(0, 1, 0)), None);
+
+        // overflow and the extremes of i64
+        assert_eq!(Scale::new(2, 1, 0).to_fixed(i64::MAX, 1), None);
+        assert_eq!(Scale::IDENTITY.to_fixed(i64::MIN, 1), Some(i64::MIN));
+        assert_eq!(Scale::new(1, -1, 0).to_fixed(i64::MIN, 1), None);
+        assert_eq!(
+            Scale::new(1, 2, 0).to_fixed(i64::MIN, 1),
+            Some(i64::MIN / 2)
+        );
+        assert_eq!(Scale::new(1, 2, 0).to_fixed(-3, 1), Some(-2));
+    }
+}
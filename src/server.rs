@@ -0,0 +1,1197 @@
+//! Answering requests on the server (slave) side
+//!
+//! Applications implement [`Handler`] for the entities they expose, [`dispatch`] decodes a request, validates it,
+//! calls the handler and builds the response (or exception response). [`respond`] adds the addressing rules for a
+//! single device and [`MultiServer`] routes requests for several devices to their own handlers.
+//!
+//! ```
+//! use modbus_frames::{exception, request, server::{self, Handler}, Exception, ValidatedFrame};
+//!
+//! struct Counter(u16);
+//!
+//! impl Handler for Counter {
+//!     fn read_holding_register(&mut self, index: u16) -> Result<u16, Exception> {
+//!         match index {
+//!             0 => Ok(self.0),
+//!             _ => Err(exception::ILLEGAL_ADDRESS),
+//!         }
+//!     }
+//! }
+//!
+//! let mut request_buffer = [0; 8];
+//! let request = request::read_holding_registers(&mut request_buffer, 1, 0, 1).unwrap();
+//! // received bytes have their CRC checked once
+//! let request = ValidatedFrame::try_from(request.raw_bytes()).unwrap();
+//! let mut response_buffer = [0; 256];
+//! let response = server::dispatch(&mut Counter(42), request, &mut response_buffer);
+//! assert_eq!(response.payload(), [2, 0, 42]);
+//! ```
+
+use byteorder::ByteOrder;
+
+use crate::{
+    decoder::CommonRequests,
+    device::AddressKind,
+    diagnostic::{self, DiagnosticCounters},
+    entity::EntityRange,
+    exception,
+    file::{self, FileStorage},
+    frame, function, pdu, request,
+    trace::Trace,
+    Device, Error, Exception, Frame, ValidatedFrame, COIL_OFF, COIL_ON,
+};
+
+/// Application side of a server
+///
+/// Each method defaults to the `ILLEGAL_FUNCTION` exception, implement those the device supports.
+/// Requests for multiple entities call the single entity method once per entity in index order, the first
+/// exception becomes the response (writes before the failing entity are not undone).
+pub trait Handler {
+    fn read_coil(&mut self, _index: u16) -> Result<bool, Exception> {
+        Err(exception::ILLEGAL_FUNCTION)
+    }
+
+    fn read_discrete_input(&mut self, _index: u16) -> Result<bool, Exception> {
+        Err(exception::ILLEGAL_FUNCTION)
+    }
+
+    fn read_holding_register(&mut self, _index: u16) -> Result<u16, Exception> {
+        Err(exception::ILLEGAL_FUNCTION)
+    }
+
+    fn read_input_register(&mut self, _index: u16) -> Result<u16, Exception> {
+        Err(exception::ILLEGAL_FUNCTION)
+    }
+
+    fn write_coil(&mut self, _index: u16, _on: bool) -> Result<(), Exception> {
+        Err(exception::ILLEGAL_FUNCTION)
+    }
+
+    fn write_holding_register(&mut self, _index: u16, _value: u16) -> Result<(), Exception> {
+        Err(exception::ILLEGAL_FUNCTION)
+    }
+
+    /// Restart Communications diagnostic (0x08/0x0001) received, reset the serial port and any communication state
+    ///
+    /// `clear_log` is set when the comm event log should also be cleared. This is called before the response is
+    /// sent, the port must not be reset until any pending response has been transmitted.
+    fn restart_communications(&mut self, _clear_log: bool) {}
+
+    /// Called with every request before any other method, an exception rejects the request without side effects
+    fn accept(&mut self, _request: Frame) -> Result<(), Exception> {
+        Ok(())
+    }
+
+    /// Storage answering Read/Write File Record (0x14/0x15), `None` passes them to `other`
+    fn files(&mut self) -> Option<&mut dyn FileStorage> {
+        None
+    }
+
+    /// Requests for any function code other than 1-6, 15 and 16
+    ///
+    /// Build the complete response in `response_buffer`
+    fn other<'b>(
+        &mut self,
+        _request: Frame,
+        _response_buffer: &'b mut [u8],
+    ) -> Result<Frame<'b>, Exception> {
+        Err(exception::ILLEGAL_FUNCTION)
+    }
+}
+
+impl<H: Handler + ?Sized> Handler for &mut H {
+    fn read_coil(&mut self, index: u16) -> Result<bool, Exception> {
+        (**self).read_coil(index)
+    }
+
+    fn read_discrete_input(&mut self, index: u16) -> Result<bool, Exception> {
+        (**self).read_discrete_input(index)
+    }
+
+    fn read_holding_register(&mut self, index: u16) -> Result<u16, Exception> {
+        (**self).read_holding_register(index)
+    }
+
+    fn read_input_register(&mut self, index: u16) -> Result<u16, Exception> {
+        (**self).read_input_register(index)
+    }
+
+    fn write_coil(&mut self, index: u16, on: bool) -> Result<(), Exception> {
+        (**self).write_coil(index, on)
+    }
+
+    fn write_holding_register(&mut self, index: u16, value: u16) -> Result<(), Exception> {
+        (**self).write_holding_register(index, value)
+    }
+
+    fn restart_communications(&mut self, clear_log: bool) {
+        (**self).restart_communications(clear_log)
+    }
+
+    fn accept(&mut self, request: Frame) -> Result<(), Exception> {
+        (**self).accept(request)
+    }
+
+    fn files(&mut self) -> Option<&mut dyn FileStorage> {
+        (**self).files()
+    }
+
+    fn other<'b>(
+        &mut self,
+        request: Frame,
+        response_buffer: &'b mut [u8],
+    ) -> Result<Frame<'b>, Exception> {
+        (**self).other(request, response_buffer)
+    }
+}
+
+/// Answer `request` using `handler`
+///
+/// `response_buffer` should be large enough for any response (256 bytes).
+/// Always returns a response, broadcast/addressing decisions are left to the caller.
+/// No [`DiagnosticCounters`] are updated, only [`MultiServer`] keeps them.
+pub fn dispatch<'b, H: Handler + ?Sized>(
+    handler: &mut H,
+    request: ValidatedFrame,
+    response_buffer: &'b mut [u8],
+) -> Frame<'b> {
+    match respond_to(handler, request.as_frame(), response_buffer) {
+        Ok(len) => Frame::new_unchecked(&response_buffer[..len]),
+        Err(exception) => request.response_exception(response_buffer, exception).0,
+    }
+}
+
+/// Answer `request` as `device`, `None` if no response should be sent
+///
+/// Broadcasts are processed without a response, requests for any other address are ignored.
+/// As with `dispatch`, no [`DiagnosticCounters`] are updated.
+///
+/// ```
+/// use modbus_frames::{request, server, source::Coils, Device, ValidatedFrame};
+///
+/// let mut handler = Coils([false; 8]);
+/// let mut request_buffer = [0; 8];
+/// let mut response_buffer = [0; 256];
+/// let request = request::write_coil(&mut request_buffer, 0, 2, true).unwrap();
+/// let request = ValidatedFrame::try_from(request).unwrap();
+/// assert!(server::respond(Device::new(1), request, &mut handler, &mut response_buffer).is_none());
+/// let request = request::read_coils(&mut request_buffer, 1, 0, 8).unwrap();
+/// let request = ValidatedFrame::try_from(request).unwrap();
+/// let response = server::respond(Device::new(1), request, &mut handler, &mut response_buffer);
+/// assert_eq!(response.unwrap().payload(), [1, 0b100]);
+/// ```
+pub fn respond<'b, H: Handler + ?Sized>(
+    device: Device,
+    request: ValidatedFrame,
+    handler: &mut H,
+    response_buffer: &'b mut [u8],
+) -> Option<Frame<'b>> {
+    match request.device().kind() {
+        AddressKind::Broadcast => {
+            dispatch(handler, request, response_buffer);
+            None
+        }
+        AddressKind::Slave if request.device() == device => {
+            Some(dispatch(handler, request, response_buffer))
+        }
+        _ => None,
+    }
+}
+
+/// Byte level, dyn compatible server interface
+///
+/// [`Handler`] and the dispatch functions are generic, this erases them for plugin registries
+/// (`Box<dyn FrameHandler>`) and C FFI layers. [`Dispatch`] and [`Respond`] adapt a [`Handler`], [`MultiServer`]
+/// implements it directly.
+/// ```
+/// use modbus_frames::{
+///     request, server::{Dispatch, FrameHandler, Respond}, source::Coils, Device, ValidatedFrame,
+/// };
+///
+/// let mut coils = Dispatch(Coils([true; 8]));
+/// let mut device = Respond::new(Device::new(2), Coils([false; 8]));
+/// let mut plugins: [&mut dyn FrameHandler; 2] = [&mut coils, &mut device];
+///
+/// let mut request_buffer = [0; 8];
+/// let mut response_buffer = [0; 256];
+/// let request = request::read_coils(&mut request_buffer, 1, 0, 8).unwrap();
+/// let request = ValidatedFrame::try_from(request).unwrap();
+/// let len = plugins[0].handle(&request, &mut response_buffer).unwrap();
+/// assert_eq!(response_buffer[..len][..4], [1, 1, 1, 0xFF]);
+/// assert_eq!(plugins[1].handle_bytes(request.raw_bytes(), &mut response_buffer), None);
+/// ```
+pub trait FrameHandler {
+    /// Answer `frame`, returning the length of the response written to the start of `out`, `None` if no response
+    /// should be sent
+    ///
+    /// `out` should be large enough for any response (256 bytes)
+    fn handle(&mut self, frame: &ValidatedFrame, out: &mut [u8]) -> Option<usize>;
+
+    /// As `handle` for received bytes, which are ignored if they aren't a valid frame
+    fn handle_bytes(&mut self, bytes: &[u8], out: &mut [u8]) -> Option<usize> {
+        let frame = ValidatedFrame::try_from(bytes).ok()?;
+        self.handle(&frame, out)
+    }
+}
+
+impl<F: FrameHandler + ?Sized> FrameHandler for &mut F {
+    fn handle(&mut self, frame: &ValidatedFrame, out: &mut [u8]) -> Option<usize> {
+        (**self).handle(frame, out)
+    }
+}
+
+/// [`FrameHandler`] answering every frame through [`dispatch`]
+#[derive(Debug, Clone, Default)]
+pub struct Dispatch<H>(pub H);
+
+impl<H: Handler> FrameHandler for Dispatch<H> {
+    fn handle(&mut self, frame: &ValidatedFrame, out: &mut [u8]) -> Option<usize> {
+        Some(dispatch(&mut self.0, *frame, out).raw_bytes().len())
+    }
+}
+
+/// [`FrameHandler`] answering as a single device through [`respond`]
+#[derive(Debug, Clone)]
+pub struct Respond<H> {
+    device: Device,
+    handler: H,
+}
+
+impl<H: Handler> Respond<H> {
+    pub fn new(device: Device, handler: H) -> Self {
+        Respond { device, handler }
+    }
+
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    pub fn handler(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    pub fn into_inner(self) -> H {
+        self.handler
+    }
+}
+
+impl<H: Handler> FrameHandler for Respond<H> {
+    fn handle(&mut self, frame: &ValidatedFrame, out: &mut [u8]) -> Option<usize> {
+        respond(self.device, *frame, &mut self.handler, out)
+            .map(|response| response.raw_bytes().len())
+    }
+}
+
+/// builds the response and returns its length
+fn respond_to<H: Handler + ?Sized>(
+    handler: &mut H,
+    request: Frame,
+    response_buffer: &mut [u8],
+) -> Result<usize, Exception> {
+    handler.accept(request)?;
+    if let function::READ_FILE_RECORD | function::WRITE_FILE_RECORD = request.function() {
+        if let Some(storage) = handler.files() {
+            return file::respond(storage, request, response_buffer)
+                .map(|response| response.raw_bytes().len());
+        }
+    }
+    let decoded = match CommonRequests::try_from(request) {
+        Ok(decoded) => decoded,
+        Err(Error::UnknownFunction(_)) => {
+            return handler
+                .other(request, response_buffer)
+                .map(|response| response.raw_bytes().len());
+        }
+        Err(_) => return Err(exception::ILLEGAL_DATA),
+    };
+    validate_request_frame(request)?;
+
+    let range = EntityRange::from_request(&decoded);
+    let mut error = None;
+    let mut record = |e| {
+        error.get_or_insert(e);
+    };
+    let response = match decoded {
+        CommonRequests::ReadCoils(req) => {
+            let coils = range.iter().map(|e| {
+                handler.read_coil(e.index).unwrap_or_else(|e| {
+                    record(e);
+                    false
+                })
+            });
+            req.response_builder(response_buffer, coils).0.as_frame()
+        }
+        CommonRequests::ReadDiscreteInputs(req) => {
+            let inputs = range.iter().map(|e| {
+                handler.read_discrete_input(e.index).unwrap_or_else(|e| {
+                    record(e);
+                    false
+                })
+            });
+            req.response_builder(response_buffer, inputs).0.as_frame()
+        }
+        CommonRequests::ReadHolsingRegisters(req) => {
+            let registers = range.iter().map(|e| {
+                handler.read_holding_register(e.index).unwrap_or_else(|e| {
+                    record(e);
+                    0
+                })
+            });
+            req.response_builder(response_buffer, registers)
+                .0
+                .as_frame()
+        }
+        CommonRequests::ReadInputRegisters(req) => {
+            let registers = range.iter().map(|e| {
+                handler.read_input_register(e.index).unwrap_or_else(|e| {
+                    record(e);
+                    0
+                })
+            });
+            req.response_builder(response_buffer, registers)
+                .0
+                .as_frame()
+        }
+        CommonRequests::WriteCoil(req) => {
+            handler.write_coil(req.index(), req.coil_state()?)?;
+            req.response_builder(response_buffer).0.as_frame()
+        }
+        CommonRequests::WriteHoldingRegister(req) => {
+            handler.write_holding_register(req.index(), req.value())?;
+            req.response_builder(response_buffer).0.as_frame()
+        }
+        CommonRequests::WriteMultipleCoils(req) => {
+            for (index, on) in req.iter_coils() {
+                handler.write_coil(index, on)?;
+            }
+            req.response_builder(response_buffer).0.as_frame()
+        }
+        CommonRequests::WriteMultipleHoldingRegisters(req) => {
+            for (index, value) in (req.start_index()..).zip(req.iter_registers()) {
+                handler.write_holding_register(index, value)?;
+            }
+            req.response_builder(response_buffer).0.as_frame()
+        }
+    };
+    match error {
+        Some(exception) => Err(exception),
+        None => Ok(response.raw_bytes().len()),
+    }
+}
+
+/// The exception a server should respond to `request` with if it breaks the length, quantity or value rules of the
+/// spec for its function
+///
+/// Only the request itself is checked, whether the addressed entities exist is up to the application. Function codes
+/// the spec doesn't define (including the user defined ranges) are `ILLEGAL_FUNCTION`.
+///
+/// ```
+/// use modbus_frames::{builder, exception, function, server};
+///
+/// let mut buffer = [0; 8];
+/// let (request, _) = builder::build_frame(&mut buffer)
+///     .for_address(1)
+///     .function(function::READ_HOLDING_REGISTERS)
+///     .registers([0, 126])
+///     .finalise();
+/// assert_eq!(server::validate_request_frame(request), Err(exception::ILLEGAL_DATA));
+/// ```
+pub fn validate_request_frame(request: Frame) -> Result<(), Exception> {
+    let payload = request.payload();
+    let word = |idx: usize| byteorder::BigEndian::read_u16(&payload[idx..]);
+    match request.function() {
+        function::READ_COILS | function::READ_DISCRETE_INPUTS => {
+            check_len(payload, 4)?;
+            check_range(word(0), word(2), request::MAX_READ_BITS)
+        }
+        function::READ_HOLDING_REGISTERS | function::READ_INPUT_REGISTERS => {
+            check_len(payload, 4)?;
+            check_range(word(0), word(2), request::MAX_READ_REGISTERS)
+        }
+        function::WRITE_COIL => {
+            check_len(payload, 4)?;
+            match word(2) {
+                COIL_ON | COIL_OFF => Ok(()),
+                _ => Err(exception::ILLEGAL_DATA),
+            }
+        }
+        function::WRITE_HOLDING_REGISTER => check_len(payload, 4),
+        function::WRITE_MULTIPLE_COILS => {
+            check_min_len(payload, 5)?;
+            check_range(word(0), word(2), request::MAX_WRITE_COILS)?;
+            check_byte_count(payload, 4, word(2).div_ceil(8).into())
+        }
+        function::WRITE_MULTIPLE_HOLDING_REGISTERS => {
+            check_min_len(payload, 5)?;
+            check_range(word(0), word(2), request::MAX_WRITE_REGISTERS)?;
+            check_byte_count(payload, 4, 2 * usize::from(word(2)))
+        }
+        function::READ_EXCEPTION_STATUS
+        | function::GET_COMM_EVENT_COUNTER
+        | function::GET_COMM_EVENT_LOG
+        | function::REPORT_SLAVE_ID => check_len(payload, 0),
+        function::DIAGNOSTIC => {
+            // sub-function followed by 16-bit data
+            check_min_len(payload, 2)?;
+            if payload.len().is_multiple_of(2) {
+                Ok(())
+            } else {
+                Err(exception::ILLEGAL_DATA)
+            }
+        }
+        function::READ_FILE_RECORD => {
+            check_min_len(payload, 1)?;
+            let byte_count = usize::from(payload[0]);
+            if !(7..=request::MAX_READ_FILE_RECORD_BYTES.into()).contains(&byte_count)
+                || !byte_count.is_multiple_of(7)
+            {
+                return Err(exception::ILLEGAL_DATA);
+            }
+            check_byte_count(payload, 0, byte_count)?;
+            // function, byte count and per record length/reference type
+            let mut response_len = 2;
+            for sub in payload[1..].chunks_exact(7) {
+                check_file_record(sub)?;
+                response_len += 2 + 2 * usize::from(byteorder::BigEndian::read_u16(&sub[5..]));
+            }
+            if response_len > pdu::MAX_PDU_LEN {
+                return Err(exception::ILLEGAL_DATA);
+            }
+            Ok(())
+        }
+        function::WRITE_FILE_RECORD => {
+            check_min_len(payload, 1)?;
+            let byte_count = usize::from(payload[0]);
+            if !(9..=request::MAX_WRITE_FILE_RECORD_BYTES.into()).contains(&byte_count) {
+                return Err(exception::ILLEGAL_DATA);
+            }
+            check_byte_count(payload, 0, byte_count)?;
+            let mut records = &payload[1..];
+            while !records.is_empty() {
+                check_min_len(records, 7)?;
+                check_file_record(records)?;
+                let len = 7 + 2 * usize::from(byteorder::BigEndian::read_u16(&records[5..]));
+                check_min_len(records, len)?;
+                records = &records[len..];
+            }
+            Ok(())
+        }
+        function::MASK_WRITE_REGISTER => check_len(payload, 6),
+        function::READ_WRITE_MULTIPLE_REGISTERS => {
+            check_min_len(payload, 9)?;
+            check_range(word(0), word(2), request::MAX_READ_REGISTERS)?;
+            check_range(word(4), word(6), request::MAX_READ_WRITE_REGISTERS)?;
+            check_byte_count(payload, 8, 2 * usize::from(word(6)))
+        }
+        function::READ_FIFO_QUEUE => check_len(payload, 2),
+        function::ENCAPSULATED_INTERFACE_TRANSPORT => {
+            check_min_len(payload, 1)?;
+            if payload[0] == function::MEI_READ_DEVICE_IDENTIFICATION {
+                check_len(payload, 3)?;
+                if !(1..=4).contains(&payload[1]) {
+                    return Err(exception::ILLEGAL_DATA);
+                }
+            }
+            Ok(())
+        }
+        _ => Err(exception::ILLEGAL_FUNCTION),
+    }
+}
+
+fn check_len(payload: &[u8], len: usize) -> Result<(), Exception> {
+    if payload.len() == len {
+        Ok(())
+    } else {
+        Err(exception::ILLEGAL_DATA)
+    }
+}
+
+fn check_min_len(payload: &[u8], len: usize) -> Result<(), Exception> {
+    if payload.len() >= len {
+        Ok(())
+    } else {
+        Err(exception::ILLEGAL_DATA)
+    }
+}
+
+/// the byte count at `idx` is `expected` and is followed by exactly that many bytes
+fn check_byte_count(payload: &[u8], idx: usize, expected: usize) -> Result<(), Exception> {
+    if usize::from(payload[idx]) == expected && payload.len() == idx + 1 + expected {
+        Ok(())
+    } else {
+        Err(exception::ILLEGAL_DATA)
+    }
+}
+
+/// quantity is checked before the address range as the spec orders them
+fn check_range(start: u16, count: u16, max_count: u16) -> Result<(), Exception> {
+    if count == 0 || count > max_count {
+        Err(exception::ILLEGAL_DATA)
+    } else if u32::from(start) + u32::from(count) > 1 << 16 {
+        Err(exception::ILLEGAL_ADDRESS)
+    } else {
+        Ok(())
+    }
+}
+
+/// reference type, file number and record number of a file record sub-request
+fn check_file_record(sub: &[u8]) -> Result<(), Exception> {
+    let file_number = byteorder::BigEndian::read_u16(&sub[1..]);
+    let record_number = byteorder::BigEndian::read_u16(&sub[3..]);
+    if sub[0] != 6 {
+        Err(exception::ILLEGAL_DATA)
+    } else if file_number == 0 || record_number > request::MAX_FILE_RECORD_NUMBER {
+        Err(exception::ILLEGAL_ADDRESS)
+    } else {
+        Ok(())
+    }
+}
+
+/// Serves several devices from one process, e.g. a gateway to devices on another bus
+///
+/// Requests for devices without a handler receive the `GATEWAY_PATH_UNAVAILABLE` exception,
+/// broadcasts are given to every handler and frames addressed to reserved addresses are ignored.
+/// Use `&mut dyn Handler` as `H` to serve devices with different handler types.
+///
+/// Communication statistics are kept in [`DiagnosticCounters`], which answers the counter Diagnostics (0x08)
+/// sub-functions and Get Comm Event Counter (0x0B) for every device. Force Listen Only Mode (0x08/0x0004) stops
+/// all responses until Restart Communications (0x08/0x0001), which is passed on to
+/// [`Handler::restart_communications`].
+pub struct MultiServer<H, const N: usize, R = ()> {
+    handlers: [(Device, H); N],
+    counters: DiagnosticCounters,
+    listen_only: bool,
+    check_crc: bool,
+    trace: R,
+}
+
+impl<H: Handler, const N: usize> MultiServer<H, N> {
+    pub fn new(handlers: [(Device, H); N]) -> Self {
+        MultiServer {
+            handlers,
+            counters: DiagnosticCounters::default(),
+            listen_only: false,
+            check_crc: true,
+            trace: (),
+        }
+    }
+}
+
+impl<H: Handler, const N: usize, R: Trace> MultiServer<H, N, R> {
+    /// Report the frames received and sent to `trace`
+    pub fn with_trace<T: Trace>(self, trace: T) -> MultiServer<H, N, T> {
+        MultiServer {
+            handlers: self.handlers,
+            counters: self.counters,
+            listen_only: self.listen_only,
+            check_crc: self.check_crc,
+            trace,
+        }
+    }
+
+    /// Skip CRC verification in `process_bytes`, only for transports which can't corrupt bytes
+    ///
+    /// see [`frame::decode_unchecked_crc`]
+    pub fn without_crc_check(self) -> Self {
+        MultiServer {
+            check_crc: false,
+            ..self
+        }
+    }
+
+    pub fn trace(&mut self) -> &mut R {
+        &mut self.trace
+    }
+
+    pub fn counters(&mut self) -> &mut DiagnosticCounters {
+        &mut self.counters
+    }
+
+    /// true while in listen only mode, no responses are sent
+    pub fn is_listen_only(&self) -> bool {
+        self.listen_only
+    }
+
+    pub fn handler(&mut self, device: Device) -> Option<&mut H> {
+        self.handlers
+            .iter_mut()
+            .find(|(d, _)| *d == device)
+            .map(|(_, handler)| handler)
+    }
+
+    pub fn into_inner(self) -> [(Device, H); N] {
+        self.handlers
+    }
+
+    /// Validate and process received bytes, invalid frames are ignored
+    pub fn process_bytes<'b>(
+        &mut self,
+        bytes: &[u8],
+        response_buffer: &'b mut [u8],
+    ) -> Option<Frame<'b>> {
+        let decoded = if self.check_crc {
+            ValidatedFrame::try_from(bytes)
+        } else {
+            frame::decode_unchecked_crc(bytes).map(ValidatedFrame::new_unchecked)
+        };
+        match decoded {
+            Ok(request) => self.process(request, response_buffer),
+            Err(error) => {
+                self.trace.on_rx(bytes);
+                self.trace.on_error(&error);
+                self.counters.record_comm_error();
+                None
+            }
+        }
+    }
+
+    /// Route `request` to its handler, `None` if no response should be sent
+    pub fn process<'b>(
+        &mut self,
+        request: ValidatedFrame,
+        response_buffer: &'b mut [u8],
+    ) -> Option<Frame<'b>> {
+        self.trace.on_rx(request.raw_bytes());
+        self.counters.record_bus_message();
+        let response = match request.device().kind() {
+            AddressKind::Broadcast => {
+                // completed if any of the devices completed it
+                let mut completed = false;
+                if !self.listen_only {
+                    for (_, handler) in self.handlers.iter_mut() {
+                        let response = dispatch(handler, request, response_buffer);
+                        completed |= response.function().0 & 0x80 == 0;
+                    }
+                }
+                self.counters.record_broadcast(*request, completed);
+                None
+            }
+            AddressKind::Reserved => None,
+            AddressKind::Slave => {
+                let response = self.process_slave(request, response_buffer);
+                self.counters.record_request(*request, response.as_ref());
+                response
+            }
+        };
+        if let Some(response) = &response {
+            self.trace.on_tx(response);
+        }
+        response
+    }
+
+    fn process_slave<'b>(
+        &mut self,
+        request: ValidatedFrame,
+        response_buffer: &'b mut [u8],
+    ) -> Option<Frame<'b>> {
+        let device = request.device();
+        let Some((_, handler)) = self.handlers.iter_mut().find(|(d, _)| *d == device) else {
+            return (!self.listen_only).then(|| {
+                request
+                    .response_exception(response_buffer, exception::GATEWAY_PATH_UNAVAILABLE)
+                    .0
+            });
+        };
+        match diagnostic::sub_function(request) {
+            Some(diagnostic::RESTART_COMMUNICATIONS) => {
+                let clear_log = match request.payload().get(2..) {
+                    Some([0x00, 0x00]) => false,
+                    Some([0xFF, 0x00]) => true,
+                    _ if self.listen_only => return None,
+                    _ => {
+                        return Some(
+                            request
+                                .response_exception(response_buffer, exception::ILLEGAL_DATA)
+                                .0,
+                        )
+                    }
+                };
+                // no response when leaving listen only mode
+                let respond = !core::mem::take(&mut self.listen_only);
+                self.counters.clear();
+                handler.restart_communications(clear_log);
+                respond.then(|| {
+                    request
+                        .response_builder(response_buffer)
+                        .bytes(request.payload().iter().copied())
+                        .finalise()
+                        .0
+                })
+            }
+            _ if self.listen_only => None,
+            Some(diagnostic::FORCE_LISTEN_ONLY) => {
+                self.listen_only = true;
+                None
+            }
+            _ if DiagnosticCounters::handles(*request) => {
+                Some(self.counters.respond(*request, response_buffer))
+            }
+            _ => Some(dispatch(handler, request, response_buffer)),
+        }
+    }
+}
+
+impl<H: Handler, const N: usize, R: Trace> FrameHandler for MultiServer<H, N, R> {
+    fn handle(&mut self, frame: &ValidatedFrame, out: &mut [u8]) -> Option<usize> {
+        self.process(*frame, out)
+            .map(|response| response.raw_bytes().len())
+    }
+
+    /// As [`MultiServer::process_bytes`], counting invalid frames as communication errors
+    fn handle_bytes(&mut self, bytes: &[u8], out: &mut [u8]) -> Option<usize> {
+        self.process_bytes(bytes, out)
+            .map(|response| response.raw_bytes().len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dispatch, respond, Dispatch, FrameHandler, Handler, MultiServer, Respond};
+    use crate::source::Coils;
+    use crate::{diagnostic, exception, request, trace::Trace, Device, Error, Exception, Frame};
+
+    #[derive(Default)]
+    struct Counts {
+        tx: u32,
+        rx: u32,
+        errors: u32,
+    }
+
+    impl Trace for Counts {
+        fn on_tx(&mut self, _frame: &Frame) {
+            self.tx += 1;
+        }
+
+        fn on_rx(&mut self, _bytes: &[u8]) {
+            self.rx += 1;
+        }
+
+        fn on_error(&mut self, _error: &Error) {
+            self.errors += 1;
+        }
+    }
+
+    #[derive(Default)]
+    struct Registers {
+        values: [u16; 4],
+        coils: u8,
+    }
+
+    impl Handler for Registers {
+        fn read_coil(&mut self, index: u16) -> Result<bool, Exception> {
+            match index {
+                0..=7 => Ok(self.coils & (1 << index) != 0),
+                _ => Err(exception::ILLEGAL_ADDRESS),
+            }
+        }
+
+        fn read_discrete_input(&mut self, index: u16) -> Result<bool, Exception> {
+            self.read_coil(index)
+        }
+
+        fn write_coil(&mut self, index: u16, on: bool) -> Result<(), Exception> {
+            match index {
+                0..=7 => {
+                    self.coils = (self.coils & !(1 << index)) | ((on as u8) << index);
+                    Ok(())
+                }
+                _ => Err(exception::ILLEGAL_ADDRESS),
+            }
+        }
+
+        fn read_holding_register(&mut self, index: u16) -> Result<u16, Exception> {
+            self.values
+                .get(index as usize)
+                .copied()
+                .ok_or(exception::ILLEGAL_ADDRESS)
+        }
+
+        fn write_holding_register(&mut self, index: u16, value: u16) -> Result<(), Exception> {
+            let reg = self
+                .values
+                .get_mut(index as usize)
+                .ok_or(exception::ILLEGAL_ADDRESS)?;
+            *reg = value;
+            Ok(())
+        }
+    }
+
+    fn exception_of(response: Frame) -> Option<Exception> {
+        (response.function().0 & 0x80 != 0).then(|| Exception(response.payload()[0]))
+    }
+
+    #[test]
+    fn dispatch_requests() {
+        let mut handler = Registers::default();
+        let mut req = [0; 32];
+        let mut res = [0; 256];
+
+        let request = request::write_multiple_holding_registers(&mut req, 1, 1, &[5, 6]).unwrap();
+        let response = dispatch(&mut handler, request.try_into().unwrap(), &mut res);
+        assert_eq!(response.payload(), [0, 1, 0, 2]);
+        assert_eq!(handler.values, [0, 5, 6, 0]);
+
+        let request = request::read_holding_registers(&mut req, 1, 0, 3).unwrap();
+        let response = dispatch(&mut handler, request.try_into().unwrap(), &mut res);
+        assert_eq!(response.payload(), [6, 0, 0, 0, 5, 0, 6]);
+
+        let request = request::read_holding_registers(&mut req, 1, 2, 3).unwrap();
+        let response = dispatch(&mut handler, request.try_into().unwrap(), &mut res);
+        assert_eq!(exception_of(response), Some(exception::ILLEGAL_ADDRESS));
+
+        let request = request::write_multiple_coils(&mut req, 1, 2, &[true, false, true]).unwrap();
+        dispatch(&mut handler, request.try_into().unwrap(), &mut res);
+        assert_eq!(handler.coils, 0b10100);
+        let request = request::read_coils(&mut req, 1, 0, 8).unwrap();
+        let response = dispatch(&mut handler, request.try_into().unwrap(), &mut res);
+        assert_eq!(response.payload(), [1, 0b10100]);
+        let request = request::read_discrete_inputs(&mut req, 1, 1, 8).unwrap();
+        let response = dispatch(&mut handler, request.try_into().unwrap(), &mut res);
+        assert_eq!(exception_of(response), Some(exception::ILLEGAL_ADDRESS));
+        let request = request::read_discrete_inputs(&mut req, 1, 2, 3).unwrap();
+        let response = dispatch(&mut handler, request.try_into().unwrap(), &mut res);
+        assert_eq!(response.payload(), [1, 0b101]);
+
+        let request = request::read_input_registers(&mut req, 1, 0, 1).unwrap();
+        let response = dispatch(&mut handler, request.try_into().unwrap(), &mut res);
+        assert_eq!(exception_of(response), Some(exception::ILLEGAL_FUNCTION));
+
+        let request = request::report_slave_id(&mut req, 1).unwrap();
+        let response = dispatch(&mut handler, request.try_into().unwrap(), &mut res);
+        assert_eq!(exception_of(response), Some(exception::ILLEGAL_FUNCTION));
+    }
+
+    #[test]
+    fn invalid_requests() {
+        let mut handler = Registers::default();
+        let mut res = [0; 256];
+
+        // write coil with a value other than 0x0000/0xFF00
+        let request = crate::builder::build_frame(&mut [0; 8])
+            .for_address(1)
+            .function(crate::function::WRITE_COIL)
+            .registers([0, 0x1234])
+            .finalise()
+            .0
+            .raw_bytes()
+            .to_vec();
+        let response = dispatch(
+            &mut handler,
+            crate::ValidatedFrame::try_from(&request[..]).unwrap(),
+            &mut res,
+        );
+        assert_eq!(exception_of(response), Some(exception::ILLEGAL_DATA));
+
+        // zero quantity
+        let request = crate::builder::build_frame(&mut [0; 8])
+            .for_address(1)
+            .function(crate::function::READ_HOLDING_REGISTERS)
+            .registers([0, 0])
+            .finalise()
+            .0
+            .raw_bytes()
+            .to_vec();
+        let response = dispatch(
+            &mut handler,
+            crate::ValidatedFrame::try_from(&request[..]).unwrap(),
+            &mut res,
+        );
+        assert_eq!(exception_of(response), Some(exception::ILLEGAL_DATA));
+
+        // past the last address
+        let mut req = [0; 8];
+        let request = request::read_holding_registers(&mut req, 1, 0xFFFF, 2).unwrap();
+        let response = dispatch(&mut handler, request.try_into().unwrap(), &mut res);
+        assert_eq!(exception_of(response), Some(exception::ILLEGAL_ADDRESS));
+    }
+
+    #[test]
+    fn multi_server() {
+        let mut first = Registers::default();
+        let mut second = Registers::default();
+        let mut server = MultiServer::new([
+            (Device::new(1), &mut first as &mut dyn Handler),
+            (Device::new(2), &mut second as &mut dyn Handler),
+        ]);
+        let mut req = [0; 8];
+        let mut res = [0; 256];
+
+        let request = request::write_holding_register(&mut req, 2, 0, 7).unwrap();
+        let response = server
+            .process(request.try_into().unwrap(), &mut res)
+            .unwrap();
+        assert_eq!(response.address(), 2);
+
+        let request = request::write_holding_register(&mut req, 0, 3, 9).unwrap();
+        assert!(server
+            .process(request.try_into().unwrap(), &mut res)
+            .is_none());
+
+        let request = request::read_holding_registers(&mut req, 3, 0, 1).unwrap();
+        let response = server
+            .process(request.try_into().unwrap(), &mut res)
+            .unwrap();
+        assert_eq!(
+            exception_of(response),
+            Some(exception::GATEWAY_PATH_UNAVAILABLE)
+        );
+
+        let request = request::read_holding_registers(&mut req, 248, 0, 1).unwrap();
+        assert!(server
+            .process(request.try_into().unwrap(), &mut res)
+            .is_none());
+
+        let mut trace = Counts::default();
+        let mut server = server.with_trace(&mut trace);
+        let request = request::read_holding_registers(&mut req, 1, 0, 1).unwrap();
+        assert!(server
+            .process(request.try_into().unwrap(), &mut res)
+            .is_some());
+        req[7] ^= 0xFF;
+        assert!(server.process_bytes(&req, &mut res).is_none());
+        // the same bytes are accepted without the CRC check
+        let mut server = server.without_crc_check();
+        assert!(server.process_bytes(&req, &mut res).is_some());
+        assert_eq!((trace.tx, trace.rx, trace.errors), (2, 3, 1));
+        assert_eq!(first.values, [0, 0, 0, 9]);
+        assert_eq!(second.values, [7, 0, 0, 9]);
+    }
+
+    #[test]
+    fn diagnostic_counters() {
+        let mut server = MultiServer::new([(Device::new(1), Registers::default())]);
+        let mut req = [0; 8];
+        let mut res = [0; 256];
+
+        let request = request::read_holding_registers(&mut req, 1, 0, 1).unwrap();
+        server.process(request.try_into().unwrap(), &mut res);
+        let request = request::read_holding_registers(&mut req, 1, 10, 1).unwrap();
+        server.process(request.try_into().unwrap(), &mut res);
+        let request = request::write_holding_register(&mut req, 0, 0, 1).unwrap();
+        server.process(request.try_into().unwrap(), &mut res);
+        let request = request::read_holding_registers(&mut req, 2, 0, 1).unwrap();
+        server.process(request.try_into().unwrap(), &mut res);
+        req[7] ^= 0xFF;
+        server.process_bytes(&req, &mut res);
+
+        let counters = server.counters().clone();
+        assert_eq!(counters.bus_messages, 4);
+        assert_eq!(counters.bus_comm_errors, 1);
+        assert_eq!(counters.bus_exceptions, 2);
+        assert_eq!(counters.server_messages, 4);
+        assert_eq!(counters.server_no_responses, 1);
+        // the read and the broadcast write completed
+        assert_eq!(counters.events, 2);
+
+        let request =
+            request::diagnostic(&mut req, 1, diagnostic::SERVER_MESSAGE_COUNT, 0).unwrap();
+        let response = server
+            .process(request.try_into().unwrap(), &mut res)
+            .unwrap();
+        assert_eq!(response.payload(), [0, 0x0E, 0, 4]);
+        assert_eq!(server.counters().bus_messages, 5);
+
+        // a broadcast which fails isn't an event
+        let request = request::write_holding_register(&mut req, 0, 10, 1).unwrap();
+        server.process(request.try_into().unwrap(), &mut res);
+        let counters = server.counters().clone();
+        assert_eq!((counters.server_no_responses, counters.events), (2, 3));
+    }
+
+    #[derive(Default)]
+    struct Restarts(Vec<bool>);
+
+    impl Handler for Restarts {
+        fn read_coil(&mut self, _index: u16) -> Result<bool, Exception> {
+            Ok(true)
+        }
+
+        fn restart_communications(&mut self, clear_log: bool) {
+            self.0.push(clear_log);
+        }
+    }
+
+    #[test]
+    fn listen_only() {
+        let mut server = MultiServer::new([(Device::new(1), Restarts::default())]);
+        let mut req = [0; 8];
+        let mut res = [0; 256];
+
+        let request =
+            request::diagnostic(&mut req, 1, diagnostic::RESTART_COMMUNICATIONS, 0xFF00).unwrap();
+        let response = server
+            .process(request.try_into().unwrap(), &mut res)
+            .unwrap();
+        assert_eq!(response.payload(), [0, 1, 0xFF, 0]);
+
+        let request = request::diagnostic(&mut req, 1, diagnostic::FORCE_LISTEN_ONLY, 0).unwrap();
+        assert!(server
+            .process(request.try_into().unwrap(), &mut res)
+            .is_none());
+        assert!(server.is_listen_only());
+        let request = request::read_coils(&mut req, 1, 0, 1).unwrap();
+        assert!(server
+            .process(request.try_into().unwrap(), &mut res)
+            .is_none());
+        let request = request::read_coils(&mut req, 2, 0, 1).unwrap();
+        assert!(server
+            .process(request.try_into().unwrap(), &mut res)
+            .is_none());
+
+        let request =
+            request::diagnostic(&mut req, 1, diagnostic::RESTART_COMMUNICATIONS, 0).unwrap();
+        assert!(server
+            .process(request.try_into().unwrap(), &mut res)
+            .is_none());
+        assert!(!server.is_listen_only());
+        let request = request::read_coils(&mut req, 1, 0, 1).unwrap();
+        assert!(server
+            .process(request.try_into().unwrap(), &mut res)
+            .is_some());
+
+        let request =
+            request::diagnostic(&mut req, 1, diagnostic::RESTART_COMMUNICATIONS, 1).unwrap();
+        let response = server
+            .process(request.try_into().unwrap(), &mut res)
+            .unwrap();
+        assert_eq!(exception_of(response), Some(exception::ILLEGAL_DATA));
+
+        assert_eq!(server.handler(Device::new(1)).unwrap().0, [true, false]);
+    }
+
+    #[test]
+    fn validate_requests() {
+        use super::validate_request_frame as validate;
+        use crate::{builder, function, request::FileRecord, Function};
+
+        let mut buf = [0; 64];
+        let ok = |frame: Result<Frame, Error>| validate(frame.unwrap());
+        assert_eq!(ok(request::read_exception_status(&mut buf, 1)), Ok(()));
+        assert_eq!(ok(request::read_fifo_queue(&mut buf, 1, 4)), Ok(()));
+        assert_eq!(
+            ok(request::mask_write_register(&mut buf, 1, 4, 1, 2)),
+            Ok(())
+        );
+        assert_eq!(
+            ok(request::read_write_multiple_registers(
+                &mut buf,
+                1,
+                0,
+                2,
+                0xFFFF,
+                &[1, 2]
+            )),
+            Err(exception::ILLEGAL_ADDRESS)
+        );
+        assert_eq!(
+            ok(request::read_device_identification(&mut buf, 1, 1, 0)),
+            Ok(())
+        );
+        let record = FileRecord {
+            file_number: 1,
+            record_number: 2,
+            record_length: 3,
+        };
+        assert_eq!(
+            ok(request::read_file_record(&mut buf, 1, &[record])),
+            Ok(())
+        );
+        let record = FileRecord {
+            record_length: 125,
+            ..record
+        };
+        assert_eq!(
+            ok(request::read_file_record(&mut buf, 1, &[record, record])),
+            Err(exception::ILLEGAL_DATA)
+        );
+        let (record, _) = builder::build_frame(&mut buf)
+            .for_address(1)
+            .function(function::WRITE_FILE_RECORD)
+            .bytes([9, 6])
+            .registers([1, 0x2710, 1, 0xABCD])
+            .finalise();
+        assert_eq!(validate(record), Err(exception::ILLEGAL_ADDRESS));
+
+        let (coil, _) = builder::build_frame(&mut buf)
+            .for_address(1)
+            .function(function::WRITE_COIL)
+            .registers([0, 0x1234])
+            .finalise();
+        assert_eq!(validate(coil), Err(exception::ILLEGAL_DATA));
+        let (custom, _) = builder::build_frame(&mut buf)
+            .for_address(1)
+            .function(Function(0x41))
+            .finalise();
+        assert_eq!(validate(custom), Err(exception::ILLEGAL_FUNCTION));
+    }
+
+    #[test]
+    fn respond_addressing() {
+        let mut handler = Coils([false; 2]);
+        let device = Device::new(3);
+        let mut req = [0; 8];
+        let mut res = [0; 256];
+
+        let request = request::write_coil(&mut req, 0, 1, true).unwrap();
+        assert_eq!(
+            respond(device, request.try_into().unwrap(), &mut handler, &mut res),
+            None
+        );
+        let request = request::write_coil(&mut req, 4, 0, true).unwrap();
+        assert_eq!(
+            respond(device, request.try_into().unwrap(), &mut handler, &mut res),
+            None
+        );
+        assert_eq!(handler.0, [false, true]);
+
+        let request = request::read_coils(&mut req, 3, 0, 2).unwrap();
+        let response =
+            respond(device, request.try_into().unwrap(), &mut handler, &mut res).unwrap();
+        assert_eq!(response.payload(), [1, 0b10]);
+        let request = request::read_coils(&mut req, 248, 0, 2).unwrap();
+        assert_eq!(
+            respond(device, request.try_into().unwrap(), &mut handler, &mut res),
+            None
+        );
+    }
+
+    #[test]
+    fn frame_handlers() {
+        let mut trace = Counts::default();
+        let server =
+            MultiServer::new([(Device::new(1), Registers::default())]).with_trace(&mut trace);
+        let mut handlers: [Box<dyn FrameHandler + '_>; 3] = [
+            Box::new(Dispatch(Registers::default())),
+            Box::new(Respond::new(Device::new(2), Registers::default())),
+            Box::new(server),
+        ];
+        let mut req = [0; 8];
+        let mut res = [0; 256];
+
+        let request = request::write_holding_register(&mut req, 1, 0, 7).unwrap();
+        let lens = handlers
+            .iter_mut()
+            .map(|handler| handler.handle(&request.try_into().unwrap(), &mut res))
+            .collect::<Vec<_>>();
+        assert_eq!(lens, [Some(8), None, Some(8)]);
+        assert_eq!(res[..8], *request.raw_bytes());
+
+        let request = request::read_holding_registers(&mut req, 2, 0, 9).unwrap();
+        let len = handlers[1]
+            .handle(&request.try_into().unwrap(), &mut res)
+            .unwrap();
+        assert_eq!(
+            exception_of(Frame::try_from(&res[..len]).unwrap()),
+            Some(exception::ILLEGAL_ADDRESS)
+        );
+
+        // invalid bytes are ignored
+        let mut corrupt = req;
+        corrupt[7] ^= 1;
+        for handler in handlers.iter_mut() {
+            assert_eq!(handler.handle_bytes(&corrupt, &mut res), None);
+        }
+        drop(handlers);
+        assert_eq!((trace.rx, trace.tx, trace.errors), (2, 1, 1));
+    }
+}
@@ -0,0 +1,434 @@
+//! Simulated slave for testing client retry logic (feature `std`)
+//!
+//! [`SlaveSim`] is a [`Transport`] answering requests from a [`Handler`] the way a device on the bus would. Each
+//! request takes the next [`Behavior`] from a script, so timeouts, lost responses, line noise and spurious exceptions
+//! happen exactly when a test wants them. [`Bus`] connects several slaves to one master with noise and propagation
+//! delay for testing receivers and monitors end to end. Time is simulated, nothing sleeps.
+//!
+//! ```
+//! use modbus_frames::{client::Client, entity::Entity, sim::{Behavior, SlaveSim}, source::Coils};
+//!
+//! let mut sim = SlaveSim::new(1, Coils([true, false]));
+//! sim.push(Behavior::Drop);
+//! sim.push(Behavior::CorruptCrc);
+//! let mut client = Client::new(sim, 1).with_retries(2);
+//! assert_eq!(client.read_bool(Entity::coil(0)), Ok(true));
+//! assert_eq!(client.transport().requests(), 3);
+//! ```
+
+use std::collections::VecDeque;
+
+use crate::{
+    builder,
+    client::Transport,
+    server::{self, Handler},
+    Device, Error, Exception, Frame, ValidatedFrame,
+};
+
+/// How the simulated slave treats a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Behavior {
+    /// process the request and respond immediately
+    Normal,
+    /// process the request and respond after this many microseconds, no response is seen if it is longer than the
+    /// timeout
+    Delay(u32),
+    /// process the request but the response is lost
+    Drop,
+    /// process the request and respond with a corrupted CRC
+    CorruptCrc,
+    /// respond with this exception without processing the request
+    Exception(Exception),
+}
+
+/// A slave device answering requests from a handler according to a script
+#[derive(Debug)]
+pub struct SlaveSim<H> {
+    device: Device,
+    handler: H,
+    script: VecDeque<Behavior>,
+    timeout_us: u32,
+    elapsed_us: u64,
+    requests: u32,
+}
+
+impl<H: Handler> SlaveSim<H> {
+    /// Slave at `address` with an empty script, requests are answered normally and time out after 100ms
+    pub fn new(address: u8, handler: H) -> Self {
+        SlaveSim {
+            device: Device::new(address),
+            handler,
+            script: VecDeque::new(),
+            timeout_us: 100_000,
+            elapsed_us: 0,
+            requests: 0,
+        }
+    }
+
+    /// How long the client waits for a response
+    pub fn with_timeout_us(self, timeout_us: u32) -> Self {
+        SlaveSim { timeout_us, ..self }
+    }
+
+    /// Apply `behavior` to the next unscripted request
+    pub fn push(&mut self, behavior: Behavior) {
+        self.script.push_back(behavior);
+    }
+
+    /// Behaviors not yet used
+    pub fn script(&self) -> &VecDeque<Behavior> {
+        &self.script
+    }
+
+    /// Requests received, including those for other addresses
+    pub fn requests(&self) -> u32 {
+        self.requests
+    }
+
+    /// Simulated time spent waiting for responses
+    pub fn elapsed_us(&self) -> u64 {
+        self.elapsed_us
+    }
+
+    pub fn handler(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    pub fn into_inner(self) -> H {
+        self.handler
+    }
+}
+
+impl<H: Handler> SlaveSim<H> {
+    /// Write the bytes the slave transmits in answer to `request`, `None` if it doesn't answer
+    ///
+    /// Returns the length written and the response delay, the delay isn't checked against the timeout
+    fn answer(&mut self, request: Frame, buffer: &mut [u8]) -> Option<(usize, u32)> {
+        self.requests += 1;
+        let behavior = self.script.pop_front().unwrap_or(Behavior::Normal);
+        let len = match behavior {
+            Behavior::Exception(exception) if request.device() == self.device => {
+                builder::build_frame(buffer)
+                    .exception_to(&request, exception)
+                    .0
+                    .raw_bytes()
+                    .len()
+            }
+            // like a real slave, requests with a bad CRC are ignored
+            _ => server::respond(
+                self.device,
+                ValidatedFrame::try_from(request).ok()?,
+                &mut self.handler,
+                buffer,
+            )?
+            .raw_bytes()
+            .len(),
+        };
+        match behavior {
+            Behavior::Drop => None,
+            Behavior::Delay(us) => Some((len, us)),
+            Behavior::CorruptCrc => {
+                buffer[len - 1] ^= 0xFF;
+                Some((len, 0))
+            }
+            _ => Some((len, 0)),
+        }
+    }
+}
+
+impl<H: Handler> Transport for SlaveSim<H> {
+    fn transact<'b>(
+        &mut self,
+        request: Frame,
+        response_buffer: &'b mut [u8],
+    ) -> Result<Frame<'b>, Error> {
+        match self.answer(request, response_buffer) {
+            Some((len, delay)) if delay <= self.timeout_us => {
+                self.elapsed_us += u64::from(delay);
+                // decoded as a transport would from the received bytes
+                let received: &'b [u8] = response_buffer;
+                Frame::try_from(&received[..len])
+            }
+            _ => {
+                self.elapsed_us += u64::from(self.timeout_us);
+                Err(Error::NoResponse)
+            }
+        }
+    }
+}
+
+/// Interference applied to a frame on the [`Bus`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Noise {
+    /// the frame arrives intact
+    Clean,
+    /// invert bit `bit` (0-7) of byte `byte`, nothing happens if the frame is shorter
+    FlipBit { byte: usize, bit: u8 },
+    /// only the first `len` bytes arrive
+    Truncate(usize),
+}
+
+impl Noise {
+    /// the length of `bytes` still arriving after the noise is applied
+    fn apply(self, bytes: &mut [u8]) -> usize {
+        match self {
+            Noise::Clean => bytes.len(),
+            Noise::FlipBit { byte, bit } => {
+                if let Some(b) = bytes.get_mut(byte) {
+                    *b ^= 1 << (bit & 7);
+                }
+                bytes.len()
+            }
+            Noise::Truncate(len) => len.min(bytes.len()),
+        }
+    }
+}
+
+/// Bytes transmitted on the [`Bus`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transmission {
+    /// simulated time the first byte was received
+    pub start_us: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// A master and any number of [`SlaveSim`]s sharing one line
+///
+/// Requests reach every slave, a slave whose copy fails the CRC ignores it like a real device. Responses from more
+/// than one slave collide and arrive garbled. Every frame takes the next [`Noise`] from a script and arrives after
+/// the propagation delay, the bytes seen on the line are kept to feed a [`Monitor`](crate::monitor::Monitor) or
+/// [`RxRing`](crate::rx::RxRing).
+#[derive(Debug)]
+pub struct Bus<H> {
+    slaves: Vec<SlaveSim<H>>,
+    noise: VecDeque<Noise>,
+    propagation_us: u32,
+    timeout_us: u32,
+    elapsed_us: u64,
+    collisions: u32,
+    line: Vec<Transmission>,
+}
+
+impl<H: Handler> Bus<H> {
+    /// Bus without noise or propagation delay, the master times out after 100ms
+    pub fn new(slaves: impl IntoIterator<Item = SlaveSim<H>>) -> Self {
+        Bus {
+            slaves: slaves.into_iter().collect(),
+            noise: VecDeque::new(),
+            propagation_us: 0,
+            timeout_us: 100_000,
+            elapsed_us: 0,
+            collisions: 0,
+            line: Vec::new(),
+        }
+    }
+
+    /// Delay between a frame being sent and arriving
+    pub fn with_propagation_us(self, propagation_us: u32) -> Self {
+        Bus {
+            propagation_us,
+            ..self
+        }
+    }
+
+    /// How long the master waits for a response, from the request arriving to the response arriving
+    pub fn with_timeout_us(self, timeout_us: u32) -> Self {
+        Bus { timeout_us, ..self }
+    }
+
+    /// Apply `noise` to the next frame, request or response, without scripted noise
+    pub fn push_noise(&mut self, noise: Noise) {
+        self.noise.push_back(noise);
+    }
+
+    pub fn slave(&mut self, idx: usize) -> Option<&mut SlaveSim<H>> {
+        self.slaves.get_mut(idx)
+    }
+
+    /// Requests answered by more than one slave
+    pub fn collisions(&self) -> u32 {
+        self.collisions
+    }
+
+    pub fn elapsed_us(&self) -> u64 {
+        self.elapsed_us
+    }
+
+    /// Everything received on the line so far, requests and responses in order
+    pub fn line(&self) -> &[Transmission] {
+        &self.line
+    }
+
+    pub fn clear_line(&mut self) {
+        self.line.clear();
+    }
+
+    pub fn into_inner(self) -> Vec<SlaveSim<H>> {
+        self.slaves
+    }
+
+    /// put `bytes` on the line after the propagation delay, returning the length that arrives
+    fn transmit(&mut self, bytes: &mut [u8]) -> usize {
+        let noise = self.noise.pop_front().unwrap_or(Noise::Clean);
+        let len = noise.apply(bytes);
+        self.elapsed_us += u64::from(self.propagation_us);
+        self.line.push(Transmission {
+            start_us: self.elapsed_us,
+            bytes: bytes[..len].to_vec(),
+        });
+        len
+    }
+}
+
+impl<H: Handler> Transport for Bus<H> {
+    fn transact<'b>(
+        &mut self,
+        request: Frame,
+        response_buffer: &'b mut [u8],
+    ) -> Result<Frame<'b>, Error> {
+        let mut sent = [0; 256];
+        let sent = &mut sent[..request.raw_bytes().len()];
+        sent.copy_from_slice(request.raw_bytes());
+        let len = self.transmit(sent);
+
+        let mut received = [0; 256];
+        let mut answer: Option<(usize, u32)> = None;
+        if let Ok(request) = Frame::try_from(&sent[..len]) {
+            for slave in &mut self.slaves {
+                let mut own = [0; 256];
+                let Some((len, delay)) = slave.answer(request, &mut own) else {
+                    continue;
+                };
+                answer = Some(match answer {
+                    None => {
+                        received[..len].copy_from_slice(&own[..len]);
+                        (len, delay)
+                    }
+                    Some((other, other_delay)) => {
+                        // both drivers fight over the line
+                        self.collisions += 1;
+                        for (r, o) in received.iter_mut().zip(&own[..len]) {
+                            *r &= o;
+                        }
+                        (other.max(len), other_delay.min(delay))
+                    }
+                });
+            }
+        }
+        let Some((len, delay)) = answer.filter(|(_, delay)| *delay <= self.timeout_us) else {
+            self.elapsed_us += u64::from(self.timeout_us);
+            return Err(Error::NoResponse);
+        };
+        self.elapsed_us += u64::from(delay);
+        let len = self.transmit(&mut received[..len]);
+        if len == 0 {
+            self.elapsed_us += u64::from(self.timeout_us - delay);
+            return Err(Error::NoResponse);
+        }
+        let response = response_buffer
+            .get_mut(..len)
+            .ok_or(Error::BufferTooSmall)?;
+        response.copy_from_slice(&received[..len]);
+        Frame::try_from(&*response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Behavior, Bus, Noise, SlaveSim};
+    use crate::{
+        client::{Client, Transport},
+        entity::Entity,
+        exception,
+        monitor::{Event, Monitor},
+        request, response,
+        rx::RxRing,
+        source::Coils,
+        Error,
+    };
+
+    #[test]
+    fn scripted() {
+        let mut sim = SlaveSim::new(1, Coils([false; 4])).with_timeout_us(1000);
+        sim.push(Behavior::Delay(500));
+        sim.push(Behavior::Delay(2000));
+        sim.push(Behavior::Exception(exception::DEVICE_BUSY));
+
+        let mut req = [0; 8];
+        let mut res = [0; 256];
+        let read = request::read_coils(&mut req, 1, 0, 4).unwrap();
+        assert_eq!(sim.transact(read, &mut res).unwrap().payload(), [1, 0]);
+        assert_eq!(sim.elapsed_us(), 500);
+        assert_eq!(sim.transact(read, &mut res), Err(Error::NoResponse));
+        assert_eq!(sim.elapsed_us(), 1500);
+        let busy = sim.transact(read, &mut res).unwrap();
+        assert_eq!(
+            response::exception_error(busy),
+            Error::Exception(exception::DEVICE_BUSY)
+        );
+        assert!(sim.script().is_empty());
+
+        // other devices don't answer
+        let other = request::read_coils(&mut req, 2, 0, 4).unwrap();
+        assert_eq!(sim.transact(other, &mut res), Err(Error::NoResponse));
+
+        // lost and corrupted responses still change the device
+        sim.push(Behavior::Drop);
+        sim.push(Behavior::CorruptCrc);
+        let mut client = Client::new(sim, 1).with_retries(1);
+        assert_eq!(client.write_bool(2, true), Err(Error::InvalidCrc));
+        assert_eq!(client.transport().handler().0, [false, false, true, false]);
+        assert_eq!(client.read_bool(Entity::coil(2)), Ok(true));
+        assert_eq!(client.into_transport().requests(), 7);
+    }
+
+    #[test]
+    fn bus() {
+        let slaves = [(1, false), (2, true), (3, true), (3, false)]
+            .map(|(address, on)| SlaveSim::new(address, Coils([on; 2])));
+        let bus = Bus::new(slaves)
+            .with_propagation_us(100)
+            .with_timeout_us(1000);
+        let mut client = Client::new(bus, 2).with_retries(1);
+
+        // the corrupted request is ignored, the retry is answered
+        client
+            .transport()
+            .push_noise(Noise::FlipBit { byte: 2, bit: 7 });
+        assert_eq!(client.read_bool(Entity::coil(1)), Ok(true));
+        assert_eq!(client.transport().elapsed_us(), 100 + 1000 + 200);
+        assert_eq!(client.transport().slave(1).unwrap().requests(), 1);
+
+        client.transport().push_noise(Noise::Clean);
+        client.transport().push_noise(Noise::Truncate(3));
+        client.transport().push_noise(Noise::Clean);
+        client.transport().push_noise(Noise::Truncate(0));
+        assert_eq!(client.read_bool(Entity::coil(0)), Err(Error::NoResponse));
+
+        // both slaves at address 3 answer
+        client.set_device(crate::Device::new(3));
+        assert!(client.read_bool(Entity::coil(0)).is_err());
+        assert_eq!(client.transport().collisions(), 2);
+
+        // the line as seen by a receiver
+        let bus = client.into_transport();
+        let mut ring = RxRing::<256>::new(50);
+        let mut frames = 0;
+        for transmission in bus.line() {
+            let now = transmission.start_us as u32;
+            frames += usize::from(ring.poll(now).is_some());
+            ring.push(&transmission.bytes, now);
+        }
+        // only the requests and the first response survive the noise and collisions
+        assert_eq!(frames + usize::from(ring.poll(u32::MAX / 2).is_some()), 6);
+        let line: Vec<u8> = bus
+            .line()
+            .iter()
+            .flat_map(|transmission| transmission.bytes.iter().copied())
+            .collect();
+        let garbage = Monitor::new(&line)
+            .filter(|event| matches!(event, Event::Garbage(_)))
+            .count();
+        assert!(garbage > 0);
+    }
+}
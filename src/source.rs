@@ -0,0 +1,238 @@
+//! Entity values computed on demand, e.g. ADC reads or values held in flash
+//!
+//! A [`RegisterSource`] is read one register at a time while the response is built, so a server never needs an
+//! intermediate array of the values. [`BitSource`] and [`BitSink`] do the same for coils and discrete inputs, and
+//! [`Coils`], [`DiscreteInputs`] and [`InputRegisters`] serve them directly through
+//! [`server::dispatch`](crate::server::dispatch).
+//!
+//! ```
+//! use modbus_frames::{builder, exception, function, Exception};
+//!
+//! let adc = |index: u16| match index {
+//!     0..=3 => Ok(100 * index),
+//!     _ => Err(exception::ILLEGAL_ADDRESS),
+//! };
+//! let mut buff = [0u8; 20];
+//! let (frame, _) = builder::build_frame(&mut buff)
+//!     .for_address(1)
+//!     .function(function::READ_INPUT_REGISTERS)
+//!     .byte(4)
+//!     .registers_from_source(2..4, &adc)
+//!     .unwrap()
+//!     .finalise();
+//! assert_eq!(frame.payload(), [4, 0, 200, 1, 44]);
+//! ```
+
+use crate::{exception, server::Handler, Exception};
+
+/// Registers read on demand
+pub trait RegisterSource {
+    fn read(&self, index: u16) -> Result<u16, Exception>;
+}
+
+/// Closures from the register index to its value
+impl<F: Fn(u16) -> Result<u16, Exception>> RegisterSource for F {
+    fn read(&self, index: u16) -> Result<u16, Exception> {
+        self(index)
+    }
+}
+
+/// Register `index` is element `index`, `ILLEGAL_ADDRESS` past the end
+impl RegisterSource for [u16] {
+    fn read(&self, index: u16) -> Result<u16, Exception> {
+        self.get(usize::from(index))
+            .copied()
+            .ok_or(exception::ILLEGAL_ADDRESS)
+    }
+}
+
+impl<const N: usize> RegisterSource for [u16; N] {
+    fn read(&self, index: u16) -> Result<u16, Exception> {
+        self.as_slice().read(index)
+    }
+}
+
+/// Coils or discrete inputs read on demand, e.g. GPIO inputs
+pub trait BitSource {
+    fn read(&self, index: u16) -> Result<bool, Exception>;
+}
+
+/// Coils written on demand, e.g. GPIO outputs
+pub trait BitSink {
+    fn write(&mut self, index: u16, on: bool) -> Result<(), Exception>;
+}
+
+/// Closures from the bit index to its state
+impl<F: Fn(u16) -> Result<bool, Exception>> BitSource for F {
+    fn read(&self, index: u16) -> Result<bool, Exception> {
+        self(index)
+    }
+}
+
+/// Closures setting the bit index to a state
+impl<F: FnMut(u16, bool) -> Result<(), Exception>> BitSink for F {
+    fn write(&mut self, index: u16, on: bool) -> Result<(), Exception> {
+        self(index, on)
+    }
+}
+
+/// Bit `index` is element `index`, `ILLEGAL_ADDRESS` past the end
+impl BitSource for [bool] {
+    fn read(&self, index: u16) -> Result<bool, Exception> {
+        self.get(usize::from(index))
+            .copied()
+            .ok_or(exception::ILLEGAL_ADDRESS)
+    }
+}
+
+/// Bit `index` is element `index`, `ILLEGAL_ADDRESS` past the end
+impl BitSink for [bool] {
+    fn write(&mut self, index: u16, on: bool) -> Result<(), Exception> {
+        let bit = self
+            .get_mut(usize::from(index))
+            .ok_or(exception::ILLEGAL_ADDRESS)?;
+        *bit = on;
+        Ok(())
+    }
+}
+
+impl<const N: usize> BitSource for [bool; N] {
+    fn read(&self, index: u16) -> Result<bool, Exception> {
+        self.as_slice().read(index)
+    }
+}
+
+impl<const N: usize> BitSink for [bool; N] {
+    fn write(&mut self, index: u16, on: bool) -> Result<(), Exception> {
+        self.as_mut_slice().write(index, on)
+    }
+}
+
+/// A server exposing only coils, other functions receive the `ILLEGAL_FUNCTION` exception
+///
+/// ```
+/// use modbus_frames::{request, server, source::Coils, ValidatedFrame};
+///
+/// let mut outputs = Coils([false; 8]);
+/// let mut request_buffer = [0; 16];
+/// let mut response_buffer = [0; 256];
+/// let request = request::write_multiple_coils(&mut request_buffer, 1, 2, &[true, true]).unwrap();
+/// server::dispatch(&mut outputs, request.try_into().unwrap(), &mut response_buffer);
+/// assert_eq!(outputs.0, [false, false, true, true, false, false, false, false]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Coils<S>(pub S);
+
+impl<S: BitSource + BitSink> Handler for Coils<S> {
+    fn read_coil(&mut self, index: u16) -> Result<bool, Exception> {
+        self.0.read(index)
+    }
+
+    fn write_coil(&mut self, index: u16, on: bool) -> Result<(), Exception> {
+        self.0.write(index, on)
+    }
+}
+
+/// A server exposing only discrete inputs, other functions receive the `ILLEGAL_FUNCTION` exception
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DiscreteInputs<S>(pub S);
+
+impl<S: BitSource> Handler for DiscreteInputs<S> {
+    fn read_discrete_input(&mut self, index: u16) -> Result<bool, Exception> {
+        self.0.read(index)
+    }
+}
+
+/// A server exposing only input registers, other functions receive the `ILLEGAL_FUNCTION` exception
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InputRegisters<S>(pub S);
+
+impl<S: RegisterSource> Handler for InputRegisters<S> {
+    fn read_input_register(&mut self, index: u16) -> Result<u16, Exception> {
+        self.0.read(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Coils, DiscreteInputs, RegisterSource};
+    use crate::{builder, exception, function, request, server};
+
+    #[test]
+    fn registers_from_source() {
+        let registers = [1, 2, 3];
+        assert_eq!(registers.read(2), Ok(3));
+        assert_eq!(registers.read(3), Err(exception::ILLEGAL_ADDRESS));
+
+        let mut buff = [0u8; 20];
+        let result = builder::build_frame(&mut buff)
+            .for_address(1)
+            .function(function::READ_HOLDING_REGISTERS)
+            .byte(6)
+            .registers_from_source(1..4, &registers);
+        assert!(matches!(result, Err(exception::ILLEGAL_ADDRESS)));
+
+        // the buffer is free again for the exception response
+        let (frame, _) = builder::build_frame(&mut buff)
+            .for_address(1)
+            .function(function::READ_HOLDING_REGISTERS)
+            .byte(4)
+            .registers_from_source(1..3, &registers[..])
+            .unwrap()
+            .finalise();
+        assert_eq!(frame.payload(), [4, 0, 2, 0, 3]);
+    }
+
+    #[test]
+    fn bits_through_server() {
+        let mut request_buffer = [0; 16];
+        let mut response_buffer = [0; 256];
+
+        let mut coils = Coils([false; 3]);
+        let request =
+            request::write_multiple_coils(&mut request_buffer, 1, 1, &[true, true]).unwrap();
+        let response = server::dispatch(
+            &mut coils,
+            request.try_into().unwrap(),
+            &mut response_buffer,
+        );
+        assert_eq!(response.function(), function::WRITE_MULTIPLE_COILS);
+        let request = request::read_coils(&mut request_buffer, 1, 0, 4).unwrap();
+        let response = server::dispatch(
+            &mut coils,
+            request.try_into().unwrap(),
+            &mut response_buffer,
+        );
+        assert_eq!(response.payload(), [exception::ILLEGAL_ADDRESS.0]);
+        let request = request::read_coils(&mut request_buffer, 1, 0, 3).unwrap();
+        let response = server::dispatch(
+            &mut coils,
+            request.try_into().unwrap(),
+            &mut response_buffer,
+        );
+        assert_eq!(response.payload(), [1, 0b110]);
+
+        // exceptions from the closure become the response
+        let mut inputs = DiscreteInputs(|index| match index {
+            0 => Ok(true),
+            _ => Err(exception::DEVICE_FAILURE),
+        });
+        let request = request::read_discrete_inputs(&mut request_buffer, 1, 0, 2).unwrap();
+        let response = server::dispatch(
+            &mut inputs,
+            request.try_into().unwrap(),
+            &mut response_buffer,
+        );
+        assert_eq!(response.payload(), [exception::DEVICE_FAILURE.0]);
+        let request = request::read_coils(&mut request_buffer, 1, 0, 1).unwrap();
+        let response = server::dispatch(
+            &mut inputs,
+            request.try_into().unwrap(),
+            &mut response_buffer,
+        );
+        assert_eq!(response.payload(), [exception::ILLEGAL_FUNCTION.0]);
+    }
+}
@@ -0,0 +1,319 @@
+//! Modbus TCP over smoltcp sockets (feature `smoltcp`)
+//!
+//! For devices without an OS running smoltcp. [`TcpServer`] answers requests on a listening socket from a
+//! [`Handler`], call [`TcpServer::poll`] after each `Interface::poll`. [`TcpTransport`] is a client [`Transport`],
+//! it polls the interface through a [`Network`] until the response arrives. Frames are converted to and from the
+//! MBAP header and [`Pdu`], the unit id is the frame address.
+
+use smoltcp::{
+    socket::tcp,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    client::Transport,
+    pdu::{Mbap, Pdu, MAX_PDU_LEN, MBAP_LEN},
+    server::{self, Handler},
+    Error, Frame, ValidatedFrame,
+};
+
+/// The longest Modbus TCP ADU
+pub const MAX_ADU_LEN: usize = MBAP_LEN + MAX_PDU_LEN;
+
+/// Modbus TCP's registered port
+pub const PORT: u16 = 502;
+
+/// Accumulates one ADU at a time from the socket stream
+#[derive(Debug)]
+struct AduBuffer {
+    bytes: [u8; MAX_ADU_LEN],
+    len: usize,
+}
+
+impl AduBuffer {
+    const fn new() -> Self {
+        AduBuffer {
+            bytes: [0; MAX_ADU_LEN],
+            len: 0,
+        }
+    }
+
+    /// Read from `socket` until an ADU is complete, `None` if more bytes are needed
+    ///
+    /// An invalid length field means the stream can't be resynchronised, the connection is aborted
+    fn receive(&mut self, socket: &mut tcp::Socket) -> Result<Option<(Mbap, Pdu<'_>)>, Error> {
+        loop {
+            let total = match self.bytes[..self.len] {
+                [_, _, _, _, hi, lo, ..] => {
+                    // the length field counts the unit id and the PDU
+                    let len = usize::from(u16::from_be_bytes([hi, lo]));
+                    if !(2..=MAX_PDU_LEN + 1).contains(&len) {
+                        self.len = 0;
+                        socket.abort();
+                        return Err(Error::InvalidLength(len));
+                    }
+                    MBAP_LEN - 1 + len
+                }
+                _ => MBAP_LEN,
+            };
+            if self.len == total {
+                self.len = 0;
+                return Pdu::from_mbap(&self.bytes[..total]).map(Some);
+            }
+            if !socket.can_recv() {
+                return Ok(None);
+            }
+            match socket.recv_slice(&mut self.bytes[self.len..total]) {
+                Ok(0) => return Ok(None),
+                Ok(read) => self.len += read,
+                Err(_) => {
+                    self.len = 0;
+                    return Err(Error::NoResponse);
+                }
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+/// Modbus TCP server for one connection at a time
+#[derive(Debug)]
+pub struct TcpServer<H> {
+    handler: H,
+    port: u16,
+    rx: AduBuffer,
+}
+
+impl<H: Handler> TcpServer<H> {
+    pub fn new(handler: H, port: u16) -> Self {
+        TcpServer {
+            handler,
+            port,
+            rx: AduBuffer::new(),
+        }
+    }
+
+    pub fn handler(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    pub fn into_inner(self) -> H {
+        self.handler
+    }
+
+    /// Answer the requests received on `socket`, returning how many were answered
+    ///
+    /// Listens on the server's port when the socket is closed, and closes it when the client does. A request is
+    /// left in the socket until there is room to send any response, requests with an invalid header abort the
+    /// connection.
+    pub fn poll(&mut self, socket: &mut tcp::Socket) -> Result<usize, Error> {
+        if !socket.is_open() {
+            self.rx.clear();
+            socket.listen(self.port).map_err(|_| Error::InvalidValue)?;
+            return Ok(0);
+        }
+        if !socket.may_recv() && socket.may_send() {
+            socket.close();
+        }
+        let mut answered = 0;
+        while socket.may_send() && socket.send_capacity() - socket.send_queue() >= MAX_ADU_LEN {
+            let Some((mbap, pdu)) = self.rx.receive(socket)? else {
+                break;
+            };
+            let mut request = [0; 256];
+            let mut response = [0; 256];
+            // built with a CRC, TCP has none to check
+            let request = ValidatedFrame::new_unchecked(pdu.to_rtu(mbap.unit, &mut request)?);
+            let response = server::dispatch(&mut self.handler, request, &mut response);
+            let mut adu = [0; MAX_ADU_LEN];
+            let adu = response.pdu().to_mbap(mbap, &mut adu)?;
+            socket.send_slice(adu).map_err(|_| Error::NoResponse)?;
+            answered += 1;
+        }
+        Ok(answered)
+    }
+}
+
+/// The interface and connected socket a [`TcpTransport`] waits on
+pub trait Network<'a> {
+    /// Poll the interface (`Interface::poll`), returning the time it was polled at
+    fn poll(&mut self) -> Instant;
+
+    /// The socket connected to the server
+    fn socket(&mut self) -> &mut tcp::Socket<'a>;
+}
+
+/// Client transport over a connected socket
+///
+/// Responses with another transaction id are discarded, a closed connection or a response taking longer than the
+/// timeout is `Error::NoResponse`
+#[derive(Debug)]
+pub struct TcpTransport<N> {
+    network: N,
+    timeout: Duration,
+    transaction: u16,
+    rx: AduBuffer,
+}
+
+impl<'a, N: Network<'a>> TcpTransport<N> {
+    /// Default timeout of one second
+    pub fn new(network: N) -> Self {
+        TcpTransport {
+            network,
+            timeout: Duration::from_secs(1),
+            transaction: 0,
+            rx: AduBuffer::new(),
+        }
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        TcpTransport { timeout, ..self }
+    }
+
+    pub fn network(&mut self) -> &mut N {
+        &mut self.network
+    }
+
+    pub fn into_inner(self) -> N {
+        self.network
+    }
+}
+
+impl<'a, N: Network<'a>> Transport for TcpTransport<N> {
+    fn transact<'b>(
+        &mut self,
+        request: Frame,
+        response_buffer: &'b mut [u8],
+    ) -> Result<Frame<'b>, Error> {
+        self.transaction = self.transaction.wrapping_add(1);
+        let mbap = Mbap {
+            transaction: self.transaction,
+            unit: request.address(),
+        };
+        let mut adu = [0; MAX_ADU_LEN];
+        let adu = request.pdu().to_mbap(mbap, &mut adu)?;
+        // anything left from a response that timed out
+        self.rx.clear();
+
+        let start = self.network.poll();
+        let mut sent = false;
+        loop {
+            let socket = self.network.socket();
+            if !socket.is_active() {
+                return Err(Error::NoResponse);
+            }
+            if !sent
+                && socket.may_send()
+                && socket.send_capacity() - socket.send_queue() >= adu.len()
+            {
+                socket.send_slice(adu).map_err(|_| Error::NoResponse)?;
+                sent = true;
+            }
+            if let Some((received, pdu)) = self.rx.receive(socket)? {
+                if received.transaction == mbap.transaction {
+                    return pdu.to_rtu(received.unit, response_buffer);
+                }
+            }
+            if self.network.poll() - start > self.timeout {
+                return Err(Error::NoResponse);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Network, TcpServer, TcpTransport, PORT};
+    use crate::{client::Client, entity::Entity, source::Coils, Error};
+    use smoltcp::{
+        iface::{Config, Interface, SocketHandle, SocketSet},
+        phy::{Loopback, Medium},
+        socket::tcp,
+        time::{Duration, Instant},
+        wire::{EthernetAddress, IpAddress, IpCidr},
+    };
+
+    /// client and server on one loopback interface, the server is polled with the interface
+    struct Net {
+        iface: Interface,
+        device: Loopback,
+        sockets: SocketSet<'static>,
+        now: Instant,
+        server: TcpServer<Coils<[bool; 4]>>,
+        server_socket: SocketHandle,
+        client_socket: SocketHandle,
+    }
+
+    impl Network<'static> for Net {
+        fn poll(&mut self) -> Instant {
+            self.now += Duration::from_millis(1);
+            self.iface
+                .poll(self.now, &mut self.device, &mut self.sockets);
+            let socket = self.sockets.get_mut(self.server_socket);
+            self.server.poll(socket).unwrap();
+            self.now
+        }
+
+        fn socket(&mut self) -> &mut tcp::Socket<'static> {
+            self.sockets.get_mut(self.client_socket)
+        }
+    }
+
+    fn socket() -> tcp::Socket<'static> {
+        let buffer = || tcp::SocketBuffer::new(vec![0; 1024]);
+        tcp::Socket::new(buffer(), buffer())
+    }
+
+    #[test]
+    fn loopback() {
+        let mut device = Loopback::new(Medium::Ethernet);
+        let config = Config::new(EthernetAddress([2, 0, 0, 0, 0, 1]).into());
+        let mut iface = Interface::new(config, &mut device, Instant::ZERO);
+        iface.update_ip_addrs(|addrs| {
+            addrs
+                .push(IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8))
+                .unwrap();
+        });
+        let mut sockets = SocketSet::new(vec![]);
+        let server_socket = sockets.add(socket());
+        let client_socket = sockets.add(socket());
+        let mut server = TcpServer::new(Coils([false, true, false, false]), PORT);
+        // the first poll starts listening
+        assert_eq!(server.poll(sockets.get_mut(server_socket)), Ok(0));
+        sockets
+            .get_mut::<tcp::Socket>(client_socket)
+            .connect(iface.context(), (IpAddress::v4(127, 0, 0, 1), PORT), 49152)
+            .unwrap();
+        let net = Net {
+            iface,
+            device,
+            sockets,
+            now: Instant::ZERO,
+            server,
+            server_socket,
+            client_socket,
+        };
+
+        let mut client = Client::new(TcpTransport::new(net), 1);
+        assert_eq!(client.read_bool(Entity::coil(1)), Ok(true));
+        client.write_bool(3, true).unwrap();
+        assert_eq!(client.read_bool(Entity::coil(3)), Ok(true));
+        assert_eq!(
+            client.read_bool(Entity::coil(4)),
+            Err(Error::Exception(crate::exception::ILLEGAL_ADDRESS))
+        );
+
+        // the server stops answering
+        let mut transport = client.into_transport();
+        let net = transport.network();
+        net.server_socket = net.sockets.add(socket());
+        let mut client = Client::new(transport.with_timeout(Duration::from_millis(50)), 1);
+        assert_eq!(client.read_bool(Entity::coil(1)), Err(Error::NoResponse));
+        let net = client.transport().network();
+        assert_eq!(net.server.handler().0, [false, true, false, true]);
+        assert!(net.now > Instant::from_millis(50));
+    }
+}
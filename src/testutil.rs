@@ -0,0 +1,316 @@
+//! Encode/decode round trip testing (feature `std`)
+//!
+//! A [`Model`] is an owned description of a frame which can be encoded and decoded again. [`check`] generates
+//! models from a seeded [`Rng`] and asserts each survives the round trip bit-for-bit. [`Request`] models the
+//! requests decoded by `CommonRequests`, implement [`Model`] for custom functions to test them the same way.
+//!
+//! ```
+//! use modbus_frames::testutil::{self, Request};
+//!
+//! testutil::check::<Request>(1, 1000);
+//! ```
+
+use crate::{decoder::CommonRequests, request, Error, Frame};
+
+/// xorshift64* generator, deterministic for a given seed
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // zero is a fixed point of xorshift
+        Rng(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    pub fn u16(&mut self) -> u16 {
+        (self.next_u64() >> 48) as u16
+    }
+
+    pub fn bool(&mut self) -> bool {
+        self.next_u64() >> 63 == 1
+    }
+
+    /// uniform in `min..=max`
+    pub fn range(&mut self, min: u16, max: u16) -> u16 {
+        let span = u64::from(max - min) + 1;
+        min + (self.next_u64() % span) as u16
+    }
+}
+
+/// An owned value which encodes to a single frame
+pub trait Model: Sized + PartialEq + core::fmt::Debug {
+    fn encode<'b>(&self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error>;
+    fn decode(frame: Frame) -> Result<Self, Error>;
+    /// A random valid value
+    fn generate(rng: &mut Rng) -> Self;
+}
+
+/// Panics unless `model` encodes to a valid frame that decodes back to `model` and re-encodes to the same bytes
+pub fn assert_round_trip<M: Model>(model: &M) {
+    let mut buffer = [0; 256];
+    let mut again = [0; 256];
+    let frame = model
+        .encode(&mut buffer)
+        .unwrap_or_else(|e| panic!("{model:?} failed to encode: {e}"));
+    let bytes = frame.raw_bytes();
+    let frame =
+        Frame::try_from(bytes).unwrap_or_else(|e| panic!("{model:?} encoded {bytes:?}: {e}"));
+    let decoded = M::decode(frame).unwrap_or_else(|e| panic!("{model:?} encoded {bytes:?}: {e}"));
+    assert_eq!(&decoded, model, "decoding {bytes:?}");
+    let encoded = decoded
+        .encode(&mut again)
+        .unwrap_or_else(|e| panic!("{decoded:?} failed to encode: {e}"));
+    assert_eq!(encoded.raw_bytes(), bytes, "re-encoding {decoded:?}");
+}
+
+/// `assert_round_trip` for `cases` models generated from `seed`
+pub fn check<M: Model>(seed: u64, cases: usize) {
+    let mut rng = Rng::new(seed);
+    for _ in 0..cases {
+        assert_round_trip(&M::generate(&mut rng));
+    }
+}
+
+/// The requests decoded by `CommonRequests`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    ReadCoils {
+        address: u8,
+        start_index: u16,
+        count: u16,
+    },
+    ReadDiscreteInputs {
+        address: u8,
+        start_index: u16,
+        count: u16,
+    },
+    ReadHoldingRegisters {
+        address: u8,
+        start_index: u16,
+        count: u16,
+    },
+    ReadInputRegisters {
+        address: u8,
+        start_index: u16,
+        count: u16,
+    },
+    WriteCoil {
+        address: u8,
+        index: u16,
+        on: bool,
+    },
+    WriteHoldingRegister {
+        address: u8,
+        index: u16,
+        value: u16,
+    },
+    WriteMultipleCoils {
+        address: u8,
+        start_index: u16,
+        coils: Vec<bool>,
+    },
+    WriteMultipleHoldingRegisters {
+        address: u8,
+        start_index: u16,
+        registers: Vec<u16>,
+    },
+}
+
+impl Model for Request {
+    fn encode<'b>(&self, buffer: &'b mut [u8]) -> Result<Frame<'b>, Error> {
+        match *self {
+            Request::ReadCoils {
+                address,
+                start_index,
+                count,
+            } => request::read_coils(buffer, address, start_index, count),
+            Request::ReadDiscreteInputs {
+                address,
+                start_index,
+                count,
+            } => request::read_discrete_inputs(buffer, address, start_index, count),
+            Request::ReadHoldingRegisters {
+                address,
+                start_index,
+                count,
+            } => request::read_holding_registers(buffer, address, start_index, count),
+            Request::ReadInputRegisters {
+                address,
+                start_index,
+                count,
+            } => request::read_input_registers(buffer, address, start_index, count),
+            Request::WriteCoil { address, index, on } => {
+                request::write_coil(buffer, address, index, on)
+            }
+            Request::WriteHoldingRegister {
+                address,
+                index,
+                value,
+            } => request::write_holding_register(buffer, address, index, value),
+            Request::WriteMultipleCoils {
+                address,
+                start_index,
+                ref coils,
+            } => request::write_multiple_coils(buffer, address, start_index, coils),
+            Request::WriteMultipleHoldingRegisters {
+                address,
+                start_index,
+                ref registers,
+            } => request::write_multiple_holding_registers(buffer, address, start_index, registers),
+        }
+    }
+
+    fn decode(frame: Frame) -> Result<Self, Error> {
+        let address = frame.address();
+        Ok(match CommonRequests::try_from(frame)? {
+            CommonRequests::ReadCoils(r) => Request::ReadCoils {
+                address,
+                start_index: r.start_index(),
+                count: r.coil_count(),
+            },
+            CommonRequests::ReadDiscreteInputs(r) => Request::ReadDiscreteInputs {
+                address,
+                start_index: r.start_index(),
+                count: r.input_count(),
+            },
+            CommonRequests::ReadHolsingRegisters(r) => Request::ReadHoldingRegisters {
+                address,
+                start_index: r.start_index(),
+                count: r.register_count(),
+            },
+            CommonRequests::ReadInputRegisters(r) => Request::ReadInputRegisters {
+                address,
+                start_index: r.start_index(),
+                count: r.register_count(),
+            },
+            CommonRequests::WriteCoil(r) => Request::WriteCoil {
+                address,
+                index: r.index(),
+                on: r.coil_state().map_err(|_| Error::InvalidValue)?,
+            },
+            CommonRequests::WriteHoldingRegister(r) => Request::WriteHoldingRegister {
+                address,
+                index: r.index(),
+                value: r.value(),
+            },
+            CommonRequests::WriteMultipleCoils(r) => Request::WriteMultipleCoils {
+                address,
+                start_index: r.start_index(),
+                coils: r.iter_coils().map(|(_, on)| on).collect(),
+            },
+            CommonRequests::WriteMultipleHoldingRegisters(r) => {
+                Request::WriteMultipleHoldingRegisters {
+                    address,
+                    start_index: r.start_index(),
+                    registers: r.iter_registers().collect(),
+                }
+            }
+        })
+    }
+
+    fn generate(rng: &mut Rng) -> Self {
+        let address = rng.u8();
+        // ranges never run past the end of the address space
+        let start = |rng: &mut Rng, count: u16| rng.range(0, u16::MAX - (count - 1));
+        match rng.range(0, 7) {
+            kind @ 0..=3 => {
+                let max = if kind < 2 {
+                    request::MAX_READ_BITS
+                } else {
+                    request::MAX_READ_REGISTERS
+                };
+                let count = rng.range(1, max);
+                let start_index = start(rng, count);
+                match kind {
+                    0 => Request::ReadCoils {
+                        address,
+                        start_index,
+                        count,
+                    },
+                    1 => Request::ReadDiscreteInputs {
+                        address,
+                        start_index,
+                        count,
+                    },
+                    2 => Request::ReadHoldingRegisters {
+                        address,
+                        start_index,
+                        count,
+                    },
+                    _ => Request::ReadInputRegisters {
+                        address,
+                        start_index,
+                        count,
+                    },
+                }
+            }
+            4 => Request::WriteCoil {
+                address,
+                index: rng.u16(),
+                on: rng.bool(),
+            },
+            5 => Request::WriteHoldingRegister {
+                address,
+                index: rng.u16(),
+                value: rng.u16(),
+            },
+            6 => {
+                let count = rng.range(1, request::MAX_WRITE_COILS);
+                Request::WriteMultipleCoils {
+                    address,
+                    start_index: start(rng, count),
+                    coils: (0..count).map(|_| rng.bool()).collect(),
+                }
+            }
+            _ => {
+                let count = rng.range(1, request::MAX_WRITE_REGISTERS);
+                Request::WriteMultipleHoldingRegisters {
+                    address,
+                    start_index: start(rng, count),
+                    registers: (0..count).map(|_| rng.u16()).collect(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, Model, Request, Rng};
+
+    #[test]
+    fn requests_round_trip() {
+        check::<Request>(0x5EED, 2000);
+    }
+
+    #[test]
+    fn generates_every_request() {
+        let mut rng = Rng::new(0);
+        let mut seen = [false; 8];
+        for _ in 0..200 {
+            let kind = match Request::generate(&mut rng) {
+                Request::ReadCoils { .. } => 0,
+                Request::ReadDiscreteInputs { .. } => 1,
+                Request::ReadHoldingRegisters { .. } => 2,
+                Request::ReadInputRegisters { .. } => 3,
+                Request::WriteCoil { .. } => 4,
+                Request::WriteHoldingRegister { .. } => 5,
+                Request::WriteMultipleCoils { .. } => 6,
+                Request::WriteMultipleHoldingRegisters { .. } => 7,
+            };
+            seen[kind] = true;
+        }
+        assert_eq!(seen, [true; 8]);
+    }
+}
@@ -0,0 +1,267 @@
+//! Rate limiting requests to a server
+//!
+//! [`Throttle`] keeps a token bucket for each request source, a unit id on a serial bus or a connection on TCP.
+//! Each request takes a token, a source without one is answered with the `DEVICE_BUSY` exception without the
+//! handler seeing the request, so an aggressive master can't starve the rest of the firmware.
+//!
+//! ```
+//! use modbus_frames::{exception, request, server, source::Coils, throttle::Throttle, ValidatedFrame};
+//!
+//! // bursts of 2 requests, then one every 100 ticks
+//! let mut throttle = Throttle::<4>::new(2, 100);
+//! let mut handler = Coils([false; 8]);
+//! let mut request_buffer = [0; 8];
+//! let mut response_buffer = [0; 256];
+//! let request = request::read_coils(&mut request_buffer, 1, 0, 8).unwrap();
+//! let request = ValidatedFrame::try_from(request).unwrap();
+//! for _ in 0..2 {
+//!     let response = throttle.dispatch(1, 0, &mut handler, request, &mut response_buffer);
+//!     assert_eq!(response.function(), request.function());
+//! }
+//! let response = throttle.dispatch(1, 50, &mut handler, request, &mut response_buffer);
+//! assert_eq!(response.payload(), [exception::DEVICE_BUSY.0]);
+//! let response = throttle.dispatch(1, 100, &mut handler, request, &mut response_buffer);
+//! assert_eq!(response.function(), request.function());
+//! ```
+
+use crate::{
+    device::AddressKind,
+    exception,
+    server::{self, Handler},
+    Device, Frame, ValidatedFrame,
+};
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    source: u8,
+    tokens: u16,
+    /// time the last token was added
+    refilled: u32,
+}
+
+impl Bucket {
+    /// tokens to add at time `now`
+    fn refills(&self, now: u32, refill_ticks: u32) -> u32 {
+        now.wrapping_sub(self.refilled)
+            .checked_div(refill_ticks)
+            .unwrap_or(u32::MAX)
+    }
+}
+
+/// Token buckets for up to `N` sources
+///
+/// When `N` sources are tracked the one with the most tokens is forgotten to make room for a new one, so a source
+/// which has been throttled isn't given a fresh burst
+#[derive(Debug, Clone)]
+pub struct Throttle<const N: usize> {
+    buckets: [Option<Bucket>; N],
+    burst: u16,
+    refill_ticks: u32,
+    throttled: u32,
+}
+
+impl<const N: usize> Throttle<N> {
+    /// Allow bursts of `burst` requests from each source, refilled by one token every `refill_ticks`
+    ///
+    /// A `refill_ticks` of 0 refills immediately, i.e. doesn't limit
+    pub const fn new(burst: u16, refill_ticks: u32) -> Self {
+        Throttle {
+            buckets: [None; N],
+            burst,
+            refill_ticks,
+            throttled: 0,
+        }
+    }
+
+    /// Requests refused since creation
+    pub fn throttled(&self) -> u32 {
+        self.throttled
+    }
+
+    /// Take a token for a request from `source` at time `now`, false if it has none left
+    pub fn allow(&mut self, source: u8, now: u32) -> bool {
+        let (burst, refill_ticks) = (self.burst, self.refill_ticks);
+        let slot = match self
+            .buckets
+            .iter()
+            .position(|b| matches!(b, Some(b) if b.source == source))
+            .or_else(|| self.buckets.iter().position(Option::is_none))
+        {
+            Some(idx) => &mut self.buckets[idx],
+            None => match self.buckets.iter_mut().max_by_key(|b| {
+                b.map(|b| b.refills(now, refill_ticks).saturating_add(b.tokens.into()))
+            }) {
+                Some(slot) => slot,
+                // nothing to track with
+                None => return true,
+            },
+        };
+        let bucket = match slot {
+            Some(bucket) if bucket.source == source => bucket,
+            _ => slot.insert(Bucket {
+                source,
+                tokens: burst,
+                refilled: now,
+            }),
+        };
+
+        let added = bucket.refills(now, refill_ticks);
+        if bucket
+            .tokens
+            .saturating_add(u16::try_from(added).unwrap_or(u16::MAX))
+            >= burst
+        {
+            bucket.tokens = burst;
+            bucket.refilled = now;
+        } else if added > 0 {
+            // keep the part of a refill period that has passed
+            bucket.tokens += added as u16;
+            bucket.refilled = bucket.refilled.wrapping_add(added * refill_ticks);
+        }
+
+        if bucket.tokens == 0 {
+            self.throttled = self.throttled.wrapping_add(1);
+            return false;
+        }
+        bucket.tokens -= 1;
+        true
+    }
+
+    /// As `server::dispatch`, answering `DEVICE_BUSY` if `source` has no tokens at time `now`
+    pub fn dispatch<'b, H: Handler + ?Sized>(
+        &mut self,
+        source: u8,
+        now: u32,
+        handler: &mut H,
+        request: ValidatedFrame,
+        response_buffer: &'b mut [u8],
+    ) -> Frame<'b> {
+        if self.allow(source, now) {
+            server::dispatch(handler, request, response_buffer)
+        } else {
+            request
+                .response_exception(response_buffer, exception::DEVICE_BUSY)
+                .0
+        }
+    }
+
+    /// As `server::respond`, throttling by the request's address
+    ///
+    /// Throttled broadcasts are dropped, requests for other devices don't take a token
+    pub fn respond<'b, H: Handler + ?Sized>(
+        &mut self,
+        device: Device,
+        now: u32,
+        request: ValidatedFrame,
+        handler: &mut H,
+        response_buffer: &'b mut [u8],
+    ) -> Option<Frame<'b>> {
+        let addressed = match request.device().kind() {
+            AddressKind::Broadcast => true,
+            AddressKind::Slave => request.device() == device,
+            _ => false,
+        };
+        if addressed && !self.allow(request.address(), now) {
+            return (!request.device().is_broadcast()).then(|| {
+                request
+                    .response_exception(response_buffer, exception::DEVICE_BUSY)
+                    .0
+            });
+        }
+        server::respond(device, request, handler, response_buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Throttle;
+    use crate::{exception, request, source::Coils, Device};
+
+    #[test]
+    fn token_buckets() {
+        let mut throttle = Throttle::<2>::new(3, 10);
+        // a burst, then one per refill period
+        assert!((0..3).all(|_| throttle.allow(1, 0)));
+        assert!(!throttle.allow(1, 9));
+        assert!(throttle.allow(1, 15));
+        // the remaining 5 ticks count towards the next token
+        assert!(throttle.allow(1, 20));
+        assert!(!throttle.allow(1, 21));
+        assert_eq!(throttle.throttled(), 2);
+        // refills up to the burst across the wrap of the clock
+        assert!((0..3).all(|_| throttle.allow(1, u32::MAX - 5)));
+        assert!(!throttle.allow(1, 2));
+        assert!(throttle.allow(1, 4));
+
+        // sources have their own buckets, the one with the most tokens is forgotten
+        assert!((0..3).all(|_| throttle.allow(2, 25)));
+        assert!(!throttle.allow(2, 25));
+        assert!(throttle.allow(3, 30));
+        assert!(!throttle.allow(2, 30));
+        assert!(throttle.allow(1, 30));
+        assert!(!throttle.allow(2, 30));
+
+        // unlimited
+        let mut unlimited = Throttle::<1>::new(1, 0);
+        assert!((0..3).all(|_| unlimited.allow(1, 0)));
+    }
+
+    #[test]
+    fn respond() {
+        let mut throttle = Throttle::<2>::new(1, 100);
+        let mut handler = Coils([false; 8]);
+        let mut request_buffer = [0; 8];
+        let mut response_buffer = [0; 256];
+        let device = Device::new(1);
+
+        let request = request::write_coil(&mut request_buffer, 0, 0, true).unwrap();
+        assert!(throttle
+            .respond(
+                device,
+                0,
+                request.try_into().unwrap(),
+                &mut handler,
+                &mut response_buffer
+            )
+            .is_none());
+        let request = request::write_coil(&mut request_buffer, 0, 1, true).unwrap();
+        assert!(throttle
+            .respond(
+                device,
+                0,
+                request.try_into().unwrap(),
+                &mut handler,
+                &mut response_buffer
+            )
+            .is_none());
+        assert_eq!(handler.0[..2], [true, false]);
+
+        let request = request::read_coils(&mut request_buffer, 2, 0, 8).unwrap();
+        assert!(throttle
+            .respond(
+                device,
+                0,
+                request.try_into().unwrap(),
+                &mut handler,
+                &mut response_buffer
+            )
+            .is_none());
+        let request = request::read_coils(&mut request_buffer, 1, 0, 8).unwrap();
+        let response = throttle.respond(
+            device,
+            0,
+            request.try_into().unwrap(),
+            &mut handler,
+            &mut response_buffer,
+        );
+        assert_eq!(response.unwrap().payload(), [1, 1]);
+        let response = throttle.respond(
+            device,
+            0,
+            request.try_into().unwrap(),
+            &mut handler,
+            &mut response_buffer,
+        );
+        assert_eq!(response.unwrap().payload(), [exception::DEVICE_BUSY.0]);
+    }
+}
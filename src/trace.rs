@@ -0,0 +1,60 @@
+//! Hooks for observing the traffic handled by the client, server and gateway
+//!
+//! Implement [`Trace`] to log, count or capture frames, every callback defaults to doing nothing.
+//! `()` is the default tracer and compiles away entirely.
+//!
+//! ```
+//! use modbus_frames::{trace::Trace, Frame};
+//!
+//! #[derive(Default)]
+//! struct Counts {
+//!     sent: u32,
+//!     received: u32,
+//! }
+//!
+//! impl Trace for Counts {
+//!     fn on_tx(&mut self, _frame: &Frame) {
+//!         self.sent += 1;
+//!     }
+//!
+//!     fn on_rx(&mut self, _bytes: &[u8]) {
+//!         self.received += 1;
+//!     }
+//! }
+//! ```
+
+use crate::{Error, Frame};
+
+pub trait Trace {
+    /// A frame is about to be sent
+    fn on_tx(&mut self, _frame: &Frame) {}
+
+    /// Bytes were received, called before they are validated
+    fn on_rx(&mut self, _bytes: &[u8]) {}
+
+    /// Received bytes were invalid or a transaction failed
+    fn on_error(&mut self, _error: &Error) {}
+
+    /// A request is being sent again, `attempt` counts from 1 for the first retry
+    fn on_retry(&mut self, _attempt: u8) {}
+}
+
+impl Trace for () {}
+
+impl<T: Trace + ?Sized> Trace for &mut T {
+    fn on_tx(&mut self, frame: &Frame) {
+        (**self).on_tx(frame)
+    }
+
+    fn on_rx(&mut self, bytes: &[u8]) {
+        (**self).on_rx(bytes)
+    }
+
+    fn on_error(&mut self, error: &Error) {
+        (**self).on_error(error)
+    }
+
+    fn on_retry(&mut self, attempt: u8) {
+        (**self).on_retry(attempt)
+    }
+}
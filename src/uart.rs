@@ -0,0 +1,236 @@
+//! RTU over a raw UART, split between interrupt and task context
+//!
+//! A [`SerialLink`] is split into an [`RxHalf`], owned by the receive interrupt, and a [`TxHalf`] used from the
+//! application task. The receive half assembles bytes into frames with an [`RxRing`] and passes complete frames to
+//! the task half through a lock-free single producer, single consumer queue. Neither half blocks or needs a critical
+//! section, matching how RTOS and async firmware usually structure serial handling.
+//!
+//! ```
+//! use modbus_frames::{request, rx, uart::{SerialLink, UartTx}, Error};
+//!
+//! struct Uart;
+//!
+//! impl UartTx for Uart {
+//!     fn write(&mut self, _bytes: &[u8]) -> Result<(), Error> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut link = SerialLink::<512>::new();
+//! let (mut rx_half, mut tx_half) = link.split(rx::silence_ticks(9600, 1000), Uart);
+//!
+//! let mut buffer = [0; 8];
+//! let request = request::read_coils(&mut buffer, 1, 0, 8).unwrap();
+//! // in the receive interrupt, and a timer interrupt to notice the silence after the last byte
+//! rx_half.push(request.raw_bytes(), 0);
+//! rx_half.tick(5);
+//! // in the task
+//! let mut frame = [0; 256];
+//! assert_eq!(tx_half.receive(&mut frame), Some(request));
+//! ```
+
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use crate::{rx::RxRing, Error, Frame};
+
+/// Transmit access to the UART used by [`TxHalf`]
+pub trait UartTx {
+    /// Write all of `bytes`, returning once they have been handed to the UART
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Error>;
+}
+
+/// Queue of up to `N` bytes of received frames, shared by the two halves
+///
+/// Each frame takes its length plus one byte. Place it in a `static` (or a `static_cell`) so the halves can be moved
+/// to the interrupt and the task.
+#[derive(Debug)]
+pub struct SerialLink<const N: usize> {
+    buffer: [AtomicU8; N],
+    /// bytes written by the receive half, wrapping
+    head: AtomicUsize,
+    /// bytes read by the task half, wrapping
+    tail: AtomicUsize,
+}
+
+impl<const N: usize> SerialLink<N> {
+    pub const fn new() -> Self {
+        SerialLink {
+            buffer: [const { AtomicU8::new(0) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// The receive half, ending frames after `silence` ticks (see [`rx::silence_ticks`](crate::rx::silence_ticks)),
+    /// and the task half transmitting on `tx`
+    ///
+    /// Any frames still queued are discarded
+    pub fn split<W: UartTx>(&mut self, silence: u32, tx: W) -> (RxHalf<'_, N>, TxHalf<'_, W, N>) {
+        *self.head.get_mut() = 0;
+        *self.tail.get_mut() = 0;
+        let link = &*self;
+        (
+            RxHalf {
+                link,
+                accumulator: RxRing::new(silence),
+                overflows: 0,
+            },
+            TxHalf { link, tx },
+        )
+    }
+
+    fn slot(&self, idx: usize) -> &AtomicU8 {
+        &self.buffer[idx % N]
+    }
+}
+
+impl<const N: usize> Default for SerialLink<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The receiving half of a [`SerialLink`], owned by the receive interrupt
+#[derive(Debug)]
+pub struct RxHalf<'l, const N: usize> {
+    link: &'l SerialLink<N>,
+    accumulator: RxRing<256>,
+    overflows: u32,
+}
+
+impl<const N: usize> RxHalf<'_, N> {
+    /// Add bytes received at time `now`, queueing the previous frame if the silence before them ended it
+    pub fn push(&mut self, bytes: &[u8], now: u32) {
+        self.accumulator.push(bytes, now);
+        self.tick(now);
+    }
+
+    /// Queue the last frame if the line has been silent long enough at time `now`
+    ///
+    /// Call from a timer or receiver idle interrupt, otherwise a frame is only queued when the next one starts
+    pub fn tick(&mut self, now: u32) {
+        let Some(frame) = self.accumulator.poll(now) else {
+            return;
+        };
+        let bytes = frame.raw_bytes();
+        let link = self.link;
+        let head = link.head.load(Ordering::Relaxed);
+        let used = head.wrapping_sub(link.tail.load(Ordering::Acquire));
+        if N - used < bytes.len() + 1 {
+            self.overflows = self.overflows.wrapping_add(1);
+            return;
+        }
+        // lengths are 4..=256 so the length less one fits a byte
+        link.slot(head)
+            .store((bytes.len() - 1) as u8, Ordering::Relaxed);
+        for (offset, byte) in bytes.iter().enumerate() {
+            link.slot(head.wrapping_add(1 + offset))
+                .store(*byte, Ordering::Relaxed);
+        }
+        link.head
+            .store(head.wrapping_add(1 + bytes.len()), Ordering::Release);
+    }
+
+    /// Frames lost to bad CRCs, overflowing the accumulator or the task not keeping up with the queue
+    pub fn dropped(&self) -> u32 {
+        self.accumulator.dropped().wrapping_add(self.overflows)
+    }
+
+    /// Discard a partially received frame, e.g. the echo of our own transmission
+    pub fn clear(&mut self) {
+        self.accumulator.clear();
+    }
+}
+
+/// The task half of a [`SerialLink`], receiving queued frames and transmitting
+#[derive(Debug)]
+pub struct TxHalf<'l, W, const N: usize> {
+    link: &'l SerialLink<N>,
+    tx: W,
+}
+
+impl<W: UartTx, const N: usize> TxHalf<'_, W, N> {
+    /// The oldest frame received, copied into `buffer`
+    pub fn receive<'b>(&mut self, buffer: &'b mut [u8; 256]) -> Option<Frame<'b>> {
+        let link = self.link;
+        let tail = link.tail.load(Ordering::Relaxed);
+        if link.head.load(Ordering::Acquire) == tail {
+            return None;
+        }
+        let len = usize::from(link.slot(tail).load(Ordering::Relaxed)) + 1;
+        for (offset, byte) in buffer[..len].iter_mut().enumerate() {
+            *byte = link
+                .slot(tail.wrapping_add(1 + offset))
+                .load(Ordering::Relaxed);
+        }
+        link.tail
+            .store(tail.wrapping_add(1 + len), Ordering::Release);
+        // checked by the accumulator
+        Some(Frame::new_unchecked(&buffer[..len]))
+    }
+
+    /// Transmit `frame`
+    pub fn send(&mut self, frame: Frame) -> Result<(), Error> {
+        self.tx.write(frame.raw_bytes())
+    }
+
+    pub fn tx(&mut self) -> &mut W {
+        &mut self.tx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SerialLink, UartTx};
+    use crate::{request, response, Error};
+
+    #[derive(Default)]
+    struct Uart(usize);
+
+    impl UartTx for Uart {
+        fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+            self.0 += bytes.len();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn split_halves() {
+        let mut buf = [0; 256];
+        let (request, rem) = request::WriteMultipleCoils::new(&mut buf, 1, 0, [true; 9]);
+        let (response, _) = response::WriteMultipleCoils::new(rem, 1, 0, 9);
+        let (request, response) = (request.as_frame(), response.as_frame());
+
+        // room for two frames of 11 bytes but not three
+        let mut link = SerialLink::<32>::new();
+        let (mut rx_half, mut tx_half) = link.split(4, Uart::default());
+        let mut frame = [0; 256];
+        assert_eq!(tx_half.receive(&mut frame), None);
+
+        // the queue wraps around the end of the buffer
+        let mut now = 0;
+        for _ in 0..5 {
+            rx_half.push(&request.raw_bytes()[..3], now);
+            rx_half.push(&request.raw_bytes()[3..], now + 1);
+            rx_half.push(response.raw_bytes(), now + 10);
+            rx_half.tick(now + 20);
+            assert_eq!(tx_half.receive(&mut frame), Some(request));
+            assert_eq!(tx_half.receive(&mut frame), Some(response));
+            assert_eq!(tx_half.receive(&mut frame), None);
+            now += 100;
+        }
+
+        for _ in 0..3 {
+            rx_half.push(request.raw_bytes(), now);
+            now += 10;
+        }
+        rx_half.tick(now);
+        assert_eq!(rx_half.dropped(), 1);
+        assert_eq!(tx_half.receive(&mut frame), Some(request));
+        assert_eq!(tx_half.receive(&mut frame), Some(request));
+        assert_eq!(tx_half.receive(&mut frame), None);
+
+        tx_half.send(response).unwrap();
+        assert_eq!(tx_half.tx().0, 8);
+    }
+}